@@ -0,0 +1,124 @@
+
+// Compares R6502::clock() with R6502::clock_block() on a compute heavy program.
+//
+//      cargo bench --bench block_cache
+//
+// Both runs have to end in the same state, the times are for the whole program.
+
+use std::time::{Duration, Instant};
+
+use re6502::r6502::{R6502, Bus};
+use re6502::r6502::block_cache::BlockCache;
+
+const RUNS: u32 = 20;
+
+// Mixes two counters through 65536 passes of a short arithmetic loop
+const PROGRAM: [u8; 22] =
+[
+    0xA0, 0x00,         // LDY #0
+    0xA2, 0x00,         // outer: LDX #0
+    0x8A,               // inner: TXA
+    0x18,               // CLC
+    0x65, 0x10,         // ADC $10
+    0x85, 0x10,         // STA $10
+    0x2A,               // ROL A
+    0x45, 0x11,         // EOR $11
+    0x85, 0x11,         // STA $11
+    0xCA,               // DEX
+    0xD0, 0xF2,         // BNE inner
+    0x88,               // DEY
+    0xD0, 0xED,         // BNE outer
+    0x60,               // RTS
+];
+
+struct RAMBus
+{
+    ram: Vec<u8>,
+}
+
+impl Bus for RAMBus
+{
+    fn read(&self, addr: u16) -> u8
+    {
+        self.ram[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, value: u8)
+    {
+        self.ram[addr as usize] = value;
+    }
+
+    fn page(&self, page: u8) -> Option<&[u8; 256]>
+    {
+        let start = (page as usize) << 8;
+        self.ram[start..start + 256].try_into().ok()
+    }
+
+    fn page_mut(&mut self, page: u8) -> Option<&mut [u8; 256]>
+    {
+        let start = (page as usize) << 8;
+        (&mut self.ram[start..start + 256]).try_into().ok()
+    }
+}
+
+fn boot() -> (R6502, RAMBus)
+{
+    let mut bus = RAMBus { ram: vec![0; 64 * 1024] };
+    bus.ram[0x0200..0x0200 + PROGRAM.len()].copy_from_slice(&PROGRAM);
+    bus.ram[0xFFFC] = 0x00;
+    bus.ram[0xFFFD] = 0x02;
+
+    let mut cpu = R6502::new();
+    cpu.reset(&mut bus);
+    (cpu, bus)
+}
+
+// Runs the program RUNS times, returns the fastest time and the final state
+fn measure(run: impl Fn(&mut R6502, &mut RAMBus)) -> (Duration, R6502, Vec<u8>)
+{
+    let mut best = Duration::MAX;
+    let mut result = None;
+
+    for _ in 0..RUNS
+    {
+        let (mut cpu, mut bus) = boot();
+        let start = Instant::now();
+        run(&mut cpu, &mut bus);
+        best = best.min(start.elapsed());
+        result = Some((cpu, bus.ram));
+    }
+
+    let (cpu, ram) = result.unwrap();
+    (best, cpu, ram)
+}
+
+fn main()
+{
+    let (clock_time, clock_cpu, clock_ram) = measure(|cpu, bus|
+    {
+        while !cpu.is_program_stopped()
+        {
+            cpu.clock(bus);
+        }
+    });
+
+    let (block_time, block_cpu, block_ram) = measure(|cpu, bus|
+    {
+        let mut cache = BlockCache::new();
+        while !cpu.is_program_stopped()
+        {
+            cpu.clock_block(bus, &mut cache);
+        }
+    });
+
+    assert_eq!(clock_cpu.state(), block_cpu.state(), "the block cache should end in the same state");
+    assert!(clock_ram == block_ram, "the block cache should leave memory the same");
+
+    let cycles = clock_cpu.cycles() as f64;
+    let mhz = |time: Duration| cycles / time.as_secs_f64() / 1_000_000.0;
+
+    println!("{} cycles, best of {} runs", clock_cpu.cycles(), RUNS);
+    println!("clock()        {:>10.2?}  {:>8.1} MHz", clock_time, mhz(clock_time));
+    println!("clock_block()  {:>10.2?}  {:>8.1} MHz", block_time, mhz(block_time));
+    println!("speedup        {:>10.2}x", clock_time.as_secs_f64() / block_time.as_secs_f64());
+}
@@ -0,0 +1,97 @@
+
+use std::{ fs, env, process };
+
+use re6502::r6502::recompiler::{self, Rom};
+
+#[cfg(test)]
+mod tests;
+
+const USAGE: &str = "Usage: recompiler <rom file> [--base <addr>] [--entry <addr>]... [-o <output.rs>]
+
+    --base <addr>   Address the ROM image is loaded at (default $0000)
+    --entry <addr>  Routine to recompile, can be given more than once.
+                    Defaults to the reset vector if the ROM covers $FFFC.
+    -o <file>       Write the Rust source to a file instead of stdout
+
+Addresses can be given in hex ($8000 or 0x8000) or decimal.";
+
+fn main()
+{
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let mut rom_file = None;
+    let mut base: u16 = 0;
+    let mut entries = Vec::new();
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next()
+    {
+        match arg.as_str()
+        {
+            "--base" => base = parse_addr(args.next()),
+            "--entry" => entries.push(parse_addr(args.next())),
+            "-o" => output = Some(args.next().unwrap_or_else(|| usage()).clone()),
+            "-h" | "--help" => usage(),
+            _ if rom_file.is_none() => rom_file = Some(arg.clone()),
+            _ => usage(),
+        }
+    }
+
+    let rom_file = rom_file.unwrap_or_else(|| usage());
+    let image = fs::read(&rom_file).unwrap_or_else(|e| fail(&format!("Failed to read rom file {}: {}", &rom_file, e)));
+    let rom = Rom::new(&image, base);
+
+    if entries.is_empty()
+    {
+        match rom.read_word(0xFFFC)
+        {
+            Some(reset) => entries.push(reset),
+            None => fail("No entry points given and the ROM doesn't contain the reset vector"),
+        }
+    }
+
+    let program = recompiler::analyze(&rom, &entries);
+    for routine in program.routines.iter()
+    {
+        for exit in routine.exits.iter()
+        {
+            eprintln!("sub_{:04X}: code at ${:04X} falls back to the interpreter", routine.entry, exit);
+        }
+    }
+
+    let source = program.to_rust();
+    match output
+    {
+        Some(file) => fs::write(&file, source).unwrap_or_else(|e| fail(&format!("Failed to write output file {}: {}", &file, e))),
+        None => print!("{}", source),
+    }
+}
+
+fn parse_addr(arg: Option<&String>) -> u16
+{
+    let arg = arg.unwrap_or_else(|| usage());
+
+    let value = if let Some(hex) = arg.strip_prefix('$').or(arg.strip_prefix("0x"))
+    {
+        u16::from_str_radix(hex, 16)
+    }
+    else
+    {
+        arg.parse::<u16>()
+    };
+
+    value.unwrap_or_else(|_| fail(&format!("Invalid address: {}", arg)))
+}
+
+fn fail(message: &str) -> !
+{
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn usage() -> !
+{
+    eprintln!("{}", USAGE);
+    process::exit(1);
+}
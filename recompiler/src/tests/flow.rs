@@ -0,0 +1,405 @@
+// Generated by the RE6502 static recompiler
+// Routines: $8000, $8040, $8050, $8060
+
+use re6502::r6502::{R6502, Bus, Flags};
+use re6502::r6502::recompiler::{self, Regs};
+
+// Returns false if there is no routine at addr
+pub fn call(addr: u16, cpu: &mut R6502, bus: &mut dyn Bus) -> bool
+{
+    match addr
+    {
+        0x8000 => sub_8000(cpu, bus),
+        0x8040 => sub_8040(cpu, bus),
+        0x8050 => sub_8050(cpu, bus),
+        0x8060 => sub_8060(cpu, bus),
+        _ => return false,
+    }
+
+    true
+}
+
+pub fn sub_8000(cpu: &mut R6502, bus: &mut dyn Bus)
+{
+    let mut r = Regs::load(cpu);
+    let frame = r.sp;
+    r.pc = 0x8000;
+    loop
+    {
+        match r.pc
+        {
+            0x8000 =>
+            {
+                // $8000  LDX #$03
+                let data: u8 = 0x03;
+                r.cycles += 2;
+                r.x = data;
+                r.set_if(Flags::Z, r.x == 0);
+                r.set_if(Flags::N, r.x & 0x80 != 0);
+                r.pc = 0x8002;
+            }
+            0x8002 =>
+            {
+                // $8002  JSR $8040
+                recompiler::read(bus, 0x8040);
+                r.cycles += 6;
+                recompiler::write(bus, r.sp, 0x80);
+                r.sp = r.sp.wrapping_sub(1);
+                recompiler::write(bus, r.sp, 0x04);
+                r.sp = r.sp.wrapping_sub(1);
+                r.pc = 0x8040;
+                r.store(cpu);
+                sub_8040(cpu, bus);
+                r = Regs::load(cpu);
+            }
+            0x8005 =>
+            {
+                // $8005  DEX
+                r.cycles += 2;
+                r.x = r.x.wrapping_sub(1);
+                r.assign(Flags::Z, r.x == 0);
+                r.assign(Flags::N, r.x & 0x80 != 0);
+
+                // $8006  BNE $8002
+                r.cycles += 2;
+                if !r.check(Flags::Z)
+                {
+                    r.cycles += 1;
+                    r.pc = 0x8002;
+                }
+                else
+                {
+                    r.pc = 0x8008;
+                }
+            }
+            0x8008 =>
+            {
+                // $8008  JMP ($80F0)
+                let lo = recompiler::read(bus, 0x80F0);
+                let hi = recompiler::read(bus, 0x80F1);
+                let addr = (hi as u16) << 8 | lo as u16;
+                r.cycles += 5;
+                r.pc = addr;
+            }
+            0x800B =>
+            {
+                // $800B  JSR $8050
+                recompiler::read(bus, 0x8050);
+                r.cycles += 6;
+                recompiler::write(bus, r.sp, 0x80);
+                r.sp = r.sp.wrapping_sub(1);
+                recompiler::write(bus, r.sp, 0x0D);
+                r.sp = r.sp.wrapping_sub(1);
+                r.pc = 0x8050;
+                r.store(cpu);
+                sub_8050(cpu, bus);
+                r = Regs::load(cpu);
+            }
+            0x800E =>
+            {
+                // $800E  JSR $8060
+                recompiler::read(bus, 0x8060);
+                r.cycles += 6;
+                recompiler::write(bus, r.sp, 0x80);
+                r.sp = r.sp.wrapping_sub(1);
+                recompiler::write(bus, r.sp, 0x10);
+                r.sp = r.sp.wrapping_sub(1);
+                r.pc = 0x8060;
+                r.store(cpu);
+                sub_8060(cpu, bus);
+                r = Regs::load(cpu);
+            }
+            0x8011 =>
+            {
+                // $8011  STA $40
+                recompiler::read(bus, 0x0040);
+                r.cycles += 3;
+                recompiler::write(bus, 0x0040, r.a);
+
+                // $8013  STX $41
+                recompiler::read(bus, 0x0041);
+                r.cycles += 3;
+                recompiler::write(bus, 0x0041, r.x);
+
+                // $8015  STY $42
+                recompiler::read(bus, 0x0042);
+                r.cycles += 3;
+                recompiler::write(bus, 0x0042, r.y);
+
+                // $8017  TSX
+                r.cycles += 2;
+                r.x = r.sp.wrapping_sub(0x100) as u8;
+
+                // $8018  TXS
+                r.cycles += 2;
+                r.sp = r.x as u16 + 0x100;
+
+                // $8019  PHP
+                r.cycles += 3;
+                recompiler::write(bus, r.sp, r.status);
+                r.sp = r.sp.wrapping_sub(1);
+
+                // $801A  PLA
+                r.cycles += 4;
+                r.sp = r.sp.wrapping_add(1);
+                r.a = recompiler::read(bus, r.sp);
+                r.assign(Flags::Z, r.a == 0);
+                r.assign(Flags::N, r.a & 0x80 != 0);
+
+                // $801B  STA $43
+                recompiler::read(bus, 0x0043);
+                r.cycles += 3;
+                recompiler::write(bus, 0x0043, r.a);
+
+                // $801D  RTS
+                r.cycles += 6;
+                if r.sp == 0x01FF
+                {
+                    r.stopped = true;
+                    r.pc = 0x801E;
+                }
+                else
+                {
+                    r.sp = r.sp.wrapping_add(1);
+                    let lo = recompiler::read(bus, r.sp);
+                    r.sp = r.sp.wrapping_add(1);
+                    let hi = recompiler::read(bus, r.sp);
+                    r.pc = ((hi as u16) << 8 | lo as u16).wrapping_add(1);
+                }
+            }
+            _ =>
+            {
+                r.store(cpu);
+                return recompiler::interpret(cpu, bus, frame);
+            }
+        }
+
+        if r.stopped || r.sp > frame
+        {
+            r.store(cpu);
+            return;
+        }
+    }
+}
+
+pub fn sub_8040(cpu: &mut R6502, bus: &mut dyn Bus)
+{
+    let mut r = Regs::load(cpu);
+    let frame = r.sp;
+    r.pc = 0x8040;
+    loop
+    {
+        match r.pc
+        {
+            0x8040 =>
+            {
+                // $8040  CLC
+                r.cycles += 2;
+                r.assign(Flags::C, false);
+
+                // $8041  ADC #$60
+                let data: u8 = 0x60;
+                r.cycles += 2;
+                let value = data;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.set_if(Flags::C, temp > 0xFF);
+                r.set_if(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $8043  BCC $8047
+                r.cycles += 2;
+                if !r.check(Flags::C)
+                {
+                    r.cycles += 1;
+                    r.pc = 0x8047;
+                }
+                else
+                {
+                    r.pc = 0x8045;
+                }
+            }
+            0x8045 =>
+            {
+                // $8045  INC $44
+                recompiler::read(bus, 0x0044);
+                r.cycles += 5;
+                let value = recompiler::read(bus, 0x0044).wrapping_add(1);
+                recompiler::write(bus, 0x0044, value);
+                r.assign(Flags::Z, value == 0);
+                r.assign(Flags::N, value & 0x80 != 0);
+                r.pc = 0x8047;
+            }
+            0x8047 =>
+            {
+                // $8047  RTS
+                r.cycles += 6;
+                if r.sp == 0x01FF
+                {
+                    r.stopped = true;
+                    r.pc = 0x8048;
+                }
+                else
+                {
+                    r.sp = r.sp.wrapping_add(1);
+                    let lo = recompiler::read(bus, r.sp);
+                    r.sp = r.sp.wrapping_add(1);
+                    let hi = recompiler::read(bus, r.sp);
+                    r.pc = ((hi as u16) << 8 | lo as u16).wrapping_add(1);
+                }
+            }
+            _ =>
+            {
+                r.store(cpu);
+                return recompiler::interpret(cpu, bus, frame);
+            }
+        }
+
+        if r.stopped || r.sp > frame
+        {
+            r.store(cpu);
+            return;
+        }
+    }
+}
+
+pub fn sub_8050(cpu: &mut R6502, bus: &mut dyn Bus)
+{
+    let mut r = Regs::load(cpu);
+    let frame = r.sp;
+    r.pc = 0x8050;
+    loop
+    {
+        match r.pc
+        {
+            0x8050 =>
+            {
+                // $8050  LDA #$80
+                let data: u8 = 0x80;
+                r.cycles += 2;
+                r.a = data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8052  PHA
+                r.cycles += 3;
+                recompiler::write(bus, r.sp, r.a);
+                r.sp = r.sp.wrapping_sub(1);
+
+                // $8053  LDA #$56
+                let data: u8 = 0x56;
+                r.cycles += 2;
+                r.a = data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8055  PHA
+                r.cycles += 3;
+                recompiler::write(bus, r.sp, r.a);
+                r.sp = r.sp.wrapping_sub(1);
+
+                // $8056  RTS
+                r.cycles += 6;
+                if r.sp == 0x01FF
+                {
+                    r.stopped = true;
+                    r.pc = 0x8057;
+                }
+                else
+                {
+                    r.sp = r.sp.wrapping_add(1);
+                    let lo = recompiler::read(bus, r.sp);
+                    r.sp = r.sp.wrapping_add(1);
+                    let hi = recompiler::read(bus, r.sp);
+                    r.pc = ((hi as u16) << 8 | lo as u16).wrapping_add(1);
+                }
+            }
+            _ =>
+            {
+                r.store(cpu);
+                return recompiler::interpret(cpu, bus, frame);
+            }
+        }
+
+        if r.stopped || r.sp > frame
+        {
+            r.store(cpu);
+            return;
+        }
+    }
+}
+
+pub fn sub_8060(cpu: &mut R6502, bus: &mut dyn Bus)
+{
+    let mut r = Regs::load(cpu);
+    let frame = r.sp;
+    r.pc = 0x8060;
+    loop
+    {
+        match r.pc
+        {
+            0x8060 =>
+            {
+                // $8060  PHP
+                r.cycles += 3;
+                recompiler::write(bus, r.sp, r.status);
+                r.sp = r.sp.wrapping_sub(1);
+
+                // $8061  PLP
+                r.cycles += 4;
+                r.sp = r.sp.wrapping_add(1);
+                r.status = recompiler::read(bus, r.sp);
+                if r.irq_pending && !r.check(Flags::I)
+                {
+                    r.pc = 0x8062;
+                    r.store(cpu);
+                    return recompiler::interpret(cpu, bus, frame);
+                }
+
+                // $8062  CLI
+                r.cycles += 2;
+                r.assign(Flags::I, false);
+                if r.irq_pending && !r.check(Flags::I)
+                {
+                    r.pc = 0x8063;
+                    r.store(cpu);
+                    return recompiler::interpret(cpu, bus, frame);
+                }
+
+                // $8063  INY
+                r.cycles += 2;
+                r.y = r.y.wrapping_add(1);
+                r.assign(Flags::Z, r.y == 0);
+                r.assign(Flags::N, r.y & 0x80 != 0);
+
+                // $8064  RTS
+                r.cycles += 6;
+                if r.sp == 0x01FF
+                {
+                    r.stopped = true;
+                    r.pc = 0x8065;
+                }
+                else
+                {
+                    r.sp = r.sp.wrapping_add(1);
+                    let lo = recompiler::read(bus, r.sp);
+                    r.sp = r.sp.wrapping_add(1);
+                    let hi = recompiler::read(bus, r.sp);
+                    r.pc = ((hi as u16) << 8 | lo as u16).wrapping_add(1);
+                }
+            }
+            _ =>
+            {
+                r.store(cpu);
+                return recompiler::interpret(cpu, bus, frame);
+            }
+        }
+
+        if r.stopped || r.sp > frame
+        {
+            r.store(cpu);
+            return;
+        }
+    }
+}
@@ -0,0 +1,307 @@
+
+use re6502::r6502::{R6502, Bus, Flags};
+use re6502::r6502::decoder;
+use re6502::r6502::state::StatusFlags;
+use re6502::r6502::recompiler::{self, Rom};
+
+// Generated by the recompiler from SAMPLE, FLOW and opcodes_rom()
+mod sample;
+mod flow;
+mod opcodes;
+
+const SAMPLE_BASE: u16 = 0x0200;
+const SAMPLE: [u8; 0x24] =
+[
+    0xA2, 0x05,         // LDX #5
+    0xA9, 0x00,         // LDA #0
+    0x20, 0x20, 0x02,   // loop: JSR add3
+    0xCA,               // DEX
+    0xD0, 0xFA,         // BNE loop
+    0x85, 0x40,         // STA $40
+    0xA9, 0x17,         // LDA #$17
+    0x85, 0x41,         // STA $41
+    0xA9, 0x02,         // LDA #$02
+    0x85, 0x42,         // STA $42
+    0x6C, 0x41, 0x00,   // JMP ($0041) - points at RAM, can't be resolved
+    0xA4, 0x40,         // LDY $40
+    0x60,               // RTS
+    0xEA, 0xEA, 0xEA, 0xEA, 0xEA, 0xEA,
+    0x18,               // add3: CLC
+    0x69, 0x03,         // ADC #3
+    0x60,               // RTS
+];
+
+// Builds the flow control test program at FLOW_BASE
+const FLOW_BASE: u16 = 0x8000;
+const FLOW: [(u16, &[u8]); 6] =
+[
+    (0x8000, &[
+        0xA2, 0x03,         // LDX #3
+        0x20, 0x40, 0x80,   // loop: JSR add
+        0xCA,               // DEX
+        0xD0, 0xFA,         // BNE loop
+        0x6C, 0xF0, 0x80,   // JMP ($80F0) - resolved from the ROM
+        0x20, 0x50, 0x80,   // JSR computed
+        0x20, 0x60, 0x80,   // JSR irq_on
+        0x85, 0x40,         // STA $40
+        0x86, 0x41,         // STX $41
+        0x84, 0x42,         // STY $42
+        0xBA,               // TSX
+        0x9A,               // TXS
+        0x08,               // PHP
+        0x68,               // PLA
+        0x85, 0x43,         // STA $43
+        0x60,               // RTS
+    ]),
+    (0x8040, &[
+        0x18,               // add: CLC
+        0x69, 0x60,         // ADC #$60
+        0x90, 0x02,         // BCC +2
+        0xE6, 0x44,         // INC $44
+        0x60,               // RTS
+    ]),
+    (0x8050, &[
+        0xA9, 0x80,         // computed: LDA #$80
+        0x48,               // PHA
+        0xA9, 0x56,         // LDA #$56
+        0x48,               // PHA
+        0x60,               // RTS - to $8057, left to the interpreter
+        0xC8,               // INY
+        0x60,               // RTS
+    ]),
+    (0x8060, &[
+        0x08,               // irq_on: PHP
+        0x28,               // PLP - interrupts are still disabled
+        0x58,               // CLI - the held IRQ is taken here
+        0xC8,               // INY
+        0x60,               // RTS
+    ]),
+    (0x8070, &[
+        0xE6, 0x45,         // irq: INC $45
+        0x40,               // RTI
+    ]),
+    (0x80F0, &[0x0B, 0x80]),
+];
+
+fn flow_rom() -> Vec<u8>
+{
+    let mut rom = vec![0; 0x100];
+    for (addr, bytes) in FLOW.iter()
+    {
+        let offset = (addr - FLOW_BASE) as usize;
+        rom[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    rom
+}
+
+// Every documented instruction that doesn't change the program counter or the stack,
+// with random operands, followed by an RTS
+const OPCODES_BASE: u16 = 0x8000;
+
+fn opcodes_rom() -> Vec<u8>
+{
+    let mut random = random(0x6502);
+    let mut rom = Vec::new();
+    for op in 0..=255u8
+    {
+        let decoded = match decoder::decode(op)
+        {
+            Some(decoded) if decoded.documented && !decoder::is_flow_control(op) => decoded,
+            _ => continue,
+        };
+
+        if [0x08, 0x28, 0x48, 0x68, 0x9A].contains(&op)
+        {
+            continue;
+        }
+
+        rom.push(op);
+        match decoded.size()
+        {
+            2 => rom.push(random()),
+            3 =>
+            {
+                // Keep absolute addresses in RAM
+                rom.push(random());
+                rom.push(random() & 0x07);
+            }
+            _ => (),
+        }
+    }
+
+    rom.push(0x60);
+    rom
+}
+
+fn random(mut seed: u32) -> impl FnMut() -> u8
+{
+    move ||
+    {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) as u8
+    }
+}
+
+// The top half of the address space is ROM, writes to it are dropped
+struct RAMBus
+{
+    ram: [u8; 64 * 1024]
+}
+
+impl Bus for RAMBus
+{
+    fn read(&self, addr: u16) -> u8
+    {
+        self.ram[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, value: u8)
+    {
+        if addr < 0x8000
+        {
+            self.ram[addr as usize] = value;
+        }
+    }
+}
+
+// The program is run from base, the reset vector points there
+fn machine(base: u16, program: &[u8]) -> (R6502, RAMBus)
+{
+    let mut bus = RAMBus { ram: [0; 64 * 1024] };
+    bus.ram[0xFFFC] = (base & 0x00FF) as u8;
+    bus.ram[0xFFFD] = (base >> 8) as u8;
+
+    let start = base as usize;
+    bus.ram[start..start + program.len()].copy_from_slice(program);
+
+    let mut cpu = R6502::new();
+    cpu.reset(&mut bus);
+    (cpu, bus)
+}
+
+fn sample_machine() -> (R6502, RAMBus)
+{
+    machine(SAMPLE_BASE, &SAMPLE)
+}
+
+fn interpret(cpu: &mut R6502, bus: &mut RAMBus)
+{
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(bus);
+    }
+}
+
+fn registers(cpu: &R6502) -> [u16; 6]
+{
+    let state = cpu.state();
+    [state.a as u16, state.x as u16, state.y as u16, state.pc, state.sp, state.status() as u16]
+}
+
+#[test]
+fn recovers_routines()
+{
+    let rom = Rom::new(&SAMPLE, SAMPLE_BASE);
+    let program = recompiler::analyze(&rom, &[SAMPLE_BASE]);
+
+    let entries: Vec<u16> = program.routines.iter().map(|r| r.entry).collect();
+    assert_eq!(vec![0x0200, 0x0220], entries);
+
+    // The JMP through RAM is left to the interpreter
+    assert_eq!(vec![0x0214], program.routine(0x0200).unwrap().exits);
+}
+
+#[test]
+fn generated_source_is_current()
+{
+    let sources = [(include_str!("sample.rs"), SAMPLE.to_vec(), SAMPLE_BASE),
+                   (include_str!("flow.rs"), flow_rom(), FLOW_BASE),
+                   (include_str!("opcodes.rs"), opcodes_rom(), OPCODES_BASE)];
+
+    for (source, image, base) in sources.iter()
+    {
+        let rom = Rom::new(image, *base);
+        let program = recompiler::analyze(&rom, &[*base]);
+
+        assert_eq!(source.replace("\r\n", "\n"), program.to_rust());
+    }
+}
+
+#[test]
+fn matches_interpreter()
+{
+    let (mut cpu, mut bus) = sample_machine();
+    interpret(&mut cpu, &mut bus);
+
+    let (mut native_cpu, mut native_bus) = sample_machine();
+    assert!(sample::call(SAMPLE_BASE, &mut native_cpu, &mut native_bus));
+
+    assert!(native_cpu.is_program_stopped());
+    assert_eq!(15, native_cpu.state().y);
+    assert_eq!(registers(&cpu), registers(&native_cpu));
+    assert!(bus.ram == native_bus.ram, "Memory should match the interpreter");
+}
+
+#[test]
+fn flow_control_matches_interpreter()
+{
+    // An IRQ is held until irq_on enables interrupts
+    let start = |(mut cpu, mut bus): (R6502, RAMBus)|
+    {
+        bus.ram[0xFFFE] = 0x70;
+        bus.ram[0xFFFF] = 0x80;
+
+        let mut state = cpu.state();
+        state.flags.i = true;
+        cpu.set_state(&state);
+        cpu.irq(&mut bus);
+        (cpu, bus)
+    };
+
+    let (mut cpu, mut bus) = start(machine(FLOW_BASE, &flow_rom()));
+    interpret(&mut cpu, &mut bus);
+
+    let (mut native_cpu, mut native_bus) = start(machine(FLOW_BASE, &flow_rom()));
+    assert!(flow::call(FLOW_BASE, &mut native_cpu, &mut native_bus));
+
+    assert!(native_cpu.is_program_stopped());
+    assert_eq!(0x56, native_bus.ram[0x40]);
+    assert_eq!([1, 1], [native_bus.ram[0x44], native_bus.ram[0x45]]);
+    assert_eq!(2, native_cpu.state().y);
+    assert_eq!(cpu.state(), native_cpu.state());
+    assert!(bus.ram == native_bus.ram, "Memory should match the interpreter");
+}
+
+#[test]
+fn opcodes_match_interpreter()
+{
+    let rom = opcodes_rom();
+    let mut random = random(0x1234);
+
+    for _ in 0..32
+    {
+        let (mut cpu, mut bus) = machine(OPCODES_BASE, &rom);
+        for addr in 0..0x0800
+        {
+            bus.ram[addr] = random();
+        }
+
+        let mut state = cpu.state();
+        state.a = random();
+        state.x = random();
+        state.y = random();
+        state.flags = StatusFlags::from_byte(random() | Flags::U as u8);
+        cpu.set_state(&state);
+
+        let mut native_cpu = R6502::new();
+        native_cpu.set_state(&state);
+        let mut native_bus = RAMBus { ram: bus.ram };
+
+        interpret(&mut cpu, &mut bus);
+        assert!(opcodes::call(OPCODES_BASE, &mut native_cpu, &mut native_bus));
+
+        assert_eq!(cpu.state(), native_cpu.state());
+        assert!(bus.ram == native_bus.ram, "Memory should match the interpreter");
+    }
+}
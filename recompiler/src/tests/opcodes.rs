@@ -0,0 +1,1229 @@
+// Generated by the RE6502 static recompiler
+// Routines: $8000
+
+use re6502::r6502::{R6502, Bus, Flags};
+use re6502::r6502::recompiler::{self, Regs};
+
+// Returns false if there is no routine at addr
+pub fn call(addr: u16, cpu: &mut R6502, bus: &mut dyn Bus) -> bool
+{
+    match addr
+    {
+        0x8000 => sub_8000(cpu, bus),
+        _ => return false,
+    }
+
+    true
+}
+
+pub fn sub_8000(cpu: &mut R6502, bus: &mut dyn Bus)
+{
+    let mut r = Regs::load(cpu);
+    let frame = r.sp;
+    r.pc = 0x8000;
+    loop
+    {
+        match r.pc
+        {
+            0x8000 =>
+            {
+                // $8000  ORA ($7D,X)
+                let pointer = (0x007D + r.x as u16) & 0x00FF;
+                let lo = recompiler::read(bus, pointer);
+                let hi = recompiler::read(bus, pointer + 1);
+                let addr = (hi as u16) << 8 | lo as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 6;
+                r.a |= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8002  ORA $0C
+                let data = recompiler::read(bus, 0x000C);
+                r.cycles += 3;
+                r.a |= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8004  ASL $73
+                let data = recompiler::read(bus, 0x0073);
+                r.cycles += 5;
+                let result = (data as u16) << 1;
+                r.assign(Flags::C, data & 0x80 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.set_if(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, 0x0073, result as u8);
+
+                // $8006  ORA #$93
+                let data: u8 = 0x93;
+                r.cycles += 2;
+                r.a |= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8008  ASL A
+                let data = r.a;
+                r.cycles += 2;
+                let result = (data as u16) << 1;
+                r.assign(Flags::C, data & 0x80 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.set_if(Flags::N, result & 0x80 != 0);
+                r.a = result as u8;
+
+                // $8009  ORA $056D
+                let data = recompiler::read(bus, 0x056D);
+                r.cycles += 4;
+                r.a |= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $800C  ASL $01D9
+                let data = recompiler::read(bus, 0x01D9);
+                r.cycles += 6;
+                let result = (data as u16) << 1;
+                r.assign(Flags::C, data & 0x80 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.set_if(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, 0x01D9, result as u8);
+
+                // $800F  ORA ($3E),Y
+                let sum = recompiler::read(bus, 0x003E) as u16 + r.y as u16;
+                let hi = recompiler::read(bus, 0x003F) as u16;
+                let addr = ((hi + (sum >> 8)) & 0x00FF) << 8 | (sum & 0x00FF);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 5;
+                if sum > 0xFF
+                {
+                    r.cycles += 1;
+                }
+                r.a |= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8011  ORA $5F,X
+                let addr = 0x005F + r.x as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                r.a |= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8013  ASL $EC,X
+                let addr = 0x00EC + r.x as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 6;
+                let result = (data as u16) << 1;
+                r.assign(Flags::C, data & 0x80 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.set_if(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, addr, result as u8);
+
+                // $8015  CLC
+                r.cycles += 2;
+                r.assign(Flags::C, false);
+
+                // $8016  ORA $0516,Y
+                let addr = 0x0516u16.wrapping_add(r.y as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0500
+                {
+                    r.cycles += 1;
+                }
+                r.a |= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8019  ORA $029B,X
+                let addr = 0x029Bu16.wrapping_add(r.x as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0200
+                {
+                    r.cycles += 1;
+                }
+                r.a |= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $801C  ASL $0660,X
+                let addr = 0x0660u16.wrapping_add(r.x as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 7;
+                let result = (data as u16) << 1;
+                r.assign(Flags::C, data & 0x80 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.set_if(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, addr, result as u8);
+
+                // $801F  AND ($26,X)
+                let pointer = (0x0026 + r.x as u16) & 0x00FF;
+                let lo = recompiler::read(bus, pointer);
+                let hi = recompiler::read(bus, pointer + 1);
+                let addr = (hi as u16) << 8 | lo as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 6;
+                r.a &= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8021  BIT $96
+                let data = recompiler::read(bus, 0x0096);
+                r.cycles += 3;
+                r.assign(Flags::Z, r.a & data == 0);
+                r.assign(Flags::V, data & 0x40 != 0);
+                r.assign(Flags::N, data & 0x80 != 0);
+
+                // $8023  AND $38
+                let data = recompiler::read(bus, 0x0038);
+                r.cycles += 3;
+                r.a &= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8025  ROL $C3
+                let data = recompiler::read(bus, 0x00C3);
+                r.cycles += 5;
+                let result = ((data as u16) << 1) ^ r.check(Flags::C) as u16;
+                r.assign(Flags::C, data & 0x80 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, 0x00C3, result as u8);
+
+                // $8027  AND #$E3
+                let data: u8 = 0xE3;
+                r.cycles += 2;
+                r.a &= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8029  ROL A
+                let data = r.a;
+                r.cycles += 2;
+                let result = ((data as u16) << 1) ^ r.check(Flags::C) as u16;
+                r.assign(Flags::C, data & 0x80 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                r.a = result as u8;
+
+                // $802A  BIT $0377
+                let data = recompiler::read(bus, 0x0377);
+                r.cycles += 4;
+                r.assign(Flags::Z, r.a & data == 0);
+                r.assign(Flags::V, data & 0x40 != 0);
+                r.assign(Flags::N, data & 0x80 != 0);
+
+                // $802D  AND $0128
+                let data = recompiler::read(bus, 0x0128);
+                r.cycles += 4;
+                r.a &= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8030  ROL $07D6
+                let data = recompiler::read(bus, 0x07D6);
+                r.cycles += 6;
+                let result = ((data as u16) << 1) ^ r.check(Flags::C) as u16;
+                r.assign(Flags::C, data & 0x80 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, 0x07D6, result as u8);
+
+                // $8033  AND ($1F),Y
+                let sum = recompiler::read(bus, 0x001F) as u16 + r.y as u16;
+                let hi = recompiler::read(bus, 0x0020) as u16;
+                let addr = ((hi + (sum >> 8)) & 0x00FF) << 8 | (sum & 0x00FF);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 5;
+                if sum > 0xFF
+                {
+                    r.cycles += 1;
+                }
+                r.a &= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8035  AND $A3,X
+                let addr = 0x00A3 + r.x as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                r.a &= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8037  ROL $B4,X
+                let addr = 0x00B4 + r.x as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 6;
+                let result = ((data as u16) << 1) ^ r.check(Flags::C) as u16;
+                r.assign(Flags::C, data & 0x80 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, addr, result as u8);
+
+                // $8039  SEC
+                r.cycles += 2;
+                r.assign(Flags::C, true);
+
+                // $803A  AND $039E,Y
+                let addr = 0x039Eu16.wrapping_add(r.y as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0300
+                {
+                    r.cycles += 1;
+                }
+                r.a &= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $803D  AND $075A,X
+                let addr = 0x075Au16.wrapping_add(r.x as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0700
+                {
+                    r.cycles += 1;
+                }
+                r.a &= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8040  ROL $07DD,X
+                let addr = 0x07DDu16.wrapping_add(r.x as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 7;
+                let result = ((data as u16) << 1) ^ r.check(Flags::C) as u16;
+                r.assign(Flags::C, data & 0x80 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, addr, result as u8);
+
+                // $8043  EOR ($42,X)
+                let pointer = (0x0042 + r.x as u16) & 0x00FF;
+                let lo = recompiler::read(bus, pointer);
+                let hi = recompiler::read(bus, pointer + 1);
+                let addr = (hi as u16) << 8 | lo as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 6;
+                r.a ^= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8045  EOR $93
+                let data = recompiler::read(bus, 0x0093);
+                r.cycles += 3;
+                r.a ^= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8047  LSR $71
+                let data = recompiler::read(bus, 0x0071);
+                r.cycles += 5;
+                let result = (data as u16) >> 1;
+                r.assign(Flags::C, data & 0x01 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, 0x0071, result as u8);
+
+                // $8049  EOR #$55
+                let data: u8 = 0x55;
+                r.cycles += 2;
+                r.a ^= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $804B  LSR A
+                let data = r.a;
+                r.cycles += 2;
+                let result = (data as u16) >> 1;
+                r.assign(Flags::C, data & 0x01 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                r.a = result as u8;
+
+                // $804C  EOR $01F7
+                let data = recompiler::read(bus, 0x01F7);
+                r.cycles += 4;
+                r.a ^= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $804F  LSR $06DE
+                let data = recompiler::read(bus, 0x06DE);
+                r.cycles += 6;
+                let result = (data as u16) >> 1;
+                r.assign(Flags::C, data & 0x01 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, 0x06DE, result as u8);
+
+                // $8052  EOR ($D6),Y
+                let sum = recompiler::read(bus, 0x00D6) as u16 + r.y as u16;
+                let hi = recompiler::read(bus, 0x00D7) as u16;
+                let addr = ((hi + (sum >> 8)) & 0x00FF) << 8 | (sum & 0x00FF);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 5;
+                if sum > 0xFF
+                {
+                    r.cycles += 1;
+                }
+                r.a ^= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8054  EOR $01,X
+                let addr = 0x0001 + r.x as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                r.a ^= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $8056  LSR $06,X
+                let addr = 0x0006 + r.x as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 6;
+                let result = (data as u16) >> 1;
+                r.assign(Flags::C, data & 0x01 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, addr, result as u8);
+
+                // $8058  CLI
+                r.cycles += 2;
+                r.assign(Flags::I, false);
+                if r.irq_pending && !r.check(Flags::I)
+                {
+                    r.pc = 0x8059;
+                    r.store(cpu);
+                    return recompiler::interpret(cpu, bus, frame);
+                }
+
+                // $8059  EOR $03D5,Y
+                let addr = 0x03D5u16.wrapping_add(r.y as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0300
+                {
+                    r.cycles += 1;
+                }
+                r.a ^= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $805C  EOR $03E5,X
+                let addr = 0x03E5u16.wrapping_add(r.x as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0300
+                {
+                    r.cycles += 1;
+                }
+                r.a ^= data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $805F  LSR $05B1,X
+                let addr = 0x05B1u16.wrapping_add(r.x as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 7;
+                let result = (data as u16) >> 1;
+                r.assign(Flags::C, data & 0x01 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, addr, result as u8);
+
+                // $8062  ADC ($A5,X)
+                let pointer = (0x00A5 + r.x as u16) & 0x00FF;
+                let lo = recompiler::read(bus, pointer);
+                let hi = recompiler::read(bus, pointer + 1);
+                let addr = (hi as u16) << 8 | lo as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 6;
+                let value = data;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.set_if(Flags::C, temp > 0xFF);
+                r.set_if(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $8064  ADC $86
+                let data = recompiler::read(bus, 0x0086);
+                r.cycles += 3;
+                let value = data;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.set_if(Flags::C, temp > 0xFF);
+                r.set_if(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $8066  ROR $96
+                let data = recompiler::read(bus, 0x0096);
+                r.cycles += 5;
+                let result = ((data as u16) >> 1) ^ ((r.check(Flags::C) as u16) << 7);
+                r.assign(Flags::C, data & 0x01 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, 0x0096, result as u8);
+
+                // $8068  ADC #$9B
+                let data: u8 = 0x9B;
+                r.cycles += 2;
+                let value = data;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.set_if(Flags::C, temp > 0xFF);
+                r.set_if(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $806A  ROR A
+                let data = r.a;
+                r.cycles += 2;
+                let result = ((data as u16) >> 1) ^ ((r.check(Flags::C) as u16) << 7);
+                r.assign(Flags::C, data & 0x01 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                r.a = result as u8;
+
+                // $806B  ADC $04B4
+                let data = recompiler::read(bus, 0x04B4);
+                r.cycles += 4;
+                let value = data;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.set_if(Flags::C, temp > 0xFF);
+                r.set_if(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $806E  ROR $054B
+                let data = recompiler::read(bus, 0x054B);
+                r.cycles += 6;
+                let result = ((data as u16) >> 1) ^ ((r.check(Flags::C) as u16) << 7);
+                r.assign(Flags::C, data & 0x01 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, 0x054B, result as u8);
+
+                // $8071  ADC ($65),Y
+                let sum = recompiler::read(bus, 0x0065) as u16 + r.y as u16;
+                let hi = recompiler::read(bus, 0x0066) as u16;
+                let addr = ((hi + (sum >> 8)) & 0x00FF) << 8 | (sum & 0x00FF);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 5;
+                if sum > 0xFF
+                {
+                    r.cycles += 1;
+                }
+                let value = data;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.set_if(Flags::C, temp > 0xFF);
+                r.set_if(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $8073  ADC $A3,X
+                let addr = 0x00A3 + r.x as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                let value = data;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.set_if(Flags::C, temp > 0xFF);
+                r.set_if(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $8075  ROR $39,X
+                let addr = 0x0039 + r.x as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 6;
+                let result = ((data as u16) >> 1) ^ ((r.check(Flags::C) as u16) << 7);
+                r.assign(Flags::C, data & 0x01 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, addr, result as u8);
+
+                // $8077  SEI
+                r.cycles += 2;
+                r.assign(Flags::I, true);
+
+                // $8078  ADC $00DB,Y
+                let addr = 0x00DBu16.wrapping_add(r.y as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0000
+                {
+                    r.cycles += 1;
+                }
+                let value = data;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.set_if(Flags::C, temp > 0xFF);
+                r.set_if(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $807B  ADC $00D0,X
+                let addr = 0x00D0u16.wrapping_add(r.x as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0000
+                {
+                    r.cycles += 1;
+                }
+                let value = data;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.set_if(Flags::C, temp > 0xFF);
+                r.set_if(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $807E  ROR $02A6,X
+                let addr = 0x02A6u16.wrapping_add(r.x as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 7;
+                let result = ((data as u16) >> 1) ^ ((r.check(Flags::C) as u16) << 7);
+                r.assign(Flags::C, data & 0x01 != 0);
+                r.assign(Flags::Z, result == 0);
+                r.assign(Flags::N, result & 0x80 != 0);
+                recompiler::write(bus, addr, result as u8);
+
+                // $8081  STA ($1E,X)
+                let pointer = (0x001E + r.x as u16) & 0x00FF;
+                let lo = recompiler::read(bus, pointer);
+                let hi = recompiler::read(bus, pointer + 1);
+                let addr = (hi as u16) << 8 | lo as u16;
+                recompiler::read(bus, addr);
+                r.cycles += 6;
+                recompiler::write(bus, addr, r.a);
+
+                // $8083  STY $79
+                recompiler::read(bus, 0x0079);
+                r.cycles += 3;
+                recompiler::write(bus, 0x0079, r.y);
+
+                // $8085  STA $68
+                recompiler::read(bus, 0x0068);
+                r.cycles += 3;
+                recompiler::write(bus, 0x0068, r.a);
+
+                // $8087  STX $38
+                recompiler::read(bus, 0x0038);
+                r.cycles += 3;
+                recompiler::write(bus, 0x0038, r.x);
+
+                // $8089  DEY
+                r.cycles += 2;
+                r.y = r.y.wrapping_sub(1);
+                r.assign(Flags::Z, r.y == 0);
+                r.assign(Flags::N, r.y & 0x80 != 0);
+
+                // $808A  TXA
+                r.cycles += 2;
+                r.a = r.x;
+                r.assign(Flags::Z, r.a == 0);
+                r.assign(Flags::N, r.a & 0x80 != 0);
+
+                // $808B  STY $0365
+                recompiler::read(bus, 0x0365);
+                r.cycles += 4;
+                recompiler::write(bus, 0x0365, r.y);
+
+                // $808E  STA $01C4
+                recompiler::read(bus, 0x01C4);
+                r.cycles += 4;
+                recompiler::write(bus, 0x01C4, r.a);
+
+                // $8091  STX $0740
+                recompiler::read(bus, 0x0740);
+                r.cycles += 4;
+                recompiler::write(bus, 0x0740, r.x);
+
+                // $8094  STA ($EE),Y
+                let sum = recompiler::read(bus, 0x00EE) as u16 + r.y as u16;
+                let hi = recompiler::read(bus, 0x00EF) as u16;
+                let addr = ((hi + (sum >> 8)) & 0x00FF) << 8 | (sum & 0x00FF);
+                recompiler::read(bus, addr);
+                r.cycles += 6;
+                recompiler::write(bus, addr, r.a);
+
+                // $8096  STY $86,X
+                let addr = 0x0086 + r.x as u16;
+                recompiler::read(bus, addr);
+                r.cycles += 4;
+                recompiler::write(bus, addr, r.y);
+
+                // $8098  STA $55,X
+                let addr = 0x0055 + r.x as u16;
+                recompiler::read(bus, addr);
+                r.cycles += 4;
+                recompiler::write(bus, addr, r.a);
+
+                // $809A  STX $32,Y
+                let addr = 0x0032 + r.y as u16;
+                recompiler::read(bus, addr);
+                r.cycles += 4;
+                recompiler::write(bus, addr, r.x);
+
+                // $809C  TYA
+                r.cycles += 2;
+                r.a = r.y;
+                r.assign(Flags::Z, r.a == 0);
+                r.assign(Flags::N, r.a & 0x80 != 0);
+
+                // $809D  STA $03D1,Y
+                let addr = 0x03D1u16.wrapping_add(r.y as u16);
+                recompiler::read(bus, addr);
+                r.cycles += 5;
+                recompiler::write(bus, addr, r.a);
+
+                // $80A0  STA $0663,X
+                let addr = 0x0663u16.wrapping_add(r.x as u16);
+                recompiler::read(bus, addr);
+                r.cycles += 5;
+                recompiler::write(bus, addr, r.a);
+
+                // $80A3  LDY #$01
+                let data: u8 = 0x01;
+                r.cycles += 2;
+                r.y = data;
+                r.set_if(Flags::Z, r.y == 0);
+                r.set_if(Flags::N, r.y & 0x80 != 0);
+
+                // $80A5  LDA ($12,X)
+                let pointer = (0x0012 + r.x as u16) & 0x00FF;
+                let lo = recompiler::read(bus, pointer);
+                let hi = recompiler::read(bus, pointer + 1);
+                let addr = (hi as u16) << 8 | lo as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 6;
+                r.a = data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $80A7  LDX #$0D
+                let data: u8 = 0x0D;
+                r.cycles += 2;
+                r.x = data;
+                r.set_if(Flags::Z, r.x == 0);
+                r.set_if(Flags::N, r.x & 0x80 != 0);
+
+                // $80A9  LDY $E7
+                let data = recompiler::read(bus, 0x00E7);
+                r.cycles += 3;
+                r.y = data;
+                r.set_if(Flags::Z, r.y == 0);
+                r.set_if(Flags::N, r.y & 0x80 != 0);
+
+                // $80AB  LDA $67
+                let data = recompiler::read(bus, 0x0067);
+                r.cycles += 3;
+                r.a = data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $80AD  LDX $94
+                let data = recompiler::read(bus, 0x0094);
+                r.cycles += 3;
+                r.x = data;
+                r.set_if(Flags::Z, r.x == 0);
+                r.set_if(Flags::N, r.x & 0x80 != 0);
+
+                // $80AF  TAY
+                r.cycles += 2;
+                r.y = r.a;
+                r.assign(Flags::Z, r.y == 0);
+                r.assign(Flags::N, r.y & 0x80 != 0);
+
+                // $80B0  LDA #$68
+                let data: u8 = 0x68;
+                r.cycles += 2;
+                r.a = data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $80B2  TAX
+                r.cycles += 2;
+                r.x = r.a;
+                r.assign(Flags::Z, r.x == 0);
+                r.assign(Flags::N, r.x & 0x80 != 0);
+
+                // $80B3  LDY $0666
+                let data = recompiler::read(bus, 0x0666);
+                r.cycles += 4;
+                r.y = data;
+                r.set_if(Flags::Z, r.y == 0);
+                r.set_if(Flags::N, r.y & 0x80 != 0);
+
+                // $80B6  LDA $061E
+                let data = recompiler::read(bus, 0x061E);
+                r.cycles += 4;
+                r.a = data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $80B9  LDX $070F
+                let data = recompiler::read(bus, 0x070F);
+                r.cycles += 4;
+                r.x = data;
+                r.set_if(Flags::Z, r.x == 0);
+                r.set_if(Flags::N, r.x & 0x80 != 0);
+
+                // $80BC  LDA ($7D),Y
+                let sum = recompiler::read(bus, 0x007D) as u16 + r.y as u16;
+                let hi = recompiler::read(bus, 0x007E) as u16;
+                let addr = ((hi + (sum >> 8)) & 0x00FF) << 8 | (sum & 0x00FF);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 5;
+                if sum > 0xFF
+                {
+                    r.cycles += 1;
+                }
+                r.a = data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $80BE  LDY $40,X
+                let addr = 0x0040 + r.x as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                r.y = data;
+                r.set_if(Flags::Z, r.y == 0);
+                r.set_if(Flags::N, r.y & 0x80 != 0);
+
+                // $80C0  LDA $23,X
+                let addr = 0x0023 + r.x as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                r.a = data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $80C2  LDX $CA,Y
+                let addr = 0x00CA + r.y as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                r.x = data;
+                r.set_if(Flags::Z, r.x == 0);
+                r.set_if(Flags::N, r.x & 0x80 != 0);
+
+                // $80C4  CLV
+                r.cycles += 2;
+                r.assign(Flags::V, false);
+
+                // $80C5  LDA $056A,Y
+                let addr = 0x056Au16.wrapping_add(r.y as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0500
+                {
+                    r.cycles += 1;
+                }
+                r.a = data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $80C8  TSX
+                r.cycles += 2;
+                r.x = r.sp.wrapping_sub(0x100) as u8;
+
+                // $80C9  LDY $07B5,X
+                let addr = 0x07B5u16.wrapping_add(r.x as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0700
+                {
+                    r.cycles += 1;
+                }
+                r.y = data;
+                r.set_if(Flags::Z, r.y == 0);
+                r.set_if(Flags::N, r.y & 0x80 != 0);
+
+                // $80CC  LDA $05CC,X
+                let addr = 0x05CCu16.wrapping_add(r.x as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0500
+                {
+                    r.cycles += 1;
+                }
+                r.a = data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $80CF  LDX $074C,Y
+                let addr = 0x074Cu16.wrapping_add(r.y as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0700
+                {
+                    r.cycles += 1;
+                }
+                r.x = data;
+                r.set_if(Flags::Z, r.x == 0);
+                r.set_if(Flags::N, r.x & 0x80 != 0);
+
+                // $80D2  CPY #$B0
+                let data: u8 = 0xB0;
+                r.cycles += 2;
+                r.assign(Flags::C, r.y >= data);
+                r.assign(Flags::Z, r.y == data);
+                r.assign(Flags::N, r.y.wrapping_sub(data) & 0x80 != 0);
+
+                // $80D4  CMP ($9D,X)
+                let pointer = (0x009D + r.x as u16) & 0x00FF;
+                let lo = recompiler::read(bus, pointer);
+                let hi = recompiler::read(bus, pointer + 1);
+                let addr = (hi as u16) << 8 | lo as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 6;
+                r.assign(Flags::C, r.a >= data);
+                r.assign(Flags::Z, r.a == data);
+                r.assign(Flags::N, r.a < data);
+
+                // $80D6  CPY $3E
+                let data = recompiler::read(bus, 0x003E);
+                r.cycles += 3;
+                r.assign(Flags::C, r.y >= data);
+                r.assign(Flags::Z, r.y == data);
+                r.assign(Flags::N, r.y.wrapping_sub(data) & 0x80 != 0);
+
+                // $80D8  CMP $16
+                let data = recompiler::read(bus, 0x0016);
+                r.cycles += 3;
+                r.assign(Flags::C, r.a >= data);
+                r.assign(Flags::Z, r.a == data);
+                r.assign(Flags::N, r.a < data);
+
+                // $80DA  DEC $08
+                recompiler::read(bus, 0x0008);
+                r.cycles += 5;
+                let value = recompiler::read(bus, 0x0008).wrapping_sub(1);
+                recompiler::write(bus, 0x0008, value);
+                r.assign(Flags::Z, value == 0);
+                r.assign(Flags::N, value & 0x80 != 0);
+
+                // $80DC  INY
+                r.cycles += 2;
+                r.y = r.y.wrapping_add(1);
+                r.assign(Flags::Z, r.y == 0);
+                r.assign(Flags::N, r.y & 0x80 != 0);
+
+                // $80DD  CMP #$60
+                let data: u8 = 0x60;
+                r.cycles += 2;
+                r.assign(Flags::C, r.a >= data);
+                r.assign(Flags::Z, r.a == data);
+                r.assign(Flags::N, r.a < data);
+
+                // $80DF  DEX
+                r.cycles += 2;
+                r.x = r.x.wrapping_sub(1);
+                r.assign(Flags::Z, r.x == 0);
+                r.assign(Flags::N, r.x & 0x80 != 0);
+
+                // $80E0  CPY $04C7
+                let data = recompiler::read(bus, 0x04C7);
+                r.cycles += 4;
+                r.assign(Flags::C, r.y >= data);
+                r.assign(Flags::Z, r.y == data);
+                r.assign(Flags::N, r.y.wrapping_sub(data) & 0x80 != 0);
+
+                // $80E3  CMP $0227
+                let data = recompiler::read(bus, 0x0227);
+                r.cycles += 4;
+                r.assign(Flags::C, r.a >= data);
+                r.assign(Flags::Z, r.a == data);
+                r.assign(Flags::N, r.a < data);
+
+                // $80E6  DEC $00EC
+                recompiler::read(bus, 0x00EC);
+                r.cycles += 6;
+                let value = recompiler::read(bus, 0x00EC).wrapping_sub(1);
+                recompiler::write(bus, 0x00EC, value);
+                r.assign(Flags::Z, value == 0);
+                r.assign(Flags::N, value & 0x80 != 0);
+
+                // $80E9  CMP ($F8),Y
+                let sum = recompiler::read(bus, 0x00F8) as u16 + r.y as u16;
+                let hi = recompiler::read(bus, 0x00F9) as u16;
+                let addr = ((hi + (sum >> 8)) & 0x00FF) << 8 | (sum & 0x00FF);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 5;
+                if sum > 0xFF
+                {
+                    r.cycles += 1;
+                }
+                r.assign(Flags::C, r.a >= data);
+                r.assign(Flags::Z, r.a == data);
+                r.assign(Flags::N, r.a < data);
+
+                // $80EB  CMP $44,X
+                let addr = 0x0044 + r.x as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                r.assign(Flags::C, r.a >= data);
+                r.assign(Flags::Z, r.a == data);
+                r.assign(Flags::N, r.a < data);
+
+                // $80ED  DEC $12,X
+                let addr = 0x0012 + r.x as u16;
+                recompiler::read(bus, addr);
+                r.cycles += 6;
+                let value = recompiler::read(bus, addr).wrapping_sub(1);
+                recompiler::write(bus, addr, value);
+                r.assign(Flags::Z, value == 0);
+                r.assign(Flags::N, value & 0x80 != 0);
+
+                // $80EF  CLD
+                r.cycles += 2;
+                r.assign(Flags::D, false);
+
+                // $80F0  CMP $02D4,Y
+                let addr = 0x02D4u16.wrapping_add(r.y as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0200
+                {
+                    r.cycles += 1;
+                }
+                r.assign(Flags::C, r.a >= data);
+                r.assign(Flags::Z, r.a == data);
+                r.assign(Flags::N, r.a < data);
+
+                // $80F3  CMP $0515,X
+                let addr = 0x0515u16.wrapping_add(r.x as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0500
+                {
+                    r.cycles += 1;
+                }
+                r.assign(Flags::C, r.a >= data);
+                r.assign(Flags::Z, r.a == data);
+                r.assign(Flags::N, r.a < data);
+
+                // $80F6  DEC $04D6,X
+                let addr = 0x04D6u16.wrapping_add(r.x as u16);
+                recompiler::read(bus, addr);
+                r.cycles += 7;
+                let value = recompiler::read(bus, addr).wrapping_sub(1);
+                recompiler::write(bus, addr, value);
+                r.assign(Flags::Z, value == 0);
+                r.assign(Flags::N, value & 0x80 != 0);
+
+                // $80F9  CPX #$2F
+                let data: u8 = 0x2F;
+                r.cycles += 2;
+                r.assign(Flags::C, r.x >= data);
+                r.assign(Flags::Z, r.x == data);
+                r.assign(Flags::N, r.x.wrapping_sub(data) & 0x80 != 0);
+
+                // $80FB  SBC ($CA,X)
+                let pointer = (0x00CA + r.x as u16) & 0x00FF;
+                let lo = recompiler::read(bus, pointer);
+                let hi = recompiler::read(bus, pointer + 1);
+                let addr = (hi as u16) << 8 | lo as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 6;
+                let value = data ^ 0xFF;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.assign(Flags::C, temp > 0xFF);
+                r.assign(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $80FD  CPX $34
+                let data = recompiler::read(bus, 0x0034);
+                r.cycles += 3;
+                r.assign(Flags::C, r.x >= data);
+                r.assign(Flags::Z, r.x == data);
+                r.assign(Flags::N, r.x.wrapping_sub(data) & 0x80 != 0);
+
+                // $80FF  SBC $EF
+                let data = recompiler::read(bus, 0x00EF);
+                r.cycles += 3;
+                let value = data ^ 0xFF;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.assign(Flags::C, temp > 0xFF);
+                r.assign(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $8101  INC $15
+                recompiler::read(bus, 0x0015);
+                r.cycles += 5;
+                let value = recompiler::read(bus, 0x0015).wrapping_add(1);
+                recompiler::write(bus, 0x0015, value);
+                r.assign(Flags::Z, value == 0);
+                r.assign(Flags::N, value & 0x80 != 0);
+
+                // $8103  INX
+                r.cycles += 2;
+                r.x = r.x.wrapping_add(1);
+                r.assign(Flags::Z, r.x == 0);
+                r.assign(Flags::N, r.x & 0x80 != 0);
+
+                // $8104  SBC #$0B
+                let data: u8 = 0x0B;
+                r.cycles += 2;
+                let value = data ^ 0xFF;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.assign(Flags::C, temp > 0xFF);
+                r.assign(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $8106  NOP
+                r.cycles += 2;
+
+                // $8107  CPX $001D
+                let data = recompiler::read(bus, 0x001D);
+                r.cycles += 4;
+                r.assign(Flags::C, r.x >= data);
+                r.assign(Flags::Z, r.x == data);
+                r.assign(Flags::N, r.x.wrapping_sub(data) & 0x80 != 0);
+
+                // $810A  SBC $05CE
+                let data = recompiler::read(bus, 0x05CE);
+                r.cycles += 4;
+                let value = data ^ 0xFF;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.assign(Flags::C, temp > 0xFF);
+                r.assign(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $810D  INC $020F
+                recompiler::read(bus, 0x020F);
+                r.cycles += 6;
+                let value = recompiler::read(bus, 0x020F).wrapping_add(1);
+                recompiler::write(bus, 0x020F, value);
+                r.assign(Flags::Z, value == 0);
+                r.assign(Flags::N, value & 0x80 != 0);
+
+                // $8110  SBC ($2B),Y
+                let sum = recompiler::read(bus, 0x002B) as u16 + r.y as u16;
+                let hi = recompiler::read(bus, 0x002C) as u16;
+                let addr = ((hi + (sum >> 8)) & 0x00FF) << 8 | (sum & 0x00FF);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 5;
+                if sum > 0xFF
+                {
+                    r.cycles += 1;
+                }
+                let value = data ^ 0xFF;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.assign(Flags::C, temp > 0xFF);
+                r.assign(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $8112  SBC $CC,X
+                let addr = 0x00CC + r.x as u16;
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                let value = data ^ 0xFF;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.assign(Flags::C, temp > 0xFF);
+                r.assign(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $8114  INC $04,X
+                let addr = 0x0004 + r.x as u16;
+                recompiler::read(bus, addr);
+                r.cycles += 6;
+                let value = recompiler::read(bus, addr).wrapping_add(1);
+                recompiler::write(bus, addr, value);
+                r.assign(Flags::Z, value == 0);
+                r.assign(Flags::N, value & 0x80 != 0);
+
+                // $8116  SED
+                r.cycles += 2;
+                r.assign(Flags::D, true);
+
+                // $8117  SBC $07AE,Y
+                let addr = 0x07AEu16.wrapping_add(r.y as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0700
+                {
+                    r.cycles += 1;
+                }
+                let value = data ^ 0xFF;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.assign(Flags::C, temp > 0xFF);
+                r.assign(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $811A  SBC $047A,X
+                let addr = 0x047Au16.wrapping_add(r.x as u16);
+                let data = recompiler::read(bus, addr);
+                r.cycles += 4;
+                if addr & 0xFF00 != 0x0400
+                {
+                    r.cycles += 1;
+                }
+                let value = data ^ 0xFF;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.assign(Flags::C, temp > 0xFF);
+                r.assign(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $811D  INC $032A,X
+                let addr = 0x032Au16.wrapping_add(r.x as u16);
+                recompiler::read(bus, addr);
+                r.cycles += 7;
+                let value = recompiler::read(bus, addr).wrapping_add(1);
+                recompiler::write(bus, addr, value);
+                r.assign(Flags::Z, value == 0);
+                r.assign(Flags::N, value & 0x80 != 0);
+
+                // $8120  RTS
+                r.cycles += 6;
+                if r.sp == 0x01FF
+                {
+                    r.stopped = true;
+                    r.pc = 0x8121;
+                }
+                else
+                {
+                    r.sp = r.sp.wrapping_add(1);
+                    let lo = recompiler::read(bus, r.sp);
+                    r.sp = r.sp.wrapping_add(1);
+                    let hi = recompiler::read(bus, r.sp);
+                    r.pc = ((hi as u16) << 8 | lo as u16).wrapping_add(1);
+                }
+            }
+            _ =>
+            {
+                r.store(cpu);
+                return recompiler::interpret(cpu, bus, frame);
+            }
+        }
+
+        if r.stopped || r.sp > frame
+        {
+            r.store(cpu);
+            return;
+        }
+    }
+}
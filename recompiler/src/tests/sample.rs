@@ -0,0 +1,186 @@
+// Generated by the RE6502 static recompiler
+// Routines: $0200, $0220
+
+use re6502::r6502::{R6502, Bus, Flags};
+use re6502::r6502::recompiler::{self, Regs};
+
+// Returns false if there is no routine at addr
+pub fn call(addr: u16, cpu: &mut R6502, bus: &mut dyn Bus) -> bool
+{
+    match addr
+    {
+        0x0200 => sub_0200(cpu, bus),
+        0x0220 => sub_0220(cpu, bus),
+        _ => return false,
+    }
+
+    true
+}
+
+pub fn sub_0200(cpu: &mut R6502, bus: &mut dyn Bus)
+{
+    let mut r = Regs::load(cpu);
+    let frame = r.sp;
+    r.pc = 0x0200;
+    loop
+    {
+        match r.pc
+        {
+            0x0200 =>
+            {
+                // $0200  LDX #$05
+                let data: u8 = 0x05;
+                r.cycles += 2;
+                r.x = data;
+                r.set_if(Flags::Z, r.x == 0);
+                r.set_if(Flags::N, r.x & 0x80 != 0);
+
+                // $0202  LDA #$00
+                let data: u8 = 0x00;
+                r.cycles += 2;
+                r.a = data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+                r.pc = 0x0204;
+            }
+            0x0204 =>
+            {
+                // $0204  JSR $0220
+                recompiler::read(bus, 0x0220);
+                r.cycles += 6;
+                recompiler::write(bus, r.sp, 0x02);
+                r.sp = r.sp.wrapping_sub(1);
+                recompiler::write(bus, r.sp, 0x06);
+                r.sp = r.sp.wrapping_sub(1);
+                r.pc = 0x0220;
+                r.store(cpu);
+                sub_0220(cpu, bus);
+                r = Regs::load(cpu);
+            }
+            0x0207 =>
+            {
+                // $0207  DEX
+                r.cycles += 2;
+                r.x = r.x.wrapping_sub(1);
+                r.assign(Flags::Z, r.x == 0);
+                r.assign(Flags::N, r.x & 0x80 != 0);
+
+                // $0208  BNE $0204
+                r.cycles += 2;
+                if !r.check(Flags::Z)
+                {
+                    r.cycles += 1;
+                    r.pc = 0x0204;
+                }
+                else
+                {
+                    r.pc = 0x020A;
+                }
+            }
+            0x020A =>
+            {
+                // $020A  STA $40
+                recompiler::read(bus, 0x0040);
+                r.cycles += 3;
+                recompiler::write(bus, 0x0040, r.a);
+
+                // $020C  LDA #$17
+                let data: u8 = 0x17;
+                r.cycles += 2;
+                r.a = data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $020E  STA $41
+                recompiler::read(bus, 0x0041);
+                r.cycles += 3;
+                recompiler::write(bus, 0x0041, r.a);
+
+                // $0210  LDA #$02
+                let data: u8 = 0x02;
+                r.cycles += 2;
+                r.a = data;
+                r.set_if(Flags::Z, r.a == 0);
+                r.set_if(Flags::N, r.a & 0x80 != 0);
+
+                // $0212  STA $42
+                recompiler::read(bus, 0x0042);
+                r.cycles += 3;
+                recompiler::write(bus, 0x0042, r.a);
+
+                // $0214  JMP ($0041)
+                r.pc = 0x0214;
+                r.store(cpu);
+                return recompiler::interpret(cpu, bus, frame);
+            }
+            _ =>
+            {
+                r.store(cpu);
+                return recompiler::interpret(cpu, bus, frame);
+            }
+        }
+
+        if r.stopped || r.sp > frame
+        {
+            r.store(cpu);
+            return;
+        }
+    }
+}
+
+pub fn sub_0220(cpu: &mut R6502, bus: &mut dyn Bus)
+{
+    let mut r = Regs::load(cpu);
+    let frame = r.sp;
+    r.pc = 0x0220;
+    loop
+    {
+        match r.pc
+        {
+            0x0220 =>
+            {
+                // $0220  CLC
+                r.cycles += 2;
+                r.assign(Flags::C, false);
+
+                // $0221  ADC #$03
+                let data: u8 = 0x03;
+                r.cycles += 2;
+                let value = data;
+                let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;
+                r.set_if(Flags::C, temp > 0xFF);
+                r.set_if(Flags::Z, temp == 0);
+                r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);
+                r.assign(Flags::N, temp & 0x80 != 0);
+                r.a = temp as u8;
+
+                // $0223  RTS
+                r.cycles += 6;
+                if r.sp == 0x01FF
+                {
+                    r.stopped = true;
+                    r.pc = 0x0224;
+                }
+                else
+                {
+                    r.sp = r.sp.wrapping_add(1);
+                    let lo = recompiler::read(bus, r.sp);
+                    r.sp = r.sp.wrapping_add(1);
+                    let hi = recompiler::read(bus, r.sp);
+                    r.pc = ((hi as u16) << 8 | lo as u16).wrapping_add(1);
+                }
+            }
+            _ =>
+            {
+                r.store(cpu);
+                return recompiler::interpret(cpu, bus, frame);
+            }
+        }
+
+        if r.stopped || r.sp > frame
+        {
+            r.store(cpu);
+            return;
+        }
+    }
+}
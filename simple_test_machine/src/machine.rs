@@ -1,6 +1,9 @@
+#![allow(dead_code)]
 
 
 use std::{io, str};
+use std::cell::RefCell;
+use std::rc::Rc;
 
 //|||||||||||||||||||||||||||||||||||||||||||||||||||||||||||||||||||||
 //				MEMORY ADDRESSES AND FLAG MASKS
@@ -27,11 +30,20 @@ pub const READ_OVERFLOW_FLAG:   u8 = 0x20;    // This flag is set after reading
 /////////////////////////////////////////////////////////////////////
 
 use re6502::r6502::{R6502, Bus, Flags};
+use re6502::r6502::throttle::{Throttle, DriftStats};
+use re6502::r6502::snapshot::{self, Snapshot, SnapshotWriter, SnapshotReader, SnapshotError};
+use re6502::r6502::rewind::Rewind;
+use re6502::r6502::write_history::{WriteHistory, WriteRecord};
+use re6502::r6502::observer::{Observer, ObserverId};
+use re6502::r6502::replay::{Recording, Replay, InputEvent};
+use re6502::r6502::trace_diff::{TraceDiff, Mismatch};
+use re6502::r6502::debug_server::Target;
 
 // The Bus is how you connect other components to the RE6502 cpu.
 // At minimium the read() and write() traits must be implement for the Bus.
 // The following is a very basic Test Bus implementation.
 
+#[derive(Clone)]
 struct TBus
 {
     memory: [u8; 64 * 1024]
@@ -72,6 +84,32 @@ impl Bus for TBus
     {
         self.memory[addr as usize] = value;    
     }
+
+    fn page(&self, page: u8) -> Option<&[u8; 256]>
+    {
+        let start = (page as usize) << 8;
+        self.memory[start..start + 256].try_into().ok()
+    }
+
+    fn page_mut(&mut self, page: u8) -> Option<&mut [u8; 256]>
+    {
+        let start = (page as usize) << 8;
+        (&mut self.memory[start..start + 256]).try_into().ok()
+    }
+}
+
+// Memory is the only state on the bus, the console doesn't keep any between clocks
+impl Snapshot for TBus
+{
+    fn save(&self, out: &mut SnapshotWriter)
+    {
+        out.bytes(&self.memory);
+    }
+
+    fn load(&mut self, data: &mut SnapshotReader) -> Result<(), SnapshotError>
+    {
+        data.bytes_into(&mut self.memory)
+    }
 }
 
 //|||||||||||||||||||||||||||||||||||||||||||||||||||||||||||||||||||||
@@ -81,10 +119,11 @@ struct Console {}
 
 impl Console
 {
-    fn clock(_cpu: &mut R6502, bus: &mut TBus )
+    // Fails if a replay has no recorded line for an input the program asks for
+    fn clock(_cpu: &mut R6502, bus: &mut TBus, inputs: &mut Inputs) -> Result<(), String>
     {
         Self::clock_output(_cpu, bus);
-        Self::clock_input(_cpu, bus);
+        Self::clock_input(_cpu, bus, inputs)
     }
 
     fn clock_output(_cpu: &mut R6502, bus: &mut TBus )
@@ -126,7 +165,7 @@ impl Console
 
     }
 
-    fn clock_input(_cpu: &mut R6502, bus: &mut TBus)
+    fn clock_input(_cpu: &mut R6502, bus: &mut TBus, inputs: &mut Inputs) -> Result<(), String>
     {
         // Check input request flag
         let mut value = bus.read(CONSOLE_FLAGS_ADDR);
@@ -135,9 +174,7 @@ impl Console
             // reset the flag
             value &= !(READ_LINE_FLAG);
 
-            let mut buffer = String::new();
-            let stdin = io::stdin();
-            stdin.read_line(&mut buffer).expect("Failed to read input from the console");
+            let buffer = inputs.read_line(_cpu.cycles())?;
 
             // Make sure the string will fit in the input buffer
             if (buffer.len() + 1) as u16 >= INPUT_BUF_SIZE
@@ -166,6 +203,47 @@ impl Console
             bus.write(CONSOLE_FLAGS_ADDR, value);
 
         }
+
+        Ok(())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////
+//				INPUTS
+/////////////////////////////////////////////////////////////////////
+
+// Where the machine's inputs come from. The only ones it has
+// are lines typed on the console and interrupts.
+enum Inputs
+{
+    Live,
+    Recording(Recording),
+    Replaying(Replay),
+}
+
+impl Inputs
+{
+    fn read_line(&mut self, cycle: u64) -> Result<String, String>
+    {
+        if let Inputs::Replaying(replay) = self
+        {
+            return match replay.take(cycle)?
+            {
+                InputEvent::Input(line) => Ok(String::from_utf8_lossy(&line).to_string()),
+                event => Err(format!("replay out of sync: input needed at cycle {}, recorded event is {:?}", cycle, event)),
+            };
+        }
+
+        let mut buffer = String::new();
+        let stdin = io::stdin();
+        stdin.read_line(&mut buffer).expect("Failed to read input from the console");
+
+        if let Inputs::Recording(recording) = self
+        {
+            recording.record(cycle, InputEvent::Input(buffer.as_bytes().to_vec()));
+        }
+
+        Ok(buffer)
     }
 }
 
@@ -177,14 +255,21 @@ pub struct TestMachine
 {
     bus: TBus,
     cpu: R6502,
+    throttle: Option<Throttle>,
+    rewind: Option<Rewind>,
+    write_history: Option<(ObserverId, Rc<RefCell<WriteHistory>>)>,
+    inputs: Inputs,
 
+    // Why a replay couldn't go on, the machine won't run any further
+    replay_error: Option<String>,
 }
 
 impl TestMachine
 {
     pub fn new() -> TestMachine
     {
-        TestMachine { bus: TBus::new(), cpu: R6502::new() }
+        TestMachine { bus: TBus::new(), cpu: R6502::new(), throttle: None, rewind: None, write_history: None, inputs: Inputs::Live,
+                        replay_error: None }
     }
 
     pub fn reset(&mut self)
@@ -206,14 +291,306 @@ impl TestMachine
         self.reset();
     }
 
+    // Direct access for debuggers and other tools
+    pub fn cpu(&self) -> &R6502
+    {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut R6502
+    {
+        &mut self.cpu
+    }
+
+    pub fn bus(&self) -> &dyn Bus
+    {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut dyn Bus
+    {
+        &mut self.bus
+    }
+
+    // The program has returned from its last RTS or run a BRK, or a replay went wrong
+    pub fn is_finished(&self) -> bool
+    {
+        self.cpu.is_program_stopped() || self.cpu.check_flag(Flags::B) != 0 || self.replay_error.is_some()
+    }
+
+    // Set when the program asked for input the replay doesn't have
+    pub fn replay_error(&self) -> Option<&str>
+    {
+        self.replay_error.as_deref()
+    }
+
+    // Snapshot of the cpu and memory that load_state() can pick up from
+    pub fn save_state(&self) -> Vec<u8>
+    {
+        snapshot::save_state(&self.cpu, &self.bus)
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), SnapshotError>
+    {
+        snapshot::load_state(&mut self.cpu, &mut self.bus, state)
+    }
+
+    // Record the program as it runs so it can be stepped back through
+    pub fn set_rewind(&mut self, enabled: bool)
+    {
+        self.rewind = if enabled { Some(Rewind::new()) } else { None };
+    }
+
+    // Undo the last instruction, returns false if there's no more history
+    pub fn step_back(&mut self) -> bool
+    {
+        match self.rewind.as_mut()
+        {
+            Some(rewind) => rewind.step_back(&mut self.cpu, &mut self.bus),
+            None => false,
+        }
+    }
+
+    // Step back to the last time the cpu was at one of the breakpoints
+    pub fn reverse_continue(&mut self, breakpoints: &[u16]) -> bool
+    {
+        match self.rewind.as_mut()
+        {
+            Some(rewind) => rewind.reverse_continue(&mut self.cpu, &mut self.bus, breakpoints),
+            None => false,
+        }
+    }
+
+    // Go back to cycle, or as close before it as the history allows.
+    // Returns the cycle the machine is at now.
+    pub fn rewind_to(&mut self, cycle: u64) -> Option<u64>
+    {
+        self.rewind.as_mut()?.rewind_to(&mut self.cpu, &mut self.bus, cycle)
+    }
+
+    // Record every input from here on, starting from the current state
+    pub fn start_recording(&mut self)
+    {
+        self.inputs = Inputs::Recording(Recording::new(self.save_state()));
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Recording>
+    {
+        match std::mem::replace(&mut self.inputs, Inputs::Live)
+        {
+            Inputs::Recording(recording) => Some(recording),
+            _ => None,
+        }
+    }
+
+    // Go back to where the recording started and take inputs from it instead of the console
+    pub fn start_replay(&mut self, recording: &Recording) -> Result<(), SnapshotError>
+    {
+        self.load_state(&recording.start)?;
+        self.inputs = Inputs::Replaying(Replay::new(recording));
+        self.replay_error = None;
+        Ok(())
+    }
+
+    // Interrupts raised from outside are recorded like any other input.
+    // During a replay they come from the recording instead.
+    pub fn irq(&mut self)
+    {
+        self.interrupt(InputEvent::Irq);
+    }
+
+    pub fn nmi(&mut self)
+    {
+        self.interrupt(InputEvent::Nmi);
+    }
+
+    fn interrupt(&mut self, event: InputEvent)
+    {
+        match &mut self.inputs
+        {
+            Inputs::Replaying(_) => return,
+            Inputs::Recording(recording) => recording.record(self.cpu.cycles(), event.clone()),
+            Inputs::Live => (),
+        }
+
+        self.raise(event);
+    }
+
+    fn raise(&mut self, event: InputEvent)
+    {
+        match event
+        {
+            InputEvent::Irq => self.cpu.irq(&mut self.bus),
+            InputEvent::Nmi => self.cpu.nmi(&mut self.bus),
+            _ => (),
+        }
+    }
+
+    // Attach a tracer, profiler or any other observer to the cpu
+    pub fn add_observer<O: Observer + 'static>(&mut self, observer: O) -> ObserverId
+    {
+        self.cpu.add_observer(observer)
+    }
+
+    pub fn remove_observer(&mut self, id: ObserverId) -> bool
+    {
+        self.cpu.remove_observer(id)
+    }
+
+    // Remember the last depth writes to each address, None turns it off
+    pub fn set_write_history(&mut self, depth: Option<usize>)
+    {
+        if let Some((id, _)) = self.write_history.take()
+        {
+            self.cpu.remove_observer(id);
+        }
+
+        if let Some(depth) = depth
+        {
+            let history = Rc::new(RefCell::new(WriteHistory::new(depth)));
+            let id = self.cpu.add_observer(history.clone());
+            self.write_history = Some((id, history));
+        }
+    }
+
+    // Writes to addr, newest first
+    pub fn write_history(&self, addr: u16) -> Vec<WriteRecord>
+    {
+        match self.write_history.as_ref()
+        {
+            Some((_, history)) => history.borrow().history(addr).copied().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Run programs at the given clock rate in Hz instead of as fast as possible
+    pub fn set_clock_rate(&mut self, hz: f64)
+    {
+        self.throttle = Some(Throttle::new(hz));
+    }
+
+    // How well the clock rate was kept up with, if there is one
+    pub fn drift_stats(&self) -> Option<DriftStats>
+    {
+        self.throttle.as_ref().map(|t| t.stats())
+    }
+
+    // Run the program checking every instruction against a reference trace, until
+    // the end of the reference or the first mismatch
+    pub fn diff_trace(&mut self, diff: &mut TraceDiff) -> Result<usize, Mismatch>
+    {
+        while !diff.is_finished()
+        {
+            diff.check(&self.cpu, &self.bus)?;
+            self.cpu.clock(&mut self.bus);
+            if !self.clock_console()
+            {
+                break;
+            }
+        }
+
+        Ok(diff.matched())
+    }
+
+    // The console isn't driven by a Scheduler event. It has no timing of its own and
+    // acts on the program writing CONSOLE_FLAGS_ADDR, which programs count on happening
+    // before their next instruction: echo.asm sets READ_LINE_FLAG and reads the input
+    // buffer straight after. An event can only be due at a cycle, not after a write,
+    // so the console is serviced after every instruction instead.
+    // Returns false if the replay went out of sync.
+    fn clock_console(&mut self) -> bool
+    {
+        match Console::clock(&mut self.cpu, &mut self.bus, &mut self.inputs)
+        {
+            Ok(()) => true,
+            Err(e) =>
+            {
+                self.replay_error = Some(e);
+                false
+            }
+        }
+    }
+
     pub fn run_program(&mut self)
     {
+        if let Some(throttle) = self.throttle.as_mut()
+        {
+            throttle.start(self.cpu.cycles());
+        }
+
         // Program should run until the cpu detects that it has stopped
-        // or when the program hits a BRK instruction
-        while !self.cpu.is_program_stopped() && self.cpu.check_flag(Flags::B) == 0
+        // or when the program hits a BRK instruction. An observer, like
+        // a debugger, can also stop it early.
+        while !self.is_finished()
         {
-            self.cpu.clock(&mut self.bus);
-            Console::clock(&mut self.cpu, &mut self.bus);
+            // Interrupts from the recording that are due
+            while let Inputs::Replaying(replay) = &mut self.inputs
+            {
+                match replay.due(self.cpu.cycles())
+                {
+                    Some(event) => self.raise(event),
+                    None => break,
+                }
+
+                if self.cpu.stop_requested()
+                {
+                    return;
+                }
+            }
+
+            match self.rewind.as_mut()
+            {
+                Some(rewind) => rewind.step(&mut self.cpu, &mut self.bus),
+                None => self.cpu.clock(&mut self.bus),
+            }
+
+            if !self.clock_console()
+            {
+                return;
+            }
+
+            if self.cpu.stop_requested()
+            {
+                return;
+            }
+
+            if let Some(throttle) = self.throttle.as_mut()
+            {
+                throttle.pace(self.cpu.cycles());
+            }
         }
     }
-}
\ No newline at end of file
+}
+// So the VICE monitor server can drive the machine (see vice_monitor.rs)
+impl Target for TestMachine
+{
+    fn cpu(&self) -> &R6502
+    {
+        &self.cpu
+    }
+
+    fn cpu_mut(&mut self) -> &mut R6502
+    {
+        &mut self.cpu
+    }
+
+    fn bus(&self) -> &dyn Bus
+    {
+        &self.bus
+    }
+
+    fn bus_mut(&mut self) -> &mut dyn Bus
+    {
+        &mut self.bus
+    }
+
+    fn run(&mut self)
+    {
+        self.run_program();
+    }
+
+    fn reset(&mut self)
+    {
+        TestMachine::reset(self);
+    }
+}
@@ -1,17 +1,105 @@
 
 
-use std::{ fs, env };
+use std::{ fs, env, io };
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
 
 mod machine;
+mod monitor;
 use machine::{OUTPUT_BUF_ADDR, PRINT_STR_FLAG, PRINT_BYTE_FLAG, TestMachine};
+use re6502::r6502::coverage::Coverage;
+use re6502::r6502::dap::{self, DapServer};
+use re6502::r6502::heatmap::Heatmap;
+use re6502::r6502::profiler::Profiler;
+use re6502::r6502::replay::Recording;
+use re6502::r6502::trace::{Tracer, TraceFormat};
+use re6502::r6502::source_map::SourceMap;
+use re6502::r6502::symbols::SymbolTable;
+use re6502::r6502::trace_diff::TraceDiff;
+use re6502::r6502::vice_monitor::{self, ViceServer};
+use monitor::Monitor;
 
+// Routines and addresses listed in a --profile report
+const PROFILE_LINES: usize = 20;
+
+// Busiest addresses listed in a --heatmap table
+const HEATMAP_LINES: usize = 20;
 
 
 fn main()
 {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    // --clock <MHz> runs the program at that clock rate, e.g. --clock 1.0
+    let clock = take_option(&mut args, "--clock").map(|mhz|
+    {
+        mhz.parse::<f64>().ok().filter(|mhz| *mhz > 0.0).expect("--clock needs a clock rate in MHz, e.g. --clock 1.0")
+    });
+
+    // --load-state <file> picks up from a save state instead of starting a program
+    // --save-state <file> saves the machine when the program stops
+    let load_state = take_option(&mut args, "--load-state");
+    let save_state = take_option(&mut args, "--save-state");
+
+    // --record <file> records the console input into a replay file
+    // --replay <file> runs a replay file instead of a program
+    let record = take_option(&mut args, "--record");
+    let replay = take_option(&mut args, "--replay");
+
+    // --trace <file> writes a line for every instruction run, in the format given by
+    // --trace-format nestest|json (default nestest). --symbols <file> loads labels to show.
+    // --trace-range <start>-<end> only traces code at those addresses, e.g. $C000-$C0FF, or
+    // symbols from --symbols, e.g. main-main+$40, and --trace-cycles <start>-<end> only the
+    // instructions that start in those cycles.
+    let trace = take_option(&mut args, "--trace");
+    let trace_format = take_option(&mut args, "--trace-format");
+    let trace_range = take_option(&mut args, "--trace-range");
+    let trace_cycles = take_option(&mut args, "--trace-cycles");
+    let symbols = take_option(&mut args, "--symbols");
+
+    // --profile <file> writes where the cycles went when the program stops, and
+    // --folded <file> the call stacks for flame graph tools (see profiler.rs)
+    let profile = take_option(&mut args, "--profile");
+    let folded = take_option(&mut args, "--folded");
+
+    // --coverage <file> writes which source lines ran as LCOV, or Cobertura XML if the file
+    // ends in .xml. --source-map <file> is the listing or ca65 debug info to map them with.
+    let coverage = take_option(&mut args, "--coverage");
+    let source_map = take_option(&mut args, "--source-map");
+
+    // --heatmap <file> counts the reads, writes and executes of every address, written as
+    // a PNG image if the file ends in .png and as a table otherwise
+    let heatmap = take_option(&mut args, "--heatmap");
+
+    // --debug starts the program stopped in the debug monitor (see monitor.rs)
+    let debug = take_flag(&mut args, "--debug");
+
+    // --vice-monitor <port> starts the program stopped and serves the VICE binary monitor
+    // protocol on localhost (see vice_monitor.rs), VICE's own port is 6502
+    let vice_monitor = take_option(&mut args, "--vice-monitor");
+
+    // --dap <port> serves the Debug Adapter Protocol on localhost (see dap.rs) so an editor
+    // can debug the program from its source, which takes a --source-map
+    let dap = take_option(&mut args, "--dap");
+
+    // diff <program> <reference trace> runs the program against a trace, --context <lines>
+    // sets how much of the reference to show before a mismatch
+    let context = take_option(&mut args, "--context");
+    if args.get(1).map(|a| a.as_str()) == Some("diff")
+    {
+        if args.len() < 4
+        {
+            println!("Usage: simple_test_machine diff <program> <reference trace> [--context <lines>]");
+            return;
+        }
+
+        let context = context.map(|n| n.parse::<usize>().expect("--context needs a number of lines"));
+        diff_trace(&args[2], &args[3], context);
+        return;
+    }
 
-    if args.len() < 2
+    if args.len() < 2 && load_state.is_none() && replay.is_none()
     {
         println!("No program provided, running internal test programs...");
         hello_world_test();
@@ -21,15 +109,279 @@ fn main()
         return;
     }
 
-    let program = fs::read(&args[1]).expect(&format!("Failed read program file: {}", &args[1]));
-    
     let mut vm = TestMachine::new();
+    if let Some(mhz) = clock
+    {
+        vm.set_clock_rate(mhz * 1_000_000.0);
+    }
+
+    if let Some(file) = replay
+    {
+        let data = fs::read(&file).unwrap_or_else(|e| panic!("Failed to read replay file {}: {}", &file, e));
+        if let Err(e) = Recording::from_bytes(&data).and_then(|recording| vm.start_replay(&recording))
+        {
+            println!("Failed to load replay {}: {}", &file, e);
+            return;
+        }
+    }
+    else if let Some(file) = load_state
+    {
+        let state = fs::read(&file).unwrap_or_else(|e| panic!("Failed to read save state file {}: {}", &file, e));
+        if let Err(e) = vm.load_state(&state)
+        {
+            println!("Failed to load save state {}: {}", &file, e);
+            return;
+        }
+    }
+    else
+    {
+        let program = fs::read(&args[1]).expect(&format!("Failed read program file: {}", &args[1]));
+        vm.load_program(&program);
+        vm.reset();
+    }
+
+    if record.is_some()
+    {
+        vm.start_recording();
+    }
+
+    let symbols = symbols.map(|file|
+    {
+        let mut table = SymbolTable::new();
+        table.load_file(&file).unwrap_or_else(|e| panic!("Failed to load symbols: {}", e));
+        table
+    });
+
+    if let Some(file) = trace
+    {
+        let format = match trace_format.as_deref()
+        {
+            None | Some("nestest") => TraceFormat::Nestest,
+            Some("json") => TraceFormat::JsonLines,
+            Some(other) => panic!("Unknown trace format: {} (expected nestest or json)", other),
+        };
+
+        let out = fs::File::create(&file).unwrap_or_else(|e| panic!("Failed to create trace file {}: {}", &file, e));
+        let mut tracer = Tracer::new(io::BufWriter::new(out), format);
+
+        if let Some(range) = trace_range
+        {
+            let table = symbols.clone().unwrap_or_default();
+            let (start, end) = parse_range(&range, |addr| table.parse_addr(addr)).expect("--trace-range needs addresses or symbols, e.g. --trace-range $C000-$C0FF");
+            tracer.set_range(start, end);
+        }
+
+        if let Some(range) = trace_cycles
+        {
+            let (start, end) = parse_range(&range, |n| n.trim().parse::<u64>().ok()).expect("--trace-cycles needs cycle counts, e.g. --trace-cycles 1000-2000");
+            tracer.set_cycles(start, end);
+        }
+
+        if let Some(table) = symbols.clone()
+        {
+            tracer.set_symbols(table);
+        }
+
+        vm.add_observer(tracer);
+    }
+
+    let profiler = match profile.is_some() || folded.is_some()
+    {
+        true =>
+        {
+            let profiler = Rc::new(RefCell::new(Profiler::new()));
+            vm.add_observer(profiler.clone());
+            Some(profiler)
+        }
+
+        false => None,
+    };
+
+    let coverage = coverage.map(|file|
+    {
+        let source_map = source_map.as_ref().expect("--coverage needs a --source-map to map the addresses to source lines");
+
+        let mut map = SourceMap::new();
+        map.load_file(source_map).unwrap_or_else(|e| panic!("Failed to load the source map: {}", e));
+
+        let coverage = Rc::new(RefCell::new(Coverage::new()));
+        vm.add_observer(coverage.clone());
+        (file, map, coverage)
+    });
+
+    let heatmap = heatmap.map(|file|
+    {
+        let heatmap = Rc::new(RefCell::new(Heatmap::new()));
+        vm.add_observer(heatmap.clone());
+        (file, heatmap)
+    });
+
+    if let Some(port) = vice_monitor
+    {
+        let port = port.parse::<u16>().unwrap_or_else(|_| panic!("--vice-monitor needs a port, e.g. --vice-monitor {}", vice_monitor::DEFAULT_PORT));
+        let listener = ViceServer::listen(port).unwrap_or_else(|e| panic!("Failed to listen on port {}: {}", port, e));
+        println!("VICE monitor listening on {}", listener.local_addr().unwrap());
+
+        let mut server = ViceServer::new(&mut vm);
+        server.serve(&mut vm, &listener).unwrap_or_else(|e| panic!("VICE monitor failed: {}", e));
+    }
+    else if let Some(port) = dap
+    {
+        let port = port.parse::<u16>().unwrap_or_else(|_| panic!("--dap needs a port, e.g. --dap {}", dap::DEFAULT_PORT));
+
+        let mut map = SourceMap::new();
+        match &source_map
+        {
+            Some(file) =>
+            {
+                map.load_file(file).unwrap_or_else(|e| panic!("Failed to load the source map: {}", e));
+            }
+
+            None => println!("No --source-map, the program can only be debugged from its disassembly"),
+        }
+
+        let listener = DapServer::listen(port).unwrap_or_else(|e| panic!("Failed to listen on port {}: {}", port, e));
+        println!("Debug adapter listening on {}", listener.local_addr().unwrap());
+
+        let mut server = DapServer::new(&mut vm, map, symbols.clone().unwrap_or_default());
+        server.serve(&mut vm, &listener).unwrap_or_else(|e| panic!("Debug adapter failed: {}", e));
+    }
+    else if debug
+    {
+        let mut monitor = Monitor::new(&mut vm, symbols.clone().unwrap_or_default());
+        monitor.run(&mut vm);
+    }
+    else
+    {
+        vm.run_program();
+        match vm.replay_error()
+        {
+            Some(e) => println!("Replay failed: {}", e),
+            None => println!("Program stopped"),
+        }
+    }
+
+    let symbols = symbols.unwrap_or_default();
+    if let Some(profiler) = profiler
+    {
+        let profiler = profiler.borrow();
+
+        if let Some(file) = profile
+        {
+            let report = format!("{}\n{}", profiler.report(&symbols, PROFILE_LINES), profiler.opcode_histogram());
+            fs::write(&file, report).unwrap_or_else(|e| panic!("Failed to write profile {}: {}", &file, e));
+        }
+
+        if let Some(file) = folded
+        {
+            fs::write(&file, profiler.folded_stacks(&symbols)).unwrap_or_else(|e| panic!("Failed to write folded stacks {}: {}", &file, e));
+        }
+    }
+
+    if let Some((file, heatmap)) = heatmap
+    {
+        let heatmap = heatmap.borrow();
+        let data = match file.to_ascii_lowercase().ends_with(".png")
+        {
+            true => heatmap.png(),
+            false => heatmap.table(&symbols, HEATMAP_LINES).into_bytes(),
+        };
+
+        fs::write(&file, data).unwrap_or_else(|e| panic!("Failed to write heatmap {}: {}", &file, e));
+    }
+
+    if let Some((file, map, coverage)) = coverage
+    {
+        let coverage = coverage.borrow();
+        let report = match file.to_ascii_lowercase().ends_with(".xml")
+        {
+            true => coverage.cobertura(&map, vm.bus()),
+            false =>
+            {
+                let name = args.get(1).and_then(|p| Path::new(p).file_stem()).map_or("re6502".into(), |s| s.to_string_lossy());
+                coverage.lcov(&map, vm.bus(), &name)
+            }
+        };
+
+        fs::write(&file, report).unwrap_or_else(|e| panic!("Failed to write coverage {}: {}", &file, e));
+    }
+
+    if let (Some(file), Some(recording)) = (record, vm.stop_recording())
+    {
+        fs::write(&file, recording.to_bytes()).unwrap_or_else(|e| panic!("Failed to write replay file {}: {}", &file, e));
+    }
+
+    if let Some(file) = save_state
+    {
+        fs::write(&file, vm.save_state()).unwrap_or_else(|e| panic!("Failed to write save state file {}: {}", &file, e));
+    }
+
+    if let Some(stats) = vm.drift_stats()
+    {
+        println!("Timing: {}", stats);
+    }
+}
+
+fn diff_trace(program: &str, reference: &str, context: Option<usize>)
+{
+    let program = fs::read(program).unwrap_or_else(|e| panic!("Failed to read program file {}: {}", program, e));
+    let text = fs::read_to_string(reference).unwrap_or_else(|e| panic!("Failed to read trace file {}: {}", reference, e));
 
+    let mut diff = match TraceDiff::parse(&text)
+    {
+        Ok(diff) => diff,
+        Err(e) => 
+        {
+            println!("Failed to load trace {}: {}", reference, e);
+            return;
+        }
+    };
+
+    if let Some(lines) = context
+    {
+        diff.set_context(lines);
+    }
+
+    let mut vm = TestMachine::new();
     vm.load_program(&program);
     vm.reset();
-    vm.run_program();
 
-    println!("Program stopped");
+    match vm.diff_trace(&mut diff)
+    {
+        Ok(count) => println!("All {} instructions match the trace", count),
+        Err(mismatch) => print!("{}", mismatch),
+    }
+}
+
+// Removes name from the arguments, returns true if it was there
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool
+{
+    let len = args.len();
+    args.retain(|a| a != name);
+    args.len() != len
+}
+
+// Removes "name value" from the arguments and returns the value
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String>
+{
+    let i = args.iter().position(|a| a == name)?;
+    let value = args.get(i + 1).cloned().unwrap_or_else(|| panic!("{} needs a value", name));
+    args.drain(i..i + 2);
+
+    Some(value)
+}
+
+// Parses <start>-<end>, both inclusive, with parse for each end. None unless start <= end.
+fn parse_range<T: PartialOrd>(text: &str, parse: impl Fn(&str) -> Option<T>) -> Option<(T, T)>
+{
+    let (start, end) = text.split_once('-')?;
+    let (start, end) = (parse(start)?, parse(end)?);
+
+    match start <= end
+    {
+        true => Some((start, end)),
+        false => None,
+    }
 }
 
 fn hello_world_test()
@@ -0,0 +1,590 @@
+#![allow(dead_code)]
+
+// Debug monitor
+//
+// An interactive command line for debugging programs on the test machine, started with
+// --debug. The program is held before its first instruction and the commands below step
+// through it, set breakpoints and look at or change registers and memory. Addresses can
+// be given in hex or as symbols (loop2, con_out+3) when labels were loaded with --symbols.
+//
+// The monitor stops on its own before a BRK, when an interrupt handler is entered and
+// on illegal opcodes. Rewind and the write history are turned on for the session, so
+// the program can be stepped backwards and asked who last wrote to an address. A shadow
+// call stack (call_stack.rs) is kept for the backtrace command.
+//
+// Pressing enter on an empty line repeats the last command, handy for stepping.
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use re6502::r6502::call_stack::CallStack;
+use re6502::r6502::debugger::{Debugger, StopReason, Watchpoint, WatchKind};
+use re6502::r6502::disasm;
+use re6502::r6502::expr::Expr;
+use re6502::r6502::observer::{Interrupt, Access};
+use re6502::r6502::state::StatusFlags;
+use re6502::r6502::symbols::{self, SymbolTable};
+
+use crate::machine::TestMachine;
+
+// How many writes to each address the history command can show
+const HISTORY_DEPTH: usize = 16;
+
+const HELP: &str = "\
+Running:
+  s, step [n]              run n instructions (default 1)
+  n, next                  run one instruction, stepping over a JSR
+  c, continue              run until something stops the program
+  u, until <addr>          run to an address
+  irq, nmi                 raise an interrupt
+Going back:
+  back [n]                 undo n instructions (default 1)
+  rc                       reverse continue to the last breakpoint hit
+  rewind <cycle>           go back to a cycle
+Breakpoints:
+  b, break <addr>          add a breakpoint
+  b <addr> if <expr>       add a breakpoint that only stops when expr is true
+  when <expr>              stop when expr becomes true, e.g. when A == $10 && [$0200] > 5 && C
+  watch <r|w|rw> <addr>[..<end>] [value]
+                           stop after a read or write in the range, of value if given
+  d, delete [addr]         remove a breakpoint, or all of them
+  d #<id>                  remove a condition or watchpoint
+  bl                       list the breakpoints, conditions and watchpoints
+Looking around:
+  r, regs                  show the registers
+  r <reg> <value>          set A, X, Y, SP, PC, P or a flag (N V B D I Z C)
+  m, mem <addr> [len]      hex dump memory
+  w, write <addr> <bytes>  change memory
+  l, list [addr] [count]   disassemble, at PC by default
+  bt, backtrace            show the subroutine calls and interrupts that led here
+  h, history <addr>        who last wrote to an address
+  q, quit
+Addresses and values are hex ($ optional) or symbols, counts are decimal.
+In expressions hex numbers need the $, see expr.rs for everything they can use.";
+
+enum Action
+{
+    Stay,
+    Run,
+    Quit,
+}
+
+pub struct Monitor
+{
+    debugger: Rc<RefCell<Debugger>>,
+    call_stack: Rc<RefCell<CallStack>>,
+    symbols: SymbolTable,
+    last_command: String,
+}
+
+impl Monitor
+{
+    pub fn new(vm: &mut TestMachine, symbols: SymbolTable) -> Monitor
+    {
+        let debugger = Rc::new(RefCell::new(Debugger::new()));
+        vm.add_observer(debugger.clone());
+        let call_stack = Rc::new(RefCell::new(CallStack::new()));
+        vm.add_observer(call_stack.clone());
+        vm.set_rewind(true);
+        vm.set_write_history(Some(HISTORY_DEPTH));
+
+        Monitor { debugger, call_stack, symbols, last_command: String::new() }
+    }
+
+    // Read and run commands until quit or the end of the input
+    pub fn run(&mut self, vm: &mut TestMachine)
+    {
+        println!("RE6502 monitor, type help for the commands");
+        self.show_position(vm);
+
+        let stdin = io::stdin();
+        loop
+        {
+            print!("> ");
+            io::stdout().flush().ok();
+
+            let mut line = String::new();
+            if stdin.read_line(&mut line).unwrap_or(0) == 0
+            {
+                break;
+            }
+
+            let line = match line.trim()
+            {
+                "" => self.last_command.clone(),
+                line => line.to_string(),
+            };
+            self.last_command = line.clone();
+
+            match self.command(vm, &line)
+            {
+                Ok(Action::Stay) => (),
+                Ok(Action::Run) => self.resume(vm),
+                Ok(Action::Quit) => break,
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+
+    fn command(&mut self, vm: &mut TestMachine, line: &str) -> Result<Action, String>
+    {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let (cmd, args) = match parts.split_first()
+        {
+            Some((cmd, args)) => (cmd.to_ascii_lowercase(), args),
+            None => return Ok(Action::Stay),
+        };
+
+        match (cmd.as_str(), args)
+        {
+            ("help" | "?", _) => println!("{}", HELP),
+            ("q" | "quit", _) => return Ok(Action::Quit),
+
+            // Running
+            ("s" | "step", []) | ("s" | "step", [_]) =>
+            {
+                let count = match args.first()
+                {
+                    Some(n) => n.parse::<u32>().map_err(|_| format!("Not a number of instructions: {}", n))?,
+                    None => 1,
+                };
+
+                self.check_running(vm)?;
+                self.debugger.borrow_mut().step(vm.cpu(), count);
+                return Ok(Action::Run);
+            }
+
+            ("n" | "next", []) =>
+            {
+                self.check_running(vm)?;
+                self.debugger.borrow_mut().next(vm.cpu(), vm.bus());
+                return Ok(Action::Run);
+            }
+
+            ("c" | "continue", []) =>
+            {
+                self.check_running(vm)?;
+                self.debugger.borrow_mut().cont(vm.cpu());
+                return Ok(Action::Run);
+            }
+
+            ("u" | "until", [addr]) =>
+            {
+                let addr = self.addr(addr)?;
+                self.check_running(vm)?;
+                self.debugger.borrow_mut().run_to(vm.cpu(), addr);
+                return Ok(Action::Run);
+            }
+
+            ("irq", []) =>
+            {
+                // With the I flag set the cpu holds on to it until a CLI, like a real IRQ line
+                vm.irq();
+                match vm.cpu().state().irq_pending
+                {
+                    true => println!("IRQ pending, interrupts are disabled (the I flag is set)"),
+                    false => self.show_stop(vm),
+                }
+            }
+
+            ("nmi", []) =>
+            {
+                vm.nmi();
+                self.show_stop(vm);
+            }
+
+            // Going back
+            ("back", []) | ("back", [_]) =>
+            {
+                let count = match args.first()
+                {
+                    Some(n) => n.parse::<u32>().map_err(|_| format!("Not a number of instructions: {}", n))?,
+                    None => 1,
+                };
+
+                for _ in 0..count
+                {
+                    if !vm.step_back()
+                    {
+                        println!("No more history");
+                        break;
+                    }
+                }
+
+                self.went_back(vm);
+            }
+
+            ("rc", []) =>
+            {
+                let breakpoints: Vec<u16> = self.debugger.borrow().breakpoints().collect();
+                if !vm.reverse_continue(&breakpoints)
+                {
+                    println!("No breakpoint hit in the history, back at the start of it");
+                }
+
+                self.went_back(vm);
+            }
+
+            ("rewind", [cycle]) =>
+            {
+                let cycle = cycle.parse::<u64>().map_err(|_| format!("Not a cycle: {}", cycle))?;
+                match vm.rewind_to(cycle)
+                {
+                    Some(_) => self.went_back(vm),
+                    None => return Err(format!("The history doesn't go back to cycle {}", cycle)),
+                }
+            }
+
+            // Breakpoints
+            ("b" | "break", [addr]) =>
+            {
+                let addr = self.addr(addr)?;
+                if !self.debugger.borrow_mut().add_breakpoint(addr)
+                {
+                    return Err(format!("There is already a breakpoint at {}", self.symbols.describe(addr)));
+                }
+            }
+
+            ("b" | "break", [addr, "if", expr @ ..]) if !expr.is_empty() =>
+            {
+                let addr = self.addr(addr)?;
+                let condition = self.expr(&expr.join(" "))?;
+                self.debugger.borrow_mut().add_conditional_breakpoint(addr, condition);
+            }
+
+            ("when", expr) if !expr.is_empty() =>
+            {
+                let condition = self.expr(&expr.join(" "))?;
+                let id = self.debugger.borrow_mut().add_condition(condition);
+                println!("Condition #{}", id);
+            }
+
+            ("watch", [kind, range]) | ("watch", [kind, range, _]) =>
+            {
+                let kind = match kind.to_ascii_lowercase().as_str()
+                {
+                    "r" => WatchKind::Read,
+                    "w" => WatchKind::Write,
+                    "rw" => WatchKind::Access,
+                    _ => return Err(format!("A watchpoint is r, w or rw, not {}", kind)),
+                };
+
+                let (start, end) = match range.split_once("..")
+                {
+                    Some((start, end)) => (self.addr(start)?, self.addr(end)?),
+                    None => (self.addr(range)?, self.addr(range)?),
+                };
+
+                if end < start
+                {
+                    return Err(format!("The range {} ends before it starts", range));
+                }
+
+                let mut watchpoint = Watchpoint::new(start, end, kind);
+                if let Some(value) = args.get(2)
+                {
+                    watchpoint = watchpoint.with_value(self.byte(value)?);
+                }
+
+                let id = self.debugger.borrow_mut().add_watchpoint(watchpoint);
+                println!("Watchpoint #{}", id);
+            }
+
+            ("d" | "delete", []) => self.debugger.borrow_mut().clear_breakpoints(),
+            ("d" | "delete", [id]) if id.starts_with('#') =>
+            {
+                let id = id[1..].parse::<u32>().map_err(|_| format!("Not an id: {}", id))?;
+                let mut debugger = self.debugger.borrow_mut();
+                if !debugger.remove_condition(id) && !debugger.remove_watchpoint(id)
+                {
+                    return Err(format!("No condition or watchpoint #{}", id));
+                }
+            }
+
+            ("d" | "delete", [addr]) =>
+            {
+                let addr = self.addr(addr)?;
+                if !self.debugger.borrow_mut().remove_breakpoint(addr)
+                {
+                    return Err(format!("No breakpoint at {}", self.symbols.describe(addr)));
+                }
+            }
+
+            ("bl", []) =>
+            {
+                let debugger = self.debugger.borrow();
+                for addr in debugger.breakpoints()
+                {
+                    match debugger.breakpoint_condition(addr)
+                    {
+                        Some(condition) => println!("${:04X}  {} if {}", addr, self.symbols.describe(addr), condition),
+                        None => println!("${:04X}  {}", addr, self.symbols.describe(addr)),
+                    }
+                }
+
+                for (id, condition) in debugger.conditions()
+                {
+                    println!("#{}  when {}", id, condition);
+                }
+
+                for (id, w) in debugger.watchpoints()
+                {
+                    let value = w.value.map(|v| format!(" = ${:02X}", v)).unwrap_or_default();
+                    println!("#{}  watch {:?} ${:04X}..${:04X}{}", id, w.kind, w.start, w.end, value);
+                }
+            }
+
+            // Looking around
+            ("r" | "regs", []) => self.show_position(vm),
+            ("r" | "regs", [reg, value]) =>
+            {
+                self.set_register(vm, reg, value)?;
+                self.show_position(vm);
+            }
+
+            ("m" | "mem", [addr]) | ("m" | "mem", [addr, _]) =>
+            {
+                let addr = self.addr(addr)?;
+                let len = match args.get(1)
+                {
+                    Some(len) => self.number(len)?,
+                    None => 64,
+                };
+
+                self.dump(vm, addr, len);
+            }
+
+            ("w" | "write", [addr, bytes @ ..]) if !bytes.is_empty() =>
+            {
+                let addr = self.addr(addr)?;
+                let bytes = bytes.iter().map(|b| self.byte(b)).collect::<Result<Vec<u8>, String>>()?;
+
+                for (i, b) in bytes.iter().enumerate()
+                {
+                    vm.bus_mut().write(addr.wrapping_add(i as u16), *b);
+                }
+            }
+
+            ("l" | "list", _) if args.len() <= 2 =>
+            {
+                let addr = match args.first()
+                {
+                    Some(addr) => self.addr(addr)?,
+                    None => vm.cpu().state().pc,
+                };
+
+                let count = match args.get(1)
+                {
+                    Some(count) => self.number(count)? as usize,
+                    None => 10,
+                };
+
+                let pc = vm.cpu().state().pc;
+                for line in disasm::disassemble(vm.bus(), addr, count)
+                {
+                    let marker = if line.addr == pc { ">" } else { " " };
+                    println!("{} {}", marker, line.line_with(&self.symbols));
+                }
+            }
+
+            ("bt" | "backtrace", []) =>
+            {
+                let call_stack = self.call_stack.borrow();
+                for line in call_stack.backtrace(vm.cpu().state().pc, &self.symbols)
+                {
+                    println!("{}", line);
+                }
+
+                // The last few places the program played with the stack
+                let anomalies: Vec<_> = call_stack.anomalies().collect();
+                if !anomalies.is_empty()
+                {
+                    println!("Stack manipulation seen {} times, the latest:", anomalies.len());
+                    for anomaly in anomalies.iter().rev().take(3)
+                    {
+                        println!("  {}", anomaly);
+                    }
+                }
+            }
+
+            ("h" | "history", [addr]) =>
+            {
+                let addr = self.addr(addr)?;
+                let history = vm.write_history(addr);
+                if history.is_empty()
+                {
+                    println!("No writes to {} recorded", self.symbols.describe(addr));
+                }
+
+                for record in history
+                {
+                    println!("{}  ({})", record, self.symbols.describe(record.pc));
+                }
+            }
+
+            _ => return Err(format!("Unknown command or wrong arguments: {} (type help for the list)", line)),
+        }
+
+        Ok(Action::Stay)
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				RUNNING
+    /////////////////////////////////////////////////////////////////////
+
+    fn check_running(&self, vm: &TestMachine) -> Result<(), String>
+    {
+        match vm.is_finished()
+        {
+            true => Err("The program has finished".to_string()),
+            false => Ok(()),
+        }
+    }
+
+    fn resume(&mut self, vm: &mut TestMachine)
+    {
+        vm.run_program();
+        self.show_stop(vm);
+    }
+
+    // Bring the call stack back to where the cpu is after going back
+    fn went_back(&self, vm: &TestMachine)
+    {
+        self.call_stack.borrow_mut().rewind(vm.cpu().cycles());
+        self.show_position(vm);
+    }
+
+    // Say why the program stopped and where it is now
+    fn show_stop(&self, vm: &TestMachine)
+    {
+        let reason = self.debugger.borrow().stop_reason();
+        match reason
+        {
+            Some(StopReason::Step) => (),
+            Some(StopReason::Breakpoint(addr)) => println!("Breakpoint at {}", self.symbols.describe(addr)),
+            Some(StopReason::Brk(addr)) => println!("BRK at {}", self.symbols.describe(addr)),
+            Some(StopReason::Interrupt(Interrupt::Irq)) => println!("Entered the IRQ handler"),
+            Some(StopReason::Interrupt(Interrupt::Nmi)) => println!("Entered the NMI handler"),
+            Some(StopReason::IllegalOpcode(addr, opcode)) => println!("Illegal opcode ${:02X} at {}", opcode, self.symbols.describe(addr)),
+            Some(StopReason::Condition(id)) => println!("Condition #{} is true", id),
+            Some(StopReason::Watchpoint { id, pc, addr, value, access }) =>
+            {
+                let verb = if access == Access::Write { "wrote" } else { "read" };
+                println!("Watchpoint #{}: {} {} ${:02X} at {}", id, self.symbols.describe(pc), verb, value, self.symbols.describe(addr));
+            }
+            None if vm.replay_error().is_some() => println!("Replay failed: {}", vm.replay_error().unwrap()),
+            None if vm.is_finished() => println!("Program stopped"),
+            None => (),
+        }
+
+        self.show_position(vm);
+    }
+
+    // The registers and the instruction at PC
+    fn show_position(&self, vm: &TestMachine)
+    {
+        let state = vm.cpu().state();
+        println!("{}  PC={:04X}  CYC:{}", state, state.pc, state.cycles);
+        println!("{}", disasm::disassemble_one(vm.bus(), state.pc).line_with(&self.symbols));
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				LOOKING AROUND
+    /////////////////////////////////////////////////////////////////////
+
+    fn set_register(&self, vm: &mut TestMachine, reg: &str, value: &str) -> Result<(), String>
+    {
+        let mut state = vm.cpu().state();
+        let flag = || match value
+        {
+            "0" => Ok(false),
+            "1" => Ok(true),
+            _ => Err(format!("A flag is 0 or 1, not {}", value)),
+        };
+
+        match reg.to_ascii_lowercase().as_str()
+        {
+            "a" => state.a = self.byte(value)?,
+            "x" => state.x = self.byte(value)?,
+            "y" => state.y = self.byte(value)?,
+            "sp" => state.sp = 0x0100 | self.byte(value)? as u16,
+            "pc" => state.pc = self.addr(value)?,
+            "p" => state.flags = StatusFlags::from_byte(self.byte(value)?),
+
+            "n" => state.flags.n = flag()?,
+            "v" => state.flags.v = flag()?,
+            "b" => state.flags.b = flag()?,
+            "d" => state.flags.d = flag()?,
+            "i" => state.flags.i = flag()?,
+            "z" => state.flags.z = flag()?,
+            "c" => state.flags.c = flag()?,
+
+            _ => return Err(format!("Unknown register: {}", reg)),
+        }
+
+        vm.cpu_mut().set_state(&state);
+        Ok(())
+    }
+
+    // 16 bytes to a line, with the printable ones as text
+    fn dump(&self, vm: &TestMachine, addr: u16, len: u16)
+    {
+        let mut offset = 0;
+        while offset < len
+        {
+            let start = addr.wrapping_add(offset);
+            let count = u16::min(16, len - offset);
+            let bytes: Vec<u8> = (0..count).map(|i| vm.bus().read(start.wrapping_add(i))).collect();
+
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            let text: String = bytes.iter().map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' }).collect();
+            println!("${:04X}  {:<48}|{}|", start, hex.join(" "), text);
+
+            offset += count;
+        }
+    }
+
+    // Addresses and values are hex, with or without the $, unless there's a symbol by that name.
+    // So are offsets: loop2+10 is 16 bytes past loop2.
+    fn addr(&self, text: &str) -> Result<u16, String>
+    {
+        let text = text.trim();
+
+        if let Some(i) = text.rfind(['+', '-']).filter(|i| *i > 0)
+        {
+            let base = self.addr(&text[..i])?;
+            let offset = self.addr(&text[i + 1..])?;
+
+            return match &text[i..i + 1]
+            {
+                "+" => Ok(base.wrapping_add(offset)),
+                _ => Ok(base.wrapping_sub(offset)),
+            };
+        }
+
+        if self.symbols.lookup(text).is_none() && !text.is_empty() && text.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            return u16::from_str_radix(text, 16).map_err(|_| format!("Not an address: {}", text));
+        }
+
+        self.symbols.parse_addr(text).ok_or(format!("Unknown address: {}", text))
+    }
+
+    fn expr(&self, text: &str) -> Result<Expr, String>
+    {
+        Expr::parse_with(text, &self.symbols)
+    }
+
+    fn byte(&self, text: &str) -> Result<u8, String>
+    {
+        let value = self.addr(text).map_err(|_| format!("Not a byte: {}", text))?;
+        u8::try_from(value).map_err(|_| format!("Not a byte: {}", text))
+    }
+
+    // Counts are decimal
+    fn number(&self, text: &str) -> Result<u16, String>
+    {
+        symbols::parse_number(text).ok_or(format!("Not a number: {}", text))
+    }
+}
@@ -29,6 +29,18 @@ impl Bus for RAMBus
     {
         self.ram[addr as usize] = value;    
     }
+
+    fn page(&self, page: u8) -> Option<&[u8; 256]>
+    {
+        let start = (page as usize) << 8;
+        self.ram[start..start + 256].try_into().ok()
+    }
+
+    fn page_mut(&mut self, page: u8) -> Option<&mut [u8; 256]>
+    {
+        let start = (page as usize) << 8;
+        (&mut self.ram[start..start + 256]).try_into().ok()
+    }
 }
 
 fn main() 
@@ -96,7 +108,7 @@ fn run_addition_test(cpu: &mut R6502)
     cpu.clock(&mut bus);
     cpu.clock(&mut bus);
 
-    println!("\nProgram result, A register: {}", cpu.debug_get_reg(r6502::Registers::A));
+    println!("\nProgram result, A register: {}", cpu.state().a);
     let result = bus.read(0x0002);
     println!("\nValue at 0x0002: {}", result);
 
@@ -142,5 +154,5 @@ fn run_simple_or_program(cpu: &mut R6502)
     cpu.clock(&mut bus);
     cpu.clock(&mut bus);
 
-    println!("\nProgram result, A register: {}", cpu.debug_get_reg(r6502::Registers::A));
+    println!("\nProgram result, A register: {}", cpu.state().a);
 }
\ No newline at end of file
@@ -0,0 +1,305 @@
+
+#![allow(dead_code)]
+
+use std::rc::Rc;
+
+use super::{R6502, Bus};
+use super::decoder::{self, Decoded};
+
+// Predecoded basic block cache
+//
+// A block is a straight-line run of instructions ending with the first instruction
+// that can change the flow of the program (branch, jump, subroutine call, etc) or
+// enable interrupts, so a held IRQ is taken between blocks just as clock() would.
+// Blocks are decoded once and cached by their start address. After that, running
+// one is a walk over the decoded instructions without going back to the decoder.
+//
+// Only code in pages the bus exposes through Bus::page() is cached. Code anywhere
+// else (memory mapped I/O, bank switched memory, ...) is run with R6502::clock().
+//
+// Writes made by the cpu while running through the cache invalidate any blocks that
+// cover the written address. Memory that is changed from outside of the cpu (a device,
+// a program loader) has to be reported with BlockCache::invalidate_range().
+
+// Keep blocks short enough that a long straight run of code doesn't
+// hold off interrupts and device updates for too long
+const MAX_BLOCK_LEN: usize = 32;
+
+#[derive(Clone, Copy)]
+pub(crate) struct CachedInstr
+{
+    pub(crate) decoded: Decoded,
+    pub(crate) bytes: [u8; 3],
+    pub(crate) len: u8,
+}
+
+pub(crate) struct Block
+{
+    pub(crate) start: u16,
+    pub(crate) end: u16,       // address after the last byte of the block
+    pub(crate) instrs: Vec<CachedInstr>,
+}
+
+impl Block
+{
+    pub(crate) fn contains(&self, addr: u16) -> bool
+    {
+        addr >= self.start && addr < self.end
+    }
+}
+
+pub struct BlockCache
+{
+    blocks: Vec<Option<Rc<Block>>>,
+
+    // start addresses of the cached blocks that have code in each page
+    page_blocks: Vec<Vec<u16>>,
+}
+
+impl BlockCache
+{
+    pub fn new() -> BlockCache
+    {
+        BlockCache { blocks: vec![None; 64 * 1024], page_blocks: vec![Vec::new(); 256] }
+    }
+
+    // Number of blocks currently cached
+    pub fn len(&self) -> usize
+    {
+        self.blocks.iter().filter(|block| block.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.page_blocks.iter().all(|blocks| blocks.is_empty())
+    }
+
+    pub fn clear(&mut self)
+    {
+        for blocks in self.page_blocks.iter_mut()
+        {
+            for start in blocks.drain(..)
+            {
+                self.blocks[start as usize] = None;
+            }
+        }
+    }
+
+    // Drop every cached block that has code in the given address range (inclusive)
+    pub fn invalidate_range(&mut self, start: u16, end: u16)
+    {
+        for page in (start >> 8)..=(end >> 8)
+        {
+            let mut i = 0;
+            while i < self.page_blocks[page as usize].len()
+            {
+                let block_start = self.page_blocks[page as usize][i];
+                let overlaps = match &self.blocks[block_start as usize]
+                {
+                    Some(block) => block.start <= end && block.end > start,
+                    None => false,
+                };
+
+                if overlaps
+                {
+                    self.remove(block_start);
+                }
+                else
+                {
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    pub fn invalidate(&mut self, addr: u16)
+    {
+        self.invalidate_range(addr, addr);
+    }
+
+    fn has_code(&self, page: u8) -> bool
+    {
+        !self.page_blocks[page as usize].is_empty()
+    }
+
+    fn remove(&mut self, start: u16)
+    {
+        if let Some(block) = self.blocks[start as usize].take()
+        {
+            for page in (block.start >> 8)..=((block.end - 1) >> 8)
+            {
+                self.page_blocks[page as usize].retain(|s| *s != start);
+            }
+        }
+    }
+
+    fn get_or_decode(&mut self, addr: u16, bus: &dyn Bus) -> Option<Rc<Block>>
+    {
+        if let Some(block) = &self.blocks[addr as usize]
+        {
+            return Some(block.clone());
+        }
+
+        let block = Rc::new(decode_block(addr, bus)?);
+        for page in (block.start >> 8)..=((block.end - 1) >> 8)
+        {
+            self.page_blocks[page as usize].push(addr);
+        }
+
+        self.blocks[addr as usize] = Some(block.clone());
+        Some(block)
+    }
+}
+
+impl Default for BlockCache
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+pub(crate) fn decode_block(start: u16, bus: &dyn Bus) -> Option<Block>
+{
+    let mut instrs = Vec::new();
+    let mut addr = start;
+
+    while instrs.len() < MAX_BLOCK_LEN
+    {
+        let mut bytes = [0; 3];
+        let page = bus.page((addr >> 8) as u8)?;
+        bytes[0] = page[(addr & 0x00FF) as usize];
+
+        let decoded = match decoder::decode(bytes[0])
+        {
+            Some(decoded) => decoded,
+
+            // Leave unknown instructions to clock()
+            None => break,
+        };
+
+        // Don't let a block wrap around the end of memory
+        let len = decoded.size();
+        if addr as u32 + len as u32 > 0xFFFF
+        {
+            break;
+        }
+
+        for i in 1..len
+        {
+            let operand_addr = addr + i;
+            let page = bus.page((operand_addr >> 8) as u8)?;
+            bytes[i as usize] = page[(operand_addr & 0x00FF) as usize];
+        }
+
+        instrs.push(CachedInstr { decoded, bytes, len: len as u8 });
+        addr += len;
+
+        if decoder::is_flow_control(bytes[0]) || decoder::can_enable_interrupts(bytes[0])
+        {
+            break;
+        }
+    }
+
+    if instrs.is_empty()
+    {
+        return None;
+    }
+
+    Some(Block { start, end: addr, instrs })
+}
+
+// Passes everything through to the real bus while invalidating
+// the cached blocks that the cpu writes into
+struct WriteWatch<'a>
+{
+    bus: &'a mut dyn Bus,
+    cache: &'a mut BlockCache,
+    running: Option<Rc<Block>>,
+    running_hit: bool,
+}
+
+impl<'a> Bus for WriteWatch<'a>
+{
+    fn read(&self, addr: u16) -> u8
+    {
+        self.bus.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8)
+    {
+        self.bus.write(addr, value);
+
+        if self.cache.has_code((addr >> 8) as u8)
+        {
+            self.cache.invalidate(addr);
+        }
+
+        if let Some(block) = &self.running
+        {
+            if block.contains(addr)
+            {
+                self.running_hit = true;
+            }
+        }
+    }
+
+    fn page(&self, page: u8) -> Option<&[u8; 256]>
+    {
+        self.bus.page(page)
+    }
+
+    // page_mut() is left out on purpose so every write goes through write()
+}
+
+impl R6502
+{
+    // Run the block of instructions at the program counter, decoding and caching
+    // it first if needed. Falls back to running a single instruction with clock()
+    // if the code can't be cached. Returns the number of instructions executed,
+    // 0 when it entered the handler for a held IRQ instead.
+    pub fn clock_block(&mut self, bus: &mut dyn Bus, cache: &mut BlockCache) -> u32
+    {
+        if self.take_pending_irq(bus)
+        {
+            return 0;
+        }
+
+        let block = cache.get_or_decode(self.pc, bus);
+        let mut watch = WriteWatch { bus, cache, running: block.clone(), running_hit: false };
+
+        // Observers need clock() to see each instruction
+        let block = match block
+        {
+            Some(block) if self.observers.is_empty() => block,
+            _ =>
+            {
+                self.clock(&mut watch);
+                return 1;
+            }
+        };
+
+        let mut count = 0;
+        for instr in block.instrs.iter()
+        {
+            // The instruction bytes come from the cache instead of the bus
+            self.fetch_addr = self.pc;
+            self.fetch_buf = instr.bytes;
+            self.fetch_len = instr.len;
+            self.pc = self.pc.wrapping_add(1);
+
+            instr.decoded.run(self, &mut watch);
+
+            self.fetch_len = 0;
+            count += 1;
+
+            // Stop if the block just modified itself, the rest of it is stale
+            if watch.running_hit || self.program_stopped
+            {
+                break;
+            }
+        }
+
+        count
+    }
+}
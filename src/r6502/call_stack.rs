@@ -0,0 +1,314 @@
+
+#![allow(dead_code)]
+
+// Shadow call stack
+//
+// An observer that keeps its own stack of the subroutines and interrupt handlers the
+// cpu is in, so a debugger can show how it got to where it is. A frame is pushed when a
+// JSR or BRK runs and when the cpu enters an IRQ or NMI handler, and popped by the RTS
+// or RTI that returns from it.
+//
+// Returns are matched to frames by the stack pointer rather than blindly popping the
+// top frame, which is how programs that play with the stack are spotted:
+//
+//      An RTS or RTI below the top frame's return address returns to something the
+//      program pushed itself, usually an address pushed to jump to it. No frame is popped.
+//      A frame whose return address was pulled off the stack (PLA PLA) or skipped over
+//      (TXS) is discarded once the stack pointer moves past it.
+//      Returning from a frame to somewhere other than where it was called from means the
+//      return address was changed on the stack, as routines with inline arguments do.
+//
+// All of these are recorded as anomalies (the most recent MAX_ANOMALIES of them) and
+// the call stack carries on from there.
+//
+// After rewinding the cpu (see rewind.rs) call rewind() with the cycle it is back at.
+// Frames entered after that are dropped and the ones returned from after it are put
+// back, as long as they're still in the log of the last MAX_POPPED frames returned from.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use super::R6502;
+use super::observer::{Observer, Instruction, Control, Interrupt};
+use super::symbols::SymbolTable;
+
+const MAX_ANOMALIES: usize = 1000;
+const MAX_POPPED: usize = 10_000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FrameKind
+{
+    Call,
+    Brk,
+    Irq,
+    Nmi,
+}
+
+impl fmt::Display for FrameKind
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let name = match self
+        {
+            FrameKind::Call => "JSR",
+            FrameKind::Brk => "BRK",
+            FrameKind::Irq => "IRQ",
+            FrameKind::Nmi => "NMI",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Frame
+{
+    pub kind: FrameKind,
+    pub from: u16,          // The JSR or BRK, or where the cpu was when the interrupt came
+    pub entry: u16,         // The subroutine or handler
+    pub return_addr: u16,   // Where the RTS or RTI should go back to
+    pub sp: u16,            // The stack pointer once the return address was pushed
+    pub cycle: u64,         // When the frame was entered
+}
+
+impl Frame
+{
+    // Bytes pushed on entry: the return address, and the status for interrupts
+    fn size(&self) -> u16
+    {
+        match self.kind
+        {
+            FrameKind::Call => 2,
+            _ => 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AnomalyKind
+{
+    UnmatchedReturn { to: u16 },                    // Returned to an address the program pushed
+    ReturnModified { expected: u16, actual: u16 },  // Returned from a frame, but not to its caller
+    Discarded(Frame),                               // The frame's return address left the stack without a return
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Anomaly
+{
+    pub pc: u16,        // The instruction it was seen at
+    pub cycle: u64,
+    pub kind: AnomalyKind,
+}
+
+impl fmt::Display for Anomaly
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "cycle {}: ${:04X} ", self.cycle, self.pc)?;
+        match self.kind
+        {
+            AnomalyKind::UnmatchedReturn { to } => write!(f, "returned to ${:04X}, which no call pushed", to),
+            AnomalyKind::ReturnModified { expected, actual } => write!(f, "returned to ${:04X} instead of ${:04X}", actual, expected),
+            AnomalyKind::Discarded(frame) => write!(f, "dropped the return address of the {} at ${:04X}", frame.kind, frame.from),
+        }
+    }
+}
+
+pub struct CallStack
+{
+    frames: Vec<Frame>,
+    popped: VecDeque<(u64, Frame)>,     // Cycle it was popped at and the frame
+    anomalies: VecDeque<Anomaly>,
+
+    // The stack pointer before the running instruction, and where the cpu will carry on
+    // from (what an interrupt returns to)
+    sp_before: u16,
+    next_pc: u16,
+}
+
+impl CallStack
+{
+    pub fn new() -> CallStack
+    {
+        CallStack { frames: Vec::new(), popped: VecDeque::new(), anomalies: VecDeque::new(), sp_before: 0, next_pc: 0 }
+    }
+
+    // Outermost first
+    pub fn frames(&self) -> &[Frame]
+    {
+        &self.frames
+    }
+
+    pub fn depth(&self) -> usize
+    {
+        self.frames.len()
+    }
+
+    // Oldest first
+    pub fn anomalies(&self) -> impl Iterator<Item = &Anomaly>
+    {
+        self.anomalies.iter()
+    }
+
+    pub fn clear_anomalies(&mut self)
+    {
+        self.anomalies.clear();
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.frames.clear();
+        self.popped.clear();
+        self.anomalies.clear();
+    }
+
+    // The cpu has been taken back to cycle
+    pub fn rewind(&mut self, cycle: u64)
+    {
+        self.frames.retain(|f| f.cycle <= cycle);
+        self.anomalies.retain(|a| a.cycle <= cycle);
+
+        // Undo the pops, newest first
+        while let Some((popped_at, frame)) = self.popped.back().copied()
+        {
+            if popped_at <= cycle
+            {
+                break;
+            }
+
+            self.popped.pop_back();
+            if frame.cycle <= cycle
+            {
+                self.frames.push(frame);
+            }
+        }
+    }
+
+    // Innermost first, starting with pc:
+    //
+    //      #0  $020C  sub+1
+    //      #1  $0203  loop+1  JSR
+    //      #2  $0250  main+3  IRQ
+    pub fn backtrace(&self, pc: u16, symbols: &SymbolTable) -> Vec<String>
+    {
+        let mut lines = vec![format!("#0  ${:04X}  {}", pc, symbols.describe(pc))];
+        for (i, frame) in self.frames.iter().rev().enumerate()
+        {
+            lines.push(format!("#{}  ${:04X}  {}  {}", i + 1, frame.from, symbols.describe(frame.from), frame.kind));
+        }
+
+        lines
+    }
+
+    fn push(&mut self, frame: Frame)
+    {
+        self.frames.push(frame);
+    }
+
+    fn pop(&mut self, cycle: u64) -> Option<Frame>
+    {
+        let frame = self.frames.pop()?;
+        self.popped.push_back((cycle, frame));
+        if self.popped.len() > MAX_POPPED
+        {
+            self.popped.pop_front();
+        }
+
+        Some(frame)
+    }
+
+    fn anomaly(&mut self, cpu: &R6502, pc: u16, kind: AnomalyKind)
+    {
+        self.anomalies.push_back(Anomaly { pc, cycle: cpu.cycles, kind });
+        if self.anomalies.len() > MAX_ANOMALIES
+        {
+            self.anomalies.pop_front();
+        }
+    }
+
+    // An RTS or RTI at pc has run
+    fn ret(&mut self, cpu: &R6502, pc: u16)
+    {
+        let sp = self.sp_before;
+
+        // Frames that are partly off the stack already can't be returned from
+        while let Some(frame) = self.frames.last().copied().filter(|f| f.sp < sp)
+        {
+            self.pop(cpu.cycles);
+            self.anomaly(cpu, pc, AnomalyKind::Discarded(frame));
+        }
+
+        match self.frames.last().copied()
+        {
+            Some(frame) if frame.sp == sp =>
+            {
+                self.pop(cpu.cycles);
+                if cpu.pc != frame.return_addr
+                {
+                    self.anomaly(cpu, pc, AnomalyKind::ReturnModified { expected: frame.return_addr, actual: cpu.pc });
+                }
+            }
+
+            _ => self.anomaly(cpu, pc, AnomalyKind::UnmatchedReturn { to: cpu.pc }),
+        }
+    }
+}
+
+impl Default for CallStack
+{
+    fn default() -> CallStack
+    {
+        CallStack::new()
+    }
+}
+
+impl Observer for CallStack
+{
+    fn before(&mut self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        self.sp_before = cpu.sp;
+        self.next_pc = instr.pc;
+        Control::Continue
+    }
+
+    fn after(&mut self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        self.next_pc = cpu.pc;
+
+        let frame = |kind, return_addr| Frame { kind, from: instr.pc, entry: cpu.pc, return_addr, sp: cpu.sp, cycle: cpu.cycles };
+        match instr.decoded.name
+        {
+            "JSR" => self.push(frame(FrameKind::Call, instr.pc.wrapping_add(3))),
+
+            // This cpu pushes the address of the byte after the BRK opcode
+            "BRK" => self.push(frame(FrameKind::Brk, instr.pc.wrapping_add(1))),
+
+            // The RTS that ends the program doesn't pull anything
+            "RTS" if cpu.is_program_stopped() => (),
+            "RTS" | "RTI" => self.ret(cpu, instr.pc),
+            _ => (),
+        }
+
+        // Drop the frames whose return address has been pulled off the stack some other way
+        while let Some(frame) = self.frames.last().copied().filter(|f| cpu.sp >= f.sp.wrapping_add(f.size()))
+        {
+            self.pop(cpu.cycles);
+            self.anomaly(cpu, instr.pc, AnomalyKind::Discarded(frame));
+        }
+
+        Control::Continue
+    }
+
+    fn interrupt(&mut self, cpu: &R6502, kind: Interrupt) -> Control
+    {
+        let kind = match kind
+        {
+            Interrupt::Irq => FrameKind::Irq,
+            Interrupt::Nmi => FrameKind::Nmi,
+        };
+
+        let from = self.next_pc;
+        self.push(Frame { kind, from, entry: cpu.pc, return_addr: from, sp: cpu.sp, cycle: cpu.cycles });
+        Control::Continue
+    }
+}
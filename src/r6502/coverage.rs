@@ -0,0 +1,329 @@
+
+#![allow(dead_code)]
+
+// Code coverage
+//
+// An observer that counts how many times the instruction at each address ran and,
+// for branches, how many times the branch was taken and not taken. With a source map
+// (see source_map.rs) the counts are written out against the assembly source as LCOV
+// tracefiles (genhtml, editor coverage gutters, Codecov) or Cobertura XML (CI tools).
+//
+// A source line counts as code if the bytes at its address decode to a documented
+// instruction of the same size as the line, and as data otherwise, so .byte tables
+// don't show up as lines that never ran. The bytes are read from the bus when the report
+// is written, so that should be the memory the program was loaded into. A line that ran
+// is always code, whatever its bytes are now.
+//
+// When several instructions come from one source line (a macro) the line's hit count is
+// the count of the one that ran the most, and its branches are all of theirs.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{R6502, Bus, Flags};
+use super::addressing_modes::ModeID;
+use super::decoder;
+use super::observer::{Observer, Instruction, Control};
+use super::source_map::SourceMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct BranchCounts
+{
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+// The coverage of one source line
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct LineCoverage
+{
+    pub hits: u64,
+    pub branches: Vec<BranchCounts>,
+}
+
+pub struct Coverage
+{
+    hits: Vec<u64>,
+    branches: BTreeMap<u16, BranchCounts>,
+}
+
+impl Coverage
+{
+    pub fn new() -> Coverage
+    {
+        Coverage { hits: vec![0; 0x10000], branches: BTreeMap::new() }
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.hits.iter_mut().for_each(|h| *h = 0);
+        self.branches.clear();
+    }
+
+    // Times the instruction at addr ran
+    pub fn hits(&self, addr: u16) -> u64
+    {
+        self.hits[addr as usize]
+    }
+
+    // The branch at addr, if it ran
+    pub fn branch(&self, addr: u16) -> Option<BranchCounts>
+    {
+        self.branches.get(&addr).copied()
+    }
+
+    // Number of different addresses run
+    pub fn executed(&self) -> usize
+    {
+        self.hits.iter().filter(|h| **h > 0).count()
+    }
+
+    // The coverage of each code line in the map, by file index and line number
+    pub fn lines(&self, map: &SourceMap, bus: &dyn Bus) -> BTreeMap<(usize, u32), LineCoverage>
+    {
+        let mut lines: BTreeMap<(usize, u32), LineCoverage> = BTreeMap::new();
+        for line in map.lines()
+        {
+            let hits = self.hits(line.addr);
+            let decoded = decoder::decode(bus.read(line.addr)).filter(|d| d.documented && d.size() == line.size);
+            if hits == 0 && decoded.is_none()
+            {
+                continue;
+            }
+
+            let coverage = lines.entry((line.file, line.line)).or_default();
+            coverage.hits = coverage.hits.max(hits);
+
+            let is_branch = decoded.is_some_and(|d| d.mode == ModeID::REL);
+            if is_branch || self.branches.contains_key(&line.addr)
+            {
+                coverage.branches.push(self.branch(line.addr).unwrap_or_default());
+            }
+        }
+
+        lines
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				REPORTS
+    /////////////////////////////////////////////////////////////////////
+
+    // An LCOV tracefile, one record per source file
+    pub fn lcov(&self, map: &SourceMap, bus: &dyn Bus, test_name: &str) -> String
+    {
+        let lines = self.lines(map, bus);
+        let mut out = String::new();
+
+        for (file, name) in map.files().iter().enumerate()
+        {
+            let file_lines: Vec<(u32, &LineCoverage)> = lines.range((file, 0)..=(file, u32::MAX)).map(|((_, n), c)| (*n, c)).collect();
+            if file_lines.is_empty()
+            {
+                continue;
+            }
+
+            writeln!(out, "TN:{}", test_name).unwrap();
+            writeln!(out, "SF:{}", name).unwrap();
+
+            // Branches are numbered taken then not taken, "-" when the line never ran
+            let (mut found, mut hit) = (0, 0);
+            for (n, coverage) in file_lines.iter()
+            {
+                for (block, branch) in coverage.branches.iter().enumerate()
+                {
+                    for (i, count) in [branch.taken, branch.not_taken].iter().enumerate()
+                    {
+                        match coverage.hits
+                        {
+                            0 => writeln!(out, "BRDA:{},{},{},-", n, block, i).unwrap(),
+                            _ => writeln!(out, "BRDA:{},{},{},{}", n, block, i, count).unwrap(),
+                        }
+
+                        found += 1;
+                        hit += (*count > 0) as usize;
+                    }
+                }
+            }
+
+            if found > 0
+            {
+                writeln!(out, "BRF:{}", found).unwrap();
+                writeln!(out, "BRH:{}", hit).unwrap();
+            }
+
+            for (n, coverage) in file_lines.iter()
+            {
+                writeln!(out, "DA:{},{}", n, coverage.hits).unwrap();
+            }
+
+            writeln!(out, "LF:{}", file_lines.len()).unwrap();
+            writeln!(out, "LH:{}", file_lines.iter().filter(|(_, c)| c.hits > 0).count()).unwrap();
+            writeln!(out, "end_of_record").unwrap();
+        }
+
+        out
+    }
+
+    // A Cobertura XML report, with each source file as a class
+    pub fn cobertura(&self, map: &SourceMap, bus: &dyn Bus) -> String
+    {
+        let lines = self.lines(map, bus);
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+
+        let total = Rates::of(lines.values());
+        let mut out = String::new();
+        writeln!(out, "<?xml version=\"1.0\" ?>").unwrap();
+        writeln!(out, "<!DOCTYPE coverage SYSTEM \"http://cobertura.sourceforge.net/xml/coverage-04.dtd\">").unwrap();
+        writeln!(out, "<coverage line-rate=\"{}\" branch-rate=\"{}\" lines-covered=\"{}\" lines-valid=\"{}\" branches-covered=\"{}\" branches-valid=\"{}\" complexity=\"0\" version=\"0.1\" timestamp=\"{}\">",
+                    total.line_rate(), total.branch_rate(), total.lines_hit, total.lines, total.branches_hit, total.branches, timestamp).unwrap();
+        writeln!(out, "  <sources><source>.</source></sources>").unwrap();
+        writeln!(out, "  <packages>").unwrap();
+        writeln!(out, "    <package name=\"re6502\" line-rate=\"{}\" branch-rate=\"{}\" complexity=\"0\">", total.line_rate(), total.branch_rate()).unwrap();
+        writeln!(out, "      <classes>").unwrap();
+
+        for (file, name) in map.files().iter().enumerate()
+        {
+            let file_lines: Vec<(u32, &LineCoverage)> = lines.range((file, 0)..=(file, u32::MAX)).map(|((_, n), c)| (*n, c)).collect();
+            if file_lines.is_empty()
+            {
+                continue;
+            }
+
+            let rates = Rates::of(file_lines.iter().map(|(_, c)| *c));
+            let name = escape_xml(name);
+            writeln!(out, "        <class name=\"{}\" filename=\"{}\" line-rate=\"{}\" branch-rate=\"{}\" complexity=\"0\">",
+                        name, name, rates.line_rate(), rates.branch_rate()).unwrap();
+            writeln!(out, "          <methods/>").unwrap();
+            writeln!(out, "          <lines>").unwrap();
+
+            for (n, coverage) in file_lines
+            {
+                match coverage.branches.is_empty()
+                {
+                    true => writeln!(out, "            <line number=\"{}\" hits=\"{}\" branch=\"false\"/>", n, coverage.hits).unwrap(),
+                    false =>
+                    {
+                        let (hit, all) = branch_counts(coverage);
+                        writeln!(out, "            <line number=\"{}\" hits=\"{}\" branch=\"true\" condition-coverage=\"{}% ({}/{})\"/>",
+                                    n, coverage.hits, hit * 100 / all, hit, all).unwrap();
+                    }
+                }
+            }
+
+            writeln!(out, "          </lines>").unwrap();
+            writeln!(out, "        </class>").unwrap();
+        }
+
+        writeln!(out, "      </classes>").unwrap();
+        writeln!(out, "    </package>").unwrap();
+        writeln!(out, "  </packages>").unwrap();
+        writeln!(out, "</coverage>").unwrap();
+        out
+    }
+}
+
+impl Default for Coverage
+{
+    fn default() -> Coverage
+    {
+        Coverage::new()
+    }
+}
+
+impl Observer for Coverage
+{
+    fn after(&mut self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        self.hits[instr.pc as usize] += 1;
+
+        if instr.decoded.mode == ModeID::REL
+        {
+            let branch = self.branches.entry(instr.pc).or_default();
+            match branch_taken(instr.bytes[0], cpu.status)
+            {
+                true => branch.taken += 1,
+                false => branch.not_taken += 1,
+            }
+        }
+
+        Control::Continue
+    }
+}
+
+// Branches don't change the flags, so they say which way it went
+fn branch_taken(opcode: u8, status: u8) -> bool
+{
+    let set = |flag: Flags| status & flag as u8 != 0;
+    match opcode
+    {
+        0x10 => !set(Flags::N),     // BPL
+        0x30 => set(Flags::N),      // BMI
+        0x50 => !set(Flags::V),     // BVC
+        0x70 => set(Flags::V),      // BVS
+        0x90 => !set(Flags::C),     // BCC
+        0xB0 => set(Flags::C),      // BCS
+        0xD0 => !set(Flags::Z),     // BNE
+        0xF0 => set(Flags::Z),      // BEQ
+        _ => true,
+    }
+}
+
+// Branch directions taken and the number of them
+fn branch_counts(coverage: &LineCoverage) -> (usize, usize)
+{
+    let hit = coverage.branches.iter().map(|b| (b.taken > 0) as usize + (b.not_taken > 0) as usize).sum();
+    (hit, coverage.branches.len() * 2)
+}
+
+#[derive(Default)]
+struct Rates
+{
+    lines: usize,
+    lines_hit: usize,
+    branches: usize,
+    branches_hit: usize,
+}
+
+impl Rates
+{
+    fn of<'a>(lines: impl Iterator<Item = &'a LineCoverage>) -> Rates
+    {
+        let mut rates = Rates::default();
+        for coverage in lines
+        {
+            let (hit, all) = branch_counts(coverage);
+            rates.lines += 1;
+            rates.lines_hit += (coverage.hits > 0) as usize;
+            rates.branches += all;
+            rates.branches_hit += hit;
+        }
+
+        rates
+    }
+
+    fn line_rate(&self) -> f64
+    {
+        rate(self.lines_hit, self.lines)
+    }
+
+    fn branch_rate(&self) -> f64
+    {
+        rate(self.branches_hit, self.branches)
+    }
+}
+
+// Nothing to cover counts as all of it covered
+fn rate(hit: usize, all: usize) -> f64
+{
+    match all
+    {
+        0 => 1.0,
+        _ => ((hit as f64 / all as f64) * 10000.0).round() / 10000.0,
+    }
+}
+
+fn escape_xml(text: &str) -> String
+{
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
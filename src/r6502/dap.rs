@@ -0,0 +1,1031 @@
+
+#![allow(dead_code)]
+
+// Debug Adapter Protocol server
+//
+// Lets an editor that speaks the Debug Adapter Protocol (VS Code, Neovim with nvim-dap,
+// Helix, Emacs with dap-mode) debug the 6502 program a machine runs at the level of its
+// assembly source. The editor connects over TCP on localhost, so the machine's own
+// console stays where it is. One editor is served, until it disconnects.
+//
+// Messages are JSON (see json.rs) with a Content-Length header in front:
+//
+//      Content-Length: 62\r\n
+//      \r\n
+//      {"seq":1,"type":"request","command":"threads","arguments":{}}
+//
+// The requests supported:
+//
+//      initialize, launch, attach, configurationDone, disconnect
+//      setBreakpoints              breakpoints on source lines, with conditions in the
+//                                  expression language of expr.rs
+//      setInstructionBreakpoints   breakpoints on addresses, from the disassembly view
+//      setExceptionBreakpoints     accepted, BRK and illegal opcodes always stop
+//      continue, next, stepIn, stepOut, pause
+//      threads, stackTrace         the shadow call stack (call_stack.rs) as frames
+//      scopes, variables           the registers, the flags and the stack
+//      setVariable                 change a register, flag or stack byte
+//      evaluate                    expressions, for the debug console and hovers
+//      readMemory, writeMemory     memory views
+//      disassemble
+//
+// Addresses are mapped to source lines with a SourceMap (see source_map.rs), so the
+// program needs a listing or ca65 debug info to be debugged from its source. Without
+// one the editor still gets the registers, memory and disassembly. Source files are
+// matched by their full path, or failing that by their name. A breakpoint on a line with
+// no code moves down to the next line that has some.
+//
+// The program is held until the editor has sent both launch (or attach) and
+// configurationDone, then it stops at its first instruction if launch asked for
+// stopOnEntry and runs otherwise. The program itself comes from the machine, launch
+// doesn't load one. Stepping goes by source line unless the editor asks for instruction
+// granularity: instructions are run until the program gets to another line, stepping
+// over subroutines for next. When the program ends the editor gets exited and
+// terminated events.
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{Bus, Flags};
+use super::call_stack::{CallStack, FrameKind};
+use super::debugger::{Debugger, StopReason};
+use super::debug_server::{self, Target, SLICE, is_return, client_waiting};
+use super::disasm::{self, Line};
+use super::expr::Expr;
+use super::json::Json;
+use super::source_map::SourceMap;
+use super::state::StatusFlags;
+use super::symbols::SymbolTable;
+
+pub const DEFAULT_PORT: u16 = 4711;
+
+// The only thread there is
+const THREAD_ID: i64 = 1;
+
+// Variables references of the scopes
+const REGISTERS_REF: i64 = 1;
+const FLAGS_REF: i64 = 2;
+const STACK_REF: i64 = 3;
+
+const FLAGS: [(&str, Flags); 7] =
+[
+    ("N", Flags::N),
+    ("V", Flags::V),
+    ("B", Flags::B),
+    ("D", Flags::D),
+    ("I", Flags::I),
+    ("Z", Flags::Z),
+    ("C", Flags::C),
+];
+
+struct Breakpoint
+{
+    id: i64,
+    addr: u16,
+    source: Option<String>,     // The path the editor gave, None for instruction breakpoints
+    condition: Option<Expr>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum RunMode
+{
+    Continue,
+    StepIn { by_line: bool },
+    StepOver { by_line: bool },
+    StepOut,
+}
+
+enum Action
+{
+    Stay,
+    Run(RunMode),
+    Pause,
+    Quit,
+}
+
+pub struct DapServer
+{
+    debugger: Rc<RefCell<Debugger>>,
+    call_stack: Rc<RefCell<CallStack>>,
+    map: SourceMap,
+    symbols: SymbolTable,
+
+    breakpoints: Vec<Breakpoint>,
+    next_id: i64,
+
+    launched: bool,
+    configured: bool,
+    started: bool,
+    stop_on_entry: bool,
+}
+
+impl DapServer
+{
+    // Adds the debugger and call stack the server uses to the machine's cpu
+    pub fn new<T: Target>(target: &mut T, map: SourceMap, symbols: SymbolTable) -> DapServer
+    {
+        let debugger = Rc::new(RefCell::new(Debugger::new()));
+        debugger.borrow_mut().set_break_on_interrupt(false);
+        target.cpu_mut().add_observer(debugger.clone());
+
+        let call_stack = Rc::new(RefCell::new(CallStack::new()));
+        target.cpu_mut().add_observer(call_stack.clone());
+
+        DapServer { debugger, call_stack, map, symbols, breakpoints: Vec::new(), next_id: 1,
+                    launched: false, configured: false, started: false, stop_on_entry: false }
+    }
+
+    // Port 0 picks a free one, local_addr() on the listener says which
+    pub fn listen(port: u16) -> io::Result<TcpListener>
+    {
+        debug_server::listen(port)
+    }
+
+    // Serve one editor until it disconnects
+    pub fn serve<T: Target>(&mut self, target: &mut T, listener: &TcpListener) -> io::Result<()>
+    {
+        let (stream, _) = listener.accept()?;
+        let mut conn = Connection::new(stream)?;
+
+        while let Some(request) = conn.read()?
+        {
+            match self.request(target, &mut conn, &request)?
+            {
+                Action::Run(mode) if self.run(target, &mut conn, mode)? => break,
+                Action::Quit => break,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn request<T: Target>(&mut self, target: &mut T, conn: &mut Connection, request: &Json) -> io::Result<Action>
+    {
+        let command = request.get("command").as_str().unwrap_or("");
+        let args = request.get("arguments");
+        let by_line = args.get("granularity").as_str() != Some("instruction");
+
+        let mut action = Action::Stay;
+        let result = match command
+        {
+            "initialize" => Ok(capabilities()),
+            "launch" | "attach" =>
+            {
+                self.launched = true;
+                self.stop_on_entry = args.get("stopOnEntry").as_bool().unwrap_or(false);
+                Ok(Json::Null)
+            }
+
+            "configurationDone" =>
+            {
+                self.configured = true;
+                Ok(Json::Null)
+            }
+
+            "disconnect" =>
+            {
+                action = Action::Quit;
+                Ok(Json::Null)
+            }
+
+            "setBreakpoints" => Ok(self.set_breakpoints(args)),
+            "setInstructionBreakpoints" => Ok(self.set_instruction_breakpoints(args)),
+            "setExceptionBreakpoints" => Ok(Json::object(vec![("breakpoints", Json::Array(Vec::new()))])),
+
+            "continue" =>
+            {
+                action = Action::Run(RunMode::Continue);
+                Ok(Json::object(vec![("allThreadsContinued", true.into())]))
+            }
+
+            "next" | "stepIn" | "stepOut" =>
+            {
+                action = Action::Run(match command
+                {
+                    "next" => RunMode::StepOver { by_line },
+                    "stepIn" => RunMode::StepIn { by_line },
+                    _ => RunMode::StepOut,
+                });
+
+                Ok(Json::Null)
+            }
+
+            "pause" =>
+            {
+                action = Action::Pause;
+                Ok(Json::Null)
+            }
+
+            "threads" =>
+            {
+                let thread = Json::object(vec![("id", THREAD_ID.into()), ("name", "6502".into())]);
+                Ok(Json::object(vec![("threads", vec![thread].into())]))
+            }
+
+            "stackTrace" => Ok(self.stack_trace(target, args)),
+            "scopes" =>
+            {
+                let scope = |name: &str, reference: i64| Json::object(vec![("name", name.into()), ("variablesReference", reference.into()), ("expensive", false.into())]);
+                Ok(Json::object(vec![("scopes", vec![scope("Registers", REGISTERS_REF), scope("Flags", FLAGS_REF), scope("Stack", STACK_REF)].into())]))
+            }
+
+            "variables" => Ok(Json::object(vec![("variables", self.variables(target, args.get("variablesReference").as_i64().unwrap_or(0)).into())])),
+            "setVariable" => self.set_variable(target, args),
+            "evaluate" => self.evaluate(target, args),
+            "readMemory" => self.read_memory(target, args),
+            "writeMemory" => self.write_memory(target, args),
+            "disassemble" => self.disassemble(target, args),
+
+            _ => Err(format!("{} isn't supported", command)),
+        };
+
+        conn.respond(request, result)?;
+
+        if command == "initialize"
+        {
+            conn.event("initialized", Json::Null)?;
+        }
+
+        // Start once the editor has launched and sent its breakpoints
+        if self.launched && self.configured && !self.started
+        {
+            self.started = true;
+            match self.stop_on_entry
+            {
+                true => conn.stopped("entry", None, &[])?,
+                false => action = Action::Run(RunMode::Continue),
+            }
+        }
+
+        Ok(action)
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				RUNNING
+    /////////////////////////////////////////////////////////////////////
+
+    // Returns true if the editor disconnected while the program ran
+    fn run<T: Target>(&mut self, target: &mut T, conn: &mut Connection, mode: RunMode) -> io::Result<bool>
+    {
+        let start_line = self.line_at(target.cpu().pc);
+        loop
+        {
+            let returning = is_return(target);
+            {
+                let mut debugger = self.debugger.borrow_mut();
+                match mode
+                {
+                    RunMode::Continue => debugger.step(target.cpu(), SLICE),
+                    RunMode::StepIn { .. } => debugger.step(target.cpu(), 1),
+                    RunMode::StepOver { .. } => debugger.next(target.cpu(), target.bus()),
+                    RunMode::StepOut if returning => debugger.step(target.cpu(), 1),
+                    RunMode::StepOut => debugger.next(target.cpu(), target.bus()),
+                }
+            }
+
+            target.run();
+
+            let reason = self.debugger.borrow().stop_reason();
+            match reason
+            {
+                Some(StopReason::Step) => (),
+                Some(reason) =>
+                {
+                    self.stopped(target, conn, reason)?;
+                    return Ok(false);
+                }
+
+                None =>
+                {
+                    conn.event("exited", Json::object(vec![("exitCode", 0i64.into())]))?;
+                    conn.event("terminated", Json::Null)?;
+                    return Ok(false);
+                }
+            }
+
+            // Steps by line carry on until the program gets to another line, as long as
+            // it started on one
+            let done = match mode
+            {
+                RunMode::Continue => false,
+                RunMode::StepIn { by_line } | RunMode::StepOver { by_line } =>
+                    !by_line || start_line.is_none() || self.line_at(target.cpu().pc) != start_line,
+                RunMode::StepOut => returning,
+            };
+
+            if done
+            {
+                conn.stopped("step", None, &[])?;
+                return Ok(false);
+            }
+
+            // The editor can still ask for things while the program runs, or pause it
+            while conn.waiting()?
+            {
+                let request = match conn.read()?
+                {
+                    Some(request) => request,
+                    None => return Ok(true),
+                };
+
+                match self.request(target, conn, &request)?
+                {
+                    Action::Pause =>
+                    {
+                        conn.stopped("pause", None, &[])?;
+                        return Ok(false);
+                    }
+
+                    Action::Quit => return Ok(true),
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    // Tell the editor why the debugger stopped the program
+    fn stopped<T: Target>(&self, target: &T, conn: &mut Connection, reason: StopReason) -> io::Result<()>
+    {
+        match reason
+        {
+            StopReason::Breakpoint(addr) =>
+            {
+                let ids: Vec<i64> = self.breakpoints.iter().filter(|b| b.addr == addr).map(|b| b.id).collect();
+                conn.stopped("breakpoint", None, &ids)
+            }
+
+            StopReason::Brk(addr) => conn.stopped("exception", Some(format!("BRK at {}", self.symbols.describe(addr))), &[]),
+            StopReason::IllegalOpcode(addr, opcode) =>
+                conn.stopped("exception", Some(format!("Illegal opcode ${:02X} at {}", opcode, self.symbols.describe(addr))), &[]),
+
+            _ => conn.stopped("pause", Some(format!("Stopped at {}", self.symbols.describe(target.cpu().pc))), &[]),
+        }
+    }
+
+    fn line_at(&self, addr: u16) -> Option<(usize, u32)>
+    {
+        self.map.lookup(addr).map(|l| (l.file, l.line))
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				BREAKPOINTS
+    /////////////////////////////////////////////////////////////////////
+
+    // Replaces the breakpoints in a source file
+    fn set_breakpoints(&mut self, args: &Json) -> Json
+    {
+        let source = args.get("source");
+        let path = source.get("path").as_str().or(source.get("name").as_str()).unwrap_or("").to_string();
+        self.breakpoints.retain(|b| b.source.as_deref() != Some(path.as_str()));
+
+        let file = self.file_index(&path);
+        let mut results = Vec::new();
+        for requested in args.get("breakpoints").as_array()
+        {
+            let line = requested.get("line").as_i64().unwrap_or(0).max(0) as u32;
+            let found = file.and_then(|file| self.code_line(file, line));
+
+            let result = match (found, self.condition(requested))
+            {
+                (None, _) => Err("No code on or after this line".to_string()),
+                (_, Err(e)) => Err(e),
+                (Some((line, addr)), Ok(condition)) => Ok((line, addr, condition)),
+            };
+
+            let id = self.next_id;
+            self.next_id += 1;
+
+            let mut json = Json::object(vec![("id", id.into())]);
+            match result
+            {
+                Ok((line, addr, condition)) =>
+                {
+                    self.breakpoints.push(Breakpoint { id, addr, source: Some(path.clone()), condition });
+                    json.set("verified", true.into());
+                    json.set("line", line.into());
+                    json.set("instructionReference", reference(addr).into());
+                    if let Some(file) = file
+                    {
+                        json.set("source", self.source(file));
+                    }
+                }
+
+                Err(message) =>
+                {
+                    json.set("verified", false.into());
+                    json.set("line", line.into());
+                    json.set("message", message.into());
+                }
+            }
+
+            results.push(json);
+        }
+
+        self.sync();
+        Json::object(vec![("breakpoints", results.into())])
+    }
+
+    // Replaces all of the breakpoints on addresses
+    fn set_instruction_breakpoints(&mut self, args: &Json) -> Json
+    {
+        self.breakpoints.retain(|b| b.source.is_some());
+
+        let mut results = Vec::new();
+        for requested in args.get("breakpoints").as_array()
+        {
+            let addr = requested.get("instructionReference").as_str().and_then(|r| self.symbols.parse_addr(r))
+                            .map(|addr| addr.wrapping_add(requested.get("offset").as_i64().unwrap_or(0) as u16));
+
+            let id = self.next_id;
+            self.next_id += 1;
+
+            let mut json = Json::object(vec![("id", id.into())]);
+            match (addr, self.condition(requested))
+            {
+                (Some(addr), Ok(condition)) =>
+                {
+                    self.breakpoints.push(Breakpoint { id, addr, source: None, condition });
+                    json.set("verified", true.into());
+                    json.set("instructionReference", reference(addr).into());
+                }
+
+                (None, _) =>
+                {
+                    json.set("verified", false.into());
+                    json.set("message", "Not an address".into());
+                }
+
+                (_, Err(e)) =>
+                {
+                    json.set("verified", false.into());
+                    json.set("message", e.into());
+                }
+            }
+
+            results.push(json);
+        }
+
+        self.sync();
+        Json::object(vec![("breakpoints", results.into())])
+    }
+
+    fn condition(&self, breakpoint: &Json) -> Result<Option<Expr>, String>
+    {
+        match breakpoint.get("condition").as_str().filter(|c| !c.trim().is_empty())
+        {
+            Some(text) => Expr::parse_with(text, &self.symbols).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // Give the debugger the breakpoints. An address with a breakpoint without a
+    // condition always stops, whatever the others there say.
+    fn sync(&mut self)
+    {
+        let mut debugger = self.debugger.borrow_mut();
+        debugger.clear_breakpoints();
+
+        for breakpoint in self.breakpoints.iter()
+        {
+            if let Some(condition) = &breakpoint.condition
+            {
+                debugger.add_conditional_breakpoint(breakpoint.addr, condition.clone());
+            }
+        }
+
+        for breakpoint in self.breakpoints.iter().filter(|b| b.condition.is_none())
+        {
+            debugger.remove_breakpoint(breakpoint.addr);
+            debugger.add_breakpoint(breakpoint.addr);
+        }
+    }
+
+    // The first line from line on that has code, and its address
+    fn code_line(&self, file: usize, line: u32) -> Option<(u32, u16)>
+    {
+        let next = self.map.lines().iter().filter(|l| l.file == file && l.line >= line).map(|l| l.line).min()?;
+        Some((next, self.map.address(file, next)?))
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				LOOKING AROUND
+    /////////////////////////////////////////////////////////////////////
+
+    // Innermost first: where the cpu is, then the JSR or interrupted instruction of each
+    // frame on the call stack
+    fn stack_trace<T: Target>(&self, target: &T, args: &Json) -> Json
+    {
+        let call_stack = self.call_stack.borrow();
+        let frames = call_stack.frames();
+
+        let mut stack = Vec::new();
+        let mut pc = target.cpu().pc;
+        for level in 0..=frames.len()
+        {
+            let frame = frames.len().checked_sub(level + 1).map(|i| frames[i]);
+            let name = match frame
+            {
+                Some(frame) =>
+                {
+                    let routine = self.symbols.name(frame.entry).map_or(format!("${:04X}", frame.entry), |n| n.to_string());
+                    match frame.kind
+                    {
+                        FrameKind::Call => routine,
+                        kind => format!("{} [{}]", routine, kind),
+                    }
+                }
+
+                None => self.symbols.describe(pc),
+            };
+
+            let mut json = Json::object(vec![("id", level.into()), ("name", name.into()), ("instructionPointerReference", reference(pc).into())]);
+            match self.map.lookup(pc)
+            {
+                Some(line) =>
+                {
+                    json.set("source", self.source(line.file));
+                    json.set("line", line.line.into());
+                    json.set("column", 1i64.into());
+                }
+
+                None =>
+                {
+                    json.set("line", 0i64.into());
+                    json.set("column", 0i64.into());
+                }
+            }
+
+            stack.push(json);
+            if let Some(frame) = frame
+            {
+                pc = frame.from;
+            }
+        }
+
+        let total = stack.len();
+        let start = args.get("startFrame").as_i64().unwrap_or(0).max(0) as usize;
+        let levels = match args.get("levels").as_i64().unwrap_or(0)
+        {
+            n if n > 0 => n as usize,
+            _ => total,
+        };
+
+        let stack: Vec<Json> = stack.into_iter().skip(start).take(levels).collect();
+        Json::object(vec![("stackFrames", stack.into()), ("totalFrames", total.into())])
+    }
+
+    fn variables<T: Target>(&self, target: &T, reference: i64) -> Vec<Json>
+    {
+        let cpu = target.cpu();
+        let variable = |name: &str, value: String, memory: Option<u16>|
+        {
+            let mut json = Json::object(vec![("name", name.into()), ("value", value.into()), ("variablesReference", 0i64.into())]);
+            if let Some(addr) = memory
+            {
+                json.set("memoryReference", self::reference(addr).into());
+            }
+
+            json
+        };
+
+        match reference
+        {
+            REGISTERS_REF =>
+            {
+                let state = cpu.state();
+                vec![
+                    variable("A", byte(state.a), None),
+                    variable("X", byte(state.x), None),
+                    variable("Y", byte(state.y), None),
+                    variable("SP", byte(state.sp as u8), Some(state.sp)),
+                    variable("PC", format!("${:04X}", state.pc), Some(state.pc)),
+                    variable("P", format!("${:02X} {}", state.status(), StatusFlags::from_byte(state.status())), None),
+                ]
+            }
+
+            FLAGS_REF => FLAGS.iter().map(|(name, flag)| variable(name, (cpu.check_flag(*flag) != 0).to_string(), None)).collect(),
+
+            // What's on the stack, the top first
+            STACK_REF => (cpu.state().sp.wrapping_add(1)..=0x01FF).map(|addr| variable(&format!("${:04X}", addr), byte(target.bus().read(addr)), Some(addr))).collect(),
+
+            _ => Vec::new(),
+        }
+    }
+
+    // The value is an expression, so $10, 16 and X+1 all work
+    fn set_variable<T: Target>(&self, target: &mut T, args: &Json) -> Result<Json, String>
+    {
+        let name = args.get("name").as_str().unwrap_or("");
+        let text = args.get("value").as_str().unwrap_or("");
+        let value = match text
+        {
+            "true" => 1,
+            "false" => 0,
+            _ => Expr::parse_with(text, &self.symbols)?.eval(target.cpu(), target.bus()),
+        };
+
+        let mut state = target.cpu().state();
+        let shown = match (args.get("variablesReference").as_i64().unwrap_or(0), name)
+        {
+            (REGISTERS_REF, "A") => { state.a = value as u8; byte(state.a) }
+            (REGISTERS_REF, "X") => { state.x = value as u8; byte(state.x) }
+            (REGISTERS_REF, "Y") => { state.y = value as u8; byte(state.y) }
+            (REGISTERS_REF, "SP") => { state.sp = 0x0100 | (value as u16 & 0xFF); byte(state.sp as u8) }
+            (REGISTERS_REF, "PC") => { state.pc = value as u16; format!("${:04X}", state.pc) }
+            (REGISTERS_REF, "P") =>
+            {
+                state.flags = StatusFlags::from_byte(value as u8);
+                format!("${:02X} {}", state.status(), state.flags)
+            }
+
+            (FLAGS_REF, name) =>
+            {
+                let (_, flag) = FLAGS.iter().find(|(n, _)| *n == name).ok_or(format!("No flag {}", name))?;
+                state.flags = StatusFlags::from_byte(match value
+                {
+                    0 => state.status() & !(*flag as u8),
+                    _ => state.status() | *flag as u8,
+                });
+
+                (value != 0).to_string()
+            }
+
+            (STACK_REF, name) =>
+            {
+                let addr = self.symbols.parse_addr(name).ok_or(format!("Not an address: {}", name))?;
+                target.bus_mut().write(addr, value as u8);
+                return Ok(Json::object(vec![("value", byte(value as u8).into())]));
+            }
+
+            _ => return Err(format!("Can't set {}", name)),
+        };
+
+        target.cpu_mut().set_state(&state);
+        Ok(Json::object(vec![("value", shown.into())]))
+    }
+
+    fn evaluate<T: Target>(&self, target: &T, args: &Json) -> Result<Json, String>
+    {
+        let text = args.get("expression").as_str().unwrap_or("");
+        let value = Expr::parse_with(text, &self.symbols)?.eval(target.cpu(), target.bus());
+
+        let result = match value
+        {
+            0..=0xFF => format!("${:02X} ({})", value, value),
+            0x100..=0xFFFF => format!("${:04X} ({})", value, value),
+            _ => value.to_string(),
+        };
+
+        // Anything that could be an address can be opened in a memory view
+        let mut json = Json::object(vec![("result", result.into()), ("variablesReference", 0i64.into())]);
+        if let Ok(addr) = u16::try_from(value)
+        {
+            json.set("memoryReference", reference(addr).into());
+        }
+
+        Ok(json)
+    }
+
+    fn read_memory<T: Target>(&self, target: &T, args: &Json) -> Result<Json, String>
+    {
+        let addr = self.memory_address(args)?;
+        let count = args.get("count").as_i64().unwrap_or(0).max(0) as usize;
+
+        // Nothing past the top of memory
+        let readable = count.min(0x10000 - addr as usize);
+        let data: Vec<u8> = (0..readable).map(|i| target.bus().read(addr + i as u16)).collect();
+
+        Ok(Json::object(vec![("address", reference(addr).into()), ("data", base64_encode(&data).into()), ("unreadableBytes", (count - readable).into())]))
+    }
+
+    fn write_memory<T: Target>(&self, target: &mut T, args: &Json) -> Result<Json, String>
+    {
+        let addr = self.memory_address(args)?;
+        let data = base64_decode(args.get("data").as_str().unwrap_or("")).ok_or("The data isn't base64")?;
+
+        let writable = data.len().min(0x10000 - addr as usize);
+        for (i, value) in data[..writable].iter().enumerate()
+        {
+            target.bus_mut().write(addr + i as u16, *value);
+        }
+
+        Ok(Json::object(vec![("bytesWritten", writable.into())]))
+    }
+
+    fn memory_address(&self, args: &Json) -> Result<u16, String>
+    {
+        let text = args.get("memoryReference").as_str().unwrap_or("");
+        let addr = self.symbols.parse_addr(text).ok_or(format!("Not an address: {}", text))?;
+        let addr = addr as i64 + args.get("offset").as_i64().unwrap_or(0);
+
+        u16::try_from(addr).map_err(|_| format!("{} is outside of memory", addr))
+    }
+
+    // instructionCount instructions from instructionOffset instructions away from the
+    // address, which can be before it
+    fn disassemble<T: Target>(&self, target: &T, args: &Json) -> Result<Json, String>
+    {
+        let addr = self.memory_address(args)?;
+        let offset = args.get("instructionOffset").as_i64().unwrap_or(0);
+        let count = args.get("instructionCount").as_i64().unwrap_or(0).max(0) as usize;
+
+        let mut lines: Vec<Option<Line>> = Vec::new();
+        if offset < 0
+        {
+            let back = (-offset) as usize;
+            let before = lines_before(target.bus(), addr, back);
+
+            // Not enough memory before the address to go that far back
+            lines.extend((before.len()..back).map(|_| None));
+            lines.extend(before.iter().skip(before.len().saturating_sub(back)).map(|l| Some(*l)));
+        }
+
+        let skip = offset.max(0) as usize;
+        let forward = (count + skip).saturating_sub(lines.len());
+        let mut last = None;
+        for line in disasm::disassemble(target.bus(), addr, forward)
+        {
+            // Stop at the top of memory rather than wrapping around
+            if last.is_some_and(|last| line.addr <= last)
+            {
+                break;
+            }
+
+            last = Some(line.addr);
+            lines.push(Some(line));
+        }
+
+        lines.resize(count + skip, None);
+
+        let instructions: Vec<Json> = lines.into_iter().skip(skip).map(|line| match line
+        {
+            Some(line) => self.instruction(&line),
+            None => Json::object(vec![("address", "0x0000".into()), ("instruction", "??".into()), ("presentationHint", "invalid".into())]),
+        }).collect();
+
+        Ok(Json::object(vec![("instructions", instructions.into())]))
+    }
+
+    fn instruction(&self, line: &Line) -> Json
+    {
+        let bytes: Vec<String> = line.bytes[..line.len as usize].iter().map(|b| format!("{:02X}", b)).collect();
+        let mut json = Json::object(vec![("address", reference(line.addr).into()), ("instructionBytes", bytes.join(" ").into()),
+                                            ("instruction", line.text_with(&self.symbols).into())]);
+
+        if let Some(name) = self.symbols.name(line.addr)
+        {
+            json.set("symbol", name.into());
+        }
+
+        if let Some(source) = self.map.lookup(line.addr)
+        {
+            json.set("location", self.source(source.file));
+            json.set("line", source.line.into());
+        }
+
+        json
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				SOURCES
+    /////////////////////////////////////////////////////////////////////
+
+    // The source map's file for a path from the editor
+    fn file_index(&self, path: &str) -> Option<usize>
+    {
+        let full = fs::canonicalize(path).ok();
+        let files = self.map.files();
+
+        files.iter().position(|f| full.is_some() && fs::canonicalize(f).ok() == full)
+            .or_else(|| files.iter().position(|f| f == path))
+            .or_else(|| files.iter().position(|f| Path::new(f).file_name() == Path::new(path).file_name()))
+    }
+
+    fn source(&self, file: usize) -> Json
+    {
+        let name = &self.map.files()[file];
+        let path = fs::canonicalize(name).map_or(name.clone(), |p| p.display().to_string());
+        let short = Path::new(name).file_name().map_or(name.clone(), |n| n.to_string_lossy().to_string());
+
+        Json::object(vec![("name", short.into()), ("path", path.into())])
+    }
+}
+
+/////////////////////////////////////////////////////////////////////
+//				CONNECTION
+/////////////////////////////////////////////////////////////////////
+
+struct Connection
+{
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    seq: i64,
+}
+
+impl Connection
+{
+    fn new(stream: TcpStream) -> io::Result<Connection>
+    {
+        stream.set_nodelay(true)?;
+        Ok(Connection { reader: BufReader::new(stream.try_clone()?), writer: stream, seq: 1 })
+    }
+
+    // The next message, None when the editor has gone
+    fn read(&mut self) -> io::Result<Option<Json>>
+    {
+        let mut len = None;
+        loop
+        {
+            let mut header = String::new();
+            if self.reader.read_line(&mut header)? == 0
+            {
+                return Ok(None);
+            }
+
+            let header = header.trim();
+            if header.is_empty()
+            {
+                break;
+            }
+
+            if let Some((name, value)) = header.split_once(':')
+            {
+                if name.trim().eq_ignore_ascii_case("Content-Length")
+                {
+                    len = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+
+        let len = len.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "message without a Content-Length"))?;
+        let mut body = vec![0; len];
+        self.reader.read_exact(&mut body)?;
+
+        let text = String::from_utf8_lossy(&body);
+        Json::parse(&text).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn send(&mut self, mut message: Json) -> io::Result<()>
+    {
+        message.set("seq", self.seq.into());
+        self.seq += 1;
+
+        let text = message.to_string();
+        write!(self.writer, "Content-Length: {}\r\n\r\n{}", text.len(), text)
+    }
+
+    fn respond(&mut self, request: &Json, result: Result<Json, String>) -> io::Result<()>
+    {
+        let mut response = Json::object(vec![("seq", 0i64.into()), ("type", "response".into()), ("request_seq", request.get("seq").clone()),
+                                                ("command", request.get("command").clone())]);
+        match result
+        {
+            Ok(body) =>
+            {
+                response.set("success", true.into());
+                if !body.is_null()
+                {
+                    response.set("body", body);
+                }
+            }
+
+            Err(message) =>
+            {
+                response.set("success", false.into());
+                response.set("message", message.into());
+            }
+        }
+
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()>
+    {
+        let mut message = Json::object(vec![("seq", 0i64.into()), ("type", "event".into()), ("event", event.into())]);
+        if !body.is_null()
+        {
+            message.set("body", body);
+        }
+
+        self.send(message)
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<String>, breakpoints: &[i64]) -> io::Result<()>
+    {
+        let mut body = Json::object(vec![("reason", reason.into()), ("threadId", THREAD_ID.into()), ("allThreadsStopped", true.into())]);
+        if let Some(text) = text
+        {
+            body.set("text", text.into());
+        }
+
+        if !breakpoints.is_empty()
+        {
+            body.set("hitBreakpointIds", breakpoints.iter().map(|id| Json::from(*id)).collect::<Vec<Json>>().into());
+        }
+
+        self.event("stopped", body)
+    }
+
+    // Whether the editor has sent something, or hung up, without waiting for it
+    fn waiting(&mut self) -> io::Result<bool>
+    {
+        if !self.reader.buffer().is_empty()
+        {
+            return Ok(true);
+        }
+
+        client_waiting(self.reader.get_ref())
+    }
+}
+
+fn capabilities() -> Json
+{
+    let supports = ["supportsConfigurationDoneRequest", "supportsConditionalBreakpoints", "supportsEvaluateForHovers", "supportsSetVariable",
+                    "supportsReadMemoryRequest", "supportsWriteMemoryRequest", "supportsDisassembleRequest", "supportsInstructionBreakpoints",
+                    "supportsSteppingGranularity"];
+
+    Json::Object(supports.iter().map(|s| (s.to_string(), Json::Bool(true))).collect())
+}
+
+// Memory and instruction references are addresses, "0x0204"
+fn reference(addr: u16) -> String
+{
+    format!("0x{:04X}", addr)
+}
+
+fn byte(value: u8) -> String
+{
+    format!("${:02X}", value)
+}
+
+// The instructions just before addr. Where they start is a guess: of the places to start
+// disassembling from that line up with addr, the one with the fewest bytes that don't
+// decode wins, the furthest back of those for the most context.
+fn lines_before(bus: &dyn Bus, addr: u16, back: usize) -> Vec<Line>
+{
+    let mut best: Option<(usize, Vec<Line>)> = None;
+    for start in addr.saturating_sub((back * 3) as u16)..addr
+    {
+        let mut lines = Vec::new();
+        let mut next = start as u32;
+        while next < addr as u32
+        {
+            let line = disasm::disassemble_one(bus, next as u16);
+            next += line.len as u32;
+            lines.push(line);
+        }
+
+        let undecoded = lines.iter().filter(|l| l.decoded.is_none()).count();
+        if next == addr as u32 && undecoded < best.as_ref().map_or(usize::MAX, |(fewest, _)| *fewest)
+        {
+            best = Some((undecoded, lines));
+        }
+    }
+
+    best.map_or(Vec::new(), |(_, lines)| lines)
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String
+{
+    let mut out = String::new();
+    for chunk in data.chunks(3)
+    {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, b)| bits | (*b as u32) << (16 - 8 * i));
+        for i in 0..4
+        {
+            match i <= chunk.len()
+            {
+                true => out.push(BASE64[(bits >> (18 - 6 * i)) as usize & 0x3F] as char),
+                false => out.push('='),
+            }
+        }
+    }
+
+    out
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>>
+{
+    let mut out = Vec::new();
+    let (mut bits, mut count) = (0u32, 0);
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+    {
+        bits = (bits << 6) | BASE64.iter().position(|b| *b == c)? as u32;
+        count += 6;
+
+        if count >= 8
+        {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+
+    Some(out)
+}
@@ -0,0 +1,57 @@
+
+#![allow(dead_code)]
+
+// Debugging server plumbing
+//
+// What the remote debugging servers (vice_monitor.rs, dap.rs) share: the Target they
+// drive a machine through and their socket handling. The run control itself is a
+// Debugger (see debugger.rs).
+
+use std::io;
+use std::net::{TcpListener, TcpStream};
+
+use super::{R6502, Bus};
+
+// What a debugging server needs from a machine
+pub trait Target
+{
+    fn cpu(&self) -> &R6502;
+    fn cpu_mut(&mut self) -> &mut R6502;
+    fn bus(&self) -> &dyn Bus;
+    fn bus_mut(&mut self) -> &mut dyn Bus;
+
+    // Run until an observer stops the cpu or the program ends
+    fn run(&mut self);
+    fn reset(&mut self);
+}
+
+// Instructions a server runs between looking for requests from its client while the machine runs
+pub(crate) const SLICE: u32 = 10_000;
+
+// Servers only listen on localhost. Port 0 picks a free one, local_addr() on the listener says which.
+pub(crate) fn listen(port: u16) -> io::Result<TcpListener>
+{
+    TcpListener::bind(("127.0.0.1", port))
+}
+
+// About to run an RTS or RTI
+pub(crate) fn is_return<T: Target>(target: &T) -> bool
+{
+    matches!(target.bus().read(target.cpu().pc), 0x60 | 0x40)
+}
+
+// Whether the client has sent something, or hung up, without waiting for it. The
+// stream is always left blocking, even when the peek fails.
+pub(crate) fn client_waiting(stream: &TcpStream) -> io::Result<bool>
+{
+    stream.set_nonblocking(true)?;
+    let waiting = match stream.peek(&mut [0u8])
+    {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    };
+
+    stream.set_nonblocking(false)?;
+    waiting
+}
@@ -0,0 +1,441 @@
+
+#![allow(dead_code)]
+
+// Debugger
+//
+// The run control behind a debugger front end: breakpoints, single stepping, stepping
+// over subroutine calls and running to an address. A Debugger is an observer, so the
+// machine keeps a handle to it (Rc<RefCell<Debugger>>), registers it with the cpu and
+// runs as it normally would. When the debugger wants to stop, the cpu stops and the
+// machine hands control back to the front end, which looks at stop_reason(), shows
+// the user what happened and calls one of the resume methods before running again.
+//
+// The cpu stops:
+//      before an instruction at a breakpoint, or at the address given to run_to()
+//      after the instructions asked for by step(), or when next() gets back from a JSR
+//      before a BRK runs, and just after an IRQ or NMI enters its handler (both can be
+//      turned off)
+//      before an undocumented opcode runs, and on an opcode the cpu can't run at all,
+//      which would otherwise panic
+//
+// The instruction the cpu is stopped at never triggers a breakpoint again when resuming,
+// so continuing from a breakpoint runs it rather than stopping straight away.
+//
+// Breakpoints can have a condition (see expr.rs), the cpu only stops at them when it is
+// true. Conditions can also be added on their own, without an address. Those are checked
+// before every instruction and stop the cpu when they become true, so a condition that
+// stays true doesn't stop it again on every instruction after that.
+//
+// Watchpoints stop the cpu after an instruction that reads or writes memory in a range
+// of addresses, optionally only when a particular value is read or written. Instruction
+// fetches don't count as reads. Conditions and watchpoints are given ids when they're
+// added, counting up from 1, which are used to remove them again.
+
+use std::collections::BTreeMap;
+
+use super::{R6502, Bus};
+use super::disasm;
+use super::expr::Expr;
+use super::observer::{Observer, Instruction, Access, Control, Interrupt};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StopReason
+{
+    Step,                       // A step(), next() or run_to() finished
+    Breakpoint(u16),
+    Brk(u16),                   // About to run the BRK at this address
+    Interrupt(Interrupt),       // Just entered the handler
+    IllegalOpcode(u16, u8),     // Address and opcode, undocumented or one the cpu can't run
+    Condition(u32),             // Id of the condition that became true
+
+    // The instruction at pc made an access a watchpoint was waiting for
+    Watchpoint { id: u32, pc: u16, addr: u16, value: u8, access: Access },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WatchKind
+{
+    Read,
+    Write,
+    Access,     // Reads and writes
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Watchpoint
+{
+    pub start: u16,
+    pub end: u16,           // Inclusive
+    pub kind: WatchKind,
+    pub value: Option<u8>,  // Only accesses of this value
+}
+
+impl Watchpoint
+{
+    pub fn new(start: u16, end: u16, kind: WatchKind) -> Watchpoint
+    {
+        Watchpoint { start, end, kind, value: None }
+    }
+
+    pub fn with_value(self, value: u8) -> Watchpoint
+    {
+        Watchpoint { value: Some(value), ..self }
+    }
+
+    fn matches(&self, addr: u16, value: u8, access: Access) -> bool
+    {
+        let kind = matches!((self.kind, access), (WatchKind::Read, Access::Read) | (WatchKind::Write, Access::Write)
+                                                  | (WatchKind::Access, Access::Read | Access::Write));
+
+        kind && addr >= self.start && addr <= self.end && (self.value.is_none() || self.value == Some(value))
+    }
+}
+
+struct Condition
+{
+    id: u32,
+    expr: Expr,
+    was_true: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Mode
+{
+    Continue,
+    Step(u32),                  // Instructions left to run
+
+    // Stop at addr, once the stack is back up to sp if there is one
+    RunTo { addr: u16, sp: Option<u16> },
+}
+
+pub struct Debugger
+{
+    breakpoints: BTreeMap<u16, Option<Expr>>,
+    conditions: Vec<Condition>,
+    watchpoints: Vec<(u32, Watchpoint)>,
+    next_id: u32,
+    break_on_brk: bool,
+    break_on_interrupt: bool,
+
+    mode: Mode,
+    resume_pc: Option<u16>,
+    stop: Option<StopReason>,
+
+    // A run_to() or next() that was stopped on the way, for carry_on()
+    interrupted: Option<Mode>,
+
+    // Watchpoint hit by the running instruction: id, address, value and access
+    watch_hit: Option<(u32, u16, u8, Access)>,
+
+    // Worked out by inspect() for before(): the first condition that became
+    // true, and whether the condition of the breakpoint at the pc is true
+    became_true: Option<u32>,
+    breakpoint_true: bool,
+}
+
+impl Debugger
+{
+    pub fn new() -> Debugger
+    {
+        Debugger { breakpoints: BTreeMap::new(), conditions: Vec::new(), watchpoints: Vec::new(), next_id: 1,
+                    break_on_brk: true, break_on_interrupt: true,
+                    mode: Mode::Continue, resume_pc: None, stop: None, interrupted: None, watch_hit: None,
+                    became_true: None, breakpoint_true: false }
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				BREAKPOINTS
+    /////////////////////////////////////////////////////////////////////
+
+    // Returns false if there was already a breakpoint at addr
+    pub fn add_breakpoint(&mut self, addr: u16) -> bool
+    {
+        if self.breakpoints.contains_key(&addr)
+        {
+            return false;
+        }
+
+        self.breakpoints.insert(addr, None);
+        true
+    }
+
+    // Only stop at addr when condition is true. Replaces the condition
+    // of a breakpoint that's already there.
+    pub fn add_conditional_breakpoint(&mut self, addr: u16, condition: Expr) -> bool
+    {
+        self.breakpoints.insert(addr, Some(condition)).is_none()
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool
+    {
+        self.breakpoints.remove(&addr).is_some()
+    }
+
+    pub fn clear_breakpoints(&mut self)
+    {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_
+    {
+        self.breakpoints.keys().copied()
+    }
+
+    pub fn breakpoint_condition(&self, addr: u16) -> Option<&Expr>
+    {
+        self.breakpoints.get(&addr).and_then(|c| c.as_ref())
+    }
+
+    // Stop whenever condition becomes true, wherever the cpu is
+    pub fn add_condition(&mut self, condition: Expr) -> u32
+    {
+        let id = self.take_id();
+        self.conditions.push(Condition { id, expr: condition, was_true: false });
+        id
+    }
+
+    pub fn remove_condition(&mut self, id: u32) -> bool
+    {
+        let len = self.conditions.len();
+        self.conditions.retain(|c| c.id != id);
+        self.conditions.len() != len
+    }
+
+    pub fn conditions(&self) -> impl Iterator<Item = (u32, &Expr)>
+    {
+        self.conditions.iter().map(|c| (c.id, &c.expr))
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> u32
+    {
+        let id = self.take_id();
+        self.watchpoints.push((id, watchpoint));
+        id
+    }
+
+    pub fn remove_watchpoint(&mut self, id: u32) -> bool
+    {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|(i, _)| *i != id);
+        self.watchpoints.len() != len
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = (u32, &Watchpoint)>
+    {
+        self.watchpoints.iter().map(|(id, w)| (*id, w))
+    }
+
+    fn take_id(&mut self) -> u32
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn set_break_on_brk(&mut self, enabled: bool)
+    {
+        self.break_on_brk = enabled;
+    }
+
+    pub fn set_break_on_interrupt(&mut self, enabled: bool)
+    {
+        self.break_on_interrupt = enabled;
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				RUN CONTROL
+    /////////////////////////////////////////////////////////////////////
+
+    // Why the cpu last stopped, None while it's running
+    pub fn stop_reason(&self) -> Option<StopReason>
+    {
+        self.stop
+    }
+
+    // Run until something stops the cpu
+    pub fn cont(&mut self, cpu: &R6502)
+    {
+        self.resume(cpu, Mode::Continue);
+    }
+
+    // Run count instructions
+    pub fn step(&mut self, cpu: &R6502, count: u32)
+    {
+        self.resume(cpu, Mode::Step(count.max(1)));
+    }
+
+    // Run one instruction, or the whole subroutine if it is a JSR
+    pub fn next(&mut self, cpu: &R6502, bus: &dyn Bus)
+    {
+        let line = disasm::disassemble_one(bus, cpu.pc);
+        match line.decoded.map(|d| d.name)
+        {
+            Some("JSR") => self.resume(cpu, Mode::RunTo { addr: line.next(), sp: Some(cpu.sp) }),
+            _ => self.resume(cpu, Mode::Step(1)),
+        }
+    }
+
+    // Run until the cpu gets to addr
+    pub fn run_to(&mut self, cpu: &R6502, addr: u16)
+    {
+        self.resume(cpu, Mode::RunTo { addr, sp: None });
+    }
+
+    // Pick up a run_to() or next() that a breakpoint or watchpoint stopped on the way,
+    // for front ends that don't always stop at them. Returns false, without resuming,
+    // if there isn't one.
+    pub fn carry_on(&mut self, cpu: &R6502) -> bool
+    {
+        match self.interrupted.take()
+        {
+            Some(mode) =>
+            {
+                self.resume(cpu, mode);
+                true
+            }
+
+            None => false,
+        }
+    }
+
+    fn resume(&mut self, cpu: &R6502, mode: Mode)
+    {
+        self.mode = mode;
+        self.resume_pc = Some(cpu.pc);
+        self.stop = None;
+    }
+
+    fn stop(&mut self, reason: StopReason) -> Control
+    {
+        self.stop = Some(reason);
+        self.interrupted = match (self.mode, reason)
+        {
+            (Mode::RunTo { .. }, StopReason::Breakpoint(_) | StopReason::Watchpoint { .. }) => Some(self.mode),
+            _ => None,
+        };
+
+        self.mode = Mode::Continue;
+        Control::Stop
+    }
+}
+
+impl Default for Debugger
+{
+    fn default() -> Debugger
+    {
+        Debugger::new()
+    }
+}
+
+impl Observer for Debugger
+{
+    fn inspect(&mut self, cpu: &R6502, bus: &dyn Bus, instr: &Instruction)
+    {
+        // Conditions are checked every time, even when resuming, so they know whether they
+        // were already true
+        self.became_true = None;
+        for condition in self.conditions.iter_mut()
+        {
+            let now = condition.expr.is_true(cpu, bus);
+            if now && !condition.was_true && self.became_true.is_none()
+            {
+                self.became_true = Some(condition.id);
+            }
+
+            condition.was_true = now;
+        }
+
+        self.breakpoint_true = match self.breakpoints.get(&instr.pc)
+        {
+            Some(Some(condition)) => condition.is_true(cpu, bus),
+            _ => false,
+        };
+    }
+
+    fn before(&mut self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        let resuming = self.resume_pc.take() == Some(instr.pc);
+        if resuming
+        {
+            return Control::Continue;
+        }
+
+        if let Some(id) = self.became_true
+        {
+            return self.stop(StopReason::Condition(id));
+        }
+
+        match self.breakpoints.get(&instr.pc)
+        {
+            Some(None) => return self.stop(StopReason::Breakpoint(instr.pc)),
+            Some(Some(_)) if self.breakpoint_true => return self.stop(StopReason::Breakpoint(instr.pc)),
+            _ => (),
+        }
+
+        if self.break_on_brk && instr.bytes[0] == 0x00
+        {
+            return self.stop(StopReason::Brk(instr.pc));
+        }
+
+        if !instr.decoded.documented
+        {
+            return self.stop(StopReason::IllegalOpcode(instr.pc, instr.bytes[0]));
+        }
+
+        match self.mode
+        {
+            Mode::RunTo { addr, sp } if addr == instr.pc && cpu.sp >= sp.unwrap_or(0) => self.stop(StopReason::Step),
+            _ => Control::Continue,
+        }
+    }
+
+    fn after(&mut self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        if let Some((id, addr, value, access)) = self.watch_hit.take()
+        {
+            return self.stop(StopReason::Watchpoint { id, pc: instr.pc, addr, value, access });
+        }
+
+        match self.mode
+        {
+            Mode::Step(1) => self.stop(StopReason::Step),
+            Mode::Step(n) =>
+            {
+                self.mode = Mode::Step(n - 1);
+                Control::Continue
+            }
+
+            _ => Control::Continue,
+        }
+    }
+
+    fn access(&mut self, addr: u16, value: u8, access: Access)
+    {
+        // The first one hit is reported
+        if self.watch_hit.is_some()
+        {
+            return;
+        }
+
+        if let Some((id, _)) = self.watchpoints.iter().find(|(_, w)| w.matches(addr, value, access))
+        {
+            self.watch_hit = Some((*id, addr, value, access));
+        }
+    }
+
+    fn interrupt(&mut self, cpu: &R6502, kind: Interrupt) -> Control
+    {
+        // Whatever was stopped at before the interrupt, the handler comes first now
+        self.resume_pc = None;
+
+        match self.break_on_interrupt
+        {
+            true => self.stop(StopReason::Interrupt(kind)),
+            false => Control::Continue,
+        }
+    }
+
+    fn illegal(&mut self, cpu: &R6502, opcode: u8) -> Control
+    {
+        self.stop(StopReason::IllegalOpcode(cpu.pc, opcode))
+    }
+}
@@ -0,0 +1,317 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use super::{R6502, Bus, Flags, exe_rts};
+use super::addressing_modes::{AddressingModes, ModeID};
+use super::instructions::Instructions;
+
+// Instruction decoding:
+// https://llx.com/Neil/a2/opcodes.html
+
+// Every opcode is decoded once, at compile time, into the addressing mode and
+// the instruction function that implement it. The cpu then only has to index
+// DECODE_TABLE with the opcode instead of picking the bit pattern apart for
+// every instruction it runs.
+
+#[derive(Clone, Copy)]
+pub struct Decoded
+{
+    pub opcode: u8,
+    pub name: &'static str,
+    pub mode: ModeID,
+
+    // false for the undocumented opcodes that happen to decode to something
+    pub documented: bool,
+
+    // Base number of cycles, and whether crossing a page
+    // while indexing the operand address costs one more
+    pub cycles: u8,
+    pub page_penalty: bool,
+
+    addr: fn(&mut R6502, &mut dyn Bus) -> ModeID,
+    op: fn(&mut R6502, &mut dyn Bus),
+}
+
+impl Decoded
+{
+    const fn new(name: &'static str, mode: ModeID, op: fn(&mut R6502, &mut dyn Bus)) -> Decoded
+    {
+        Decoded { opcode: 0, name, mode, documented: false, cycles: 0, page_penalty: false, addr: AddressingModes::get(mode), op }
+    }
+
+    // Size of the instruction in bytes, including the opcode
+    pub fn size(&self) -> u16
+    {
+        1 + self.mode.operand_len()
+    }
+
+    pub(crate) fn run(&self, cpu: &mut R6502, bus: &mut dyn Bus)
+    {
+        cpu.addr_mode = (self.addr)(cpu, bus);
+
+        let mut cycles = self.cycles as u64;
+        if self.page_penalty && page_crossed(cpu)
+        {
+            cycles += 1;
+        }
+
+        let next = cpu.pc;
+        let taken = self.mode == ModeID::REL && branch_taken(cpu, self.opcode);
+        (self.op)(cpu, bus);
+
+        // Taken branches cost one more cycle, two if they land on another page
+        if taken
+        {
+            cycles += 1;
+            if cpu.pc & 0xFF00 != next & 0xFF00
+            {
+                cycles += 1;
+            }
+        }
+
+        cpu.cycles += cycles;
+    }
+}
+
+// Branches are xxy10000, they are taken when the flag picked by xx equals y
+fn branch_taken(cpu: &R6502, opcode: u8) -> bool
+{
+    const FLAGS: [Flags; 4] = [Flags::N, Flags::V, Flags::C, Flags::Z];
+
+    let flag = FLAGS[(opcode >> 6) as usize] as u8;
+    let set = cpu.status & flag != 0;
+    set == (opcode & 0x20 != 0)
+}
+
+// Did indexing move the operand address onto the next page
+fn page_crossed(cpu: &R6502) -> bool
+{
+    let index = match cpu.addr_mode
+    {
+        ModeID::ABX => cpu.x,
+        ModeID::ABY | ModeID::IZY => cpu.y,
+        _ => return false,
+    };
+
+    let base = cpu.working_addr.wrapping_sub(index as u16);
+    base & 0xFF00 != cpu.working_addr & 0xFF00
+}
+
+pub(crate) static DECODE_TABLE: [Option<Decoded>; 256] = build_table();
+
+// Returns None for opcodes the cpu can't execute
+pub fn decode(opcode: u8) -> Option<Decoded>
+{
+    DECODE_TABLE[opcode as usize]
+}
+
+// Branches, jumps, subroutine calls/returns and interrupts. Anything
+// that can move the program counter somewhere other than the next instruction.
+pub fn is_flow_control(opcode: u8) -> bool
+{
+    const JMP_ABS: u8 = 0x4C;
+    const JMP_IND: u8 = 0x6C;
+
+    match opcode
+    {
+        0x00 | 0x20 | 0x40 | 0x60 | JMP_ABS | JMP_IND => true,
+        _ => opcode & 0x1F == 0x10,
+    }
+}
+
+// CLI, PLP and RTI can enable interrupts, after which a held IRQ is taken
+pub fn can_enable_interrupts(opcode: u8) -> bool
+{
+    matches!(opcode, 0x58 | 0x28 | 0x40)
+}
+
+// One bit per opcode for the 151 documented NMOS 6502 instructions
+const DOCUMENTED: [u64; 4] = [0x6363777363636763, 0x6363776363637763, 0x7773777727737572, 0x6363777363637773];
+
+// Base cycle count for each opcode
+// http://www.6502.org/tutorials/6502opcodes.html
+const CYCLES: [u8; 256] = 
+[
+    7, 6, 2, 0, 3, 3, 5, 0, 3, 2, 2, 0, 4, 4, 6, 0,   // 0_
+    2, 5, 2, 0, 4, 4, 6, 0, 2, 4, 2, 0, 4, 4, 7, 0,   // 1_
+    6, 6, 2, 0, 3, 3, 5, 0, 4, 2, 2, 0, 4, 4, 6, 0,   // 2_
+    2, 5, 2, 0, 4, 4, 6, 0, 2, 4, 2, 0, 4, 4, 7, 0,   // 3_
+    6, 6, 2, 0, 3, 3, 5, 0, 3, 2, 2, 0, 3, 4, 6, 0,   // 4_
+    2, 5, 2, 0, 3, 4, 6, 0, 2, 4, 2, 0, 3, 4, 7, 0,   // 5_
+    6, 6, 2, 0, 3, 3, 5, 0, 4, 2, 2, 0, 5, 4, 6, 0,   // 6_
+    2, 5, 2, 0, 3, 4, 6, 0, 2, 4, 2, 0, 3, 4, 7, 0,   // 7_
+    2, 6, 2, 0, 3, 3, 3, 0, 2, 2, 2, 0, 4, 4, 4, 0,   // 8_
+    2, 6, 2, 0, 4, 4, 4, 0, 2, 5, 2, 0, 5, 5, 5, 0,   // 9_
+    2, 6, 2, 0, 3, 3, 3, 0, 2, 2, 2, 0, 4, 4, 4, 0,   // A_
+    2, 5, 2, 0, 4, 4, 4, 0, 2, 4, 2, 0, 4, 4, 4, 0,   // B_
+    2, 6, 2, 0, 3, 3, 5, 0, 2, 2, 2, 0, 4, 4, 6, 0,   // C_
+    2, 5, 2, 0, 4, 4, 6, 0, 2, 4, 2, 0, 4, 4, 7, 0,   // D_
+    2, 6, 2, 0, 3, 3, 5, 0, 2, 2, 2, 0, 4, 4, 6, 0,   // E_
+    2, 5, 2, 0, 4, 4, 6, 0, 2, 4, 2, 0, 4, 4, 7, 0,   // F_
+];
+
+// One bit per opcode for the reads that take an extra cycle when
+// indexing (absolute,X  absolute,Y  (indirect),Y) crosses a page
+const PAGE_PENALTY: [u64; 4] = [0x3202000022020000, 0x2202000022020000, 0x7202000000000000, 0x3202000032020000];
+
+const GROUP_ONE_NAMES: [&str; 8] = ["ORA", "AND", "EOR", "ADC", "STA", "LDA", "CMP", "SBC"];
+const GROUP_TWO_NAMES: [&str; 8] = ["ASL", "ROL", "LSR", "ROR", "STX", "LDX", "DEC", "INC"];
+const GROUP_THREE_NAMES: [&str; 8] = ["???", "BIT", "JMP", "JMP", "STY", "LDY", "CPY", "CPX"];
+const BRANCH_NAMES: [&str; 8] = ["BPL", "BMI", "BVC", "BVS", "BCC", "BCS", "BNE", "BEQ"];
+const SB1_NAMES: [&str; 16] = ["PHP", "CLC", "PLP", "SEC", "PHA", "CLI", "PLA", "SEI", 
+                               "DEY", "TYA", "TAY", "CLV", "INY", "CLD", "INX", "SED"];
+const SB2_NAMES: [&str; 6] = ["TXA", "TXS", "TAX", "TSX", "DEX", "NOP"];
+
+const fn build_table() -> [Option<Decoded>; 256]
+{
+    let mut table = [None; 256];
+
+    let mut i = 0;
+    while i < table.len()
+    {
+        table[i] = match decode_op(i as u8)
+        {
+            Some(mut decoded) => 
+            {
+                decoded.opcode = i as u8;
+                decoded.documented = DOCUMENTED[i / 64] & (1 << (i % 64)) != 0;
+                decoded.cycles = CYCLES[i];
+                decoded.page_penalty = PAGE_PENALTY[i / 64] & (1 << (i % 64)) != 0;
+                Some(decoded)
+            }
+
+            None => None,
+        };
+
+        i += 1;
+    }
+
+    table
+}
+
+const fn decode_op(instruction: u8) -> Option<Decoded>
+{
+    // Check if this is a branch instruction
+    // The conditional branch instructions all have the form xxy10000
+    // 0x1F == 11111
+    // 0x10 == 10000
+    const BRANCH_OP: u8 = 0x10;
+    const BRANCH_MASK: u8 = 0x1F;
+    if instruction & BRANCH_MASK == BRANCH_OP
+    {
+        // Decode instruction
+        // Need to map: 10	30	50	70	90	B0 	D0	F0 - op code
+        // to:          0   1   2   3   4   5   6   7  - method index
+        let idx = ((instruction - 16) / 32) as usize;
+        return Some(Decoded::new(BRANCH_NAMES[idx], ModeID::REL, Instructions::GROUP_BRANCHING_OPS[idx]));
+    }
+
+    // Interrupt and Subroutine
+    const BRK: u8 = 0x00;
+    const JSR: u8 = 0x20;
+    const RTI: u8 = 0x40;
+    const RTS: u8 = 0x60;
+
+    match instruction
+    {
+        BRK => return Some(Decoded::new("BRK", ModeID::IMP, Instructions::BRK)),
+        JSR => return Some(Decoded::new("JSR", ModeID::ABS, Instructions::JSR)),
+        RTI => return Some(Decoded::new("RTI", ModeID::IMP, Instructions::RTI)),
+
+        // RTS also has to check for the end of the program
+        RTS => return Some(Decoded::new("RTS", ModeID::IMP, exe_rts)),
+
+        _ => ()
+    }
+
+    // Single byte instructions
+
+    // Group of 8's
+    // PHP CLC PLP SEC PHA CLI PLA SEI DEY TYA TAY CLV INY CLD INX SED
+    //  08  18  28  38  48  58  68 	78  88 	98  A8  B8 	C8  D8  E8  F8 
+    // Index = (Value-8) / 16
+    const EIGHT_MASK: u8 = 0x0F;
+    if instruction & EIGHT_MASK == 0x08
+    {
+        let i = ((instruction - 0x08) / 0x10) as usize;
+        return Some(Decoded::new(SB1_NAMES[i], ModeID::IMP, Instructions::GROUP_SB1_OPS[i]));
+    }
+
+    if let Some(decoded) = decode_group_a(instruction)
+    {
+        return Some(decoded);
+    }
+
+    // Instructions with arguments
+    const GROUP_ONE_OP:   u8 = 0x01;
+    const GROUP_TWO_OP:   u8 = 0x02;
+    const GROUP_THREE_OP: u8 = 0x00;
+
+    let addr_mask = ((instruction & 0x1C) >> 2) as usize;
+    let op_mask = ((instruction & 0xE0) >> 5) as usize;
+
+    let group_code = instruction & 0x03; // group one has a bit pattern of xxxxxx01
+    match group_code
+    {
+        GROUP_ONE_OP => Some(Decoded::new(GROUP_ONE_NAMES[op_mask], AddressingModes::GROUP_ONE_MODES[addr_mask], Instructions::GROUP_ONE_OPS[op_mask])),
+        GROUP_TWO_OP => Some(decode_group_two(instruction, addr_mask, op_mask)),
+        GROUP_THREE_OP => Some(decode_group_three(instruction, addr_mask, op_mask)),
+
+        _ => None
+    }
+}
+
+// Group of A's
+// TXA 	TXS  TAX  TSX  DEX 	NOP
+// 8A 	9A 	  AA   BA 	CA 	 EA	
+// Index = (Value-8A) /	16
+//
+// The low nibble has to be all of A. The interpreter used to mask with 0x05, which never
+// matched, so these ran as the group two instruction the bits spell out (8A as STX with
+// the accumulator mode, CA as DEC). NOP breaks the pattern since DA is not an instruction.
+const fn decode_group_a(instruction: u8) -> Option<Decoded>
+{
+    const A_MASK: u8 = 0x0F;
+    const NOP: u8 = 0xEA;
+    if instruction == NOP
+    {
+        return Some(Decoded::new("NOP", ModeID::IMP, Instructions::NOP));
+    }
+
+    if instruction >= 0x8A && instruction <= 0xCA && instruction & A_MASK == 0x0A
+    {
+        let i = ((instruction - 0x8A) / 0x10) as usize;
+        return Some(Decoded::new(SB2_NAMES[i], ModeID::IMP, Instructions::GROUP_SB2_OPS[i]));
+    }
+
+    None
+}
+
+const fn decode_group_two(instruction: u8, addr_mask: usize, op_mask: usize) -> Decoded
+{
+    // With STX and LDX, "zero page,X" addressing becomes "zero page,Y", and with LDX, "absolute,X" becomes "absolute,Y".
+    const STX_ZPX: u8 = 0x96;
+    const LDX_ZPX: u8 = 0xB6;
+    const LDX_ABX: u8 = 0xBE;
+
+    match instruction
+    {
+        STX_ZPX => Decoded::new("STX", ModeID::ZPY, Instructions::STX),
+        LDX_ZPX => Decoded::new("LDX", ModeID::ZPY, Instructions::LDX),
+        LDX_ABX => Decoded::new("LDX", ModeID::ABY, Instructions::LDX),
+
+        _ => Decoded::new(GROUP_TWO_NAMES[op_mask], AddressingModes::GROUP_TWO_MODES[addr_mask], Instructions::GROUP_TWO_OPS[op_mask]),
+    }
+}
+
+const fn decode_group_three(instruction: u8, addr_mask: usize, op_mask: usize) -> Decoded
+{
+    // SPECIAL CASE FOR JMP (abs)
+    const JMP_IND: u8 = 0x6C;
+    if instruction == JMP_IND
+    {
+        return Decoded::new("JMP", ModeID::IND, Instructions::GROUP_THREE_OPS[op_mask]);
+    }
+
+    Decoded::new(GROUP_THREE_NAMES[op_mask], AddressingModes::GROUP_THREE_MODES[addr_mask], Instructions::GROUP_THREE_OPS[op_mask])
+}
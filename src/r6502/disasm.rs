@@ -0,0 +1,226 @@
+
+#![allow(dead_code)]
+
+// Disassembler
+//
+// Turns code into text, one line per instruction:
+//
+//      $0200  A9 10     LDA #$10
+//      $0202  D0 FC     BNE $0200
+//      $0204  02        .byte $02
+//
+// Relative branches show the address they branch to. Bytes that don't decode
+// to a documented instruction are shown as .byte, as are instructions cut off
+// by the end of the data.
+//
+// The _with() versions take a SymbolTable and show names instead of addresses
+// wherever there is one, along with the label of the line itself:
+//
+//      $0200  A2 00     main    LDX #$00
+//      $0202  BD 13 02  loop    LDA text,X
+
+use std::fmt;
+
+use super::Bus;
+use super::addressing_modes::ModeID;
+use super::decoder::{self, Decoded};
+use super::symbols::SymbolTable;
+
+#[derive(Clone, Copy)]
+pub struct Line
+{
+    pub addr: u16,
+    pub bytes: [u8; 3],
+    pub len: u8,
+    pub decoded: Option<Decoded>,   // None for .byte lines
+}
+
+impl Line
+{
+    // Address of the next instruction
+    pub fn next(&self) -> u16
+    {
+        self.addr.wrapping_add(self.len as u16)
+    }
+
+    // The mnemonic and operand, "LDA #$10"
+    pub fn text(&self) -> String
+    {
+        self.format_text(None)
+    }
+
+    pub fn text_with(&self, symbols: &SymbolTable) -> String
+    {
+        self.format_text(Some(symbols))
+    }
+
+    // The whole line with the label column
+    pub fn line_with(&self, symbols: &SymbolTable) -> String
+    {
+        let label = symbols.name(self.addr).unwrap_or("");
+        format!("${:04X}  {:<8}  {:<8}{}", self.addr, self.hex_bytes(), label, self.text_with(symbols))
+    }
+
+    fn format_text(&self, symbols: Option<&SymbolTable>) -> String
+    {
+        match self.decoded
+        {
+            Some(decoded) => format_instr_sym(&decoded, &self.bytes, self.addr, symbols),
+            None => format!(".byte ${:02X}", self.bytes[0]),
+        }
+    }
+
+    fn hex_bytes(&self) -> String
+    {
+        let bytes: Vec<String> = self.bytes[..self.len as usize].iter().map(|b| format!("{:02X}", b)).collect();
+        bytes.join(" ")
+    }
+}
+
+impl fmt::Display for Line
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "${:04X}  {:<8}  {}", self.addr, self.hex_bytes(), self.text())
+    }
+}
+
+// Formats an instruction that starts at addr
+pub fn format_instr(decoded: &Decoded, bytes: &[u8; 3], addr: u16) -> String
+{
+    format_instr_sym(decoded, bytes, addr, None)
+}
+
+pub fn format_instr_with(decoded: &Decoded, bytes: &[u8; 3], addr: u16, symbols: &SymbolTable) -> String
+{
+    format_instr_sym(decoded, bytes, addr, Some(symbols))
+}
+
+fn format_instr_sym(decoded: &Decoded, bytes: &[u8; 3], addr: u16, symbols: Option<&SymbolTable>) -> String
+{
+    let name = decoded.name;
+    let op8 = bytes[1];
+    let op16 = ((bytes[2] as u16) << 8) | bytes[1] as u16;
+
+    // An address operand, by name if it has one
+    let zp = || operand(op8 as u16, format!("${:02X}", op8), symbols);
+    let abs = |addr: u16| operand(addr, format!("${:04X}", addr), symbols);
+
+    match decoded.mode
+    {
+        ModeID::IMP | ModeID::ERR => name.to_string(),
+        ModeID::ACM => format!("{} A", name),
+        ModeID::IMM => format!("{} #${:02X}", name, op8),
+        ModeID::ZP0 => format!("{} {}", name, zp()),
+        ModeID::ZPX => format!("{} {},X", name, zp()),
+        ModeID::ZPY => format!("{} {},Y", name, zp()),
+        ModeID::REL => format!("{} {}", name, abs(branch_target(addr, op8))),
+        ModeID::ABS => format!("{} {}", name, abs(op16)),
+        ModeID::ABX => format!("{} {},X", name, abs(op16)),
+        ModeID::ABY => format!("{} {},Y", name, abs(op16)),
+        ModeID::IND => format!("{} ({})", name, abs(op16)),
+        ModeID::IZX => format!("{} ({},X)", name, zp()),
+        ModeID::IZY => format!("{} ({}),Y", name, zp()),
+    }
+}
+
+fn operand(addr: u16, hex: String, symbols: Option<&SymbolTable>) -> String
+{
+    match symbols.and_then(|s| s.name(addr))
+    {
+        Some(name) => name.to_string(),
+        None => hex,
+    }
+}
+
+// Where a branch at addr with the given offset goes
+pub fn branch_target(addr: u16, offset: u8) -> u16
+{
+    addr.wrapping_add(2).wrapping_add(offset as i8 as u16)
+}
+
+// Decode the instruction at addr. read returns None past the end of the code.
+fn decode_line(addr: u16, read: impl Fn(u16) -> Option<u8>) -> Option<Line>
+{
+    let opcode = read(addr)?;
+    let byte = Line { addr, bytes: [opcode, 0, 0], len: 1, decoded: None };
+
+    // Undocumented opcodes are data as far as the disassembly goes
+    let decoded = match decoder::decode(opcode)
+    {
+        Some(decoded) if decoded.documented => decoded,
+        _ => return Some(byte),
+    };
+
+    let mut bytes = [opcode, 0, 0];
+    for i in 1..decoded.size()
+    {
+        match read(addr.wrapping_add(i))
+        {
+            Some(b) => bytes[i as usize] = b,
+            None => return Some(byte),
+        }
+    }
+
+    Some(Line { addr, bytes, len: decoded.size() as u8, decoded: Some(decoded) })
+}
+
+// Disassemble one instruction from the bus
+pub fn disassemble_one(bus: &dyn Bus, addr: u16) -> Line
+{
+    decode_line(addr, |a| Some(bus.read(a))).unwrap()
+}
+
+// Disassemble count instructions from the bus starting at addr
+pub fn disassemble(bus: &dyn Bus, addr: u16, count: usize) -> Vec<Line>
+{
+    let mut lines = Vec::with_capacity(count);
+    let mut addr = addr;
+
+    for _ in 0..count
+    {
+        let line = disassemble_one(bus, addr);
+        addr = line.next();
+        lines.push(line);
+    }
+
+    lines
+}
+
+// Disassemble the bus from start up to and including end. Like disassemble_bytes(),
+// an instruction cut off by end is shown as a .byte.
+pub fn disassemble_range(bus: &dyn Bus, start: u16, end: u16) -> Vec<Line>
+{
+    let len = end.wrapping_sub(start) as usize + 1;
+    let read = |addr: u16| if (addr.wrapping_sub(start) as usize) < len { Some(bus.read(addr)) } else { None };
+
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    while offset < len
+    {
+        let line = decode_line(start.wrapping_add(offset as u16), read).unwrap();
+        offset += line.len as usize;
+        lines.push(line);
+    }
+
+    lines
+}
+
+// Disassemble all of data, which is loaded at base
+pub fn disassemble_bytes(data: &[u8], base: u16) -> Vec<Line>
+{
+    let read = |addr: u16| data.get(addr.wrapping_sub(base) as usize).copied();
+
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    while offset < data.len()
+    {
+        let line = decode_line(base.wrapping_add(offset as u16), read).unwrap();
+        offset += line.len as usize;
+        lines.push(line);
+    }
+
+    lines
+}
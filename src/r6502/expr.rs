@@ -0,0 +1,414 @@
+
+#![allow(dead_code)]
+
+// Expressions
+//
+// A small expression language over the cpu and memory, used for breakpoint conditions:
+//
+//      A == $10 && [$0200] > 5 && C
+//      X >= 3 || [ptr] + [ptr+1] * 256 == $C000
+//      CYC > 100000 && !I
+//
+// Values:
+//      $FF 0xFF %1010 255      numbers in hex, binary or decimal
+//      A X Y SP PC P           registers, P is the status byte
+//      N V B D I Z C           flags, 1 when set and 0 when clear
+//      CYC                     the cycle count
+//      [addr]                  the byte in memory at addr, which can be any expression
+//      loop2                   symbols, when a SymbolTable is given to parse_with()
+//
+// Operators, lowest precedence first (as in Rust, bitwise operators bind tighter
+// than comparisons):
+//      ||
+//      &&
+//      == != < <= > >=
+//      |
+//      ^
+//      &
+//      << >>
+//      + -
+//      * / %
+//      ! - ~                   unary not, negate and complement
+//
+// Arithmetic is done on 64 bit signed integers, comparisons and logic give 1 or 0 and
+// anything that isn't 0 is true. Dividing by 0 gives 0. Names are case insensitive,
+// registers and flags win over symbols with the same name.
+
+use std::fmt;
+
+use super::{R6502, Bus, Flags};
+use super::symbols::SymbolTable;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Value
+{
+    A,
+    X,
+    Y,
+    SP,
+    PC,
+    P,
+    Flag(u8),   // Mask for the flag in the status byte
+    Cycles,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Op
+{
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Node
+{
+    Number(i64),
+    Value(Value),
+    Memory(Box<Node>),
+    Not(Box<Node>),
+    Neg(Box<Node>),
+    Complement(Box<Node>),
+    Binary(Op, Box<Node>, Box<Node>),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Expr
+{
+    text: String,
+    root: Node,
+}
+
+impl Expr
+{
+    pub fn parse(text: &str) -> Result<Expr, String>
+    {
+        Expr::parse_symbols(text, None)
+    }
+
+    pub fn parse_with(text: &str, symbols: &SymbolTable) -> Result<Expr, String>
+    {
+        Expr::parse_symbols(text, Some(symbols))
+    }
+
+    fn parse_symbols(text: &str, symbols: Option<&SymbolTable>) -> Result<Expr, String>
+    {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, pos: 0, symbols };
+
+        let root = parser.expr(0)?;
+        match parser.peek()
+        {
+            None => Ok(Expr { text: text.trim().to_string(), root }),
+            Some(token) => Err(format!("unexpected {} in \"{}\"", token, text.trim())),
+        }
+    }
+
+    pub fn eval(&self, cpu: &R6502, bus: &dyn Bus) -> i64
+    {
+        eval(&self.root, cpu, bus)
+    }
+
+    pub fn is_true(&self, cpu: &R6502, bus: &dyn Bus) -> bool
+    {
+        self.eval(cpu, bus) != 0
+    }
+}
+
+// The expression as it was written
+impl fmt::Display for Expr
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}", self.text)
+    }
+}
+
+fn eval(node: &Node, cpu: &R6502, bus: &dyn Bus) -> i64
+{
+    match node
+    {
+        Node::Number(n) => *n,
+        Node::Value(value) => match value
+        {
+            Value::A => cpu.a as i64,
+            Value::X => cpu.x as i64,
+            Value::Y => cpu.y as i64,
+            Value::SP => (cpu.sp & 0x00FF) as i64,
+            Value::PC => cpu.pc as i64,
+            Value::P => cpu.status as i64,
+            Value::Flag(mask) => (cpu.status & mask != 0) as i64,
+            Value::Cycles => cpu.cycles as i64,
+        },
+
+        Node::Memory(addr) => bus.read(eval(addr, cpu, bus) as u16) as i64,
+        Node::Not(n) => (eval(n, cpu, bus) == 0) as i64,
+        Node::Neg(n) => eval(n, cpu, bus).wrapping_neg(),
+        Node::Complement(n) => !eval(n, cpu, bus),
+
+        // Only evaluate the right hand side of && and || when it's needed
+        Node::Binary(Op::And, l, r) => (eval(l, cpu, bus) != 0 && eval(r, cpu, bus) != 0) as i64,
+        Node::Binary(Op::Or, l, r) => (eval(l, cpu, bus) != 0 || eval(r, cpu, bus) != 0) as i64,
+
+        Node::Binary(op, l, r) =>
+        {
+            let (l, r) = (eval(l, cpu, bus), eval(r, cpu, bus));
+            match op
+            {
+                Op::Eq => (l == r) as i64,
+                Op::Ne => (l != r) as i64,
+                Op::Lt => (l < r) as i64,
+                Op::Le => (l <= r) as i64,
+                Op::Gt => (l > r) as i64,
+                Op::Ge => (l >= r) as i64,
+                Op::BitOr => l | r,
+                Op::BitXor => l ^ r,
+                Op::BitAnd => l & r,
+                Op::Shl => l.wrapping_shl(r as u32),
+                Op::Shr => l.wrapping_shr(r as u32),
+                Op::Add => l.wrapping_add(r),
+                Op::Sub => l.wrapping_sub(r),
+                Op::Mul => l.wrapping_mul(r),
+                Op::Div => l.checked_div(r).unwrap_or(0),
+                Op::Rem => l.checked_rem(r).unwrap_or(0),
+                Op::And | Op::Or => unreachable!(),
+            }
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////
+//				TOKENS
+/////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token
+{
+    Number(i64),
+    Name(String),
+    Punct(&'static str),
+}
+
+impl fmt::Display for Token
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Name(name) => write!(f, "\"{}\"", name),
+            Token::Punct(s) => write!(f, "\"{}\"", s),
+        }
+    }
+}
+
+// Longest first so "<=" isn't read as "<" then "="
+const PUNCTUATION: [&str; 24] = ["||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "<", ">", "|", "^", "&",
+                             "+", "-", "*", "/", "%", "!", "~", "(", ")", "[", "]"];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String>
+{
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+
+    while let Some(c) = rest.chars().next()
+    {
+        // % is a binary number where an operand goes and the remainder everywhere else
+        let operand_expected = match tokens.last()
+        {
+            None => true,
+            Some(Token::Punct(p)) => *p != ")" && *p != "]",
+            Some(_) => false,
+        };
+
+        let (radix, prefix) = match c
+        {
+            '$' => (16, 1),
+            '0' if rest.starts_with("0x") || rest.starts_with("0X") => (16, 2),
+            '%' if operand_expected && rest[1..].starts_with(['0', '1']) => (2, 1),
+            '0'..='9' => (10, 0),
+            _ => (0, 0),
+        };
+
+        if radix != 0
+        {
+            let digits: String = rest[prefix..].chars().take_while(|c| c.is_ascii_alphanumeric()).collect();
+            let n = i64::from_str_radix(&digits, radix).map_err(|_| format!("bad number {}", &rest[..prefix + digits.len()]))?;
+
+            tokens.push(Token::Number(n));
+            rest = &rest[prefix + digits.len()..];
+        }
+        else if c.is_ascii_alphabetic() || c == '_' || c == '.'
+        {
+            let name: String = rest.chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '.').collect();
+            rest = &rest[name.len()..];
+            tokens.push(Token::Name(name));
+        }
+        else
+        {
+            let punct = PUNCTUATION.iter().find(|s| rest.starts_with(**s)).ok_or(format!("unexpected \"{}\"", c))?;
+            tokens.push(Token::Punct(punct));
+            rest = &rest[punct.len()..];
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/////////////////////////////////////////////////////////////////////
+//				PARSER
+/////////////////////////////////////////////////////////////////////
+
+// Binary operators by precedence level, lowest first
+const LEVELS: [&[(&str, Op)]; 9] =
+[
+    &[("||", Op::Or)],
+    &[("&&", Op::And)],
+    &[("==", Op::Eq), ("!=", Op::Ne), ("<", Op::Lt), ("<=", Op::Le), (">", Op::Gt), (">=", Op::Ge)],
+    &[("|", Op::BitOr)],
+    &[("^", Op::BitXor)],
+    &[("&", Op::BitAnd)],
+    &[("<<", Op::Shl), (">>", Op::Shr)],
+    &[("+", Op::Add), ("-", Op::Sub)],
+    &[("*", Op::Mul), ("/", Op::Div), ("%", Op::Rem)],
+];
+
+struct Parser<'a>
+{
+    tokens: Vec<Token>,
+    pos: usize,
+    symbols: Option<&'a SymbolTable>,
+}
+
+impl<'a> Parser<'a>
+{
+    fn peek(&self) -> Option<&Token>
+    {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token>
+    {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String>
+    {
+        match self.next()
+        {
+            Some(Token::Punct(s)) if s == symbol => Ok(()),
+            Some(token) => Err(format!("expected \"{}\", found {}", symbol, token)),
+            None => Err(format!("expected \"{}\" at the end", symbol)),
+        }
+    }
+
+    // Left associative binary operators from level down
+    fn expr(&mut self, level: usize) -> Result<Node, String>
+    {
+        if level == LEVELS.len()
+        {
+            return self.unary();
+        }
+
+        let mut node = self.expr(level + 1)?;
+        loop
+        {
+            let op = match self.peek()
+            {
+                Some(Token::Punct(s)) => LEVELS[level].iter().find(|(name, _)| name == s).map(|(_, op)| *op),
+                _ => None,
+            };
+
+            match op
+            {
+                Some(op) =>
+                {
+                    self.pos += 1;
+                    let right = self.expr(level + 1)?;
+                    node = Node::Binary(op, Box::new(node), Box::new(right));
+                }
+
+                None => return Ok(node),
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, String>
+    {
+        match self.next()
+        {
+            Some(Token::Punct("!")) => Ok(Node::Not(Box::new(self.unary()?))),
+            Some(Token::Punct("-")) => Ok(Node::Neg(Box::new(self.unary()?))),
+            Some(Token::Punct("~")) => Ok(Node::Complement(Box::new(self.unary()?))),
+
+            Some(Token::Punct("(")) =>
+            {
+                let node = self.expr(0)?;
+                self.expect(")")?;
+                Ok(node)
+            }
+
+            Some(Token::Punct("[")) =>
+            {
+                let node = self.expr(0)?;
+                self.expect("]")?;
+                Ok(Node::Memory(Box::new(node)))
+            }
+
+            Some(Token::Number(n)) => Ok(Node::Number(n)),
+            Some(Token::Name(name)) => self.name(&name),
+
+            Some(token) => Err(format!("unexpected {}", token)),
+            None => Err("unexpected end of the expression".to_string()),
+        }
+    }
+
+    fn name(&self, name: &str) -> Result<Node, String>
+    {
+        let value = match name.to_ascii_uppercase().as_str()
+        {
+            "A" => Value::A,
+            "X" => Value::X,
+            "Y" => Value::Y,
+            "SP" => Value::SP,
+            "PC" => Value::PC,
+            "P" => Value::P,
+            "CYC" => Value::Cycles,
+
+            "N" => Value::Flag(Flags::N as u8),
+            "V" => Value::Flag(Flags::V as u8),
+            "B" => Value::Flag(Flags::B as u8),
+            "D" => Value::Flag(Flags::D as u8),
+            "I" => Value::Flag(Flags::I as u8),
+            "Z" => Value::Flag(Flags::Z as u8),
+            "C" => Value::Flag(Flags::C as u8),
+
+            _ => return match self.symbols.and_then(|s| s.lookup(name))
+            {
+                Some(addr) => Ok(Node::Number(addr as i64)),
+                None => Err(format!("unknown name \"{}\"", name)),
+            },
+        };
+
+        Ok(Node::Value(value))
+    }
+}
@@ -0,0 +1,218 @@
+
+#![allow(dead_code)]
+
+// Memory access heatmap
+//
+// An observer that counts the reads, writes and executes of every address. Executes
+// are the instruction fetches, so the operand bytes of an instruction count as executed
+// along with its opcode. Only the accesses made by instructions are seen (see
+// observer.rs), not the stack pushes of irq() and nmi() or what devices do.
+//
+// The counts are what this cpu does on the bus, which isn't always what a real 6502
+// does: the addressing modes read the operand for stores and read-modify-write
+// instructions too. The read of the target address JSR and JMP make that way is left
+// out though, so every subroutine doesn't look like code that's also read as data.
+//
+// The counts come out as a table, by page and for the busiest addresses, or as a 256x256
+// PNG with one pixel per byte: each row is a page, so the zero page is the top row and
+// the stack the one under it. Reads are red, writes green and executes blue, so code
+// shows up blue, variables yellow (read and written) and code that is also read or
+// written as data (tables in the code, self modifying code) magenta or white. The
+// brightness of each channel is the log of the count, scaled to the busiest address,
+// and anything accessed at all is bright enough to see.
+
+use std::fmt::Write;
+
+use super::R6502;
+use super::observer::{Observer, Instruction, Access, Control};
+use super::addressing_modes::ModeID;
+use super::png;
+use super::symbols::SymbolTable;
+
+// Channel value for an address accessed once, the busiest one gets 255
+const MIN_BRIGHTNESS: f64 = 64.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct AccessCounts
+{
+    pub reads: u64,
+    pub writes: u64,
+    pub executes: u64,
+}
+
+impl AccessCounts
+{
+    pub fn total(&self) -> u64
+    {
+        self.reads + self.writes + self.executes
+    }
+
+    // Executed and read or written as data
+    pub fn is_overlap(&self) -> bool
+    {
+        self.executes > 0 && (self.reads > 0 || self.writes > 0)
+    }
+}
+
+pub struct Heatmap
+{
+    counts: Vec<AccessCounts>,
+
+    // The jump target the running instruction reads without using it
+    jump_target: Option<u16>,
+}
+
+impl Heatmap
+{
+    pub fn new() -> Heatmap
+    {
+        Heatmap { counts: vec![AccessCounts::default(); 0x10000], jump_target: None }
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.counts.iter_mut().for_each(|c| *c = AccessCounts::default());
+    }
+
+    pub fn counts(&self, addr: u16) -> AccessCounts
+    {
+        self.counts[addr as usize]
+    }
+
+    // Totals for the 256 bytes of a page
+    pub fn page(&self, page: u8) -> AccessCounts
+    {
+        let start = (page as usize) << 8;
+        self.counts[start..start + 0x100].iter().fold(AccessCounts::default(), |sum, c|
+            AccessCounts { reads: sum.reads + c.reads, writes: sum.writes + c.writes, executes: sum.executes + c.executes })
+    }
+
+    // The count addresses with the most accesses, most first
+    pub fn hottest(&self, count: usize) -> Vec<(u16, AccessCounts)>
+    {
+        let mut addrs: Vec<(u16, AccessCounts)> = self.counts.iter().enumerate()
+            .filter(|(_, c)| c.total() > 0)
+            .map(|(addr, c)| (addr as u16, *c))
+            .collect();
+
+        addrs.sort_by_key(|(addr, c)| (std::cmp::Reverse(c.total()), *addr));
+        addrs.truncate(count);
+        addrs
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				REPORTS
+    /////////////////////////////////////////////////////////////////////
+
+    // The pages that were used, then the top busiest addresses
+    pub fn table(&self, symbols: &SymbolTable, top: usize) -> String
+    {
+        let mut out = String::new();
+
+        writeln!(out, "{:<6} {:>12} {:>12} {:>12} {:>6} {:>8}", "Page", "Reads", "Writes", "Executes", "Used", "Overlap").unwrap();
+        for page in 0..=0xFF
+        {
+            let start = (page as usize) << 8;
+            let bytes = &self.counts[start..start + 0x100];
+            let used = bytes.iter().filter(|c| c.total() > 0).count();
+            if used == 0
+            {
+                continue;
+            }
+
+            let sum = self.page(page);
+            let overlap = bytes.iter().filter(|c| c.is_overlap()).count();
+            writeln!(out, "${:02X}    {:>12} {:>12} {:>12} {:>6} {:>8}", page, sum.reads, sum.writes, sum.executes, used, overlap).unwrap();
+        }
+
+        writeln!(out).unwrap();
+        writeln!(out, "{:<22} {:>12} {:>12} {:>12}", "Address", "Reads", "Writes", "Executes").unwrap();
+        for (addr, c) in self.hottest(top)
+        {
+            writeln!(out, "${:04X}  {:<15} {:>12} {:>12} {:>12}", addr, symbols.describe(addr), c.reads, c.writes, c.executes).unwrap();
+        }
+
+        out
+    }
+
+    // RGB for the pixel of an address: reads, writes and executes
+    pub fn pixel(&self, addr: u16) -> [u8; 3]
+    {
+        pixel(&self.counts[addr as usize], &self.busiest())
+    }
+
+    // 256x256 PNG, a page per row
+    pub fn png(&self) -> Vec<u8>
+    {
+        let max = self.busiest();
+        let pixels: Vec<u8> = self.counts.iter().flat_map(|c| pixel(c, &max)).collect();
+        png::encode_rgb(256, 256, &pixels)
+    }
+
+    // The highest count of each kind, over all addresses
+    fn busiest(&self) -> AccessCounts
+    {
+        self.counts.iter().fold(AccessCounts::default(), |max, c|
+            AccessCounts { reads: max.reads.max(c.reads), writes: max.writes.max(c.writes), executes: max.executes.max(c.executes) })
+    }
+}
+
+impl Default for Heatmap
+{
+    fn default() -> Heatmap
+    {
+        Heatmap::new()
+    }
+}
+
+impl Observer for Heatmap
+{
+    fn before(&mut self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        self.jump_target = match (instr.decoded.name, instr.decoded.mode)
+        {
+            ("JSR", _) | ("JMP", ModeID::ABS) => instr.addr,
+            _ => None,
+        };
+
+        Control::Continue
+    }
+
+    fn access(&mut self, addr: u16, value: u8, access: Access)
+    {
+        if access == Access::Read && self.jump_target == Some(addr)
+        {
+            self.jump_target = None;
+            return;
+        }
+
+        let counts = &mut self.counts[addr as usize];
+        match access
+        {
+            Access::Read => counts.reads += 1,
+            Access::Write => counts.writes += 1,
+            Access::Fetch => counts.executes += 1,
+        }
+    }
+}
+
+fn pixel(counts: &AccessCounts, max: &AccessCounts) -> [u8; 3]
+{
+    [brightness(counts.reads, max.reads), brightness(counts.writes, max.writes), brightness(counts.executes, max.executes)]
+}
+
+fn brightness(count: u64, max: u64) -> u8
+{
+    if count == 0
+    {
+        return 0;
+    }
+
+    let scale = match max
+    {
+        1 => 1.0,
+        _ => (count as f64).ln() / (max as f64).ln(),
+    };
+
+    (MIN_BRIGHTNESS + (255.0 - MIN_BRIGHTNESS) * scale).round() as u8
+}
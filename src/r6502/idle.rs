@@ -0,0 +1,170 @@
+
+#![allow(dead_code)]
+
+// Idle loop detection
+//
+// Firmware waiting on a device or an interrupt usually spins in a tight loop:
+//
+//      wait:   LDA status
+//              BEQ wait
+//
+// run_until() watches for loops like this. A loop is idle when the cpu comes back
+// to the loop start with exactly the same registers and nothing was written to the
+// bus on the way around. Every pass after that is identical, so with idle skipping
+// on (see R6502::set_idle_skip()) the cpu skips straight ahead by whole passes to
+// the cycle the caller asked to run until. That should be the time of the next
+// device event or interrupt, and devices must not change what the loop reads
+// in between (i.e. they are updated between calls to run_until()).
+//
+// An idle loop that can't be broken out of - interrupts are disabled and it doesn't
+// read any memory mapped I/O - is reported as RunResult::InfiniteLoop. Only an
+// NMI or a reset can get the cpu out of it. R6502::run_scheduled() only reports
+// it when there are no scheduled events left that could do that.
+
+use std::cell::Cell;
+
+use super::{R6502, Bus, Flags};
+
+// Longer loops aren't checked
+const MAX_LOOP_LEN: u32 = 16;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RunResult
+{
+    CycleLimit,             // Ran up to the requested cycle
+    ProgramStopped,
+    InfiniteLoop(u16),      // Stuck in the idle loop that starts at this address
+    Break,                  // An observer asked to stop
+}
+
+// Where a possible idle loop starts, and the state the cpu was in there
+struct LoopStart
+{
+    pc: u16,
+    regs: [u16; 5],
+    cycles: u64,
+    len: u32,
+}
+
+// Tells run_until() what the loop did with the bus
+struct IdleWatch<'a>
+{
+    bus: &'a mut dyn Bus,
+    wrote: bool,
+    read_io: Cell<bool>,
+}
+
+impl<'a> Bus for IdleWatch<'a>
+{
+    fn read(&self, addr: u16) -> u8
+    {
+        if self.bus.page((addr >> 8) as u8).is_none()
+        {
+            self.read_io.set(true);
+        }
+
+        self.bus.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8)
+    {
+        self.wrote = true;
+        self.bus.write(addr, value);
+    }
+
+    fn page(&self, page: u8) -> Option<&[u8; 256]>
+    {
+        self.bus.page(page)
+    }
+
+    // page_mut() is left out so every write is seen
+}
+
+impl R6502
+{
+    // Skip ahead through idle loops in run_until()
+    pub fn set_idle_skip(&mut self, enabled: bool)
+    {
+        self.idle_skip = enabled;
+    }
+
+    // Run until the cycle counter reaches cycle (the last instruction may go
+    // past it), the program stops, or the cpu gets stuck in an infinite loop.
+    pub fn run_until(&mut self, bus: &mut dyn Bus, cycle: u64) -> RunResult
+    {
+        self.run_until_wake(bus, cycle, false)
+    }
+
+    // can_wake is set when something outside of the cpu (a scheduled event)
+    // could still break it out of a loop that would otherwise be stuck
+    pub(crate) fn run_until_wake(&mut self, bus: &mut dyn Bus, cycle: u64, can_wake: bool) -> RunResult
+    {
+        let mut watch = IdleWatch { bus, wrote: false, read_io: Cell::new(false) };
+        let mut start: Option<LoopStart> = None;
+
+        loop
+        {
+            if self.program_stopped
+            {
+                return RunResult::ProgramStopped;
+            }
+
+            if self.cycles >= cycle
+            {
+                return RunResult::CycleLimit;
+            }
+
+            let pc = self.pc;
+            self.clock(&mut watch);
+
+            if self.stop_requested
+            {
+                return RunResult::Break;
+            }
+
+            if let Some(s) = start.as_mut()
+            {
+                s.len += 1;
+                if s.len > MAX_LOOP_LEN
+                {
+                    start = None;
+                }
+            }
+
+            // Only a jump or branch backwards can close a loop
+            if self.pc > pc
+            {
+                continue;
+            }
+
+            let regs = [self.a as u16, self.x as u16, self.y as u16, self.sp, self.status as u16];
+            match start.as_mut()
+            {
+                Some(s) if s.pc == self.pc && s.regs == regs && !watch.wrote =>
+                {
+                    if !can_wake && !watch.read_io.get() && self.check_flag(Flags::I) != 0
+                    {
+                        return RunResult::InfiniteLoop(self.pc);
+                    }
+
+                    // Observers have to see every pass
+                    if self.idle_skip && self.observers.is_empty()
+                    {
+                        let pass = self.cycles - s.cycles;
+                        self.cycles += (cycle.saturating_sub(self.cycles) / pass) * pass;
+                    }
+
+                    s.cycles = self.cycles;
+                    s.len = 0;
+                }
+
+                _ =>
+                {
+                    start = Some(LoopStart { pc: self.pc, regs, cycles: self.cycles, len: 0 });
+                    watch.wrote = false;
+                    watch.read_io.set(false);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,1313 @@
+
+#![allow(dead_code)]
+
+// Dynamic recompiler (enabled with the "jit" cargo feature)
+//
+// Blocks of code that run often enough (see Jit::set_threshold()) are translated
+// into host machine code with cranelift. Everything else, and any block that can't
+// be translated, runs through the block cache and the interpreter.
+//
+// Translated code has to give exactly the same register, flag and bus results as
+// the interpreter, so the code generated for each instruction mirrors its function
+// in Instructions - quirks included. Memory is always accessed through jit_read()
+// and jit_write(), which take the same page fast path as the interpreter. Blocks
+// with zero page or absolute operands that point at memory mapped I/O are left
+// to the interpreter.
+//
+// Writes that land on translated code invalidate it. A block that writes into
+// itself returns straight after that instruction and is translated again the
+// next time it gets hot.
+//
+// Differential mode (Jit::set_differential()) runs every translated block on the
+// interpreter as well and panics at the first block where the two engines disagree.
+
+use std::collections::HashMap;
+use std::mem::offset_of;
+
+use cranelift_codegen::ir::{types, AbiParam, FuncRef, InstBuilder, MemFlagsData, Signature, Value};
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::isa::TargetFrontendConfig;
+use cranelift_codegen::Context;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+
+use super::{R6502, Bus, Flags, read_byte, write_byte};
+use super::addressing_modes::ModeID;
+use super::block_cache::{BlockCache, CachedInstr, decode_block};
+
+const DEFAULT_THRESHOLD: u16 = 32;
+
+// Marks a block start address that failed to translate
+const REJECTED: u16 = u16::MAX;
+
+/////////////////////////////////////////////////////////////////////
+//				RUNTIME INTERFACE
+/////////////////////////////////////////////////////////////////////
+
+// The cpu registers as seen by the generated code
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct JitRegs
+{
+    a: u8,
+    x: u8,
+    y: u8,
+    status: u8,
+    sp: u16,
+    pc: u16,
+    stopped: u8,
+    cycles: u64,
+}
+
+impl JitRegs
+{
+    fn from_cpu(cpu: &R6502) -> JitRegs
+    {
+        JitRegs { a: cpu.a, x: cpu.x, y: cpu.y, status: cpu.status, sp: cpu.sp, pc: cpu.pc, stopped: cpu.program_stopped as u8, cycles: cpu.cycles }
+    }
+
+    fn store(&self, cpu: &mut R6502)
+    {
+        cpu.a = self.a;
+        cpu.x = self.x;
+        cpu.y = self.y;
+        cpu.status = self.status;
+        cpu.sp = self.sp;
+        cpu.pc = self.pc;
+        cpu.program_stopped = self.stopped != 0;
+        cpu.cycles = self.cycles;
+    }
+}
+
+type BlockFn = unsafe extern "C" fn(*mut JitRegs, *mut u8) -> u32;
+
+#[derive(Clone, Copy)]
+struct CompiledBlock
+{
+    start: u16,
+    end: u16,
+    len: u32,   // number of instructions
+    func: BlockFn,
+}
+
+// Translated blocks and the hot counters for the code that isn't translated yet
+struct CodeMap
+{
+    blocks: Vec<Option<CompiledBlock>>,
+    page_blocks: Vec<Vec<u16>>,
+    counts: Vec<u16>,
+}
+
+impl CodeMap
+{
+    fn new() -> CodeMap
+    {
+        CodeMap { blocks: vec![None; 64 * 1024], page_blocks: vec![Vec::new(); 256], counts: vec![0; 64 * 1024] }
+    }
+
+    fn insert(&mut self, block: CompiledBlock)
+    {
+        for page in (block.start >> 8)..=((block.end - 1) >> 8)
+        {
+            self.page_blocks[page as usize].push(block.start);
+        }
+
+        self.blocks[block.start as usize] = Some(block);
+    }
+
+    fn has_code(&self, page: u8) -> bool
+    {
+        !self.page_blocks[page as usize].is_empty()
+    }
+
+    fn invalidate_range(&mut self, start: u16, end: u16)
+    {
+        for page in (start >> 8)..=(end >> 8)
+        {
+            let mut i = 0;
+            while i < self.page_blocks[page as usize].len()
+            {
+                let block_start = self.page_blocks[page as usize][i];
+                let block = match self.blocks[block_start as usize]
+                {
+                    Some(block) if block.start <= end && block.end > start => block,
+                    _ =>
+                    {
+                        i += 1;
+                        continue;
+                    }
+                };
+
+                // The machine code itself stays allocated, cranelift can't free single functions
+                self.blocks[block_start as usize] = None;
+                for page in (block.start >> 8)..=((block.end - 1) >> 8)
+                {
+                    self.page_blocks[page as usize].retain(|s| *s != block_start);
+                }
+            }
+        }
+
+        // Give rejected code another chance now that it changed
+        for addr in start..=end
+        {
+            self.counts[addr as usize] = 0;
+        }
+    }
+
+    fn invalidate(&mut self, addr: u16)
+    {
+        if self.has_code((addr >> 8) as u8)
+        {
+            self.invalidate_range(addr, addr);
+        }
+
+        if self.counts[addr as usize] == REJECTED
+        {
+            self.counts[addr as usize] = 0;
+        }
+    }
+}
+
+// Passed to the generated code and handed back to the memory helpers
+struct JitCtx<'a>
+{
+    bus: &'a mut dyn Bus,
+    code: &'a mut CodeMap,
+    cache: &'a mut BlockCache,
+
+    // range of the running block
+    start: u16,
+    end: u16,
+}
+
+extern "C" fn jit_read(ctx: *mut u8, addr: u32) -> u32
+{
+    let ctx = unsafe { &mut *(ctx as *mut JitCtx) };
+    let addr = addr as u16;
+
+    read_byte(ctx.bus, addr) as u32
+}
+
+// Returns 1 if the write landed on the running block
+extern "C" fn jit_write(ctx: *mut u8, addr: u32, value: u32) -> u32
+{
+    let ctx = unsafe { &mut *(ctx as *mut JitCtx) };
+    let addr = addr as u16;
+
+    write_byte(ctx.bus, addr, value as u8);
+    ctx.code.invalidate(addr);
+    ctx.cache.invalidate(addr);
+
+    (addr >= ctx.start && addr < ctx.end) as u32
+}
+
+// Used while the interpreter runs cold code so its writes
+// still invalidate any translated blocks
+struct CodeWatch<'a>
+{
+    bus: &'a mut dyn Bus,
+    code: &'a mut CodeMap,
+}
+
+impl<'a> Bus for CodeWatch<'a>
+{
+    fn read(&self, addr: u16) -> u8
+    {
+        self.bus.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8)
+    {
+        self.bus.write(addr, value);
+        self.code.invalidate(addr);
+    }
+
+    fn page(&self, page: u8) -> Option<&[u8; 256]>
+    {
+        self.bus.page(page)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////
+//				JIT
+/////////////////////////////////////////////////////////////////////
+
+pub struct Jit
+{
+    module: JITModule,
+    ctx: Context,
+    fctx: FunctionBuilderContext,
+    block_sig: Signature,
+    read_id: FuncId,
+    write_id: FuncId,
+
+    code: CodeMap,
+    cache: BlockCache,
+
+    threshold: u16,
+    differential: bool,
+
+    blocks_compiled: u32,
+    blocks_run: u64,
+}
+
+impl Jit
+{
+    pub fn new() -> Result<Jit, String>
+    {
+        let mut builder = JITBuilder::with_flags(&[("opt_level", "speed")], default_libcall_names())
+            .map_err(|e| e.to_string())?;
+
+        builder.symbol("re6502_jit_read", jit_read as *const u8);
+        builder.symbol("re6502_jit_write", jit_write as *const u8);
+
+        let mut module = JITModule::new(builder);
+        let ptr = module.target_config().pointer_type();
+
+        let mut read_sig = module.make_signature();
+        read_sig.params.push(AbiParam::new(ptr));
+        read_sig.params.push(AbiParam::new(types::I32));
+        read_sig.returns.push(AbiParam::new(types::I32));
+
+        let mut write_sig = module.make_signature();
+        write_sig.params.push(AbiParam::new(ptr));
+        write_sig.params.push(AbiParam::new(types::I32));
+        write_sig.params.push(AbiParam::new(types::I32));
+        write_sig.returns.push(AbiParam::new(types::I32));
+
+        let mut block_sig = module.make_signature();
+        block_sig.params.push(AbiParam::new(ptr));
+        block_sig.params.push(AbiParam::new(ptr));
+        block_sig.returns.push(AbiParam::new(types::I32));
+
+        let read_id = module.declare_function("re6502_jit_read", Linkage::Import, &read_sig).map_err(|e| e.to_string())?;
+        let write_id = module.declare_function("re6502_jit_write", Linkage::Import, &write_sig).map_err(|e| e.to_string())?;
+
+        Ok(Jit
+        {
+            ctx: module.make_context(),
+            module,
+            fctx: FunctionBuilderContext::new(),
+            block_sig,
+            read_id,
+            write_id,
+            code: CodeMap::new(),
+            cache: BlockCache::new(),
+            threshold: DEFAULT_THRESHOLD,
+            differential: false,
+            blocks_compiled: 0,
+            blocks_run: 0,
+        })
+    }
+
+    // Number of times a block has to run before it gets translated
+    pub fn set_threshold(&mut self, threshold: u16)
+    {
+        self.threshold = threshold.clamp(1, REJECTED - 1);
+    }
+
+    // Check every translated block against the interpreter as it runs
+    pub fn set_differential(&mut self, enabled: bool)
+    {
+        self.differential = enabled;
+    }
+
+    // Number of blocks translated so far
+    pub fn blocks_compiled(&self) -> u32
+    {
+        self.blocks_compiled
+    }
+
+    // Number of times translated code has been run
+    pub fn blocks_run(&self) -> u64
+    {
+        self.blocks_run
+    }
+
+    // Memory changed from outside of the cpu has to be reported
+    pub fn invalidate_range(&mut self, start: u16, end: u16)
+    {
+        self.code.invalidate_range(start, end);
+        self.cache.invalidate_range(start, end);
+    }
+
+    fn compile(&mut self, start: u16, bus: &dyn Bus) -> Option<CompiledBlock>
+    {
+        let block = decode_block(start, bus)?;
+
+        // Leave code that touches memory mapped I/O to the interpreter
+        for instr in block.instrs.iter()
+        {
+            if !instr.decoded.documented || fixed_operand_in_mmio(instr, bus)
+            {
+                return None;
+            }
+        }
+
+        self.module.clear_context(&mut self.ctx);
+        self.ctx.func.signature = self.block_sig.clone();
+
+        let id = self.module.declare_anonymous_function(&self.block_sig).ok()?;
+        let read_fn = self.module.declare_func_in_func(self.read_id, &mut self.ctx.func);
+        let write_fn = self.module.declare_func_in_func(self.write_id, &mut self.ctx.func);
+
+        {
+            let config = self.module.target_config();
+            let builder = FunctionBuilder::new(&mut self.ctx.func, &mut self.fctx);
+            Translator::new(builder, read_fn, write_fn).translate(&block.instrs, block.start, config);
+        }
+
+        self.module.define_function(id, &mut self.ctx).ok()?;
+        self.module.clear_context(&mut self.ctx);
+        self.module.finalize_definitions().ok()?;
+
+        let code = self.module.get_finalized_function(id);
+        let func = unsafe { std::mem::transmute::<*const u8, BlockFn>(code) };
+
+        self.blocks_compiled += 1;
+        Some(CompiledBlock { start: block.start, end: block.end, len: block.instrs.len() as u32, func })
+    }
+
+    fn run_compiled(&mut self, cpu: &mut R6502, bus: &mut dyn Bus, block: CompiledBlock) -> u32
+    {
+        let mut regs = JitRegs::from_cpu(cpu);
+        let mut ctx = JitCtx { bus, code: &mut self.code, cache: &mut self.cache, start: block.start, end: block.end };
+
+        let count = unsafe { (block.func)(&mut regs, &mut ctx as *mut JitCtx as *mut u8) };
+        regs.store(cpu);
+
+        self.blocks_run += 1;
+        count
+    }
+
+    // Run the block on both engines and make sure they agree
+    fn run_differential(&mut self, cpu: &mut R6502, bus: &mut dyn Bus, block: CompiledBlock) -> u32
+    {
+        // Interpreter first, on a copy of the cpu and with its writes held back
+        let mut shadow = cpu.clone();
+        let mut overlay = OverlayBus { base: &*bus, memory: HashMap::new(), writes: Vec::new() };
+        let mut expected = 0;
+        while expected < block.len && !shadow.program_stopped
+        {
+            shadow.clock(&mut overlay);
+            expected += 1;
+
+            if overlay.writes.iter().any(|(addr, _)| *addr >= block.start && *addr < block.end)
+            {
+                break;
+            }
+        }
+
+        let expected_writes = overlay.writes;
+
+        // Then the translated code against the real bus
+        let mut recorder = RecordingBus { bus, writes: Vec::new() };
+        let count = self.run_compiled(cpu, &mut recorder, block);
+
+        // The registers, the cycle count, how far each got and what each wrote
+        let shadow_regs = JitRegs::from_cpu(&shadow);
+        let regs = JitRegs::from_cpu(cpu);
+        if shadow_regs != regs || shadow.cycles != cpu.cycles || expected != count || expected_writes != recorder.writes
+        {
+            panic!("JIT MISMATCH in block ${:04X}-${:04X}\n  interpreter: {:?} at cycle {} after {} instructions, writes: {:?}\n  jit:         {:?} at cycle {} after {} instructions, writes: {:?}",
+                block.start, block.end, shadow_regs, shadow.cycles, expected, expected_writes, regs, cpu.cycles, count, recorder.writes);
+        }
+
+        count
+    }
+}
+
+// Zero page and absolute operands are known at translation time
+fn fixed_operand_in_mmio(instr: &CachedInstr, bus: &dyn Bus) -> bool
+{
+    let addr = match instr.decoded.mode
+    {
+        ModeID::ZP0 => instr.bytes[1] as u16,
+        ModeID::ABS | ModeID::IND => (instr.bytes[2] as u16) << 8 | instr.bytes[1] as u16,
+        _ => return false,
+    };
+
+    // JMP and JSR read their target through the addressing mode too
+    bus.page((addr >> 8) as u8).is_none()
+}
+
+// Serves reads from the real bus but keeps the writes to itself
+struct OverlayBus<'a>
+{
+    base: &'a dyn Bus,
+    memory: HashMap<u16, u8>,
+    writes: Vec<(u16, u8)>,
+}
+
+impl<'a> Bus for OverlayBus<'a>
+{
+    fn read(&self, addr: u16) -> u8
+    {
+        match self.memory.get(&addr)
+        {
+            Some(value) => *value,
+            None => self.base.read(addr),
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8)
+    {
+        self.memory.insert(addr, value);
+        self.writes.push((addr, value));
+    }
+}
+
+struct RecordingBus<'a>
+{
+    bus: &'a mut dyn Bus,
+    writes: Vec<(u16, u8)>,
+}
+
+impl<'a> Bus for RecordingBus<'a>
+{
+    fn read(&self, addr: u16) -> u8
+    {
+        self.bus.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8)
+    {
+        self.bus.write(addr, value);
+        self.writes.push((addr, value));
+    }
+
+    fn page(&self, page: u8) -> Option<&[u8; 256]>
+    {
+        self.bus.page(page)
+    }
+}
+
+impl R6502
+{
+    // Like clock_block() but runs translated code for hot blocks.
+    // Returns the number of instructions executed, 0 for a held IRQ.
+    pub fn clock_jit(&mut self, bus: &mut dyn Bus, jit: &mut Jit) -> u32
+    {
+        if self.take_pending_irq(bus)
+        {
+            return 0;
+        }
+
+        let pc = self.pc as usize;
+
+        if let Some(block) = jit.code.blocks[pc].filter(|_| self.observers.is_empty())
+        {
+            return match jit.differential
+            {
+                true => jit.run_differential(self, bus, block),
+                false => jit.run_compiled(self, bus, block),
+            };
+        }
+
+        let count = jit.code.counts[pc];
+        if count != REJECTED
+        {
+            jit.code.counts[pc] = count + 1;
+            if count + 1 >= jit.threshold
+            {
+                match jit.compile(self.pc, bus)
+                {
+                    Some(block) => jit.code.insert(block),
+                    None => jit.code.counts[pc] = REJECTED,
+                }
+            }
+        }
+
+        let mut watch = CodeWatch { bus, code: &mut jit.code };
+        self.clock_block(&mut watch, &mut jit.cache)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////
+//				CODE GENERATION
+/////////////////////////////////////////////////////////////////////
+
+// All of the registers are kept in 32 bit values, masked
+// down to their real size wherever the interpreter would wrap
+struct Translator<'a>
+{
+    b: FunctionBuilder<'a>,
+    read_fn: FuncRef,
+    write_fn: FuncRef,
+
+    regs: Value,
+    ctx: Value,
+
+    a: Variable,
+    x: Variable,
+    y: Variable,
+    status: Variable,
+    sp: Variable,
+    pc: Variable,
+    hit: Variable,      // set when the block writes into itself
+    cycles: Variable,   // 64 bits, unlike the registers
+
+    // Set by address() when indexing crossed a page
+    crossed: Option<Value>,
+}
+
+impl<'a> Translator<'a>
+{
+    fn new(mut b: FunctionBuilder<'a>, read_fn: FuncRef, write_fn: FuncRef) -> Translator<'a>
+    {
+        let entry = b.create_block();
+        b.append_block_params_for_function_params(entry);
+        b.switch_to_block(entry);
+
+        let regs = b.block_params(entry)[0];
+        let ctx = b.block_params(entry)[1];
+
+        let a = b.declare_var(types::I32);
+        let x = b.declare_var(types::I32);
+        let y = b.declare_var(types::I32);
+        let status = b.declare_var(types::I32);
+        let sp = b.declare_var(types::I32);
+        let pc = b.declare_var(types::I32);
+        let hit = b.declare_var(types::I32);
+        let cycles = b.declare_var(types::I64);
+
+        let mut t = Translator { b, read_fn, write_fn, regs, ctx, a, x, y, status, sp, pc, hit, cycles, crossed: None };
+
+        for (var, offset) in [(a, offset_of!(JitRegs, a)), (x, offset_of!(JitRegs, x)),
+                              (y, offset_of!(JitRegs, y)), (status, offset_of!(JitRegs, status))]
+        {
+            let value = t.b.ins().uload8(types::I32, MemFlagsData::trusted(), regs, offset as i32);
+            t.b.def_var(var, value);
+        }
+
+        for (var, offset) in [(sp, offset_of!(JitRegs, sp)), (pc, offset_of!(JitRegs, pc))]
+        {
+            let value = t.b.ins().uload16(types::I32, MemFlagsData::trusted(), regs, offset as i32);
+            t.b.def_var(var, value);
+        }
+
+        let value = t.b.ins().load(types::I64, MemFlagsData::trusted(), regs, offset_of!(JitRegs, cycles) as i32);
+        t.b.def_var(cycles, value);
+
+        let zero = t.imm(0);
+        t.b.def_var(hit, zero);
+
+        t
+    }
+
+    fn translate(mut self, instrs: &[CachedInstr], start: u16, config: TargetFrontendConfig)
+    {
+        let mut addr = start;
+        for (i, instr) in instrs.iter().enumerate()
+        {
+            let next = addr.wrapping_add(instr.len as u16);
+            let next_val = self.imm(next as i64);
+            self.b.def_var(self.pc, next_val);
+
+            self.instruction(instr, addr, next);
+
+            // Bail out if the block just modified itself
+            if i + 1 < instrs.len()
+            {
+                let hit = self.b.use_var(self.hit);
+                let exit = self.b.create_block();
+                let cont = self.b.create_block();
+                self.b.ins().brif(hit, exit, &[], cont, &[]);
+
+                self.b.switch_to_block(exit);
+                self.exit(i as u32 + 1);
+
+                self.b.switch_to_block(cont);
+            }
+
+            addr = next;
+        }
+
+        self.exit(instrs.len() as u32);
+        self.b.seal_all_blocks();
+        self.b.finalize(config);
+    }
+
+    // Store the registers back and return the instruction count
+    fn exit(&mut self, count: u32)
+    {
+        for (var, offset) in [(self.a, offset_of!(JitRegs, a)), (self.x, offset_of!(JitRegs, x)),
+                              (self.y, offset_of!(JitRegs, y)), (self.status, offset_of!(JitRegs, status))]
+        {
+            let value = self.b.use_var(var);
+            self.b.ins().istore8(MemFlagsData::trusted(), value, self.regs, offset as i32);
+        }
+
+        for (var, offset) in [(self.sp, offset_of!(JitRegs, sp)), (self.pc, offset_of!(JitRegs, pc))]
+        {
+            let value = self.b.use_var(var);
+            self.b.ins().istore16(MemFlagsData::trusted(), value, self.regs, offset as i32);
+        }
+
+        let cycles = self.b.use_var(self.cycles);
+        self.b.ins().store(MemFlagsData::trusted(), cycles, self.regs, offset_of!(JitRegs, cycles) as i32);
+
+        let count = self.imm(count as i64);
+        self.b.ins().return_(&[count]);
+    }
+
+    ///////////////////////////////////////////////////////////
+    // HELPERS
+    ///////////////////////////////////////////////////////////
+
+    fn imm(&mut self, value: i64) -> Value
+    {
+        self.b.ins().iconst(types::I32, value)
+    }
+
+    fn read(&mut self, addr: Value) -> Value
+    {
+        let call = self.b.ins().call(self.read_fn, &[self.ctx, addr]);
+        self.b.inst_results(call)[0]
+    }
+
+    fn write(&mut self, addr: Value, value: Value)
+    {
+        let call = self.b.ins().call(self.write_fn, &[self.ctx, addr, value]);
+        let result = self.b.inst_results(call)[0];
+
+        let hit = self.b.use_var(self.hit);
+        let hit = self.b.ins().bor(hit, result);
+        self.b.def_var(self.hit, hit);
+    }
+
+    fn add_cycles(&mut self, count: Value)
+    {
+        let count = self.b.ins().uextend(types::I64, count);
+        let cycles = self.b.use_var(self.cycles);
+        let cycles = self.b.ins().iadd(cycles, count);
+        self.b.def_var(self.cycles, cycles);
+    }
+
+    fn set(&mut self, var: Variable, value: Value)
+    {
+        self.b.def_var(var, value);
+    }
+
+    fn get(&mut self, var: Variable) -> Value
+    {
+        self.b.use_var(var)
+    }
+
+    fn is_zero(&mut self, value: Value) -> Value
+    {
+        self.b.ins().icmp_imm_u(IntCC::Equal, value, 0)
+    }
+
+    fn bit_set(&mut self, value: Value, mask: i64) -> Value
+    {
+        let bit = self.b.ins().band_imm_u(value, mask);
+        self.b.ins().icmp_imm_u(IntCC::NotEqual, bit, 0)
+    }
+
+    // 1 or 0
+    fn check_flag(&mut self, flag: Flags) -> Value
+    {
+        let status = self.get(self.status);
+        let shifted = self.b.ins().ushr_imm_u(status, (flag as u8).trailing_zeros() as i64);
+        self.b.ins().band_imm_u(shifted, 1)
+    }
+
+    // set_flag() when cond is true, clear_flag() otherwise
+    fn assign_flag(&mut self, flag: Flags, cond: Value)
+    {
+        let status = self.get(self.status);
+        let cleared = self.b.ins().band_imm_u(status, !(flag as u8 as i64) & 0xFF);
+        let bit = self.imm(flag as u8 as i64);
+        let zero = self.imm(0);
+        let bit = self.b.ins().select(cond, bit, zero);
+        let status = self.b.ins().bor(cleared, bit);
+        self.set(self.status, status);
+    }
+
+    // set_flag() when cond is true, otherwise leave the flag alone
+    fn set_flag_if(&mut self, flag: Flags, cond: Value)
+    {
+        let status = self.get(self.status);
+        let bit = self.imm(flag as u8 as i64);
+        let zero = self.imm(0);
+        let bit = self.b.ins().select(cond, bit, zero);
+        let status = self.b.ins().bor(status, bit);
+        self.set(self.status, status);
+    }
+
+    fn set_flag(&mut self, flag: Flags)
+    {
+        let status = self.get(self.status);
+        let status = self.b.ins().bor_imm_u(status, flag as u8 as i64);
+        self.set(self.status, status);
+    }
+
+    fn clear_flag(&mut self, flag: Flags)
+    {
+        let status = self.get(self.status);
+        let status = self.b.ins().band_imm_u(status, !(flag as u8 as i64) & 0xFF);
+        self.set(self.status, status);
+    }
+
+    // R6502::set_zn_flags()
+    fn set_zn_flags(&mut self, value: Value)
+    {
+        let zero = self.is_zero(value);
+        self.assign_flag(Flags::Z, zero);
+        let neg = self.bit_set(value, 0x80);
+        self.assign_flag(Flags::N, neg);
+    }
+
+    // The ORA/LDA/LDX style of flag update, which never clears Z or N
+    fn set_zn_only(&mut self, value: Value)
+    {
+        let zero = self.is_zero(value);
+        self.set_flag_if(Flags::Z, zero);
+        let neg = self.bit_set(value, 0x80);
+        self.set_flag_if(Flags::N, neg);
+    }
+
+    fn stack_push(&mut self, value: Value)
+    {
+        let sp = self.get(self.sp);
+        self.write(sp, value);
+        let sp = self.b.ins().iadd_imm_s(sp, -1);
+        let sp = self.b.ins().band_imm_u(sp, 0xFFFF);
+        self.set(self.sp, sp);
+    }
+
+    fn stack_pop(&mut self) -> Value
+    {
+        let sp = self.get(self.sp);
+        let sp = self.b.ins().iadd_imm_s(sp, 1);
+        let sp = self.b.ins().band_imm_u(sp, 0xFFFF);
+        self.set(self.sp, sp);
+        self.read(sp)
+    }
+
+    fn push_pc(&mut self, pc: Value)
+    {
+        let hi = self.b.ins().ushr_imm_u(pc, 8);
+        let lo = self.b.ins().band_imm_u(pc, 0xFF);
+        self.stack_push(hi);
+        self.stack_push(lo);
+    }
+
+    fn make_addr(&mut self, hi: Value, lo: Value) -> Value
+    {
+        let hi = self.b.ins().ishl_imm_u(hi, 8);
+        self.b.ins().bor(hi, lo)
+    }
+
+    ///////////////////////////////////////////////////////////
+    // ADDRESSING MODES
+    ///////////////////////////////////////////////////////////
+
+    // Returns (working_addr, working_data) as the addressing mode would leave them
+    fn address(&mut self, instr: &CachedInstr) -> (Option<Value>, Option<Value>)
+    {
+        let op8 = instr.bytes[1] as i64;
+        let op16 = (instr.bytes[2] as i64) << 8 | op8;
+
+        match instr.decoded.mode
+        {
+            ModeID::IMP | ModeID::ERR => (None, None),
+
+            ModeID::ACM => (None, Some(self.get(self.a))),
+
+            ModeID::IMM | ModeID::REL => (None, Some(self.imm(op8))),
+
+            ModeID::ZP0 => self.read_at(op8),
+            ModeID::ABS => self.read_at(op16),
+
+            // No zero page wrap, the same as the interpreter
+            ModeID::ZPX => self.read_indexed(op8, self.x, 0xFFFF),
+            ModeID::ZPY => self.read_indexed(op8, self.y, 0xFFFF),
+            ModeID::ABX => self.read_indexed(op16, self.x, 0xFFFF),
+            ModeID::ABY => self.read_indexed(op16, self.y, 0xFFFF),
+
+            ModeID::IND =>
+            {
+                let ptr = op16 as u16;
+                let lo_addr = self.imm(ptr as i64);
+                let lo = self.read(lo_addr);
+                let hi_addr = self.imm(ptr.wrapping_add(1) as i64);
+                let mut hi = self.read(hi_addr);
+
+                // Emulate the bug
+                if ptr & 0x00FF == 0xFF
+                {
+                    let bug_addr = self.imm((ptr & 0xFF00) as i64);
+                    hi = self.read(bug_addr);
+                }
+
+                (Some(self.make_addr(hi, lo)), None)
+            }
+
+            ModeID::IZX =>
+            {
+                let x = self.get(self.x);
+                let pointer = self.b.ins().iadd_imm_s(x, op8);
+                let pointer = self.b.ins().band_imm_u(pointer, 0xFF);
+                let lo = self.read(pointer);
+                let pointer_next = self.b.ins().iadd_imm_s(pointer, 1);
+                let hi = self.read(pointer_next);
+                let addr = self.make_addr(hi, lo);
+                let data = self.read(addr);
+                (Some(addr), Some(data))
+            }
+
+            ModeID::IZY =>
+            {
+                let zp_pointer = self.imm(op8);
+                let zp_value = self.read(zp_pointer);
+                let y = self.get(self.y);
+                let sum = self.b.ins().iadd(zp_value, y);
+                self.crossed = Some(self.b.ins().icmp_imm_u(IntCC::UnsignedGreaterThan, sum, 0xFF));
+                let lo = self.b.ins().band_imm_u(sum, 0xFF);
+
+                let zp_next_addr = self.imm(op8 + 1);
+                let zp_next = self.read(zp_next_addr);
+                let carry = self.b.ins().ushr_imm_u(sum, 8);
+                let hi = self.b.ins().iadd(carry, zp_next);
+                let hi = self.b.ins().band_imm_u(hi, 0xFF);
+
+                let addr = self.make_addr(hi, lo);
+                let data = self.read(addr);
+                (Some(addr), Some(data))
+            }
+        }
+    }
+
+    fn read_at(&mut self, addr: i64) -> (Option<Value>, Option<Value>)
+    {
+        let addr = self.imm(addr);
+        let data = self.read(addr);
+        (Some(addr), Some(data))
+    }
+
+    fn read_indexed(&mut self, base: i64, index: Variable, mask: i64) -> (Option<Value>, Option<Value>)
+    {
+        let index = self.get(index);
+        let addr = self.b.ins().iadd_imm_s(index, base);
+        let addr = self.b.ins().band_imm_u(addr, mask);
+
+        let offset = self.b.ins().iadd_imm_u(index, base & 0xFF);
+        self.crossed = Some(self.b.ins().icmp_imm_u(IntCC::UnsignedGreaterThan, offset, 0xFF));
+
+        let data = self.read(addr);
+        (Some(addr), Some(data))
+    }
+
+    ///////////////////////////////////////////////////////////
+    // INSTRUCTIONS
+    ///////////////////////////////////////////////////////////
+
+    fn instruction(&mut self, instr: &CachedInstr, addr: u16, next: u16)
+    {
+        self.crossed = None;
+        let (working_addr, working_data) = self.address(instr);
+
+        let cycles = self.imm(instr.decoded.cycles as i64);
+        self.add_cycles(cycles);
+        if let (true, Some(crossed)) = (instr.decoded.page_penalty, self.crossed)
+        {
+            let extra = self.b.ins().uextend(types::I32, crossed);
+            self.add_cycles(extra);
+        }
+
+        let wa = working_addr.unwrap_or_else(|| self.imm(0));
+        let wd = working_data.unwrap_or_else(|| self.imm(0));
+        let is_acm = instr.decoded.mode == ModeID::ACM;
+
+        match instr.decoded.name
+        {
+            ///////////////////////////////////////////////////////////
+            // GROUP ONE
+            "ORA" | "AND" | "EOR" | "LDA" =>
+            {
+                let a = self.get(self.a);
+                let a = match instr.decoded.name
+                {
+                    "ORA" => self.b.ins().bor(a, wd),
+                    "AND" => self.b.ins().band(a, wd),
+                    "EOR" => self.b.ins().bxor(a, wd),
+                    _ => wd,
+                };
+
+                self.set(self.a, a);
+                self.set_zn_only(a);
+            }
+
+            "ADC" | "SBC" =>
+            {
+                let is_adc = instr.decoded.name == "ADC";
+                let value = match is_adc
+                {
+                    true => wd,
+                    false => self.b.ins().bxor_imm_u(wd, 0xFF),
+                };
+
+                let a = self.get(self.a);
+                let carry = self.check_flag(Flags::C);
+                let temp = self.b.ins().iadd(a, value);
+                let temp = self.b.ins().iadd(temp, carry);
+
+                // ADC only ever sets C and Z, SBC sets or clears them
+                let carry_out = self.b.ins().icmp_imm_u(IntCC::UnsignedGreaterThan, temp, 255);
+                let zero = self.is_zero(temp);
+                if is_adc
+                {
+                    self.set_flag_if(Flags::C, carry_out);
+                    self.set_flag_if(Flags::Z, zero);
+                }
+                else
+                {
+                    self.assign_flag(Flags::C, carry_out);
+                    self.assign_flag(Flags::Z, zero);
+                }
+
+                let same_sign = self.b.ins().bxor(a, value);
+                let same_sign = self.b.ins().bnot(same_sign);
+                let changed = self.b.ins().bxor(a, temp);
+                let overflow = self.b.ins().band(same_sign, changed);
+                let overflow = self.bit_set(overflow, 0x80);
+                self.assign_flag(Flags::V, overflow);
+
+                let neg = self.bit_set(temp, 0x80);
+                self.assign_flag(Flags::N, neg);
+
+                let a = self.b.ins().band_imm_u(temp, 0xFF);
+                self.set(self.a, a);
+            }
+
+            "STA" =>
+            {
+                let a = self.get(self.a);
+                self.write(wa, a);
+            }
+
+            "CMP" =>
+            {
+                let a = self.get(self.a);
+                let ge = self.b.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, a, wd);
+                self.assign_flag(Flags::C, ge);
+                let eq = self.b.ins().icmp(IntCC::Equal, a, wd);
+                self.assign_flag(Flags::Z, eq);
+                let lt = self.b.ins().icmp(IntCC::UnsignedLessThan, a, wd);
+                self.assign_flag(Flags::N, lt);
+            }
+
+            ///////////////////////////////////////////////////////////
+            // GROUP TWO
+            "ASL" | "ROL" | "LSR" | "ROR" =>
+            {
+                let carry = self.check_flag(Flags::C);
+                let (result, carry_out) = match instr.decoded.name
+                {
+                    "ASL" => (self.b.ins().ishl_imm_u(wd, 1), self.bit_set(wd, 0x80)),
+                    "ROL" =>
+                    {
+                        let shifted = self.b.ins().ishl_imm_u(wd, 1);
+                        (self.b.ins().bxor(shifted, carry), self.bit_set(wd, 0x80))
+                    }
+                    "LSR" => (self.b.ins().ushr_imm_u(wd, 1), self.bit_set(wd, 0x01)),
+                    _ =>
+                    {
+                        let shifted = self.b.ins().ushr_imm_u(wd, 1);
+                        let carry_in = self.b.ins().ishl_imm_u(carry, 7);
+                        (self.b.ins().bxor(shifted, carry_in), self.bit_set(wd, 0x01))
+                    }
+                };
+
+                self.assign_flag(Flags::C, carry_out);
+
+                // Z is checked before the result is cut down to 8 bits
+                let zero = self.is_zero(result);
+                self.assign_flag(Flags::Z, zero);
+
+                // ASL never clears N
+                let neg = self.bit_set(result, 0x80);
+                match instr.decoded.name
+                {
+                    "ASL" => self.set_flag_if(Flags::N, neg),
+                    _ => self.assign_flag(Flags::N, neg),
+                }
+
+                let result = self.b.ins().band_imm_u(result, 0xFF);
+                if is_acm
+                {
+                    self.set(self.a, result);
+                }
+                else
+                {
+                    self.write(wa, result);
+                }
+            }
+
+            "STX" =>
+            {
+                let x = self.get(self.x);
+                self.write(wa, x);
+            }
+
+            "LDX" =>
+            {
+                self.set(self.x, wd);
+                self.set_zn_only(wd);
+            }
+
+            "DEC" | "INC" =>
+            {
+                let value = self.read(wa);
+                let value = match instr.decoded.name
+                {
+                    "DEC" => self.b.ins().iadd_imm_s(value, -1),
+                    _ => self.b.ins().iadd_imm_s(value, 1),
+                };
+
+                let value = self.b.ins().band_imm_u(value, 0xFF);
+                self.write(wa, value);
+                self.set_zn_flags(value);
+            }
+
+            ///////////////////////////////////////////////////////////
+            // GROUP THREE
+            "BIT" =>
+            {
+                let a = self.get(self.a);
+                let and = self.b.ins().band(a, wd);
+                let zero = self.is_zero(and);
+                self.assign_flag(Flags::Z, zero);
+                let overflow = self.bit_set(wd, 0x40);
+                self.assign_flag(Flags::V, overflow);
+                let neg = self.bit_set(wd, 0x80);
+                self.assign_flag(Flags::N, neg);
+            }
+
+            "JMP" => self.set(self.pc, wa),
+
+            "STY" =>
+            {
+                let y = self.get(self.y);
+                self.write(wa, y);
+            }
+
+            "LDY" =>
+            {
+                self.set(self.y, wd);
+                self.set_zn_only(wd);
+            }
+
+            "CPX" | "CPY" =>
+            {
+                let reg = match instr.decoded.name
+                {
+                    "CPX" => self.get(self.x),
+                    _ => self.get(self.y),
+                };
+
+                let ge = self.b.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, reg, wd);
+                self.assign_flag(Flags::C, ge);
+                let eq = self.b.ins().icmp(IntCC::Equal, reg, wd);
+                self.assign_flag(Flags::Z, eq);
+                let diff = self.b.ins().isub(reg, wd);
+                let neg = self.bit_set(diff, 0x80);
+                self.assign_flag(Flags::N, neg);
+            }
+
+            ///////////////////////////////////////////////////////////
+            // BRANCHING
+            "BPL" | "BMI" | "BVC" | "BVS" | "BCC" | "BCS" | "BNE" | "BEQ" =>
+            {
+                let (flag, taken_when_set) = match instr.decoded.name
+                {
+                    "BPL" => (Flags::N, false),
+                    "BMI" => (Flags::N, true),
+                    "BVC" => (Flags::V, false),
+                    "BVS" => (Flags::V, true),
+                    "BCC" => (Flags::C, false),
+                    "BCS" => (Flags::C, true),
+                    "BNE" => (Flags::Z, false),
+                    _ => (Flags::Z, true),
+                };
+
+                let target = next.wrapping_add(instr.bytes[1] as i8 as u16);
+                let flag = self.check_flag(flag);
+                let cond = match taken_when_set
+                {
+                    true => self.b.ins().icmp_imm_u(IntCC::NotEqual, flag, 0),
+                    false => self.b.ins().icmp_imm_u(IntCC::Equal, flag, 0),
+                };
+
+                // One more cycle if taken, two if that crosses a page
+                let extra = match target & 0xFF00 == next & 0xFF00
+                {
+                    true => 1,
+                    false => 2,
+                };
+
+                let extra = self.imm(extra);
+                let zero = self.imm(0);
+                let extra = self.b.ins().select(cond, extra, zero);
+                self.add_cycles(extra);
+
+                let target = self.imm(target as i64);
+                let next = self.imm(next as i64);
+                let pc = self.b.ins().select(cond, target, next);
+                self.set(self.pc, pc);
+            }
+
+            ///////////////////////////////////////////////////////////
+            // INTERRUPT AND SUBROUTINE
+            "BRK" =>
+            {
+                let pc = self.imm(next as i64);
+                self.push_pc(pc);
+                let status = self.get(self.status);
+                self.stack_push(status);
+
+                let lo_addr = self.imm(0xFFFE);
+                let lo = self.read(lo_addr);
+                let hi_addr = self.imm(0xFFFF);
+                let hi = self.read(hi_addr);
+                let pc = self.make_addr(hi, lo);
+                self.set(self.pc, pc);
+                self.set_flag(Flags::B);
+            }
+
+            "JSR" =>
+            {
+                let ret = self.imm(next.wrapping_sub(1) as i64);
+                self.push_pc(ret);
+                self.set(self.pc, wa);
+            }
+
+            "RTI" =>
+            {
+                let status = self.stack_pop();
+                self.set(self.status, status);
+                let lo = self.stack_pop();
+                let hi = self.stack_pop();
+                let pc = self.make_addr(hi, lo);
+                self.set(self.pc, pc);
+            }
+
+            "RTS" =>
+            {
+                // Use the stack pointer to detect if this is the end of the program
+                let sp = self.get(self.sp);
+                let at_end = self.b.ins().icmp_imm_u(IntCC::Equal, sp, 0x01FF);
+                let stop = self.b.create_block();
+                let ret = self.b.create_block();
+                let done = self.b.create_block();
+                self.b.ins().brif(at_end, stop, &[], ret, &[]);
+
+                self.b.switch_to_block(stop);
+                let one = self.imm(1);
+                self.b.ins().istore8(MemFlagsData::trusted(), one, self.regs, offset_of!(JitRegs, stopped) as i32);
+                let pc = self.imm(addr.wrapping_add(1) as i64);
+                self.set(self.pc, pc);
+                self.b.ins().jump(done, &[]);
+
+                self.b.switch_to_block(ret);
+                let lo = self.stack_pop();
+                let hi = self.stack_pop();
+                let pc = self.make_addr(hi, lo);
+                let pc = self.b.ins().iadd_imm_s(pc, 1);
+                let pc = self.b.ins().band_imm_u(pc, 0xFFFF);
+                self.set(self.pc, pc);
+                self.b.ins().jump(done, &[]);
+
+                self.b.switch_to_block(done);
+            }
+
+            ///////////////////////////////////////////////////////////
+            // SINGLE BYTE
+            "PHP" =>
+            {
+                let status = self.get(self.status);
+                self.stack_push(status);
+            }
+
+            "PLP" =>
+            {
+                let status = self.stack_pop();
+                self.set(self.status, status);
+            }
+
+            "PHA" =>
+            {
+                let a = self.get(self.a);
+                self.stack_push(a);
+            }
+
+            "PLA" =>
+            {
+                let a = self.stack_pop();
+                self.set(self.a, a);
+                self.set_zn_flags(a);
+            }
+
+            "CLC" => self.clear_flag(Flags::C),
+            "SEC" => self.set_flag(Flags::C),
+            "CLI" => self.clear_flag(Flags::I),
+            "SEI" => self.set_flag(Flags::I),
+            "CLV" => self.clear_flag(Flags::V),
+            "CLD" => self.clear_flag(Flags::D),
+            "SED" => self.set_flag(Flags::D),
+
+            "DEX" | "INX" | "DEY" | "INY" =>
+            {
+                let (reg, delta) = match instr.decoded.name
+                {
+                    "DEX" => (self.x, -1),
+                    "INX" => (self.x, 1),
+                    "DEY" => (self.y, -1),
+                    _ => (self.y, 1),
+                };
+
+                let value = self.get(reg);
+                let value = self.b.ins().iadd_imm_s(value, delta);
+                let value = self.b.ins().band_imm_u(value, 0xFF);
+                self.set(reg, value);
+                self.set_zn_flags(value);
+            }
+
+            "TAX" | "TAY" | "TXA" | "TYA" =>
+            {
+                let (from, to) = match instr.decoded.name
+                {
+                    "TAX" => (self.a, self.x),
+                    "TAY" => (self.a, self.y),
+                    "TXA" => (self.x, self.a),
+                    _ => (self.y, self.a),
+                };
+
+                let value = self.get(from);
+                self.set(to, value);
+                self.set_zn_flags(value);
+            }
+
+            "TXS" =>
+            {
+                let x = self.get(self.x);
+                let sp = self.b.ins().iadd_imm_s(x, 0x100);
+                self.set(self.sp, sp);
+            }
+
+            "TSX" =>
+            {
+                let sp = self.get(self.sp);
+                let x = self.b.ins().iadd_imm_s(sp, -0x100);
+                let x = self.b.ins().band_imm_u(x, 0xFF);
+                self.set(self.x, x);
+            }
+
+            // NOP
+            _ => (),
+        }
+    }
+}
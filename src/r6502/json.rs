@@ -0,0 +1,460 @@
+
+#![allow(dead_code)]
+
+// JSON
+//
+// Just enough JSON for the debug adapter (dap.rs) without pulling in a crate: a value
+// type, a parser and Display to write a value back out on one line. Numbers are kept as
+// f64, like JavaScript does, which holds every integer the protocols here use. Objects
+// keep their keys in the order they were written.
+//
+// Values are looked up with get(), which gives Null for a key that isn't there (or
+// when the value isn't an object), so optional fields read as
+//
+//      args.get("source").get("path").as_str()
+
+use std::fmt;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Json
+{
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+static NULL: Json = Json::Null;
+
+impl Json
+{
+    pub fn parse(text: &str) -> Result<Json, String>
+    {
+        let mut parser = Parser { text: text.as_bytes(), pos: 0 };
+        let value = parser.value()?;
+
+        parser.skip_space();
+        match parser.pos == parser.text.len()
+        {
+            true => Ok(value),
+            false => Err(format!("unexpected text after the value at {}", parser.pos)),
+        }
+    }
+
+    // An object from key and value pairs
+    pub fn object(pairs: Vec<(&str, Json)>) -> Json
+    {
+        Json::Object(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    pub fn get(&self, key: &str) -> &Json
+    {
+        match self
+        {
+            Json::Object(pairs) => pairs.iter().find(|(k, _)| k == key).map_or(&NULL, |(_, v)| v),
+            _ => &NULL,
+        }
+    }
+
+    // Adds the key to an object, or replaces its value
+    pub fn set(&mut self, key: &str, value: Json)
+    {
+        if let Json::Object(pairs) = self
+        {
+            match pairs.iter_mut().find(|(k, _)| k == key)
+            {
+                Some((_, v)) => *v = value,
+                None => pairs.push((key.to_string(), value)),
+            }
+        }
+    }
+
+    pub fn is_null(&self) -> bool
+    {
+        *self == Json::Null
+    }
+
+    pub fn as_str(&self) -> Option<&str>
+    {
+        match self
+        {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool>
+    {
+        match self
+        {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    // Whole numbers only
+    pub fn as_i64(&self) -> Option<i64>
+    {
+        match self
+        {
+            Json::Number(n) if n.fract() == 0.0 => Some(*n as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> &[Json]
+    {
+        match self
+        {
+            Json::Array(items) => items,
+            _ => &[],
+        }
+    }
+}
+
+impl From<bool> for Json
+{
+    fn from(value: bool) -> Json
+    {
+        Json::Bool(value)
+    }
+}
+
+impl From<i64> for Json
+{
+    fn from(value: i64) -> Json
+    {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<u16> for Json
+{
+    fn from(value: u16) -> Json
+    {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<u32> for Json
+{
+    fn from(value: u32) -> Json
+    {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<usize> for Json
+{
+    fn from(value: usize) -> Json
+    {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<&str> for Json
+{
+    fn from(value: &str) -> Json
+    {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json
+{
+    fn from(value: String) -> Json
+    {
+        Json::String(value)
+    }
+}
+
+impl From<Vec<Json>> for Json
+{
+    fn from(value: Vec<Json>) -> Json
+    {
+        Json::Array(value)
+    }
+}
+
+impl fmt::Display for Json
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Json::Number(n) if n.is_finite() => write!(f, "{}", n),
+            Json::Number(_) => write!(f, "null"),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) =>
+            {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate()
+                {
+                    if i > 0
+                    {
+                        write!(f, ",")?;
+                    }
+
+                    write!(f, "{}", item)?;
+                }
+
+                write!(f, "]")
+            }
+
+            Json::Object(pairs) =>
+            {
+                write!(f, "{{")?;
+                for (i, (key, value)) in pairs.iter().enumerate()
+                {
+                    if i > 0
+                    {
+                        write!(f, ",")?;
+                    }
+
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, text: &str) -> fmt::Result
+{
+    write!(f, "\"")?;
+    for c in text.chars()
+    {
+        match c
+        {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+
+    write!(f, "\"")
+}
+
+struct Parser<'a>
+{
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_>
+{
+    fn value(&mut self) -> Result<Json, String>
+    {
+        self.skip_space();
+        match self.text.get(self.pos)
+        {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b't') => self.word("true", Json::Bool(true)),
+            Some(b'f') => self.word("false", Json::Bool(false)),
+            Some(b'n') => self.word("null", Json::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(c) => Err(format!("unexpected {} at {}", *c as char, self.pos)),
+            None => Err("unexpected end of the text".to_string()),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String>
+    {
+        self.pos += 1;
+        let mut pairs = Vec::new();
+
+        self.skip_space();
+        if self.eat(b'}')
+        {
+            return Ok(Json::Object(pairs));
+        }
+
+        loop
+        {
+            self.skip_space();
+            if self.text.get(self.pos) != Some(&b'"')
+            {
+                return Err(format!("expected a key at {}", self.pos));
+            }
+
+            let key = self.string()?;
+            self.skip_space();
+            if !self.eat(b':')
+            {
+                return Err(format!("expected : at {}", self.pos));
+            }
+
+            pairs.push((key, self.value()?));
+
+            self.skip_space();
+            if self.eat(b'}')
+            {
+                return Ok(Json::Object(pairs));
+            }
+
+            if !self.eat(b',')
+            {
+                return Err(format!("expected , or }} at {}", self.pos));
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String>
+    {
+        self.pos += 1;
+        let mut items = Vec::new();
+
+        self.skip_space();
+        if self.eat(b']')
+        {
+            return Ok(Json::Array(items));
+        }
+
+        loop
+        {
+            items.push(self.value()?);
+
+            self.skip_space();
+            if self.eat(b']')
+            {
+                return Ok(Json::Array(items));
+            }
+
+            if !self.eat(b',')
+            {
+                return Err(format!("expected , or ] at {}", self.pos));
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String>
+    {
+        self.pos += 1;
+        let mut bytes = Vec::new();
+
+        loop
+        {
+            let c = *self.text.get(self.pos).ok_or("unterminated string")?;
+            self.pos += 1;
+
+            match c
+            {
+                b'"' => break,
+                b'\\' =>
+                {
+                    let escape = *self.text.get(self.pos).ok_or("unterminated string")?;
+                    self.pos += 1;
+
+                    let c = match escape
+                    {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode()?,
+                        _ => return Err(format!("bad escape \\{} at {}", escape as char, self.pos)),
+                    };
+
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+
+                _ => bytes.push(c),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| "string isn't UTF-8".to_string())
+    }
+
+    // The hex digits of a \u escape, and a second one for a surrogate pair
+    fn unicode(&mut self) -> Result<char, String>
+    {
+        let first = self.hex4()?;
+        let code = match first
+        {
+            0xD800..=0xDBFF if self.text[self.pos..].starts_with(b"\\u") =>
+            {
+                self.pos += 2;
+                let second = self.hex4()?;
+                0x10000 + ((first - 0xD800) << 10) + (second.wrapping_sub(0xDC00) & 0x3FF)
+            }
+
+            _ => first,
+        };
+
+        Ok(char::from_u32(code).unwrap_or('\u{FFFD}'))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String>
+    {
+        let digits = self.text.get(self.pos..self.pos + 4).ok_or("short \\u escape")?;
+        self.pos += 4;
+
+        let digits = std::str::from_utf8(digits).map_err(|_| "bad \\u escape")?;
+        u32::from_str_radix(digits, 16).map_err(|_| format!("bad \\u escape {}", digits))
+    }
+
+    fn number(&mut self) -> Result<Json, String>
+    {
+        let start = self.pos;
+        while self.text.get(self.pos).is_some_and(|c| matches!(c, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.pos += 1;
+        }
+
+        let text = std::str::from_utf8(&self.text[start..self.pos]).unwrap();
+        text.parse::<f64>().map(Json::Number).map_err(|_| format!("bad number {} at {}", text, start))
+    }
+
+    fn word(&mut self, word: &str, value: Json) -> Result<Json, String>
+    {
+        match self.text[self.pos..].starts_with(word.as_bytes())
+        {
+            true =>
+            {
+                self.pos += word.len();
+                Ok(value)
+            }
+
+            false => Err(format!("unexpected text at {}", self.pos)),
+        }
+    }
+
+    fn eat(&mut self, c: u8) -> bool
+    {
+        match self.text.get(self.pos) == Some(&c)
+        {
+            true =>
+            {
+                self.pos += 1;
+                true
+            }
+
+            false => false,
+        }
+    }
+
+    fn skip_space(&mut self)
+    {
+        while self.text.get(self.pos).is_some_and(|c| c.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+}
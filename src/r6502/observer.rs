@@ -0,0 +1,396 @@
+
+#![allow(dead_code)]
+
+// Instruction and bus observers
+//
+// An Observer registered with R6502::add_observer() is called before and after every
+// instruction the cpu runs, and with every read and write the instruction makes on
+// the bus. Tracers, profilers, coverage tools and breakpoints are all built on this.
+// Observers that need to look at memory first, like breakpoint conditions, get the bus
+// through inspect(), which is called just before before(). Its reads aren't reported.
+//
+// While any observer is registered R6502::clock() takes a slower path: the instruction
+// is read and decoded up front so the observers can be told what is about to run, and
+// the bus is wrapped so its traffic can be reported. clock_block() and clock_jit() fall
+// back to clock() for as long as there are observers. Recompiled code (see recompiler.rs)
+// and the accesses made by reset(), irq() and nmi() are not observed, but observers are
+// told when irq() or nmi() enters an interrupt handler. They are also asked what to do
+// about an opcode the cpu can't run before the cpu gives up on it.
+//
+// Returning Control::Stop from a callback asks the cpu to stop. From before() the
+// instruction is not run, so the same instruction is seen again on the next clock().
+// R6502::stop_requested() says whether the last clock() (or irq() or nmi()) was stopped,
+// and run_until() returns RunResult::Break.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::{R6502, Bus};
+use super::decoder::{self, Decoded};
+use super::addressing_modes::ModeID;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Access
+{
+    Fetch,      // Opcode and operand bytes
+    Read,
+    Write,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interrupt
+{
+    Irq,
+    Nmi,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Control
+{
+    Continue,
+    Stop,
+}
+
+// The instruction being run
+#[derive(Clone, Copy)]
+pub struct Instruction
+{
+    pub pc: u16,
+    pub bytes: [u8; 3],     // Opcode then operands, unused bytes are 0
+    pub decoded: Decoded,
+
+    // Address the operand refers to, the target for jumps and branches.
+    // None for implied, accumulator and immediate instructions.
+    pub addr: Option<u16>,
+}
+
+pub trait Observer
+{
+    fn inspect(&mut self, cpu: &R6502, bus: &dyn Bus, instr: &Instruction)
+    {
+    }
+
+    fn before(&mut self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        Control::Continue
+    }
+
+    fn after(&mut self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        Control::Continue
+    }
+
+    fn access(&mut self, addr: u16, value: u8, access: Access)
+    {
+    }
+
+    // The cpu has just entered the handler for an interrupt
+    fn interrupt(&mut self, cpu: &R6502, kind: Interrupt) -> Control
+    {
+        Control::Continue
+    }
+
+    // The opcode at the program counter isn't one the cpu can run. If every
+    // observer continues the cpu panics, as it does without observers.
+    fn illegal(&mut self, cpu: &R6502, opcode: u8) -> Control
+    {
+        Control::Continue
+    }
+}
+
+// Lets an observer be registered with the cpu while the caller keeps a handle to it
+impl<T: Observer> Observer for Rc<RefCell<T>>
+{
+    fn inspect(&mut self, cpu: &R6502, bus: &dyn Bus, instr: &Instruction)
+    {
+        self.borrow_mut().inspect(cpu, bus, instr)
+    }
+
+    fn before(&mut self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        self.borrow_mut().before(cpu, instr)
+    }
+
+    fn after(&mut self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        self.borrow_mut().after(cpu, instr)
+    }
+
+    fn access(&mut self, addr: u16, value: u8, access: Access)
+    {
+        self.borrow_mut().access(addr, value, access)
+    }
+
+    fn interrupt(&mut self, cpu: &R6502, kind: Interrupt) -> Control
+    {
+        self.borrow_mut().interrupt(cpu, kind)
+    }
+
+    fn illegal(&mut self, cpu: &R6502, opcode: u8) -> Control
+    {
+        self.borrow_mut().illegal(cpu, opcode)
+    }
+}
+
+pub type ObserverId = u64;
+
+// The observers registered with a cpu. They aren't part of the cpu's state,
+// so clones of the cpu share them and comparing cpus leaves them out.
+#[derive(Clone, Default)]
+pub(crate) struct Observers
+{
+    list: Vec<(ObserverId, Rc<RefCell<dyn Observer>>)>,
+    next_id: ObserverId,
+}
+
+impl Observers
+{
+    pub(crate) fn is_empty(&self) -> bool
+    {
+        self.list.is_empty()
+    }
+
+    fn inspect(&self, cpu: &R6502, bus: &dyn Bus, instr: &Instruction)
+    {
+        for (_, observer) in self.list.iter()
+        {
+            observer.borrow_mut().inspect(cpu, bus, instr);
+        }
+    }
+
+    fn before(&self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        let mut control = Control::Continue;
+        for (_, observer) in self.list.iter()
+        {
+            if observer.borrow_mut().before(cpu, instr) == Control::Stop
+            {
+                control = Control::Stop;
+            }
+        }
+
+        control
+    }
+
+    fn after(&self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        let mut control = Control::Continue;
+        for (_, observer) in self.list.iter()
+        {
+            if observer.borrow_mut().after(cpu, instr) == Control::Stop
+            {
+                control = Control::Stop;
+            }
+        }
+
+        control
+    }
+
+    fn access(&self, addr: u16, value: u8, access: Access)
+    {
+        for (_, observer) in self.list.iter()
+        {
+            observer.borrow_mut().access(addr, value, access);
+        }
+    }
+
+    fn interrupt(&self, cpu: &R6502, kind: Interrupt) -> Control
+    {
+        let mut control = Control::Continue;
+        for (_, observer) in self.list.iter()
+        {
+            if observer.borrow_mut().interrupt(cpu, kind) == Control::Stop
+            {
+                control = Control::Stop;
+            }
+        }
+
+        control
+    }
+
+    fn illegal(&self, cpu: &R6502, opcode: u8) -> Control
+    {
+        let mut control = Control::Continue;
+        for (_, observer) in self.list.iter()
+        {
+            if observer.borrow_mut().illegal(cpu, opcode) == Control::Stop
+            {
+                control = Control::Stop;
+            }
+        }
+
+        control
+    }
+}
+
+// Reports the bus traffic of an instruction to the observers
+struct ObservedBus<'a>
+{
+    bus: &'a mut dyn Bus,
+    observers: &'a Observers,
+}
+
+impl<'a> Bus for ObservedBus<'a>
+{
+    fn read(&self, addr: u16) -> u8
+    {
+        let value = self.bus.read(addr);
+        self.observers.access(addr, value, Access::Read);
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8)
+    {
+        self.bus.write(addr, value);
+        self.observers.access(addr, value, Access::Write);
+    }
+
+    // page() and page_mut() are left out so every access is seen
+}
+
+impl R6502
+{
+    // Returns an id that can be passed to remove_observer()
+    pub fn add_observer<O: Observer + 'static>(&mut self, observer: O) -> ObserverId
+    {
+        let id = self.observers.next_id;
+        self.observers.next_id += 1;
+
+        self.observers.list.push((id, Rc::new(RefCell::new(observer))));
+        id
+    }
+
+    pub fn remove_observer(&mut self, id: ObserverId) -> bool
+    {
+        let len = self.observers.list.len();
+        self.observers.list.retain(|(i, _)| *i != id);
+        self.observers.list.len() != len
+    }
+
+    // Did an observer stop the last instruction
+    pub fn stop_requested(&self) -> bool
+    {
+        self.stop_requested
+    }
+
+    // Called by irq() and nmi() once the handler has been entered
+    pub(crate) fn notify_interrupt(&mut self, kind: Interrupt)
+    {
+        if self.observers.is_empty()
+        {
+            return;
+        }
+
+        let observers = std::mem::take(&mut self.observers);
+        self.stop_requested = observers.interrupt(self, kind) == Control::Stop;
+        self.observers = observers;
+    }
+
+    // clock() for when there are observers
+    pub(crate) fn clock_observed(&mut self, bus: &mut dyn Bus)
+    {
+        // Take the list out so the observers can be handed the cpu
+        let observers = std::mem::take(&mut self.observers);
+        self.stop_requested = self.step_observed(bus, &observers);
+        self.observers = observers;
+    }
+
+    // Returns true if an observer asked to stop
+    fn step_observed(&mut self, bus: &mut dyn Bus, observers: &Observers) -> bool
+    {
+        let pc = self.pc;
+        let opcode = bus.read(pc);
+        let decoded = match decoder::decode(opcode)
+        {
+            Some(decoded) => decoded,
+            None if observers.illegal(self, opcode) == Control::Stop => return true,
+            None => panic!("UNKNOWN INSTRUCTION: {:#02X}", opcode),
+        };
+
+        let mut bytes = [opcode, 0, 0];
+        for i in 1..decoded.size()
+        {
+            bytes[i as usize] = bus.read(pc.wrapping_add(i));
+        }
+
+        let instr = Instruction { pc, bytes, decoded, addr: effective_address(self, bus, &decoded, &bytes) };
+
+        observers.inspect(self, bus, &instr);
+        if observers.before(self, &instr) == Control::Stop
+        {
+            return true;
+        }
+
+        for i in 0..decoded.size()
+        {
+            observers.access(pc.wrapping_add(i), bytes[i as usize], Access::Fetch);
+        }
+
+        // The instruction bytes have already been read, run it from the prefetch buffer
+        self.fetch_addr = pc;
+        self.fetch_buf = bytes;
+        self.fetch_len = decoded.size() as u8;
+        self.pc = pc.wrapping_add(1);
+
+        let mut watch = ObservedBus { bus, observers };
+        decoded.run(self, &mut watch);
+
+        self.fetch_len = 0;
+
+        observers.after(self, &instr) == Control::Stop
+    }
+}
+
+// Works out the address an instruction will use without running it.
+// Matches the addressing modes in addressing_modes.rs, wrapping bugs included.
+fn effective_address(cpu: &R6502, bus: &dyn Bus, decoded: &Decoded, bytes: &[u8; 3]) -> Option<u16>
+{
+    let value = ((bytes[2] as u16) << 8) | bytes[1] as u16;
+    let zp = bytes[1] as u16;
+
+    match decoded.mode
+    {
+        ModeID::IMP | ModeID::ACM | ModeID::IMM | ModeID::ERR => None,
+
+        ModeID::REL =>
+        {
+            let next = cpu.pc.wrapping_add(2);
+            Some(next.wrapping_add(bytes[1] as i8 as u16))
+        }
+
+        ModeID::ZP0 => Some(zp),
+        ModeID::ZPX => Some(zp + cpu.x as u16),
+        ModeID::ZPY => Some(zp + cpu.y as u16),
+
+        ModeID::ABS => Some(value),
+        ModeID::ABX => Some(value.wrapping_add(cpu.x as u16)),
+        ModeID::ABY => Some(value.wrapping_add(cpu.y as u16)),
+
+        ModeID::IND =>
+        {
+            let lo = bus.read(value) as u16;
+            let hi = match value & 0x00FF
+            {
+                0xFF => bus.read(value & 0xFF00) as u16,
+                _ => bus.read(value.wrapping_add(1)) as u16,
+            };
+
+            Some((hi << 8) | lo)
+        }
+
+        ModeID::IZX =>
+        {
+            let pointer = (zp + cpu.x as u16) & 0x00FF;
+            let lo = bus.read(pointer) as u16;
+            let hi = bus.read(pointer + 1) as u16;
+            Some((hi << 8) | lo)
+        }
+
+        ModeID::IZY =>
+        {
+            let sum = bus.read(zp) as u16 + cpu.y as u16;
+            let hi = ((sum >> 8) + bus.read(zp + 1) as u16) as u8;
+            Some(((hi as u16) << 8) | (sum & 0x00FF))
+        }
+    }
+}
@@ -0,0 +1,109 @@
+
+#![allow(dead_code)]
+
+// PNG encoding
+//
+// Just enough PNG to write out an RGB image without pulling in a crate: an IHDR, one
+// IDAT and an IEND. The image data goes into the zlib stream as stored (uncompressed)
+// deflate blocks, so the files are bigger than they need to be but every viewer and
+// browser reads them. A 256x256 image is about 193KB.
+
+// Largest stored deflate block
+const MAX_BLOCK: usize = 0xFFFF;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+// pixels holds width * height RGB triples, row by row from the top
+pub fn encode_rgb(width: u32, height: u32, pixels: &[u8]) -> Vec<u8>
+{
+    assert_eq!(pixels.len(), width as usize * height as usize * 3, "pixels doesn't match the image size");
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);     // 8 bit RGB, deflate, no filtering, not interlaced
+
+    // Each row starts with its filter type, 0 for none
+    let row = width as usize * 3;
+    let mut raw = Vec::with_capacity((row + 1) * height as usize);
+    for line in pixels.chunks(row.max(1)).take(height as usize)
+    {
+        raw.push(0);
+        raw.extend_from_slice(line);
+    }
+
+    let mut png = SIGNATURE.to_vec();
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8])
+{
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// A zlib stream of stored deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8>
+{
+    let mut out = vec![0x78, 0x01];     // Deflate with a 32K window, no dictionary, fastest
+
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none()
+    {
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+
+    while let Some(block) = blocks.next()
+    {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+
+        out.push(last as u8);           // BFINAL, and BTYPE 00 for stored
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+pub fn crc32(data: &[u8]) -> u32
+{
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data
+    {
+        crc ^= *byte as u32;
+        for _ in 0..8
+        {
+            crc = match crc & 1
+            {
+                1 => (crc >> 1) ^ 0xEDB8_8320,
+                _ => crc >> 1,
+            };
+        }
+    }
+
+    !crc
+}
+
+pub fn adler32(data: &[u8]) -> u32
+{
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data
+    {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
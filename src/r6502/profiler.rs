@@ -0,0 +1,309 @@
+
+#![allow(dead_code)]
+
+// Cycle profiler
+//
+// An observer that counts the instructions run and the cycles they took, per address,
+// per opcode and per subroutine. Subroutines come from a shadow call stack (see
+// call_stack.rs) the profiler keeps for itself: an instruction belongs to the routine
+// on top of the stack when it starts, so a JSR counts towards the caller and the RTS
+// towards the subroutine. Whatever runs outside of any call belongs to the root, the
+// first address the profiler saw.
+//
+// Each routine gets its self cycles (spent in the routine itself) and its total cycles
+// (including the routines it called). Interrupt handlers count as routines of their own.
+// Only instruction cycles are counted, not the 7 cycles an interrupt takes to enter.
+//
+// The results come out as a text report of the hot spots, an opcode histogram, or as
+// folded stacks for flame graph tools (flamegraph.pl, inferno, speedscope):
+//
+//      main;draw;plot 1234
+//
+// one line per call path with the self cycles spent in it.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::R6502;
+use super::call_stack::CallStack;
+use super::decoder;
+use super::observer::{Observer, Instruction, Control, Interrupt};
+use super::symbols::SymbolTable;
+
+// Width of the longest bar in the opcode histogram
+const BAR_WIDTH: usize = 40;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Counts
+{
+    pub instructions: u64,
+    pub cycles: u64,
+}
+
+impl Counts
+{
+    fn add(&mut self, cycles: u64)
+    {
+        self.instructions += 1;
+        self.cycles += cycles;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct RoutineStats
+{
+    pub entry: u16,
+    pub calls: u64,
+    pub instructions: u64,      // Run in the routine itself
+    pub self_cycles: u64,
+    pub total_cycles: u64,      // Including the routines it called
+}
+
+pub struct Profiler
+{
+    stack: CallStack,
+    root: Option<u16>,
+
+    total: Counts,
+    addrs: Vec<Counts>,
+    last_opcode: Vec<u8>,       // The opcode last run at each address
+    opcodes: Vec<Counts>,
+    routines: HashMap<u16, RoutineStats>,
+    folded: HashMap<Vec<u16>, u64>,
+
+    // The instruction being run: the call path it's in and the cycle it started on
+    path: Vec<u16>,
+    start: u64,
+}
+
+impl Profiler
+{
+    pub fn new() -> Profiler
+    {
+        Profiler { stack: CallStack::new(), root: None, total: Counts::default(),
+                    addrs: vec![Counts::default(); 0x10000], last_opcode: vec![0; 0x10000], opcodes: vec![Counts::default(); 256],
+                    routines: HashMap::new(), folded: HashMap::new(), path: Vec::new(), start: 0 }
+    }
+
+    pub fn clear(&mut self)
+    {
+        *self = Profiler::new();
+    }
+
+    pub fn total(&self) -> Counts
+    {
+        self.total
+    }
+
+    pub fn address(&self, addr: u16) -> Counts
+    {
+        self.addrs[addr as usize]
+    }
+
+    pub fn opcode(&self, opcode: u8) -> Counts
+    {
+        self.opcodes[opcode as usize]
+    }
+
+    pub fn routine(&self, entry: u16) -> Option<&RoutineStats>
+    {
+        self.routines.get(&entry)
+    }
+
+    // Most self cycles first
+    pub fn routines(&self) -> Vec<RoutineStats>
+    {
+        let mut routines: Vec<RoutineStats> = self.routines.values().copied().collect();
+        routines.sort_by_key(|r| (std::cmp::Reverse(r.self_cycles), r.entry));
+        routines
+    }
+
+    // The count addresses that took the most cycles, most first
+    pub fn hot_spots(&self, count: usize) -> Vec<(u16, Counts)>
+    {
+        let mut spots: Vec<(u16, Counts)> = self.addrs.iter().enumerate()
+            .filter(|(_, c)| c.instructions > 0)
+            .map(|(addr, c)| (addr as u16, *c))
+            .collect();
+
+        spots.sort_by_key(|(addr, c)| (std::cmp::Reverse(c.cycles), *addr));
+        spots.truncate(count);
+        spots
+    }
+
+    // Instruction counts by mnemonic, most first
+    pub fn mnemonics(&self) -> Vec<(&'static str, Counts)>
+    {
+        let mut by_name: HashMap<&'static str, Counts> = HashMap::new();
+        for (opcode, counts) in self.opcodes.iter().enumerate().filter(|(_, c)| c.instructions > 0)
+        {
+            let name = decoder::decode(opcode as u8).map_or("???", |d| d.name);
+            let entry = by_name.entry(name).or_default();
+            entry.instructions += counts.instructions;
+            entry.cycles += counts.cycles;
+        }
+
+        let mut names: Vec<(&'static str, Counts)> = by_name.into_iter().collect();
+        names.sort_by_key(|(name, c)| (std::cmp::Reverse(c.instructions), *name));
+        names
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				REPORTS
+    /////////////////////////////////////////////////////////////////////
+
+    // The routines and the top addresses by cycles
+    pub fn report(&self, symbols: &SymbolTable, top: usize) -> String
+    {
+        let mut out = String::new();
+        let total = self.total.cycles.max(1);
+        let percent = |cycles: u64| cycles as f64 * 100.0 / total as f64;
+
+        writeln!(out, "Total: {} cycles, {} instructions", self.total.cycles, self.total.instructions).unwrap();
+        writeln!(out).unwrap();
+
+        writeln!(out, "{:>10} {:>6}  {:>10} {:>6}  {:>8}  {:>10}  Routine", "Self", "%", "Total", "%", "Calls", "Instrs").unwrap();
+        for r in self.routines().iter().filter(|r| r.instructions > 0).take(top)
+        {
+            writeln!(out, "{:>10} {:>5.1}%  {:>10} {:>5.1}%  {:>8}  {:>10}  {}", r.self_cycles, percent(r.self_cycles),
+                        r.total_cycles, percent(r.total_cycles), r.calls, r.instructions, symbols.describe(r.entry)).unwrap();
+        }
+
+        writeln!(out).unwrap();
+        writeln!(out, "{:>10} {:>6}  {:>10}  Address", "Cycles", "%", "Count").unwrap();
+        for (addr, c) in self.hot_spots(top)
+        {
+            let name = decoder::decode(self.last_opcode[addr as usize]).map_or("???", |d| d.name);
+            writeln!(out, "{:>10} {:>5.1}%  {:>10}  ${:04X}  {:<12} {}", c.cycles, percent(c.cycles), c.instructions,
+                        addr, symbols.describe(addr), name).unwrap();
+        }
+
+        out
+    }
+
+    // How often each instruction ran, as a bar chart
+    pub fn opcode_histogram(&self) -> String
+    {
+        let mut out = String::new();
+        let names = self.mnemonics();
+        let most = names.first().map_or(1, |(_, c)| c.instructions.max(1));
+        let total = self.total.instructions.max(1);
+
+        for (name, c) in names
+        {
+            let bar = (c.instructions * BAR_WIDTH as u64).div_ceil(most) as usize;
+            writeln!(out, "{}  {:>10} {:>5.1}%  {}", name, c.instructions, c.instructions as f64 * 100.0 / total as f64, "#".repeat(bar)).unwrap();
+        }
+
+        out
+    }
+
+    // One line per call path, outermost routine first, with the self cycles spent in it
+    pub fn folded_stacks(&self, symbols: &SymbolTable) -> String
+    {
+        let root = self.root.map_or("root".to_string(), |addr| symbols.describe(addr));
+
+        let mut lines: Vec<String> = self.folded.iter()
+            .filter(|(_, cycles)| **cycles > 0)
+            .map(|(path, cycles)|
+            {
+                let names: Vec<String> = path.iter().map(|addr| symbols.describe(*addr)).collect();
+                match names.is_empty()
+                {
+                    true => format!("{} {}", root, cycles),
+                    false => format!("{};{} {}", root, names.join(";"), cycles),
+                }
+            })
+            .collect();
+
+        lines.sort();
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    // The stats for the routine at entry, added the first time it's seen
+    fn routine_entry(&mut self, entry: u16) -> &mut RoutineStats
+    {
+        self.routines.entry(entry).or_insert(RoutineStats { entry, ..RoutineStats::default() })
+    }
+}
+
+impl Default for Profiler
+{
+    fn default() -> Profiler
+    {
+        Profiler::new()
+    }
+}
+
+impl Observer for Profiler
+{
+    fn before(&mut self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        let root = *self.root.get_or_insert(instr.pc);
+        if self.routines.is_empty()
+        {
+            self.routine_entry(root).calls = 1;
+        }
+
+        self.path.clear();
+        self.path.extend(self.stack.frames().iter().map(|f| f.entry));
+        self.start = cpu.cycles;
+
+        self.stack.before(cpu, instr)
+    }
+
+    fn after(&mut self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        let cycles = cpu.cycles - self.start;
+        self.total.add(cycles);
+        self.addrs[instr.pc as usize].add(cycles);
+        self.last_opcode[instr.pc as usize] = instr.bytes[0];
+        self.opcodes[instr.bytes[0] as usize].add(cycles);
+
+        let root = self.root.unwrap_or(instr.pc);
+        let current = self.path.last().copied().unwrap_or(root);
+        let routine = self.routine_entry(current);
+        routine.instructions += 1;
+        routine.self_cycles += cycles;
+
+        // Every routine on the path, once each when it's recursive
+        let path = std::mem::take(&mut self.path);
+        let entry = |i: usize| if i == 0 { root } else { path[i - 1] };
+        for i in 0..=path.len()
+        {
+            if !(0..i).any(|j| entry(j) == entry(i))
+            {
+                self.routine_entry(entry(i)).total_cycles += cycles;
+            }
+        }
+        self.path = path;
+
+        match self.folded.get_mut(self.path.as_slice())
+        {
+            Some(total) => *total += cycles,
+            None => { self.folded.insert(self.path.clone(), cycles); }
+        }
+
+        let depth = self.stack.depth();
+        let control = self.stack.after(cpu, instr);
+        if self.stack.depth() > depth
+        {
+            let entry = self.stack.frames()[self.stack.depth() - 1].entry;
+            self.routine_entry(entry).calls += 1;
+        }
+
+        control
+    }
+
+    fn interrupt(&mut self, cpu: &R6502, kind: Interrupt) -> Control
+    {
+        let control = self.stack.interrupt(cpu, kind);
+        if let Some(frame) = self.stack.frames().last()
+        {
+            let entry = frame.entry;
+            self.routine_entry(entry).calls += 1;
+        }
+
+        control
+    }
+}
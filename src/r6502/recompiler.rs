@@ -0,0 +1,906 @@
+
+#![allow(dead_code)]
+
+// Static recompiler
+//
+// Recovers the code reachable from a set of entry points in a ROM image and
+// turns every routine (the entry points and anything they JSR to) into a Rust
+// function that runs against the Bus trait:
+//
+//      pub fn sub_8000(cpu: &mut R6502, bus: &mut dyn Bus)
+//
+// Each instruction becomes inline Rust working on a copy of the registers (Regs):
+// the same bus reads and writes, flag updates and cycle counts as its function in
+// Instructions, quirks included, with native control flow between the basic blocks.
+// Subroutine calls to recovered routines call their functions directly.
+//
+// Control flow is still checked at run time. The rest of a routine is handed to
+// the interpreter for BRK, indirect jumps that can't be resolved from the ROM, RTS
+// to a computed address, and undocumented opcodes or code outside of the ROM image.
+// So is a held IRQ once CLI, PLP or RTI enables interrupts. The ROM is assumed not
+// to modify its own code, and observers don't see the recompiled instructions.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::{R6502, Bus, Flags, read_byte, write_byte};
+use super::addressing_modes::ModeID;
+use super::decoder::{self, Decoded};
+use super::disasm;
+
+const JSR: u8 = 0x20;
+const RTI: u8 = 0x40;
+const RTS: u8 = 0x60;
+const JMP_ABS: u8 = 0x4C;
+const JMP_IND: u8 = 0x6C;
+const BRK: u8 = 0x00;
+
+/////////////////////////////////////////////////////////////////////
+//				RUNTIME
+/////////////////////////////////////////////////////////////////////
+
+// Called by the generated code
+
+// The registers as the generated code sees them. A routine loads them when it
+// starts and stores them back before it calls another routine or the interpreter,
+// and when it returns.
+pub struct Regs
+{
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub status: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub cycles: u64,
+    pub stopped: bool,
+
+    // Only the interpreter takes a held IRQ, so this is never stored back
+    pub irq_pending: bool,
+}
+
+impl Regs
+{
+    pub fn load(cpu: &R6502) -> Regs
+    {
+        Regs
+        {
+            a: cpu.a,
+            x: cpu.x,
+            y: cpu.y,
+            status: cpu.status,
+            sp: cpu.sp,
+            pc: cpu.pc,
+            cycles: cpu.cycles,
+            stopped: cpu.program_stopped,
+            irq_pending: cpu.irq_pending,
+        }
+    }
+
+    pub fn store(&self, cpu: &mut R6502)
+    {
+        cpu.a = self.a;
+        cpu.x = self.x;
+        cpu.y = self.y;
+        cpu.status = self.status;
+        cpu.sp = self.sp;
+        cpu.pc = self.pc;
+        cpu.cycles = self.cycles;
+        cpu.program_stopped = self.stopped;
+    }
+
+    #[inline]
+    pub fn check(&self, flag: Flags) -> bool
+    {
+        self.status & flag as u8 != 0
+    }
+
+    // Set the flag when cond is true, otherwise leave it alone
+    #[inline]
+    pub fn set_if(&mut self, flag: Flags, cond: bool)
+    {
+        if cond
+        {
+            self.status |= flag as u8;
+        }
+    }
+
+    // Set the flag when cond is true, clear it otherwise
+    #[inline]
+    pub fn assign(&mut self, flag: Flags, cond: bool)
+    {
+        self.status &= !(flag as u8);
+        self.set_if(flag, cond);
+    }
+}
+
+// Memory accesses take the same page fast path as the interpreter
+#[inline]
+pub fn read(bus: &dyn Bus, addr: u16) -> u8
+{
+    read_byte(bus, addr)
+}
+
+#[inline]
+pub fn write(bus: &mut dyn Bus, addr: u16, value: u8)
+{
+    write_byte(bus, addr, value)
+}
+
+// Run the rest of a routine on the interpreter. The routine is done once
+// its RTS pops the stack above frame, the stack pointer when it started.
+pub fn interpret(cpu: &mut R6502, bus: &mut dyn Bus, frame: u16)
+{
+    while !cpu.program_stopped && cpu.sp <= frame
+    {
+        cpu.clock(bus);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////
+//				CODE RECOVERY
+/////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy)]
+pub struct Instr
+{
+    pub addr: u16,
+    pub bytes: [u8; 3],
+    pub decoded: Decoded,
+}
+
+impl Instr
+{
+    fn next(&self) -> u16
+    {
+        self.addr.wrapping_add(self.decoded.size())
+    }
+
+    fn operand16(&self) -> u16
+    {
+        (self.bytes[2] as u16) << 8 | self.bytes[1] as u16
+    }
+
+    fn branch_target(&self) -> u16
+    {
+        self.next().wrapping_add(self.bytes[1] as i8 as u16)
+    }
+}
+
+pub struct CodeBlock
+{
+    pub start: u16,
+    pub instrs: Vec<Instr>,
+}
+
+pub struct Routine
+{
+    pub entry: u16,
+    pub blocks: Vec<CodeBlock>,
+
+    // Instructions that leave the recovered code - unresolved indirect jumps, BRK,
+    // and jumps to code outside of the ROM, undocumented or that doesn't decode
+    pub exits: Vec<u16>,
+}
+
+pub struct Program
+{
+    pub routines: Vec<Routine>,
+}
+
+pub struct Rom<'a>
+{
+    base: u16,
+    data: &'a [u8],
+}
+
+impl<'a> Rom<'a>
+{
+    // The image is mapped starting at base
+    pub fn new(data: &'a [u8], base: u16) -> Rom<'a>
+    {
+        Rom { base, data }
+    }
+
+    pub fn read(&self, addr: u16) -> Option<u8>
+    {
+        let offset = addr.wrapping_sub(self.base) as usize;
+        self.data.get(offset).copied()
+    }
+
+    pub fn read_word(&self, addr: u16) -> Option<u16>
+    {
+        Some((self.read(addr.wrapping_add(1))? as u16) << 8 | self.read(addr)? as u16)
+    }
+
+    fn instr(&self, addr: u16) -> Option<Instr>
+    {
+        let decoded = decoder::decode(self.read(addr)?)?;
+
+        let mut bytes = [0; 3];
+        for i in 0..decoded.size()
+        {
+            bytes[i as usize] = self.read(addr.wrapping_add(i))?;
+        }
+
+        Some(Instr { addr, bytes, decoded })
+    }
+
+    // JMP ($xxxx), with the page wrap bug, if the vector is in the ROM
+    fn resolve_indirect(&self, ptr: u16) -> Option<u16>
+    {
+        let hi_addr = match ptr & 0x00FF
+        {
+            0xFF => ptr & 0xFF00,
+            _ => ptr.wrapping_add(1),
+        };
+
+        Some((self.read(hi_addr)? as u16) << 8 | self.read(ptr)? as u16)
+    }
+}
+
+// Recover the routines reachable from the entry points
+pub fn analyze(rom: &Rom, entries: &[u16]) -> Program
+{
+    let mut pending: Vec<u16> = entries.to_vec();
+    let mut done = BTreeMap::new();
+
+    while let Some(entry) = pending.pop()
+    {
+        if done.contains_key(&entry)
+        {
+            continue;
+        }
+
+        let (routine, calls) = recover_routine(rom, entry);
+        done.insert(entry, routine);
+        pending.extend(calls);
+    }
+
+    Program { routines: done.into_values().collect() }
+}
+
+// Follow everything but subroutine calls from entry. Returns
+// the routine and the subroutines it calls.
+fn recover_routine(rom: &Rom, entry: u16) -> (Routine, BTreeSet<u16>)
+{
+    let mut instrs = BTreeMap::new();
+    let mut labels = BTreeSet::new();
+    let mut calls = BTreeSet::new();
+    let mut exits = BTreeSet::new();
+
+    let mut pending = vec![entry];
+    labels.insert(entry);
+
+    while let Some(addr) = pending.pop()
+    {
+        if instrs.contains_key(&addr)
+        {
+            continue;
+        }
+
+        // Undocumented opcodes are left to the interpreter
+        let instr = match rom.instr(addr)
+        {
+            Some(instr) if instr.decoded.documented => instr,
+            _ =>
+            {
+                exits.insert(addr);
+                continue;
+            }
+        };
+
+        instrs.insert(addr, instr);
+
+        let opcode = instr.bytes[0];
+        let mut targets = Vec::new();
+        match opcode
+        {
+            RTS | RTI => (),
+
+            BRK =>
+            {
+                exits.insert(addr);
+            }
+
+            JSR =>
+            {
+                calls.insert(instr.operand16());
+                targets.push(instr.next());
+            }
+
+            JMP_ABS => targets.push(instr.operand16()),
+
+            JMP_IND => match rom.resolve_indirect(instr.operand16())
+            {
+                Some(target) => targets.push(target),
+                None =>
+                {
+                    exits.insert(addr);
+                }
+            },
+
+            _ if decoder::is_flow_control(opcode) =>
+            {
+                targets.push(instr.next());
+                targets.push(instr.branch_target());
+            }
+
+            _ =>
+            {
+                pending.push(instr.next());
+                continue;
+            }
+        }
+
+        labels.extend(targets.iter());
+        pending.extend(targets);
+    }
+
+    // Split the code into basic blocks that start at each label
+    let mut blocks = Vec::new();
+    for label in labels.iter()
+    {
+        let mut block = CodeBlock { start: *label, instrs: Vec::new() };
+        let mut addr = *label;
+        while let Some(instr) = instrs.get(&addr)
+        {
+            block.instrs.push(*instr);
+            addr = instr.next();
+
+            if decoder::is_flow_control(instr.bytes[0]) || labels.contains(&addr)
+            {
+                break;
+            }
+        }
+
+        if !block.instrs.is_empty()
+        {
+            blocks.push(block);
+        }
+    }
+
+    (Routine { entry, blocks, exits: exits.into_iter().collect() }, calls)
+}
+
+/////////////////////////////////////////////////////////////////////
+//				CODE GENERATION
+/////////////////////////////////////////////////////////////////////
+
+// Indent of the code in a block's match arm
+const INDENT: &str = "                ";
+
+impl Program
+{
+    pub fn routine(&self, entry: u16) -> Option<&Routine>
+    {
+        self.routines.iter().find(|r| r.entry == entry)
+    }
+
+    // Rust source for the whole program
+    pub fn to_rust(&self) -> String
+    {
+        let mut out = String::new();
+
+        let entries: Vec<String> = self.routines.iter().map(|r| format!("${:04X}", r.entry)).collect();
+        writeln!(out, "// Generated by the RE6502 static recompiler").unwrap();
+        writeln!(out, "// Routines: {}", entries.join(", ")).unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "use re6502::r6502::{{R6502, Bus, Flags}};").unwrap();
+        writeln!(out, "use re6502::r6502::recompiler::{{self, Regs}};").unwrap();
+
+        // Lets the host call routines by address
+        writeln!(out).unwrap();
+        writeln!(out, "// Returns false if there is no routine at addr").unwrap();
+        writeln!(out, "pub fn call(addr: u16, cpu: &mut R6502, bus: &mut dyn Bus) -> bool").unwrap();
+        writeln!(out, "{{").unwrap();
+        writeln!(out, "    match addr").unwrap();
+        writeln!(out, "    {{").unwrap();
+        for routine in self.routines.iter()
+        {
+            writeln!(out, "        0x{:04X} => sub_{:04X}(cpu, bus),", routine.entry, routine.entry).unwrap();
+        }
+        writeln!(out, "        _ => return false,").unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "    true").unwrap();
+        writeln!(out, "}}").unwrap();
+
+        for routine in self.routines.iter()
+        {
+            writeln!(out).unwrap();
+            routine.write_rust(&mut out, self);
+        }
+
+        out
+    }
+}
+
+impl Routine
+{
+    fn write_rust(&self, out: &mut String, program: &Program)
+    {
+        writeln!(out, "pub fn sub_{:04X}(cpu: &mut R6502, bus: &mut dyn Bus)", self.entry).unwrap();
+        writeln!(out, "{{").unwrap();
+        writeln!(out, "    let mut r = Regs::load(cpu);").unwrap();
+        writeln!(out, "    let frame = r.sp;").unwrap();
+        writeln!(out, "    r.pc = 0x{:04X};", self.entry).unwrap();
+        writeln!(out, "    loop").unwrap();
+        writeln!(out, "    {{").unwrap();
+        writeln!(out, "        match r.pc").unwrap();
+        writeln!(out, "        {{").unwrap();
+
+        for block in self.blocks.iter()
+        {
+            let mut code = Vec::new();
+            for instr in block.instrs.iter()
+            {
+                if !code.is_empty()
+                {
+                    code.push(String::new());
+                }
+
+                code.push(format!("// ${:04X}  {}", instr.addr, format_instr(instr)));
+                self.write_instr(&mut code, instr, program);
+            }
+
+            // The block runs into the next one
+            let last = block.instrs[block.instrs.len() - 1];
+            if !decoder::is_flow_control(last.bytes[0])
+            {
+                code.push(format!("r.pc = 0x{:04X};", last.next()));
+            }
+
+            writeln!(out, "            0x{:04X} =>", block.start).unwrap();
+            writeln!(out, "            {{").unwrap();
+            for line in code.iter()
+            {
+                match line.is_empty()
+                {
+                    true => writeln!(out).unwrap(),
+                    false => writeln!(out, "{}{}", INDENT, line).unwrap(),
+                }
+            }
+            writeln!(out, "            }}").unwrap();
+        }
+
+        writeln!(out, "            _ =>").unwrap();
+        writeln!(out, "            {{").unwrap();
+        writeln!(out, "                r.store(cpu);").unwrap();
+        writeln!(out, "                return recompiler::interpret(cpu, bus, frame);").unwrap();
+        writeln!(out, "            }}").unwrap();
+        writeln!(out, "        }}").unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "        if r.stopped || r.sp > frame").unwrap();
+        writeln!(out, "        {{").unwrap();
+        writeln!(out, "            r.store(cpu);").unwrap();
+        writeln!(out, "            return;").unwrap();
+        writeln!(out, "        }}").unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}").unwrap();
+    }
+
+    // The code for one instruction mirrors its function in Instructions
+    fn write_instr(&self, code: &mut Vec<String>, instr: &Instr, program: &Program)
+    {
+        // BRK and the indirect jumps that couldn't be resolved
+        if self.exits.contains(&instr.addr)
+        {
+            interpret_at(code, instr.addr);
+            return;
+        }
+
+        let decoded = &instr.decoded;
+        let next = instr.next();
+        let (addr, crossed) = write_addressing(code, instr);
+
+        code.push(format!("r.cycles += {};", decoded.cycles));
+        if let (true, Some(crossed)) = (decoded.page_penalty, crossed)
+        {
+            write_if(code, &crossed, &["r.cycles += 1;".to_string()], &[]);
+        }
+
+        match decoded.name
+        {
+            ///////////////////////////////////////////////////////////
+            // GROUP ONE
+            "ORA" => code.push("r.a |= data;".to_string()),
+            "AND" => code.push("r.a &= data;".to_string()),
+            "EOR" => code.push("r.a ^= data;".to_string()),
+            "LDA" => code.push("r.a = data;".to_string()),
+
+            "ADC" | "SBC" =>
+            {
+                match decoded.name
+                {
+                    "ADC" => code.push("let value = data;".to_string()),
+                    _ => code.push("let value = data ^ 0xFF;".to_string()),
+                }
+
+                code.push("let temp = r.a as u16 + value as u16 + r.check(Flags::C) as u16;".to_string());
+
+                // ADC only ever sets C and Z, SBC sets or clears them
+                let update = match decoded.name
+                {
+                    "ADC" => "set_if",
+                    _ => "assign",
+                };
+
+                code.push(format!("r.{}(Flags::C, temp > 0xFF);", update));
+                code.push(format!("r.{}(Flags::Z, temp == 0);", update));
+                code.push("r.assign(Flags::V, !(r.a ^ value) & (r.a ^ temp as u8) & 0x80 != 0);".to_string());
+                code.push("r.assign(Flags::N, temp & 0x80 != 0);".to_string());
+                code.push("r.a = temp as u8;".to_string());
+            }
+
+            "STA" => code.push(format!("recompiler::write(bus, {}, r.a);", addr)),
+
+            "CMP" => write_compare(code, "r.a"),
+
+            ///////////////////////////////////////////////////////////
+            // GROUP TWO
+            "ASL" | "ROL" | "LSR" | "ROR" =>
+            {
+                let (result, carry_out) = match decoded.name
+                {
+                    "ASL" => ("(data as u16) << 1", "data & 0x80"),
+                    "ROL" => ("((data as u16) << 1) ^ r.check(Flags::C) as u16", "data & 0x80"),
+                    "LSR" => ("(data as u16) >> 1", "data & 0x01"),
+                    _ => ("((data as u16) >> 1) ^ ((r.check(Flags::C) as u16) << 7)", "data & 0x01"),
+                };
+
+                code.push(format!("let result = {};", result));
+                code.push(format!("r.assign(Flags::C, {} != 0);", carry_out));
+
+                // Z is checked before the result is cut down to 8 bits, and ASL never clears N
+                code.push("r.assign(Flags::Z, result == 0);".to_string());
+                match decoded.name
+                {
+                    "ASL" => code.push("r.set_if(Flags::N, result & 0x80 != 0);".to_string()),
+                    _ => code.push("r.assign(Flags::N, result & 0x80 != 0);".to_string()),
+                }
+
+                match decoded.mode
+                {
+                    ModeID::ACM => code.push("r.a = result as u8;".to_string()),
+                    _ => code.push(format!("recompiler::write(bus, {}, result as u8);", addr)),
+                }
+            }
+
+            "STX" => code.push(format!("recompiler::write(bus, {}, r.x);", addr)),
+            "LDX" => code.push("r.x = data;".to_string()),
+
+            "DEC" | "INC" =>
+            {
+                let op = match decoded.name
+                {
+                    "DEC" => "wrapping_sub",
+                    _ => "wrapping_add",
+                };
+
+                code.push(format!("let value = recompiler::read(bus, {}).{}(1);", addr, op));
+                code.push(format!("recompiler::write(bus, {}, value);", addr));
+                write_zn(code, "value");
+            }
+
+            ///////////////////////////////////////////////////////////
+            // GROUP THREE
+            "BIT" =>
+            {
+                code.push("r.assign(Flags::Z, r.a & data == 0);".to_string());
+                code.push("r.assign(Flags::V, data & 0x40 != 0);".to_string());
+                code.push("r.assign(Flags::N, data & 0x80 != 0);".to_string());
+            }
+
+            "JMP" => code.push(format!("r.pc = {};", addr)),
+
+            "STY" => code.push(format!("recompiler::write(bus, {}, r.y);", addr)),
+            "LDY" => code.push("r.y = data;".to_string()),
+
+            "CPX" => write_compare(code, "r.x"),
+            "CPY" => write_compare(code, "r.y"),
+
+            ///////////////////////////////////////////////////////////
+            // BRANCHING
+            "BPL" | "BMI" | "BVC" | "BVS" | "BCC" | "BCS" | "BNE" | "BEQ" =>
+            {
+                let cond = match decoded.name
+                {
+                    "BPL" => "!r.check(Flags::N)",
+                    "BMI" => "r.check(Flags::N)",
+                    "BVC" => "!r.check(Flags::V)",
+                    "BVS" => "r.check(Flags::V)",
+                    "BCC" => "!r.check(Flags::C)",
+                    "BCS" => "r.check(Flags::C)",
+                    "BNE" => "!r.check(Flags::Z)",
+                    _ => "r.check(Flags::Z)",
+                };
+
+                // One more cycle if taken, two if that crosses a page
+                let target = instr.branch_target();
+                let extra = match target & 0xFF00 == next & 0xFF00
+                {
+                    true => 1,
+                    false => 2,
+                };
+
+                let taken = [format!("r.cycles += {};", extra), format!("r.pc = 0x{:04X};", target)];
+                write_if(code, cond, &taken, &[format!("r.pc = 0x{:04X};", next)]);
+            }
+
+            ///////////////////////////////////////////////////////////
+            // INTERRUPT AND SUBROUTINE
+            "JSR" =>
+            {
+                let ret = next.wrapping_sub(1);
+                write_push(code, &format!("0x{:02X}", ret >> 8));
+                write_push(code, &format!("0x{:02X}", ret & 0x00FF));
+                code.push(format!("r.pc = {};", addr));
+
+                if program.routine(instr.operand16()).is_some()
+                {
+                    code.push("r.store(cpu);".to_string());
+                    code.push(format!("sub_{:04X}(cpu, bus);", instr.operand16()));
+                    code.push("r = Regs::load(cpu);".to_string());
+                }
+            }
+
+            "RTI" =>
+            {
+                write_pop(code, "r.status");
+                write_pop(code, "let lo");
+                write_pop(code, "let hi");
+                code.push("r.pc = (hi as u16) << 8 | lo as u16;".to_string());
+            }
+
+            "RTS" =>
+            {
+                // Use the stack pointer to detect if this is the end of the program
+                let stop = ["r.stopped = true;".to_string(), format!("r.pc = 0x{:04X};", instr.addr.wrapping_add(1))];
+
+                let mut ret = Vec::new();
+                write_pop(&mut ret, "let lo");
+                write_pop(&mut ret, "let hi");
+                ret.push("r.pc = ((hi as u16) << 8 | lo as u16).wrapping_add(1);".to_string());
+
+                write_if(code, "r.sp == 0x01FF", &stop, &ret);
+            }
+
+            ///////////////////////////////////////////////////////////
+            // SINGLE BYTE
+            "PHP" => write_push(code, "r.status"),
+            "PLP" => write_pop(code, "r.status"),
+            "PHA" => write_push(code, "r.a"),
+            "PLA" => write_pop(code, "r.a"),
+
+            "CLC" => code.push("r.assign(Flags::C, false);".to_string()),
+            "SEC" => code.push("r.assign(Flags::C, true);".to_string()),
+            "CLI" => code.push("r.assign(Flags::I, false);".to_string()),
+            "SEI" => code.push("r.assign(Flags::I, true);".to_string()),
+            "CLV" => code.push("r.assign(Flags::V, false);".to_string()),
+            "CLD" => code.push("r.assign(Flags::D, false);".to_string()),
+            "SED" => code.push("r.assign(Flags::D, true);".to_string()),
+
+            "DEX" => code.push("r.x = r.x.wrapping_sub(1);".to_string()),
+            "INX" => code.push("r.x = r.x.wrapping_add(1);".to_string()),
+            "DEY" => code.push("r.y = r.y.wrapping_sub(1);".to_string()),
+            "INY" => code.push("r.y = r.y.wrapping_add(1);".to_string()),
+
+            "TAX" => code.push("r.x = r.a;".to_string()),
+            "TAY" => code.push("r.y = r.a;".to_string()),
+            "TXA" => code.push("r.a = r.x;".to_string()),
+            "TYA" => code.push("r.a = r.y;".to_string()),
+
+            "TXS" => code.push("r.sp = r.x as u16 + 0x100;".to_string()),
+            "TSX" => code.push("r.x = r.sp.wrapping_sub(0x100) as u8;".to_string()),
+
+            // NOP
+            _ => (),
+        }
+
+        // The flag updates that follow the register loads
+        match decoded.name
+        {
+            "ORA" | "AND" | "EOR" | "LDA" => write_zn_only(code, "r.a"),
+            "LDX" => write_zn_only(code, "r.x"),
+            "LDY" => write_zn_only(code, "r.y"),
+            "PLA" | "TXA" | "TYA" => write_zn(code, "r.a"),
+            "DEX" | "INX" | "TAX" => write_zn(code, "r.x"),
+            "DEY" | "INY" | "TAY" => write_zn(code, "r.y"),
+            _ => (),
+        }
+
+        // A held IRQ is taken by the interpreter as soon as interrupts are enabled
+        if decoder::can_enable_interrupts(instr.bytes[0])
+        {
+            let mut take = Vec::new();
+            match instr.bytes[0]
+            {
+                RTI => interpret_from(&mut take),
+                _ => interpret_at(&mut take, next),
+            }
+
+            write_if(code, "r.irq_pending && !r.check(Flags::I)", &take, &[]);
+        }
+    }
+}
+
+// Emits the bus reads the instruction's addressing mode makes, leaving the operand
+// in data when the instruction uses it. Returns the operand address and when there
+// is one, the condition for the page crossing penalty.
+fn write_addressing(code: &mut Vec<String>, instr: &Instr) -> (String, Option<String>)
+{
+    let op8 = instr.bytes[1] as u16;
+    let op16 = instr.operand16();
+
+    let (addr, crossed) = match instr.decoded.mode
+    {
+        ModeID::IMP | ModeID::ERR | ModeID::REL => return (String::new(), None),
+
+        ModeID::ACM =>
+        {
+            code.push("let data = r.a;".to_string());
+            return (String::new(), None);
+        }
+
+        ModeID::IMM =>
+        {
+            code.push(format!("let data: u8 = 0x{:02X};", op8));
+            return (String::new(), None);
+        }
+
+        ModeID::ZP0 => (format!("0x{:04X}", op8), None),
+        ModeID::ABS => (format!("0x{:04X}", op16), None),
+
+        // No zero page wrap, the same as the interpreter
+        ModeID::ZPX | ModeID::ZPY =>
+        {
+            let index = index_reg(instr.decoded.mode);
+            code.push(format!("let addr = 0x{:04X} + {} as u16;", op8, index));
+            ("addr".to_string(), None)
+        }
+
+        ModeID::ABX | ModeID::ABY =>
+        {
+            let index = index_reg(instr.decoded.mode);
+            code.push(format!("let addr = 0x{:04X}u16.wrapping_add({} as u16);", op16, index));
+            ("addr".to_string(), Some(format!("addr & 0xFF00 != 0x{:04X}", op16 & 0xFF00)))
+        }
+
+        // JMP only needs the address
+        ModeID::IND =>
+        {
+            // Emulate the bug
+            let hi_addr = match op16 & 0x00FF
+            {
+                0xFF => op16 & 0xFF00,
+                _ => op16.wrapping_add(1),
+            };
+
+            code.push(format!("let lo = recompiler::read(bus, 0x{:04X});", op16));
+            if hi_addr != op16.wrapping_add(1)
+            {
+                code.push(format!("recompiler::read(bus, 0x{:04X});", op16.wrapping_add(1)));
+            }
+            code.push(format!("let hi = recompiler::read(bus, 0x{:04X});", hi_addr));
+            code.push("let addr = (hi as u16) << 8 | lo as u16;".to_string());
+            return ("addr".to_string(), None);
+        }
+
+        ModeID::IZX =>
+        {
+            code.push(format!("let pointer = (0x{:04X} + r.x as u16) & 0x00FF;", op8));
+            code.push("let lo = recompiler::read(bus, pointer);".to_string());
+            code.push("let hi = recompiler::read(bus, pointer + 1);".to_string());
+            code.push("let addr = (hi as u16) << 8 | lo as u16;".to_string());
+            ("addr".to_string(), None)
+        }
+
+        ModeID::IZY =>
+        {
+            code.push(format!("let sum = recompiler::read(bus, 0x{:04X}) as u16 + r.y as u16;", op8));
+            code.push(format!("let hi = recompiler::read(bus, 0x{:04X}) as u16;", op8 + 1));
+            code.push("let addr = ((hi + (sum >> 8)) & 0x00FF) << 8 | (sum & 0x00FF);".to_string());
+            ("addr".to_string(), Some("sum > 0xFF".to_string()))
+        }
+    };
+
+    // The operand is read even when the instruction only needs its address
+    match uses_data(instr.decoded.name)
+    {
+        true => code.push(format!("let data = recompiler::read(bus, {});", addr)),
+        false => code.push(format!("recompiler::read(bus, {});", addr)),
+    }
+
+    (addr, crossed)
+}
+
+fn index_reg(mode: ModeID) -> &'static str
+{
+    match mode
+    {
+        ModeID::ZPX | ModeID::ABX => "r.x",
+        _ => "r.y",
+    }
+}
+
+fn uses_data(name: &str) -> bool
+{
+    matches!(name, "ORA" | "AND" | "EOR" | "LDA" | "ADC" | "SBC" | "CMP" | "ASL" | "ROL" | "LSR" | "ROR"
+                 | "LDX" | "LDY" | "BIT" | "CPX" | "CPY")
+}
+
+fn write_if(code: &mut Vec<String>, cond: &str, then: &[String], otherwise: &[String])
+{
+    code.push(format!("if {}", cond));
+    write_braced(code, then);
+
+    if !otherwise.is_empty()
+    {
+        code.push("else".to_string());
+        write_braced(code, otherwise);
+    }
+}
+
+fn write_braced(code: &mut Vec<String>, lines: &[String])
+{
+    code.push("{".to_string());
+    code.extend(lines.iter().map(|line| format!("    {}", line)));
+    code.push("}".to_string());
+}
+
+// R6502::set_zn_flags()
+fn write_zn(code: &mut Vec<String>, value: &str)
+{
+    code.push(format!("r.assign(Flags::Z, {} == 0);", value));
+    code.push(format!("r.assign(Flags::N, {} & 0x80 != 0);", value));
+}
+
+// The ORA/LDA/LDX style of flag update, which never clears Z or N
+fn write_zn_only(code: &mut Vec<String>, value: &str)
+{
+    code.push(format!("r.set_if(Flags::Z, {} == 0);", value));
+    code.push(format!("r.set_if(Flags::N, {} & 0x80 != 0);", value));
+}
+
+fn write_compare(code: &mut Vec<String>, reg: &str)
+{
+    code.push(format!("r.assign(Flags::C, {} >= data);", reg));
+    code.push(format!("r.assign(Flags::Z, {} == data);", reg));
+    match reg
+    {
+        "r.a" => code.push("r.assign(Flags::N, r.a < data);".to_string()),
+        _ => code.push(format!("r.assign(Flags::N, {}.wrapping_sub(data) & 0x80 != 0);", reg)),
+    }
+}
+
+fn write_push(code: &mut Vec<String>, value: &str)
+{
+    code.push(format!("recompiler::write(bus, r.sp, {});", value));
+    code.push("r.sp = r.sp.wrapping_sub(1);".to_string());
+}
+
+// Pops into a register or a new binding
+fn write_pop(code: &mut Vec<String>, target: &str)
+{
+    code.push("r.sp = r.sp.wrapping_add(1);".to_string());
+    code.push(format!("{} = recompiler::read(bus, r.sp);", target));
+}
+
+// Hand the rest of the routine to the interpreter, starting at pc
+fn interpret_at(code: &mut Vec<String>, pc: u16)
+{
+    code.push(format!("r.pc = 0x{:04X};", pc));
+    interpret_from(code);
+}
+
+fn interpret_from(code: &mut Vec<String>)
+{
+    code.push("r.store(cpu);".to_string());
+    code.push("return recompiler::interpret(cpu, bus, frame);".to_string());
+}
+
+fn format_instr(instr: &Instr) -> String
+{
+    disasm::format_instr(&instr.decoded, &instr.bytes, instr.addr)
+}
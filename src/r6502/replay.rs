@@ -0,0 +1,198 @@
+
+#![allow(dead_code)]
+
+// Input recording and replay
+//
+// Everything a machine run depends on that doesn't come from the machine itself -
+// keyboard input, interrupts raised from outside - is an input event.
+// A Recording holds the save state the run started from and every input event
+// stamped with the cpu cycle it happened on. Feeding the same events back in at the
+// same cycles, starting from the same state, reproduces the run bit-for-bit.
+//
+// The machine decides what its inputs are. While recording it adds each one with
+// Recording::record(). While replaying it takes them back from a Replay, asking for
+// the input it needs right now (Replay::take()) or for the interrupts due by the
+// current cycle (Replay::due()).
+//
+// Replay files use the same encoding as save states (see snapshot.rs):
+//      "RE6R"              magic
+//      u16                 format version (REPLAY_VERSION)
+//      bytes               save state the run starts from
+//      u32                 number of events
+//      events              u64 cycle, u8 kind, then the data for the kind
+
+use super::snapshot::{SnapshotWriter, SnapshotReader, SnapshotError};
+
+pub const REPLAY_VERSION: u16 = 1;
+
+const MAGIC: &[u8; 4] = b"RE6R";
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum InputEvent
+{
+    Input(Vec<u8>),     // Data read from outside, like a line typed on the console
+    Irq,
+    Nmi,
+}
+
+impl InputEvent
+{
+    fn kind(&self) -> u8
+    {
+        match self
+        {
+            InputEvent::Input(_) => 0,
+            InputEvent::Irq => 1,
+            InputEvent::Nmi => 2,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Recording
+{
+    pub start: Vec<u8>,                     // Save state the run starts from
+    pub events: Vec<(u64, InputEvent)>,
+}
+
+impl Recording
+{
+    pub fn new(start: Vec<u8>) -> Recording
+    {
+        Recording { start, events: Vec::new() }
+    }
+
+    pub fn record(&mut self, cycle: u64, event: InputEvent)
+    {
+        self.events.push((cycle, event));
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8>
+    {
+        let mut out = SnapshotWriter::new();
+        for b in MAGIC
+        {
+            out.u8(*b);
+        }
+
+        out.u16(REPLAY_VERSION);
+        out.bytes(&self.start);
+
+        out.u32(self.events.len() as u32);
+        for (cycle, event) in self.events.iter()
+        {
+            out.u64(*cycle);
+            out.u8(event.kind());
+
+            match event
+            {
+                InputEvent::Input(data) => out.bytes(data),
+                InputEvent::Irq | InputEvent::Nmi => (),
+            }
+        }
+
+        out.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Recording, SnapshotError>
+    {
+        let mut data = SnapshotReader::new(bytes);
+
+        for b in MAGIC
+        {
+            if data.u8().ok() != Some(*b)
+            {
+                return Err(SnapshotError::NotASnapshot);
+            }
+        }
+
+        let version = data.u16()?;
+        if version != REPLAY_VERSION
+        {
+            return Err(SnapshotError::WrongVersion(version));
+        }
+
+        let start = data.bytes()?.to_vec();
+
+        let count = data.u32()?;
+        let mut events = Vec::new();
+        for _ in 0..count
+        {
+            let cycle = data.u64()?;
+            let event = match data.u8()?
+            {
+                0 => InputEvent::Input(data.bytes()?.to_vec()),
+                1 => InputEvent::Irq,
+                2 => InputEvent::Nmi,
+                kind => return Err(SnapshotError::Invalid(format!("unknown input event {}", kind))),
+            };
+
+            events.push((cycle, event));
+        }
+
+        if !data.is_empty()
+        {
+            return Err(SnapshotError::Invalid("unexpected data after the end".to_string()));
+        }
+
+        Ok(Recording { start, events })
+    }
+}
+
+// Plays the events of a recording back in order
+pub struct Replay
+{
+    events: Vec<(u64, InputEvent)>,
+    next: usize,
+}
+
+impl Replay
+{
+    pub fn new(recording: &Recording) -> Replay
+    {
+        Replay { events: recording.events.clone(), next: 0 }
+    }
+
+    pub fn is_finished(&self) -> bool
+    {
+        self.next >= self.events.len()
+    }
+
+    // Cycle of the next event
+    pub fn next_cycle(&self) -> Option<u64>
+    {
+        self.events.get(self.next).map(|(cycle, _)| *cycle)
+    }
+
+    // The next event, if it is an interrupt due by cycle
+    pub fn due(&mut self, cycle: u64) -> Option<InputEvent>
+    {
+        match self.events.get(self.next)
+        {
+            Some((due, event)) if *due <= cycle && matches!(event, InputEvent::Irq | InputEvent::Nmi) =>
+            {
+                self.next += 1;
+                Some(event.clone())
+            }
+
+            _ => None,
+        }
+    }
+
+    // The machine needs an input at cycle. Returns the recorded event if it was made
+    // at exactly that cycle, otherwise the run has gone differently to the recording.
+    pub fn take(&mut self, cycle: u64) -> Result<InputEvent, String>
+    {
+        match self.events.get(self.next)
+        {
+            Some((due, event)) if *due == cycle =>
+            {
+                self.next += 1;
+                Ok(event.clone())
+            }
+
+            Some((due, event)) => Err(format!("replay out of sync: input needed at cycle {}, next recorded event is {:?} at cycle {}", cycle, event, due)),
+            None => Err(format!("replay out of sync: input needed at cycle {}, no recorded events left", cycle)),
+        }
+    }
+}
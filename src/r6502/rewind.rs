@@ -0,0 +1,329 @@
+
+#![allow(dead_code)]
+
+// Rewind and reverse stepping
+//
+// Rewind::step() runs one instruction and logs what is needed to undo it: the cpu
+// registers from before it ran and the old value of every byte it wrote. Stepping
+// back pops the last entry and puts all of that back. The log only holds the most
+// recent instructions (see set_max_steps()), so full save states (snapshot.rs) are
+// also taken every so many cycles to be able to go back further than that. To get to
+// a point between two of them, rewind_to() loads the one before it and runs forward
+// again, logging the instructions as it goes so they can be stepped back through.
+//
+// Only changes made by the cpu are logged. Changes made from outside of it (a device
+// writing to memory, a program loader, an irq() call) are only undone by going back to
+// a snapshot, and aren't made again when running forward from one. Observers aren't
+// told about any of this, stepping back or running forward.
+//
+// Going back and then running forwards again starts a new history, anything that
+// was recorded after the point that was rewound to is thrown away.
+
+use std::collections::VecDeque;
+
+use super::{R6502, Bus};
+use super::addressing_modes::ModeID;
+use super::snapshot::{self, Snapshot};
+
+const DEFAULT_SNAPSHOT_INTERVAL: u64 = 1_000_000;
+const DEFAULT_MAX_SNAPSHOTS: usize = 32;
+const DEFAULT_MAX_STEPS: usize = 100_000;
+
+// The cpu as it was before an instruction ran
+#[derive(Clone, Copy)]
+struct Regs
+{
+    a: u8,
+    x: u8,
+    y: u8,
+    pc: u16,
+    sp: u16,
+    status: u8,
+    cycles: u64,
+    addr_mode: ModeID,
+    working_data: u16,
+    working_addr: u16,
+    program_stopped: bool,
+    irq_pending: bool,
+}
+
+impl Regs
+{
+    fn from_cpu(cpu: &R6502) -> Regs
+    {
+        Regs { a: cpu.a, x: cpu.x, y: cpu.y, pc: cpu.pc, sp: cpu.sp, status: cpu.status, cycles: cpu.cycles,
+                addr_mode: cpu.addr_mode, working_data: cpu.working_data, working_addr: cpu.working_addr,
+                program_stopped: cpu.program_stopped, irq_pending: cpu.irq_pending }
+    }
+
+    fn restore(&self, cpu: &mut R6502)
+    {
+        cpu.a = self.a;
+        cpu.x = self.x;
+        cpu.y = self.y;
+        cpu.pc = self.pc;
+        cpu.sp = self.sp;
+        cpu.status = self.status;
+        cpu.cycles = self.cycles;
+        cpu.addr_mode = self.addr_mode;
+        cpu.working_data = self.working_data;
+        cpu.working_addr = self.working_addr;
+        cpu.program_stopped = self.program_stopped;
+        cpu.irq_pending = self.irq_pending;
+        cpu.stop_requested = false;
+    }
+}
+
+struct Step
+{
+    regs: Regs,
+    writes: usize,  // Number of writes it made, their old values are at the end of Rewind::writes
+}
+
+struct Checkpoint
+{
+    cycle: u64,
+    state: Vec<u8>,
+}
+
+// Logs the old value of every byte written
+struct WriteLog<'a>
+{
+    bus: &'a mut dyn Bus,
+    writes: &'a mut VecDeque<(u16, u8)>,
+    count: usize,
+}
+
+impl<'a> Bus for WriteLog<'a>
+{
+    fn read(&self, addr: u16) -> u8
+    {
+        self.bus.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8)
+    {
+        self.writes.push_back((addr, self.bus.read(addr)));
+        self.count += 1;
+        self.bus.write(addr, value);
+    }
+
+    fn page(&self, page: u8) -> Option<&[u8; 256]>
+    {
+        self.bus.page(page)
+    }
+
+    // page_mut() is left out so every write is seen
+}
+
+pub struct Rewind
+{
+    snapshot_interval: u64,
+    max_snapshots: usize,
+    max_steps: usize,
+
+    snapshots: VecDeque<Checkpoint>,
+    steps: VecDeque<Step>,
+    writes: VecDeque<(u16, u8)>,
+}
+
+impl Rewind
+{
+    pub fn new() -> Rewind
+    {
+        Rewind { snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL, max_snapshots: DEFAULT_MAX_SNAPSHOTS, max_steps: DEFAULT_MAX_STEPS,
+                    snapshots: VecDeque::new(), steps: VecDeque::new(), writes: VecDeque::new() }
+    }
+
+    // Cycles between save states
+    pub fn set_snapshot_interval(&mut self, cycles: u64)
+    {
+        self.snapshot_interval = cycles.max(1);
+    }
+
+    pub fn set_max_snapshots(&mut self, count: usize)
+    {
+        self.max_snapshots = count.max(1);
+        while self.snapshots.len() > self.max_snapshots
+        {
+            self.snapshots.pop_front();
+        }
+    }
+
+    // Number of instructions that can be stepped back through one at a time
+    pub fn set_max_steps(&mut self, count: usize)
+    {
+        self.max_steps = count;
+        while self.steps.len() > self.max_steps
+        {
+            self.drop_oldest_step();
+        }
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.snapshots.clear();
+        self.steps.clear();
+        self.writes.clear();
+    }
+
+    // Number of instructions that can be stepped back through
+    pub fn steps(&self) -> usize
+    {
+        self.steps.len()
+    }
+
+    // Earliest cycle that can be rewound to
+    pub fn oldest_cycle(&self) -> Option<u64>
+    {
+        let snapshot = self.snapshots.front().map(|s| s.cycle);
+        let step = self.steps.front().map(|s| s.regs.cycles);
+
+        match (snapshot, step)
+        {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    // Run one instruction, recording it so it can be undone
+    pub fn step<B: Bus + Snapshot + Clone>(&mut self, cpu: &mut R6502, bus: &mut B)
+    {
+        let due = match self.snapshots.back()
+        {
+            Some(last) => cpu.cycles >= last.cycle + self.snapshot_interval,
+            None => true,
+        };
+
+        if due
+        {
+            self.snapshots.push_back(Checkpoint { cycle: cpu.cycles, state: snapshot::save_state(cpu, bus) });
+            if self.snapshots.len() > self.max_snapshots
+            {
+                self.snapshots.pop_front();
+            }
+        }
+
+        let regs = Regs::from_cpu(cpu);
+        let mut log = WriteLog { bus, writes: &mut self.writes, count: 0 };
+        cpu.clock(&mut log);
+
+        // An observer can stop the instruction before it runs, there's nothing to undo then
+        if cpu.cycles == regs.cycles
+        {
+            return;
+        }
+
+        let writes = log.count;
+        self.steps.push_back(Step { regs, writes });
+        if self.steps.len() > self.max_steps
+        {
+            self.drop_oldest_step();
+        }
+    }
+
+    fn drop_oldest_step(&mut self)
+    {
+        if let Some(step) = self.steps.pop_front()
+        {
+            self.writes.drain(..step.writes);
+        }
+    }
+
+    // Undo the last instruction. Returns false if there's nothing left to undo.
+    pub fn step_back<B: Bus + Snapshot + Clone>(&mut self, cpu: &mut R6502, bus: &mut B) -> bool
+    {
+        let step = match self.steps.pop_back()
+        {
+            Some(step) => step,
+            None => return false,
+        };
+
+        for _ in 0..step.writes
+        {
+            let (addr, old) = self.writes.pop_back().unwrap();
+            bus.write(addr, old);
+        }
+
+        step.regs.restore(cpu);
+
+        // Snapshots taken after this point are in the future now
+        while self.snapshots.back().is_some_and(|s| s.cycle > cpu.cycles)
+        {
+            self.snapshots.pop_back();
+        }
+
+        true
+    }
+
+    // Step back until done() returns true (checked after each step back), or
+    // until there's nothing left to undo. Returns true if done() was satisfied.
+    pub fn step_back_until<B, F>(&mut self, cpu: &mut R6502, bus: &mut B, mut done: F) -> bool
+        where B: Bus + Snapshot + Clone, F: FnMut(&R6502) -> bool
+    {
+        while self.step_back(cpu, bus)
+        {
+            if done(cpu)
+            {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Step back to the last instruction that started at one of the breakpoints
+    pub fn reverse_continue<B: Bus + Snapshot + Clone>(&mut self, cpu: &mut R6502, bus: &mut B, breakpoints: &[u16]) -> bool
+    {
+        self.step_back_until(cpu, bus, |cpu| breakpoints.contains(&cpu.pc))
+    }
+
+    // Go back to the last instruction that started at or before cycle. If that's further
+    // back than the instruction log goes the machine is restored from the latest snapshot
+    // at or before cycle and run forward from there. Returns the cycle the machine is at
+    // now, or None (and nothing is changed) if there's no recorded point that early.
+    pub fn rewind_to<B: Bus + Snapshot + Clone>(&mut self, cpu: &mut R6502, bus: &mut B, cycle: u64) -> Option<u64>
+    {
+        if cpu.cycles <= cycle
+        {
+            return Some(cpu.cycles);
+        }
+
+        if self.steps.front().is_some_and(|s| s.regs.cycles <= cycle)
+        {
+            self.step_back_until(cpu, bus, |cpu| cpu.cycles <= cycle);
+            return Some(cpu.cycles);
+        }
+
+        let index = self.snapshots.iter().rposition(|s| s.cycle <= cycle)?;
+        snapshot::load_state(cpu, bus, &self.snapshots[index].state).expect("rewind snapshot failed to load");
+
+        self.snapshots.truncate(index + 1);
+        self.steps.clear();
+        self.writes.clear();
+
+        // Keep the observers out of it, they have already seen these instructions
+        let observers = std::mem::take(&mut cpu.observers);
+        while cpu.cycles <= cycle && !cpu.program_stopped
+        {
+            self.step(cpu, bus);
+        }
+
+        // The last one went past it
+        if cpu.cycles > cycle
+        {
+            self.step_back(cpu, bus);
+        }
+
+        cpu.observers = observers;
+        Some(cpu.cycles)
+    }
+}
+
+impl Default for Rewind
+{
+    fn default() -> Self
+    {
+        Rewind::new()
+    }
+}
@@ -0,0 +1,148 @@
+
+#![allow(dead_code)]
+
+// Cycle-stamped event scheduler
+//
+// Devices register callbacks for the cycle they next need attention at (a timer
+// running out, the end of a scanline, a byte finishing on a serial line...) instead
+// of being polled after every instruction. R6502::run_scheduled() runs the cpu up to
+// the next event, fires it, and carries on.
+//
+// Instructions aren't split up, so an event fires after the instruction that
+// reaches its cycle. The callback gets the cycle it was scheduled for and can
+// return the cycle to fire again at, which keeps periodic events from drifting.
+// A cycle that has already gone by fires after the next instruction.
+
+use std::collections::{BTreeMap, HashMap};
+
+use super::{R6502, Bus};
+use super::idle::RunResult;
+
+pub type EventId = u64;
+
+// Called with the cycle the event was due at. Return Some(cycle) to fire again.
+pub type EventFn<B> = Box<dyn FnMut(u64, &mut R6502, &mut B, &mut Scheduler<B>) -> Option<u64>>;
+
+pub struct Scheduler<B: Bus>
+{
+    // Keyed by cycle then id, so events due on the same cycle fire in the order they were added
+    events: BTreeMap<(u64, EventId), EventFn<B>>,
+    due: HashMap<EventId, u64>,
+    next_id: EventId,
+}
+
+impl<B: Bus> Scheduler<B>
+{
+    pub fn new() -> Scheduler<B>
+    {
+        Scheduler { events: BTreeMap::new(), due: HashMap::new(), next_id: 0 }
+    }
+
+    pub fn schedule<F>(&mut self, cycle: u64, callback: F) -> EventId
+        where F: FnMut(u64, &mut R6502, &mut B, &mut Scheduler<B>) -> Option<u64> + 'static
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.insert(id, cycle, Box::new(callback));
+        id
+    }
+
+    // Returns false if the event already fired (and wasn't rescheduled) or was cancelled
+    pub fn cancel(&mut self, id: EventId) -> bool
+    {
+        match self.due.remove(&id)
+        {
+            Some(cycle) => self.events.remove(&(cycle, id)).is_some(),
+            None => false,
+        }
+    }
+
+    // Cycle the event is due at
+    pub fn due(&self, id: EventId) -> Option<u64>
+    {
+        self.due.get(&id).copied()
+    }
+
+    // Cycle of the earliest event
+    pub fn next_event(&self) -> Option<u64>
+    {
+        self.events.keys().next().map(|(cycle, _)| *cycle)
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.events.is_empty()
+    }
+
+    fn insert(&mut self, id: EventId, cycle: u64, callback: EventFn<B>)
+    {
+        self.events.insert((cycle, id), callback);
+        self.due.insert(id, cycle);
+    }
+
+    // Fire every event that is due by the cpu's cycle count
+    pub fn fire_due(&mut self, cpu: &mut R6502, bus: &mut B)
+    {
+        while let Some(entry) = self.events.first_entry()
+        {
+            let (cycle, id) = *entry.key();
+            if cycle > cpu.cycles()
+            {
+                break;
+            }
+
+            let mut callback = entry.remove();
+            self.due.remove(&id);
+
+            // An event can't be due again before the next instruction, or it would fire forever
+            if let Some(next) = callback(cycle, cpu, bus, self)
+            {
+                self.insert(id, next.max(cpu.cycles() + 1), callback);
+            }
+        }
+    }
+}
+
+impl<B: Bus> Default for Scheduler<B>
+{
+    fn default() -> Self
+    {
+        Scheduler::new()
+    }
+}
+
+impl R6502
+{
+    // Like run_until() but fires the scheduled events along the way. Idle loops are
+    // skipped up to the next event (if idle skipping is on), and only count as
+    // infinite loops when there are no events left that could end them.
+    pub fn run_scheduled<B: Bus>(&mut self, bus: &mut B, scheduler: &mut Scheduler<B>, cycle: u64) -> RunResult
+    {
+        loop
+        {
+            scheduler.fire_due(self, bus);
+
+            let next = match scheduler.next_event()
+            {
+                Some(event) => u64::min(event, cycle),
+                None => cycle,
+            };
+
+            match self.run_until_wake(bus, next, !scheduler.is_empty())
+            {
+                RunResult::CycleLimit if self.cycles < cycle => (),
+                result =>
+                {
+                    scheduler.fire_due(self, bus);
+                    return result;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,306 @@
+
+#![allow(dead_code)]
+
+// Save states
+//
+// A snapshot is a versioned binary image of the cpu and everything on the bus side
+// that is needed to carry on exactly where it was saved. The cpu is saved in full,
+// including the helper variables and the cycle counter, so a restored machine runs
+// on bit-for-bit the same as the original. Observers are not part of the state.
+//
+// The bus (memory, devices) saves itself by implementing Snapshot. Fields are written
+// in order with SnapshotWriter and read back in the same order with SnapshotReader.
+// Loading reads into a clone of the bus, which replaces it once the whole snapshot
+// checks out.
+//
+// Layout:
+//      "RE65"              magic
+//      u16                 format version (SNAPSHOT_VERSION)
+//      cpu state
+//      bus state
+//
+// Numbers are little endian. Bump SNAPSHOT_VERSION whenever the cpu layout changes,
+// loading a snapshot with a different version fails instead of restoring garbage.
+
+use std::fmt;
+
+use super::{R6502, Bus};
+use super::addressing_modes::ModeID;
+
+pub const SNAPSHOT_VERSION: u16 = 2;
+
+const MAGIC: &[u8; 4] = b"RE65";
+
+// Index in this list is what gets saved
+const MODES: [ModeID; 14] = [ModeID::IMP, ModeID::ACM, ModeID::IMM, ModeID::ZP0, ModeID::ZPX, ModeID::ZPY, ModeID::REL,
+                            ModeID::ABS, ModeID::ABX, ModeID::ABY, ModeID::IND, ModeID::IZX, ModeID::IZY, ModeID::ERR];
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum SnapshotError
+{
+    NotASnapshot,
+    WrongVersion(u16),
+    Truncated,
+    Invalid(String),    // The data doesn't make sense, says what was wrong
+}
+
+impl fmt::Display for SnapshotError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            SnapshotError::NotASnapshot => write!(f, "not a save state"),
+            SnapshotError::WrongVersion(v) => write!(f, "save state version {} is not supported (expected {})", v, SNAPSHOT_VERSION),
+            SnapshotError::Truncated => write!(f, "save state is truncated"),
+            SnapshotError::Invalid(what) => write!(f, "invalid save state: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+pub trait Snapshot
+{
+    fn save(&self, out: &mut SnapshotWriter);
+    fn load(&mut self, data: &mut SnapshotReader) -> Result<(), SnapshotError>;
+}
+
+#[derive(Default)]
+pub struct SnapshotWriter
+{
+    data: Vec<u8>,
+}
+
+impl SnapshotWriter
+{
+    pub fn new() -> SnapshotWriter
+    {
+        SnapshotWriter { data: Vec::new() }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8>
+    {
+        self.data
+    }
+
+    pub fn u8(&mut self, value: u8)
+    {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool)
+    {
+        self.data.push(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16)
+    {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32)
+    {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64)
+    {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // Length prefixed
+    pub fn bytes(&mut self, bytes: &[u8])
+    {
+        self.u32(bytes.len() as u32);
+        self.data.extend_from_slice(bytes);
+    }
+}
+
+pub struct SnapshotReader<'a>
+{
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SnapshotReader<'a>
+{
+    pub fn new(data: &'a [u8]) -> SnapshotReader<'a>
+    {
+        SnapshotReader { data, pos: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.pos >= self.data.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError>
+    {
+        if self.data.len() - self.pos < len
+        {
+            return Err(SnapshotError::Truncated);
+        }
+
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, SnapshotError>
+    {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, SnapshotError>
+    {
+        match self.u8()?
+        {
+            0 => Ok(false),
+            1 => Ok(true),
+            v => Err(SnapshotError::Invalid(format!("bad bool value {}", v))),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, SnapshotError>
+    {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, SnapshotError>
+    {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, SnapshotError>
+    {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8], SnapshotError>
+    {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    // Read bytes into a buffer that has to be exactly the right size
+    pub fn bytes_into(&mut self, buf: &mut [u8]) -> Result<(), SnapshotError>
+    {
+        let bytes = self.bytes()?;
+        if bytes.len() != buf.len()
+        {
+            return Err(SnapshotError::Invalid(format!("expected {} bytes, found {}", buf.len(), bytes.len())));
+        }
+
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+impl Snapshot for R6502
+{
+    fn save(&self, out: &mut SnapshotWriter)
+    {
+        out.u8(self.a);
+        out.u8(self.x);
+        out.u8(self.y);
+        out.u16(self.pc);
+        out.u16(self.sp);
+        out.u8(self.status);
+        out.u64(self.cycles);
+
+        out.u8(MODES.iter().position(|m| *m == self.addr_mode).unwrap() as u8);
+        out.u16(self.working_data);
+        out.u16(self.working_addr);
+
+        out.bool(self.program_stopped);
+        out.bool(self.idle_skip);
+        out.bool(self.stop_requested);
+        out.bool(self.irq_pending);
+
+        out.u16(self.fetch_addr);
+        out.u8(self.fetch_len);
+        out.bytes(&self.fetch_buf);
+    }
+
+    fn load(&mut self, data: &mut SnapshotReader) -> Result<(), SnapshotError>
+    {
+        // Read everything before changing anything so a bad snapshot leaves the cpu alone
+        let mut cpu = self.clone();
+
+        cpu.a = data.u8()?;
+        cpu.x = data.u8()?;
+        cpu.y = data.u8()?;
+        cpu.pc = data.u16()?;
+        cpu.sp = data.u16()?;
+        cpu.status = data.u8()?;
+        cpu.cycles = data.u64()?;
+
+        let mode = data.u8()? as usize;
+        cpu.addr_mode = *MODES.get(mode).ok_or(SnapshotError::Invalid(format!("bad addressing mode {}", mode)))?;
+        cpu.working_data = data.u16()?;
+        cpu.working_addr = data.u16()?;
+
+        cpu.program_stopped = data.bool()?;
+        cpu.idle_skip = data.bool()?;
+        cpu.stop_requested = data.bool()?;
+        cpu.irq_pending = data.bool()?;
+
+        cpu.fetch_addr = data.u16()?;
+        cpu.fetch_len = data.u8()?;
+        data.bytes_into(&mut cpu.fetch_buf)?;
+
+        if cpu.fetch_len as usize > cpu.fetch_buf.len()
+        {
+            return Err(SnapshotError::Invalid(format!("bad prefetch length {}", cpu.fetch_len)));
+        }
+
+        *self = cpu;
+        Ok(())
+    }
+}
+
+// Save the cpu and the bus into a snapshot
+pub fn save_state<B: Bus + Snapshot>(cpu: &R6502, bus: &B) -> Vec<u8>
+{
+    let mut out = SnapshotWriter::new();
+    out.data.extend_from_slice(MAGIC);
+    out.u16(SNAPSHOT_VERSION);
+
+    cpu.save(&mut out);
+    bus.save(&mut out);
+
+    out.into_bytes()
+}
+
+// Restore a snapshot made by save_state(). The whole snapshot is read into a copy
+// of the bus and the cpu first, so a bad one leaves the machine as it was.
+pub fn load_state<B: Bus + Snapshot + Clone>(cpu: &mut R6502, bus: &mut B, snapshot: &[u8]) -> Result<(), SnapshotError>
+{
+    let mut data = SnapshotReader::new(snapshot);
+
+    if data.take(MAGIC.len()).ok() != Some(&MAGIC[..])
+    {
+        return Err(SnapshotError::NotASnapshot);
+    }
+
+    let version = data.u16()?;
+    if version != SNAPSHOT_VERSION
+    {
+        return Err(SnapshotError::WrongVersion(version));
+    }
+
+    let mut restored = cpu.clone();
+    restored.load(&mut data)?;
+    let mut restored_bus = bus.clone();
+    restored_bus.load(&mut data)?;
+
+    if !data.is_empty()
+    {
+        return Err(SnapshotError::Invalid("unexpected data after the end".to_string()));
+    }
+
+    *cpu = restored;
+    *bus = restored_bus;
+    Ok(())
+}
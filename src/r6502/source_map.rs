@@ -0,0 +1,379 @@
+
+#![allow(dead_code)]
+
+// Source maps
+//
+// Maps addresses to the assembly source lines they were assembled from, so tools can
+// talk about echo.asm line 23 instead of $0204. A map can be loaded from:
+//
+//      ca65/ld65 debug info (ld65 --dbgfile)   the file, line, span and seg records
+//      Assembler listings                      one line per source line with its address
+//                                              and bytes, like
+//
+//          23  0204  BD 1E 02    loop    lda text,x      with a source line number
+//          000204  1  BD 1E 02   loop:   lda text,x      ca65 -l, without one
+//
+// When a listing has no line numbers the source text on each listing line is looked
+// up in the source file to find its line number, working down the file in order.
+// Listing lines without any bytes (comments, labels on their own, equates) are skipped.
+//
+// ca65 marks addresses it doesn't know yet with an r (000204r): they are offsets into a
+// segment that ld65 places later, and the listing doesn't say where. Only code after an
+// .org has real addresses in a ca65 listing. Relocatable code with bytes is an error,
+// the ld65 debug info (--dbgfile) has the final addresses.
+//
+// Each source line keeps the address and the number of bytes it assembled to. Only the
+// address is needed to find the line an instruction came from; the size is used to
+// tell code from data (see coverage.rs). If more than one line covers an address (a
+// macro and the line it was used on) the first one loaded is used, with ca65 debug
+// info preferring the lines from the source over ones from macros.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::symbols;
+
+// Extensions a listing's source file is looked for with, next to the listing
+const SOURCE_EXTENSIONS: [&str; 3] = ["asm", "s", "a65"];
+
+// Hex bytes shown on one listing line at most
+const MAX_LISTING_BYTES: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SourceLine
+{
+    pub file: usize,    // Index into SourceMap::files()
+    pub line: u32,      // From 1
+    pub addr: u16,
+    pub size: u16,      // Bytes assembled from the line
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct SourceMap
+{
+    files: Vec<String>,
+    lines: Vec<SourceLine>,
+    by_addr: BTreeMap<u16, usize>,      // Index into lines of the line starting at each address
+}
+
+impl SourceMap
+{
+    pub fn new() -> SourceMap
+    {
+        SourceMap { files: Vec::new(), lines: Vec::new(), by_addr: BTreeMap::new() }
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.lines.is_empty()
+    }
+
+    pub fn files(&self) -> &[String]
+    {
+        &self.files
+    }
+
+    // In the order they were loaded
+    pub fn lines(&self) -> &[SourceLine]
+    {
+        &self.lines
+    }
+
+    // The index of a file, added if it isn't known yet
+    pub fn add_file(&mut self, name: &str) -> usize
+    {
+        match self.files.iter().position(|f| f == name)
+        {
+            Some(i) => i,
+            None =>
+            {
+                self.files.push(name.to_string());
+                self.files.len() - 1
+            }
+        }
+    }
+
+    pub fn add_line(&mut self, file: usize, line: u32, addr: u16, size: u16)
+    {
+        self.lines.push(SourceLine { file, line, addr, size });
+        self.by_addr.entry(addr).or_insert(self.lines.len() - 1);
+    }
+
+    // The line addr was assembled from
+    pub fn lookup(&self, addr: u16) -> Option<&SourceLine>
+    {
+        let (_, i) = self.by_addr.range(..=addr).next_back()?;
+        let line = &self.lines[*i];
+
+        match addr - line.addr < line.size.max(1)
+        {
+            true => Some(line),
+            false => None,
+        }
+    }
+
+    // "echo.asm:23"
+    pub fn describe(&self, addr: u16) -> Option<String>
+    {
+        self.lookup(addr).map(|l| format!("{}:{}", self.files[l.file], l.line))
+    }
+
+    // The lowest address assembled from a line of a file
+    pub fn address(&self, file: usize, line: u32) -> Option<u16>
+    {
+        self.lines.iter().filter(|l| l.file == file && l.line == line).map(|l| l.addr).min()
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				LOADING
+    /////////////////////////////////////////////////////////////////////
+
+    // A .dbg file is read as ca65 debug info, anything else as a listing of the source
+    // file next to it with the same name. Returns the number of lines loaded.
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, String>
+    {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        if ext == "dbg" || text.trim_start().starts_with("version")
+        {
+            return self.load_ca65_dbg(&text).map_err(|e| format!("{}: {}", path.display(), e));
+        }
+
+        let source = SOURCE_EXTENSIONS.iter().map(|ext| path.with_extension(ext)).find(|p| p.is_file());
+        let (name, source_text) = match source
+        {
+            Some(source) =>
+            {
+                let text = fs::read_to_string(&source).map_err(|e| format!("{}: {}", source.display(), e))?;
+                (source.display().to_string(), Some(text))
+            }
+
+            None => (path.with_extension("asm").display().to_string(), None),
+        };
+
+        self.load_listing(&text, &name, source_text.as_deref()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // ca65/ld65 debug info. The records can come in any order so they're all read first.
+    pub fn load_ca65_dbg(&mut self, text: &str) -> Result<usize, String>
+    {
+        let mut files = BTreeMap::new();                // id -> name
+        let mut segs = BTreeMap::new();                 // id -> start
+        let mut spans = BTreeMap::new();                // id -> (seg, start, size)
+        let mut lines = Vec::new();                     // (type, file, line, span ids)
+
+        for (n, line) in symbols::lines(text)
+        {
+            let (kind, fields) = match line.split_once([' ', '\t'])
+            {
+                Some((kind, fields)) => (kind, fields.trim()),
+                None => continue,
+            };
+
+            let mut values = BTreeMap::new();
+            for field in symbols::split_fields(fields)
+            {
+                if let Some((key, value)) = field.split_once('=')
+                {
+                    values.insert(key, value);
+                }
+            }
+
+            let number = |key: &str| -> Result<u32, String>
+            {
+                let value = values.get(key).ok_or(format!("line {}: {} without {}", n, kind, key))?;
+                parse_dbg_number(value).ok_or(format!("line {}: bad {} {}", n, key, value))
+            };
+
+            match kind
+            {
+                "file" => { files.insert(number("id")?, values.get("name").unwrap_or(&"").trim_matches('"').to_string()); }
+                "seg" => { segs.insert(number("id")?, number("start")?); }
+                "span" => { spans.insert(number("id")?, (number("seg")?, number("start")?, number("size")?)); }
+                "line" =>
+                {
+                    let ids: Vec<u32> = match values.get("span")
+                    {
+                        Some(ids) => ids.split('+').map(|id| parse_dbg_number(id).ok_or(format!("line {}: bad span {}", n, ids)))
+                                        .collect::<Result<Vec<u32>, String>>()?,
+                        None => continue,
+                    };
+
+                    let kind = values.get("type").and_then(|t| parse_dbg_number(t)).unwrap_or(0);
+                    lines.push((kind, number("file")?, number("line")?, ids));
+                }
+
+                _ => (),
+            }
+        }
+
+        // Source lines before the macro expansions and C lines
+        lines.sort_by_key(|(kind, ..)| *kind != 0);
+
+        let mut count = 0;
+        for (_, file, line, ids) in lines
+        {
+            let name = files.get(&file).ok_or(format!("line record for unknown file {}", file))?;
+            let file = self.add_file(name);
+
+            for id in ids
+            {
+                let (seg, start, size) = *spans.get(&id).ok_or(format!("unknown span {}", id))?;
+                let seg_start = *segs.get(&seg).ok_or(format!("unknown segment {}", seg))?;
+
+                let addr = u16::try_from(seg_start + start).map_err(|_| format!("span {} is outside of 16 bits", id))?;
+                self.add_line(file, line, addr, size as u16);
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    // An assembler listing of the source file called file. source is its text, which is
+    // only needed when the listing doesn't have line numbers.
+    pub fn load_listing(&mut self, listing: &str, file: &str, source: Option<&str>) -> Result<usize, String>
+    {
+        let source_lines: Vec<String> = source.unwrap_or("").lines().map(normalize).collect();
+        let mut next_source = 0;
+
+        let file = self.add_file(file);
+        let mut count = 0;
+        for (n, line) in listing.lines().enumerate()
+        {
+            let parsed = match parse_listing_line(line)
+            {
+                Some(parsed) => parsed,
+                None => continue,
+            };
+
+            if parsed.relocatable
+            {
+                return Err(format!("line {}: the address is relocatable, use the ld65 debug info (--dbgfile) instead", n + 1));
+            }
+
+            let number = match parsed.number
+            {
+                Some(number) => number,
+                None if source.is_none() => return Err(format!("line {}: no line number, and no source file to find it in", n + 1)),
+                None =>
+                {
+                    // The next source line with the same text
+                    let text = normalize(parsed.text);
+                    match source_lines[next_source..].iter().position(|l| *l == text)
+                    {
+                        Some(i) =>
+                        {
+                            next_source += i + 1;
+                            next_source as u32
+                        }
+
+                        None => continue,
+                    }
+                }
+            };
+
+            self.add_line(file, number, parsed.addr, parsed.size);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+struct ListingLine<'a>
+{
+    number: Option<u32>,
+    addr: u16,
+    relocatable: bool,
+    size: u16,
+    text: &'a str,
+}
+
+// [line number] address [include depth] bytes source
+fn parse_listing_line(line: &str) -> Option<ListingLine<'_>>
+{
+    let (first, rest) = next_word(line)?;
+    let (number, addr, mut rest) = match next_word(rest)
+    {
+        Some((second, after)) if first.chars().all(|c| c.is_ascii_digit()) && listing_address(second).is_some() =>
+            (first.parse::<u32>().ok(), listing_address(second)?, after),
+        _ => (None, listing_address(first)?, rest),
+    };
+
+    // ca65 shows how deep in .include files the line is
+    if let Some((depth, after)) = next_word(rest)
+    {
+        if depth.len() == 1 && depth.chars().all(|c| c.is_ascii_digit())
+        {
+            rest = after;
+        }
+    }
+
+    let mut size = 0;
+    while let Some((byte, after)) = next_word(rest)
+    {
+        if size == MAX_LISTING_BYTES || byte.len() != 2 || !byte.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            break;
+        }
+
+        size += 1;
+        rest = after;
+    }
+
+    match size
+    {
+        0 => None,
+        _ => Some(ListingLine { number, addr, relocatable: is_relocatable(line), size: size as u16, text: rest.trim() }),
+    }
+}
+
+// ca65's 000204r, the first word that looks like an address
+fn is_relocatable(line: &str) -> bool
+{
+    line.split_whitespace().take(2).any(|word| word.ends_with('r') && listing_address(word).is_some())
+}
+
+// 0204, 0204: or ca65's 000204 and 000204r
+fn listing_address(word: &str) -> Option<u16>
+{
+    let hex = word.trim_end_matches([':', 'r']);
+    if (hex.len() != 4 && hex.len() != 6) || !hex.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return None;
+    }
+
+    u32::from_str_radix(hex, 16).ok().and_then(|addr| u16::try_from(addr).ok())
+}
+
+fn next_word(text: &str) -> Option<(&str, &str)>
+{
+    let text = text.trim_start();
+    if text.is_empty()
+    {
+        return None;
+    }
+
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    Some((&text[..end], &text[end..]))
+}
+
+// Listings change the spacing, so lines are compared with it collapsed
+fn normalize(line: &str) -> String
+{
+    line.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// Debug info numbers are decimal or 0x hex
+fn parse_dbg_number(text: &str) -> Option<u32>
+{
+    match text.strip_prefix("0x")
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
@@ -0,0 +1,167 @@
+
+#![allow(dead_code)]
+
+// Cpu state snapshots
+//
+// CpuState is a plain copy of everything visible about the cpu: the registers,
+// the status flags decoded into booleans, the cycle count and whether the
+// program has stopped. R6502::state() takes one and R6502::set_state() puts
+// one back. Comparing two R6502s compares their states.
+//
+// An IRQ that comes in while interrupts are disabled is held until they are
+// enabled again, irq_pending says whether there is one. Clearing it drops it,
+// like a device letting go of the IRQ line. NMIs are always taken at once.
+//
+// CpuState::reg() and set_reg() get at single registers by name, for code
+// that used R6502::debug_get_reg() and debug_set_reg().
+
+use std::fmt;
+
+use super::{R6502, Flags, Registers};
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct StatusFlags
+{
+    pub n: bool,    // Negative
+    pub v: bool,    // Overflow
+    pub u: bool,    // Unused
+    pub b: bool,    // Break Command
+    pub d: bool,    // Decimal Mode
+    pub i: bool,    // Interrupt Disable
+    pub z: bool,    // Zero
+    pub c: bool,    // Carry
+}
+
+impl StatusFlags
+{
+    pub fn from_byte(status: u8) -> StatusFlags
+    {
+        let set = |flag: Flags| status & (flag as u8) != 0;
+
+        StatusFlags { n: set(Flags::N), v: set(Flags::V), u: set(Flags::U), b: set(Flags::B),
+                        d: set(Flags::D), i: set(Flags::I), z: set(Flags::Z), c: set(Flags::C) }
+    }
+
+    pub fn to_byte(self) -> u8
+    {
+        let bits = [(self.n, Flags::N), (self.v, Flags::V), (self.u, Flags::U), (self.b, Flags::B),
+                    (self.d, Flags::D), (self.i, Flags::I), (self.z, Flags::Z), (self.c, Flags::C)];
+
+        bits.iter().filter(|(set, _)| *set).fold(0, |status, (_, flag)| status | *flag as u8)
+    }
+}
+
+// Set flags in upper case, clear flags in lower case: nv-BdIzc
+impl fmt::Display for StatusFlags
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let flags = [(self.n, 'N'), (self.v, 'V'), (false, '-'), (self.b, 'B'),
+                     (self.d, 'D'), (self.i, 'I'), (self.z, 'Z'), (self.c, 'C')];
+
+        for (set, name) in flags
+        {
+            let c = if set { name } else { name.to_ascii_lowercase() };
+            write!(f, "{}", c)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct CpuState
+{
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub pc: u16,
+
+    // The full stack address, the stack lives in page 1
+    pub sp: u16,
+
+    pub flags: StatusFlags,
+    pub cycles: u64,
+    pub halted: bool,       // The program has stopped, see R6502::is_program_stopped()
+    pub irq_pending: bool,  // See R6502::irq()
+}
+
+impl CpuState
+{
+    pub fn status(&self) -> u8
+    {
+        self.flags.to_byte()
+    }
+
+    pub fn reg(&self, reg: Registers) -> u16
+    {
+        match reg
+        {
+            Registers::A => self.a as u16,
+            Registers::X => self.x as u16,
+            Registers::Y => self.y as u16,
+            Registers::PC => self.pc,
+            Registers::SP => self.sp,
+            Registers::STATUS => self.status() as u16,
+        }
+    }
+
+    pub fn set_reg(&mut self, reg: Registers, value: u16)
+    {
+        match reg
+        {
+            Registers::A => self.a = value as u8,
+            Registers::X => self.x = value as u8,
+            Registers::Y => self.y = value as u8,
+            Registers::PC => self.pc = value,
+            Registers::SP => self.sp = value,
+            Registers::STATUS => self.flags = StatusFlags::from_byte(value as u8),
+        }
+    }
+}
+
+// A=00 X=00 Y=00 SP=FF P=nv-bdIzc
+impl fmt::Display for CpuState
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "A={:02X} X={:02X} Y={:02X} SP={:02X} P={}", self.a, self.x, self.y, self.sp & 0x00FF, self.flags)
+    }
+}
+
+// Two cpus are equal when their states are, whatever observers they have
+impl PartialEq for R6502
+{
+    fn eq(&self, other: &R6502) -> bool
+    {
+        self.state() == other.state()
+    }
+}
+
+impl R6502
+{
+    pub fn state(&self) -> CpuState
+    {
+        CpuState
+        {
+            a: self.a, x: self.x, y: self.y, pc: self.pc, sp: self.sp,
+            flags: StatusFlags::from_byte(self.status),
+            cycles: self.cycles,
+            halted: self.program_stopped,
+            irq_pending: self.irq_pending,
+        }
+    }
+
+    pub fn set_state(&mut self, state: &CpuState)
+    {
+        self.a = state.a;
+        self.x = state.x;
+        self.y = state.y;
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.status = state.status();
+        self.cycles = state.cycles;
+        self.program_stopped = state.halted;
+        self.irq_pending = state.irq_pending;
+    }
+}
@@ -0,0 +1,386 @@
+
+#![allow(dead_code)]
+
+// Symbol tables
+//
+// Maps label names to addresses and back so tools can show "loop2" instead of $0215.
+// Labels can be loaded from:
+//
+//      VICE label files (.lbl, .vs)    al C:0215 .loop2
+//      ca65 debug info (.dbg)          sym id=3,name="loop2",...,val=0x215,type=lab
+//      Plain listings                  loop2 = $0215
+//
+// and exported as VICE label files or plain listings. An address can have more than
+// one name, the first one loaded is the one shown.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+// How far past a symbol an address can be and still be shown as symbol+offset
+const MAX_OFFSET: u16 = 0x100;
+
+#[derive(Clone, Default, Debug)]
+pub struct SymbolTable
+{
+    by_addr: BTreeMap<u16, Vec<String>>,
+    by_name: HashMap<String, u16>,
+}
+
+impl SymbolTable
+{
+    pub fn new() -> SymbolTable
+    {
+        SymbolTable { by_addr: BTreeMap::new(), by_name: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.by_name.is_empty()
+    }
+
+    // Adding a name that already exists moves it to the new address
+    pub fn insert(&mut self, name: &str, addr: u16)
+    {
+        self.remove(name);
+
+        self.by_name.insert(name.to_string(), addr);
+        self.by_addr.entry(addr).or_default().push(name.to_string());
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<u16>
+    {
+        let addr = self.by_name.remove(name)?;
+
+        let names = self.by_addr.get_mut(&addr).unwrap();
+        names.retain(|n| n != name);
+        if names.is_empty()
+        {
+            self.by_addr.remove(&addr);
+        }
+
+        Some(addr)
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<u16>
+    {
+        self.by_name.get(name).copied()
+    }
+
+    // The name shown for addr
+    pub fn name(&self, addr: u16) -> Option<&str>
+    {
+        self.by_addr.get(&addr).map(|names| names[0].as_str())
+    }
+
+    // Every name and its address, in address order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u16)>
+    {
+        self.by_addr.iter().flat_map(|(addr, names)| names.iter().map(move |n| (n.as_str(), *addr)))
+    }
+
+    // The closest symbol at or before addr, and how far past it addr is
+    pub fn nearest(&self, addr: u16) -> Option<(&str, u16)>
+    {
+        let (sym_addr, names) = self.by_addr.range(..=addr).next_back()?;
+        let offset = addr - sym_addr;
+
+        match offset < MAX_OFFSET
+        {
+            true => Some((names[0].as_str(), offset)),
+            false => None,
+        }
+    }
+
+    // "loop2", "loop2+3" or "$0218" if there's no symbol close enough
+    pub fn describe(&self, addr: u16) -> String
+    {
+        match self.nearest(addr)
+        {
+            Some((name, 0)) => name.to_string(),
+            Some((name, offset)) => format!("{}+{}", name, offset),
+            None => format!("${:04X}", addr),
+        }
+    }
+
+    // Parse an address typed by the user: a symbol, symbol+offset, symbol-offset,
+    // $hex, 0xhex or decimal
+    pub fn parse_addr(&self, text: &str) -> Option<u16>
+    {
+        let text = text.trim();
+
+        if let Some(i) = text.rfind(['+', '-']).filter(|i| *i > 0)
+        {
+            let base = self.parse_addr(&text[..i])?;
+            let offset = parse_number(&text[i + 1..])?;
+
+            return match &text[i..i + 1]
+            {
+                "+" => Some(base.wrapping_add(offset)),
+                _ => Some(base.wrapping_sub(offset)),
+            };
+        }
+
+        parse_number(text).or_else(|| self.lookup(text))
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				LOADING
+    /////////////////////////////////////////////////////////////////////
+
+    // Load a file, the format is picked from the extension (.lbl and .vs are VICE
+    // label files, .dbg is ca65 debug info) or failing that from the contents.
+    // Returns the number of symbols loaded.
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, String>
+    {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        let result = match ext.as_str()
+        {
+            "lbl" | "vs" => self.load_vice(&text),
+            "dbg" => self.load_ca65_dbg(&text),
+            _ if text.trim_start().starts_with("al ") => self.load_vice(&text),
+            _ if text.trim_start().starts_with("version") => self.load_ca65_dbg(&text),
+            _ => self.load_listing(&text),
+        };
+
+        result.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // VICE monitor labels, one per line: al C:0215 .loop2
+    pub fn load_vice(&mut self, text: &str) -> Result<usize, String>
+    {
+        let mut count = 0;
+        for (n, line) in lines(text)
+        {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let (addr, name) = match parts.as_slice()
+            {
+                ["al", addr, name] => (*addr, *name),
+                _ => return Err(format!("line {}: expected \"al <address> .<name>\"", n)),
+            };
+
+            // The address can have a memory space prefix, C: for the cpu
+            let addr = addr.rsplit(':').next().unwrap();
+            let addr = u16::from_str_radix(addr, 16).map_err(|_| format!("line {}: bad address {}", n, addr))?;
+
+            self.insert(name.strip_prefix('.').unwrap_or(name), addr);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    // ca65/ld65 debug info (ld65 --dbgfile). Labels and equates that fit in 16 bits
+    // are loaded. Symbols in a .scope or .proc are named the way ca65 writes them,
+    // outer::inner::name, so locals with the same name in different scopes don't
+    // replace each other. Cheap locals (@name) belong to a label rather than a scope
+    // and are left out.
+    pub fn load_ca65_dbg(&mut self, text: &str) -> Result<usize, String>
+    {
+        let scopes = ca65_scopes(text);
+
+        let mut count = 0;
+        for (n, line) in lines(text)
+        {
+            let fields = match line.strip_prefix("sym")
+            {
+                Some(fields) if fields.starts_with([' ', '\t']) => fields,
+                _ => continue,
+            };
+
+            let mut name = None;
+            let mut val = None;
+            let mut scope = None;
+            let mut cheap = false;
+            for field in split_fields(fields.trim())
+            {
+                match field.split_once('=')
+                {
+                    Some(("name", v)) => name = Some(v.trim_matches('"')),
+                    Some(("val", v)) => val = Some(v),
+                    Some(("scope", v)) => scope = v.parse::<u32>().ok(),
+                    Some(("parent", _)) => cheap = true,
+                    _ => (),
+                }
+            }
+
+            if cheap
+            {
+                continue;
+            }
+
+            let (name, val) = match (name, val)
+            {
+                (Some(name), Some(val)) => (name, val),
+                (Some(_), None) => continue,    // Imports have no value
+                _ => return Err(format!("line {}: sym without a name", n)),
+            };
+
+            // Constants can be wider than an address (e.g. val=0x12345), those are left out
+            let val = match parse_number(val)
+            {
+                Some(val) => val,
+                None if val.strip_prefix("0x").is_some_and(|hex| u32::from_str_radix(hex, 16).is_ok()) => continue,
+                None => return Err(format!("line {}: bad value {}", n, val)),
+            };
+            self.insert(&qualified_name(&scopes, scope, name), val);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    // name = $addr, one per line. ; starts a comment.
+    pub fn load_listing(&mut self, text: &str) -> Result<usize, String>
+    {
+        let mut count = 0;
+        for (n, line) in lines(text)
+        {
+            let line = line.split(';').next().unwrap().trim();
+            if line.is_empty()
+            {
+                continue;
+            }
+
+            let (name, addr) = line.split_once('=').ok_or(format!("line {}: expected \"name = $addr\"", n))?;
+            let (name, addr) = (name.trim(), addr.trim());
+
+            if name.is_empty() || name.contains(char::is_whitespace)
+            {
+                return Err(format!("line {}: bad name \"{}\"", n, name));
+            }
+
+            let addr = parse_number(addr).ok_or(format!("line {}: bad address {}", n, addr))?;
+            self.insert(name, addr);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				EXPORTING
+    /////////////////////////////////////////////////////////////////////
+
+    pub fn to_vice(&self) -> String
+    {
+        self.iter().map(|(name, addr)| format!("al C:{:04X} .{}\n", addr, name)).collect()
+    }
+
+    pub fn to_listing(&self) -> String
+    {
+        self.iter().map(|(name, addr)| format!("{} = ${:04X}\n", name, addr)).collect()
+    }
+}
+
+// Non-empty lines with their line numbers
+pub(crate) fn lines(text: &str) -> impl Iterator<Item = (usize, &str)>
+{
+    text.lines().enumerate().map(|(i, l)| (i + 1, l.trim())).filter(|(_, l)| !l.is_empty())
+}
+
+// The name and parent of every scope line in ca65 debug info, by id
+fn ca65_scopes(text: &str) -> HashMap<u32, (&str, Option<u32>)>
+{
+    let mut scopes = HashMap::new();
+    for (_, line) in lines(text)
+    {
+        let fields = match line.strip_prefix("scope")
+        {
+            Some(fields) if fields.starts_with([' ', '\t']) => fields,
+            _ => continue,
+        };
+
+        let mut id = None;
+        let mut name = "";
+        let mut parent = None;
+        for field in split_fields(fields.trim())
+        {
+            match field.split_once('=')
+            {
+                Some(("id", v)) => id = v.parse::<u32>().ok(),
+                Some(("name", v)) => name = v.trim_matches('"'),
+                Some(("parent", v)) => parent = v.parse::<u32>().ok(),
+                _ => (),
+            }
+        }
+
+        if let Some(id) = id
+        {
+            scopes.insert(id, (name, parent));
+        }
+    }
+
+    scopes
+}
+
+// name with the names of the scopes it's in in front, the file scope has no name
+fn qualified_name(scopes: &HashMap<u32, (&str, Option<u32>)>, scope: Option<u32>, name: &str) -> String
+{
+    let mut qualified = name.to_string();
+    let mut next = scope;
+
+    // The depth limit stops a parent loop in a broken file
+    for _ in 0..scopes.len()
+    {
+        let (scope_name, parent) = match next.and_then(|id| scopes.get(&id))
+        {
+            Some(scope) => *scope,
+            None => break,
+        };
+
+        if !scope_name.is_empty()
+        {
+            qualified = format!("{}::{}", scope_name, qualified);
+        }
+        next = parent;
+    }
+
+    qualified
+}
+
+// Split key=value,key="value, with commas" fields
+pub(crate) fn split_fields(text: &str) -> Vec<&str>
+{
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+
+    for (i, c) in text.char_indices()
+    {
+        match c
+        {
+            '"' => quoted = !quoted,
+            ',' if !quoted =>
+            {
+                fields.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+
+    fields.push(&text[start..]);
+    fields
+}
+
+// $hex, 0xhex or decimal
+pub fn parse_number(text: &str) -> Option<u16>
+{
+    let text = text.trim();
+
+    if let Some(hex) = text.strip_prefix('$').or(text.strip_prefix("0x")).or(text.strip_prefix("0X"))
+    {
+        return u16::from_str_radix(hex, 16).ok();
+    }
+
+    text.parse::<u16>().ok()
+}
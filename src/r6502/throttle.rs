@@ -0,0 +1,212 @@
+
+#![allow(dead_code)]
+
+// Real-time throttling
+//
+// Paces emulation to a target clock rate against the host's monotonic clock.
+// The cpu runs in bursts of cycles (10ms worth by default) and after each burst
+// the throttle sleeps until real time catches up with emulated time. Sleeping
+// per burst instead of per instruction keeps the overhead low, the cost is that
+// timing is only exact at burst boundaries.
+//
+// If the host falls too far behind (a slow host, or the program was paused in
+// a debugger) the throttle starts over from the current cycle instead of running
+// flat out until it has caught up.
+//
+// Real time comes from a Clock, which is the host's (HostClock) unless another
+// one is given to Throttle::with_clock(), e.g. to test without sleeping.
+
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{R6502, Bus};
+use super::idle::RunResult;
+
+// Some common clock rates in Hz
+pub const CLOCK_1MHZ: f64 = 1_000_000.0;
+pub const CLOCK_NES_NTSC: f64 = 1_789_773.0;
+pub const CLOCK_C64_PAL: f64 = 985_248.0;
+
+// How far behind real time emulation can get before starting over
+const MAX_BEHIND: Duration = Duration::from_millis(250);
+
+// Where the throttle gets the time from, and how it waits
+pub trait Clock
+{
+    // Time since some fixed point, which never goes backwards
+    fn now(&self) -> Duration;
+    fn sleep(&mut self, time: Duration);
+}
+
+pub struct HostClock
+{
+    origin: Instant,
+}
+
+impl HostClock
+{
+    pub fn new() -> HostClock
+    {
+        HostClock { origin: Instant::now() }
+    }
+}
+
+impl Default for HostClock
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl Clock for HostClock
+{
+    fn now(&self) -> Duration
+    {
+        self.origin.elapsed()
+    }
+
+    fn sleep(&mut self, time: Duration)
+    {
+        thread::sleep(time);
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct DriftStats
+{
+    pub bursts: u64,
+    pub late_bursts: u64,       // bursts that finished behind real time
+    pub resyncs: u64,           // times the throttle fell so far behind that it started over
+    pub slept: Duration,
+    pub max_behind: Duration,
+
+    // Emulated time minus real time at the end of the last burst,
+    // in seconds. Negative when emulation is running behind.
+    pub drift: f64,
+}
+
+impl fmt::Display for DriftStats
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{} bursts ({} late, {} resyncs), slept {:.3}s, max behind {:.3}ms, drift {:+.3}ms",
+            self.bursts, self.late_bursts, self.resyncs, self.slept.as_secs_f64(),
+            self.max_behind.as_secs_f64() * 1000.0, self.drift * 1000.0)
+    }
+}
+
+pub struct Throttle
+{
+    hz: f64,
+    burst: u64,
+    clock: Box<dyn Clock>,
+
+    // Real time and cpu cycle that emulated time is measured from
+    start: Duration,
+    start_cycle: u64,
+    next_burst: u64,
+
+    stats: DriftStats,
+}
+
+impl Throttle
+{
+    // hz is the clock rate to run at, e.g. CLOCK_1MHZ
+    pub fn new(hz: f64) -> Throttle
+    {
+        Throttle::with_clock(hz, HostClock::new())
+    }
+
+    // Paced against clock instead of the host's clock
+    pub fn with_clock<C: Clock + 'static>(hz: f64, clock: C) -> Throttle
+    {
+        let hz = hz.max(1.0);
+        let burst = ((hz / 100.0) as u64).max(1);
+        let start = clock.now();
+        Throttle { hz, burst, clock: Box::new(clock), start, start_cycle: 0, next_burst: burst, stats: DriftStats::default() }
+    }
+
+    pub fn hz(&self) -> f64
+    {
+        self.hz
+    }
+
+    // Number of cycles to run between checks against real time
+    pub fn set_burst(&mut self, cycles: u64)
+    {
+        self.burst = cycles.max(1);
+        self.next_burst = self.start_cycle + self.burst;
+    }
+
+    pub fn stats(&self) -> DriftStats
+    {
+        self.stats
+    }
+
+    // Start measuring from now, with the cpu at cycle
+    pub fn start(&mut self, cycle: u64)
+    {
+        self.start = self.clock.now();
+        self.start_cycle = cycle;
+        self.next_burst = cycle + self.burst;
+    }
+
+    // Call with the cpu's cycle count as it runs. Sleeps once a burst
+    // has been run if emulation is ahead of real time.
+    pub fn pace(&mut self, cycle: u64)
+    {
+        if cycle < self.next_burst
+        {
+            return;
+        }
+
+        let emulated = Duration::from_secs_f64((cycle - self.start_cycle) as f64 / self.hz);
+        let elapsed = self.clock.now().saturating_sub(self.start);
+
+        self.stats.bursts += 1;
+        self.next_burst = cycle + self.burst;
+
+        if emulated >= elapsed
+        {
+            let ahead = emulated - elapsed;
+            self.clock.sleep(ahead);
+            self.stats.slept += ahead;
+            self.stats.drift = ahead.as_secs_f64();
+            return;
+        }
+
+        let behind = elapsed - emulated;
+        self.stats.late_bursts += 1;
+        self.stats.max_behind = self.stats.max_behind.max(behind);
+        self.stats.drift = -behind.as_secs_f64();
+
+        if behind > MAX_BEHIND
+        {
+            self.stats.resyncs += 1;
+            self.start(cycle);
+        }
+    }
+}
+
+impl R6502
+{
+    // Like run_until() but paced to the throttle's clock rate
+    pub fn run_throttled(&mut self, bus: &mut dyn Bus, throttle: &mut Throttle, cycle: u64) -> RunResult
+    {
+        throttle.start(self.cycles);
+
+        loop
+        {
+            let burst_end = u64::min(self.cycles.saturating_add(throttle.burst), cycle);
+            let result = self.run_until(bus, burst_end);
+            throttle.pace(self.cycles);
+
+            if result != RunResult::CycleLimit || self.cycles >= cycle
+            {
+                return result;
+            }
+        }
+    }
+}
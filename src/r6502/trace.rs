@@ -0,0 +1,229 @@
+
+#![allow(dead_code)]
+
+// Instruction tracing
+//
+// Tracer is an observer that writes a line for every instruction the cpu runs, with
+// the registers and cycle count from just before it ran. Two formats are supported:
+//
+// Nestest, the same layout as the nestest.log used to check NES cpu cores (without
+// the PPU column, there's no PPU here):
+//
+//      C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD CYC:7
+//      C7A1  B1 89     LDA ($89),Y = 0300 @ 0300 = 89  A:00 X:00 Y:00 P:26 SP:FB CYC:1103
+//      C6BD  04 A9    *NOP $A9 = 00                    A:AA X:97 Y:4E P:EF SP:F5 CYC:1182
+//
+// Like nestest.log, the memory an instruction uses is shown after it as it was before
+// the instruction ran: the address once indexed (@) and the pointer or value there (=).
+// Undocumented opcodes are marked with a *.
+//
+// JsonLines, one JSON object per line:
+//
+//      {"pc":49152,"bytes":[76,245,197],"disasm":"JMP $C5F5","a":0,"x":0,"y":0,"p":36,"sp":253,"cycles":7}
+//
+// Tracing can be limited to code in an address range and to a window of cycles.
+
+use std::io::{self, Write};
+
+use super::{R6502, Bus};
+use super::addressing_modes::ModeID;
+use super::disasm;
+use super::observer::{Observer, Instruction, Control};
+use super::state::CpuState;
+use super::symbols::SymbolTable;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TraceFormat
+{
+    Nestest,
+    JsonLines,
+}
+
+pub struct Tracer
+{
+    out: Box<dyn Write>,
+    format: TraceFormat,
+    symbols: Option<SymbolTable>,
+
+    // Filters, both inclusive
+    range: (u16, u16),
+    cycles: (u64, u64),
+
+    // The line for the instruction about to run, made in inspect() while the memory
+    // it uses can still be read
+    line: Option<String>,
+
+    // The first error writing the trace, nothing more is written after one
+    error: Option<io::Error>,
+}
+
+impl Tracer
+{
+    pub fn new<W: Write + 'static>(out: W, format: TraceFormat) -> Tracer
+    {
+        Tracer { out: Box::new(out), format, symbols: None, range: (0x0000, 0xFFFF), cycles: (0, u64::MAX), line: None, error: None }
+    }
+
+    // Only trace instructions that start in start..=end
+    pub fn set_range(&mut self, start: u16, end: u16)
+    {
+        self.range = (start, end);
+    }
+
+    // Only trace instructions that start on a cycle in start..=end
+    pub fn set_cycles(&mut self, start: u64, end: u64)
+    {
+        self.cycles = (start, end);
+    }
+
+    // Show symbol names in the disassembly
+    pub fn set_symbols(&mut self, symbols: SymbolTable)
+    {
+        self.symbols = Some(symbols);
+    }
+
+    pub fn error(&self) -> Option<&io::Error>
+    {
+        self.error.as_ref()
+    }
+
+    pub fn flush(&mut self) -> io::Result<()>
+    {
+        self.out.flush()
+    }
+}
+
+impl Observer for Tracer
+{
+    fn inspect(&mut self, cpu: &R6502, bus: &dyn Bus, instr: &Instruction)
+    {
+        let cycle = cpu.cycles();
+        if self.error.is_some() || instr.pc < self.range.0 || instr.pc > self.range.1 || cycle < self.cycles.0 || cycle > self.cycles.1
+        {
+            self.line = None;
+            return;
+        }
+
+        self.line = Some(format_line(self.format, &cpu.state(), instr, self.symbols.as_ref(), Some(bus)));
+    }
+
+    fn before(&mut self, _cpu: &R6502, _instr: &Instruction) -> Control
+    {
+        if let Some(line) = self.line.take()
+        {
+            if let Err(e) = writeln!(self.out, "{}", line)
+            {
+                self.error = Some(e);
+            }
+        }
+
+        Control::Continue
+    }
+}
+
+// One line of trace for an instruction about to run with the cpu in state. The
+// nestest format shows the memory the instruction uses when given the bus.
+pub fn format_line(format: TraceFormat, state: &CpuState, instr: &Instruction, symbols: Option<&SymbolTable>, bus: Option<&dyn Bus>) -> String
+{
+    let len = instr.decoded.size() as usize;
+    let text = match symbols
+    {
+        Some(symbols) => disasm::format_instr_with(&instr.decoded, &instr.bytes, instr.pc, symbols),
+        None => disasm::format_instr(&instr.decoded, &instr.bytes, instr.pc),
+    };
+
+    match format
+    {
+        TraceFormat::Nestest =>
+        {
+            let bytes: Vec<String> = instr.bytes[..len].iter().map(|b| format!("{:02X}", b)).collect();
+            let mark = if instr.decoded.documented { ' ' } else { '*' };
+            let text = match bus
+            {
+                Some(bus) => format!("{}{}", text, annotation(state, instr, bus)),
+                None => text,
+            };
+
+            format!("{:04X}  {:<8} {}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+                instr.pc, bytes.join(" "), mark, text, state.a, state.x, state.y, state.status(), state.sp & 0x00FF, state.cycles)
+        }
+
+        TraceFormat::JsonLines =>
+        {
+            let bytes: Vec<String> = instr.bytes[..len].iter().map(|b| b.to_string()).collect();
+            format!("{{\"pc\":{},\"bytes\":[{}],\"disasm\":\"{}\",\"a\":{},\"x\":{},\"y\":{},\"p\":{},\"sp\":{},\"cycles\":{}}}",
+                instr.pc, bytes.join(","), json_escape(&text), state.a, state.x, state.y, state.status(), state.sp & 0x00FF, state.cycles)
+        }
+    }
+}
+
+// The memory an instruction uses, the way nestest.log shows it
+fn annotation(state: &CpuState, instr: &Instruction, bus: &dyn Bus) -> String
+{
+    let op8 = instr.bytes[1];
+    let op16 = ((instr.bytes[2] as u16) << 8) | op8 as u16;
+
+    // Pointers in zero page wrap around within it
+    let zp_pointer = |zp: u8| ((bus.read(zp.wrapping_add(1) as u16) as u16) << 8) | bus.read(zp as u16) as u16;
+
+    match instr.decoded.mode
+    {
+        ModeID::ZP0 => format!(" = {:02X}", bus.read(op8 as u16)),
+        ModeID::ZPX | ModeID::ZPY =>
+        {
+            let index = if instr.decoded.mode == ModeID::ZPX { state.x } else { state.y };
+            let addr = op8.wrapping_add(index);
+            format!(" @ {:02X} = {:02X}", addr, bus.read(addr as u16))
+        }
+
+        // Jumps don't use the memory at their target
+        ModeID::ABS if matches!(instr.decoded.name, "JMP" | "JSR") => String::new(),
+        ModeID::ABS => format!(" = {:02X}", bus.read(op16)),
+        ModeID::ABX | ModeID::ABY =>
+        {
+            let index = if instr.decoded.mode == ModeID::ABX { state.x } else { state.y };
+            let addr = op16.wrapping_add(index as u16);
+            format!(" @ {:04X} = {:02X}", addr, bus.read(addr))
+        }
+
+        // With the same page wrap bug as the cpu
+        ModeID::IND =>
+        {
+            let hi = (op16 & 0xFF00) | (op16.wrapping_add(1) & 0x00FF);
+            format!(" = {:04X}", ((bus.read(hi) as u16) << 8) | bus.read(op16) as u16)
+        }
+
+        ModeID::IZX =>
+        {
+            let zp = op8.wrapping_add(state.x);
+            let addr = zp_pointer(zp);
+            format!(" @ {:02X} = {:04X} = {:02X}", zp, addr, bus.read(addr))
+        }
+
+        ModeID::IZY =>
+        {
+            let base = zp_pointer(op8);
+            let addr = base.wrapping_add(state.y as u16);
+            format!(" = {:04X} @ {:04X} = {:02X}", base, addr, bus.read(addr))
+        }
+
+        _ => String::new(),
+    }
+}
+
+fn json_escape(text: &str) -> String
+{
+    let mut out = String::new();
+    for c in text.chars()
+    {
+        match c
+        {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
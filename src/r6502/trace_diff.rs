@@ -0,0 +1,299 @@
+
+#![allow(dead_code)]
+
+// Trace comparison
+//
+// Runs a program while checking every instruction against a reference trace, like
+// the nestest.log used to validate NES cpu cores or a trace saved from an earlier,
+// known good, version of this emulator (see trace.rs). The first instruction where
+// the program counter, a register, the flags or the cycle count don't match stops
+// the run with a Mismatch showing what was expected, what happened and the lines
+// of the reference that led up to it.
+//
+// Both of the formats Tracer writes are understood. Nestest lines can carry extra
+// columns (the PPU column in nestest.log) and the disassembly is never compared,
+// only the numbers. A reference without cycle counts just doesn't check them.
+//
+// diff_trace() runs the cpu through the whole reference. Machines that need to update devices between
+// instructions use a TraceDiff directly, calling check() before each instruction.
+
+use std::fmt;
+
+use super::{R6502, Bus};
+use super::decoder;
+use super::observer::Instruction;
+use super::state::{CpuState, StatusFlags};
+use super::trace::{self, TraceFormat};
+
+// Lines of the reference shown before a mismatch
+pub const DEFAULT_CONTEXT: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TraceEntry
+{
+    pub pc: u16,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub sp: u8,
+    pub cycles: Option<u64>,
+}
+
+struct RefLine
+{
+    number: usize,
+    text: String,
+    format: TraceFormat,
+    entry: TraceEntry,
+}
+
+#[derive(Clone, Debug)]
+pub struct Mismatch
+{
+    pub step: usize,            // Instructions that matched before this one
+    pub line: usize,            // Line number in the reference
+    pub context: Vec<String>,   // Reference lines before the mismatch
+    pub expected: String,
+    pub actual: String,
+    pub differences: Vec<String>,
+}
+
+impl fmt::Display for Mismatch
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        writeln!(f, "Trace mismatch after {} matching instructions, at line {} of the reference:", self.step, self.line)?;
+        for line in self.context.iter()
+        {
+            writeln!(f, "  {}", line)?;
+        }
+
+        writeln!(f, "- {}", self.expected)?;
+        writeln!(f, "+ {}", self.actual)?;
+
+        for difference in self.differences.iter()
+        {
+            writeln!(f, "    {}", difference)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+pub struct TraceDiff
+{
+    lines: Vec<RefLine>,
+    next: usize,
+    context: usize,
+}
+
+impl TraceDiff
+{
+    // Blank lines are skipped, anything else has to be a trace line
+    pub fn parse(reference: &str) -> Result<TraceDiff, String>
+    {
+        let mut lines = Vec::new();
+        for (i, text) in reference.lines().enumerate()
+        {
+            let text = text.trim_end();
+            if text.trim().is_empty()
+            {
+                continue;
+            }
+
+            let (format, entry) = parse_line(text).ok_or(format!("line {}: not a trace line: {}", i + 1, text))?;
+            lines.push(RefLine { number: i + 1, text: text.to_string(), format, entry });
+        }
+
+        Ok(TraceDiff { lines, next: 0, context: DEFAULT_CONTEXT })
+    }
+
+    pub fn set_context(&mut self, lines: usize)
+    {
+        self.context = lines;
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.lines.is_empty()
+    }
+
+    // Number of instructions checked so far
+    pub fn matched(&self) -> usize
+    {
+        self.next
+    }
+
+    pub fn is_finished(&self) -> bool
+    {
+        self.next >= self.lines.len()
+    }
+
+    // Check the cpu against the next line of the reference. Call it before each
+    // instruction is run, it moves on to the next line when everything matches.
+    pub fn check(&mut self, cpu: &R6502, bus: &dyn Bus) -> Result<(), Mismatch>
+    {
+        let line = match self.lines.get(self.next)
+        {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+
+        let state = cpu.state();
+
+        let mut differences = compare(&line.entry, &state);
+        if cpu.is_program_stopped()
+        {
+            differences.push("the program has stopped".to_string());
+        }
+
+        let instr = instruction_at(bus, state.pc);
+        if instr.is_none()
+        {
+            differences.push(format!("${:02X} at ${:04X} is not an instruction", bus.read(state.pc), state.pc));
+        }
+
+        if differences.is_empty()
+        {
+            self.next += 1;
+            return Ok(());
+        }
+
+        // Only formatted for a mismatch, most lines match
+        let actual = match instr
+        {
+            Some(instr) => trace::format_line(line.format, &state, &instr, None, Some(bus)),
+            None => format!("{:04X}  .byte ${:02X}", state.pc, bus.read(state.pc)),
+        };
+
+        let first = self.next.saturating_sub(self.context);
+        Err(Mismatch
+        {
+            step: self.next,
+            line: line.number,
+            context: self.lines[first..self.next].iter().map(|l| l.text.clone()).collect(),
+            expected: line.text.clone(),
+            actual,
+            differences,
+        })
+    }
+}
+
+// Run the cpu through the whole reference. Returns the number of instructions checked.
+pub fn diff_trace(cpu: &mut R6502, bus: &mut dyn Bus, diff: &mut TraceDiff) -> Result<usize, Mismatch>
+{
+    while !diff.is_finished()
+    {
+        diff.check(cpu, bus)?;
+        cpu.clock(bus);
+    }
+
+    Ok(diff.matched())
+}
+
+// The instruction at pc, undocumented opcodes included
+fn instruction_at(bus: &dyn Bus, pc: u16) -> Option<Instruction>
+{
+    let opcode = bus.read(pc);
+    let decoded = decoder::decode(opcode)?;
+
+    let mut bytes = [opcode, 0, 0];
+    for i in 1..decoded.size()
+    {
+        bytes[i as usize] = bus.read(pc.wrapping_add(i));
+    }
+
+    Some(Instruction { pc, bytes, decoded, addr: None })
+}
+
+fn compare(expected: &TraceEntry, state: &CpuState) -> Vec<String>
+{
+    let mut differences = Vec::new();
+
+    if expected.pc != state.pc
+    {
+        differences.push(format!("PC: expected ${:04X}, got ${:04X}", expected.pc, state.pc));
+    }
+
+    let registers = [("A", expected.a, state.a), ("X", expected.x, state.x), ("Y", expected.y, state.y), ("SP", expected.sp, (state.sp & 0x00FF) as u8)];
+    for (name, want, got) in registers
+    {
+        if want != got
+        {
+            differences.push(format!("{}: expected ${:02X}, got ${:02X}", name, want, got));
+        }
+    }
+
+    if expected.p != state.status()
+    {
+        differences.push(format!("P: expected {} (${:02X}), got {} (${:02X})", StatusFlags::from_byte(expected.p), expected.p, state.flags, state.status()));
+    }
+
+    match expected.cycles
+    {
+        Some(cycles) if cycles != state.cycles => differences.push(format!("CYC: expected {}, got {}", cycles, state.cycles)),
+        _ => (),
+    }
+
+    differences
+}
+
+// Read a line in either of the formats Tracer writes
+pub fn parse_line(line: &str) -> Option<(TraceFormat, TraceEntry)>
+{
+    match line.trim_start().starts_with('{')
+    {
+        true => parse_json(line).map(|e| (TraceFormat::JsonLines, e)),
+        false => parse_nestest(line).map(|e| (TraceFormat::Nestest, e)),
+    }
+}
+
+fn parse_nestest(line: &str) -> Option<TraceEntry>
+{
+    let pc = u16::from_str_radix(line.get(..4)?, 16).ok()?;
+
+    let field = |name: &str| line.split_whitespace().find_map(|token| token.strip_prefix(name));
+    let reg = |name: &str| field(name).and_then(|v| u8::from_str_radix(v, 16).ok());
+
+    Some(TraceEntry
+    {
+        pc,
+        a: reg("A:")?,
+        x: reg("X:")?,
+        y: reg("Y:")?,
+        p: reg("P:")?,
+        sp: reg("SP:")?,
+        cycles: field("CYC:").and_then(|v| v.parse().ok()),
+    })
+}
+
+fn parse_json(line: &str) -> Option<TraceEntry>
+{
+    // Just enough JSON for the fields we need, which are all plain numbers. Values
+    // too big for their register make it not a trace line.
+    let number = |key: &str| -> Option<u64>
+    {
+        let start = line.find(&format!("\"{}\":", key))? + key.len() + 3;
+        let digits: String = line[start..].trim_start().chars().take_while(|c| c.is_ascii_digit()).collect();
+        digits.parse().ok()
+    };
+
+    Some(TraceEntry
+    {
+        pc: u16::try_from(number("pc")?).ok()?,
+        a: u8::try_from(number("a")?).ok()?,
+        x: u8::try_from(number("x")?).ok()?,
+        y: u8::try_from(number("y")?).ok()?,
+        p: u8::try_from(number("p")?).ok()?,
+        sp: u8::try_from(number("sp")?).ok()?,
+        cycles: number("cycles"),
+    })
+}
@@ -0,0 +1,754 @@
+
+#![allow(dead_code)]
+
+// VICE binary monitor server
+//
+// A TCP server that speaks the binary remote monitor protocol of the VICE emulator
+// (x64sc -binarymonitor), so IDEs and debuggers that already drive VICE can drive a
+// machine built on this cpu instead. Only one client is served at a time, on localhost.
+//
+// Every message starts with an STX (0x02) and the API version (2), then the length of
+// the body as a little endian u32:
+//
+//      request     STX, version, length, request id (u32), command, body
+//      response    STX, version, length, response type, error code, request id (u32), body
+//
+// The response type is the command it answers, and events the server sends on its own
+// use the request id 0xFFFFFFFF. The commands supported:
+//
+//      0x01 memory get         0x02 memory set
+//      0x11 checkpoint get     0x12 checkpoint set     0x13 checkpoint delete
+//      0x14 checkpoint list    0x15 checkpoint toggle  0x22 condition set
+//      0x31 registers get      0x32 registers set      0x83 registers available
+//      0x71 advance instructions                       0x73 execute until return
+//      0x81 ping               0x82 banks available    0x85 VICE info
+//      0xAA exit (resume)      0xBB quit               0xCC reset
+//
+// There is one memory space (0, the main cpu) with one bank, and the registers are A, X,
+// Y, PC, SP and FL (the status byte) with VICE's ids. Anything else gets an error
+// response.
+//
+// The machine starts stopped. Exit, advance instructions and execute until return send
+// a resumed event (0x63) with the PC and run the machine, and when it stops again for
+// any reason, the program ending included, a stopped event (0x62) follows. Checkpoints
+// that are hit send their checkpoint info (0x11) first. A command sent while the machine
+// is running stops it, as it does in VICE, and the machine stays stopped until the
+// client resumes it. A client disconnecting leaves the machine stopped for the next one.
+//
+// Checkpoints are run by a Debugger (see debugger.rs): executes as breakpoints on every
+// address in their range, loads and stores as watchpoints. Conditions and ignore counts
+// are checked by the server when the debugger stops, and checkpoints that don't stop
+// the machine (tracepoints) just send their info and carry on.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::rc::Rc;
+
+use super::{R6502, Registers};
+use super::debugger::{Debugger, StopReason, Watchpoint, WatchKind};
+use super::debug_server::{self, Target, SLICE, is_return, client_waiting};
+use super::expr::Expr;
+
+pub const DEFAULT_PORT: u16 = 6502;
+
+const STX: u8 = 0x02;
+const API_VERSION: u8 = 0x02;
+const EVENT_ID: u32 = 0xFFFF_FFFF;
+
+// The biggest body a command can have, a memory set of all 64K after its 8 bytes of parameters
+const MAX_BODY: usize = 8 + 0x10000;
+
+// Commands, and the response and event types
+const MEMORY_GET: u8 = 0x01;
+const MEMORY_SET: u8 = 0x02;
+const CHECKPOINT_GET: u8 = 0x11;        // Also the checkpoint info response
+const CHECKPOINT_SET: u8 = 0x12;
+const CHECKPOINT_DELETE: u8 = 0x13;
+const CHECKPOINT_LIST: u8 = 0x14;
+const CHECKPOINT_TOGGLE: u8 = 0x15;
+const CONDITION_SET: u8 = 0x22;
+const REGISTERS_GET: u8 = 0x31;
+const REGISTERS_SET: u8 = 0x32;
+const ADVANCE_INSTRUCTIONS: u8 = 0x71;
+const EXECUTE_UNTIL_RETURN: u8 = 0x73;
+const PING: u8 = 0x81;
+const BANKS_AVAILABLE: u8 = 0x82;
+const REGISTERS_AVAILABLE: u8 = 0x83;
+const VICE_INFO: u8 = 0x85;
+const EXIT: u8 = 0xAA;
+const QUIT: u8 = 0xBB;
+const RESET: u8 = 0xCC;
+
+const STOPPED_EVENT: u8 = 0x62;
+const RESUMED_EVENT: u8 = 0x63;
+
+// Error codes
+const OK: u8 = 0x00;
+const OBJECT_MISSING: u8 = 0x01;
+const INVALID_MEMSPACE: u8 = 0x02;
+const INVALID_LENGTH: u8 = 0x80;
+const INVALID_PARAMETER: u8 = 0x81;
+const INVALID_API_VERSION: u8 = 0x82;
+const INVALID_COMMAND: u8 = 0x83;
+
+// Checkpoint operations, a mask
+const OP_LOAD: u8 = 0x01;
+const OP_STORE: u8 = 0x02;
+const OP_EXEC: u8 = 0x04;
+
+// VICE's register ids and the registers they are
+const REGISTERS: [(u8, &str, u8, Registers); 6] =
+[
+    (0x00, "A", 8, Registers::A),
+    (0x01, "X", 8, Registers::X),
+    (0x02, "Y", 8, Registers::Y),
+    (0x03, "PC", 16, Registers::PC),
+    (0x04, "SP", 8, Registers::SP),
+    (0x05, "FL", 8, Registers::STATUS),
+];
+
+#[derive(Clone, Debug)]
+struct Checkpoint
+{
+    number: u32,
+    start: u16,
+    end: u16,           // Inclusive
+    stop: bool,         // Stop when hit, or just report it
+    enabled: bool,
+    op: u8,
+    temporary: bool,    // Deleted after its first hit
+    hits: u32,
+    ignore: u32,        // Hits to ignore before it counts
+    condition: Option<Expr>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum RunMode
+{
+    Continue,
+    Step(u16),          // Instructions left
+    StepOver(u16),
+    UntilReturn,
+}
+
+enum Action
+{
+    Stay,
+    Run(RunMode),
+    Quit,
+}
+
+struct Request
+{
+    version: u8,
+    id: u32,
+    command: u8,
+    body: Vec<u8>,
+}
+
+pub struct ViceServer
+{
+    debugger: Rc<RefCell<Debugger>>,
+    checkpoints: BTreeMap<u32, Checkpoint>,
+    next_number: u32,
+
+    // Debugger watchpoint ids and the checkpoints they are for
+    watchpoints: Vec<(u32, u32)>,
+}
+
+impl ViceServer
+{
+    // Adds the debugger the server runs the machine with to its cpu
+    pub fn new<T: Target>(target: &mut T) -> ViceServer
+    {
+        let debugger = Rc::new(RefCell::new(Debugger::new()));
+        debugger.borrow_mut().set_break_on_interrupt(false);
+        target.cpu_mut().add_observer(debugger.clone());
+
+        ViceServer { debugger, checkpoints: BTreeMap::new(), next_number: 1, watchpoints: Vec::new() }
+    }
+
+    // Port 0 picks a free one, local_addr() on the listener says which
+    pub fn listen(port: u16) -> io::Result<TcpListener>
+    {
+        debug_server::listen(port)
+    }
+
+    // Serve clients one after another until one sends quit
+    pub fn serve<T: Target>(&mut self, target: &mut T, listener: &TcpListener) -> io::Result<()>
+    {
+        for stream in listener.incoming()
+        {
+            // Losing a client only ends its session
+            if let Ok(true) = self.session(target, stream?)
+            {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns true when the client asked to quit
+    fn session<T: Target>(&mut self, target: &mut T, mut stream: TcpStream) -> io::Result<bool>
+    {
+        stream.set_nodelay(true)?;
+
+        loop
+        {
+            let request = match read_request(&mut stream)
+            {
+                Ok(Some(request)) => request,
+                Ok(None) => return Ok(false),
+                Err(e) => return Err(e),
+            };
+
+            let mut out = Vec::new();
+            let action = match request.version
+            {
+                1 | API_VERSION => self.command(target, &request, &mut out),
+                _ => Err(INVALID_API_VERSION),
+            };
+
+            let action = action.unwrap_or_else(|error|
+            {
+                out = message(request.command, error, request.id, &[]);
+                Action::Stay
+            });
+
+            stream.write_all(&out)?;
+
+            match action
+            {
+                Action::Stay => (),
+                Action::Run(mode) => self.run(target, &mut stream, mode)?,
+                Action::Quit => return Ok(true),
+            }
+        }
+    }
+
+    fn command<T: Target>(&mut self, target: &mut T, request: &Request, out: &mut Vec<u8>) -> Result<Action, u8>
+    {
+        let mut body = Body { data: &request.body, pos: 0 };
+        let respond = |out: &mut Vec<u8>, kind: u8, data: &[u8]| out.extend(message(kind, OK, request.id, data));
+
+        match request.command
+        {
+            MEMORY_GET =>
+            {
+                let (_side_effects, start, end) = (body.u8()?, body.u16()?, body.u16()?);
+                memspace(body.u8()?)?;
+                body.u16()?;        // Bank
+
+                if end < start
+                {
+                    return Err(INVALID_PARAMETER);
+                }
+
+                // As in VICE the length is a u16, so all 64K comes back as 0
+                let len = end as usize - start as usize + 1;
+                let mut data = (len as u16).to_le_bytes().to_vec();
+                data.extend((start..=end).map(|addr| target.bus().read(addr)));
+                respond(out, MEMORY_GET, &data);
+            }
+
+            MEMORY_SET =>
+            {
+                let (_side_effects, start, end) = (body.u8()?, body.u16()?, body.u16()?);
+                memspace(body.u8()?)?;
+                body.u16()?;
+
+                if end < start
+                {
+                    return Err(INVALID_PARAMETER);
+                }
+
+                let data = body.bytes(end as usize - start as usize + 1)?;
+                for (i, value) in data.iter().enumerate()
+                {
+                    target.bus_mut().write(start.wrapping_add(i as u16), *value);
+                }
+
+                respond(out, MEMORY_SET, &[]);
+            }
+
+            CHECKPOINT_GET =>
+            {
+                let checkpoint = self.checkpoints.get(&body.u32()?).ok_or(OBJECT_MISSING)?;
+                respond(out, CHECKPOINT_GET, &checkpoint_info(checkpoint, false));
+            }
+
+            CHECKPOINT_SET =>
+            {
+                let (start, end, stop, enabled, op, temporary) = (body.u16()?, body.u16()?, body.u8()? != 0, body.u8()? != 0, body.u8()?, body.u8()? != 0);
+                if body.remaining() > 0
+                {
+                    memspace(body.u8()?)?;
+                }
+
+                if end < start || op & (OP_LOAD | OP_STORE | OP_EXEC) == 0
+                {
+                    return Err(INVALID_PARAMETER);
+                }
+
+                let number = self.next_number;
+                self.next_number += 1;
+
+                let checkpoint = Checkpoint { number, start, end, stop, enabled, op, temporary, hits: 0, ignore: 0, condition: None };
+                respond(out, CHECKPOINT_GET, &checkpoint_info(&checkpoint, false));
+                self.checkpoints.insert(number, checkpoint);
+                self.sync();
+            }
+
+            CHECKPOINT_DELETE =>
+            {
+                self.checkpoints.remove(&body.u32()?).ok_or(OBJECT_MISSING)?;
+                self.sync();
+                respond(out, CHECKPOINT_DELETE, &[]);
+            }
+
+            CHECKPOINT_LIST =>
+            {
+                for checkpoint in self.checkpoints.values()
+                {
+                    respond(out, CHECKPOINT_GET, &checkpoint_info(checkpoint, false));
+                }
+
+                respond(out, CHECKPOINT_LIST, &(self.checkpoints.len() as u32).to_le_bytes());
+            }
+
+            CHECKPOINT_TOGGLE =>
+            {
+                let (number, enabled) = (body.u32()?, body.u8()? != 0);
+                self.checkpoints.get_mut(&number).ok_or(OBJECT_MISSING)?.enabled = enabled;
+                self.sync();
+                respond(out, CHECKPOINT_TOGGLE, &[]);
+            }
+
+            CONDITION_SET =>
+            {
+                let (number, len) = (body.u32()?, body.u8()?);
+                let text = String::from_utf8_lossy(body.bytes(len as usize)?).to_string();
+                let condition = Expr::parse(&text).map_err(|_| INVALID_PARAMETER)?;
+
+                self.checkpoints.get_mut(&number).ok_or(OBJECT_MISSING)?.condition = Some(condition);
+                respond(out, CONDITION_SET, &[]);
+            }
+
+            REGISTERS_GET =>
+            {
+                memspace(body.u8()?)?;
+                respond(out, REGISTERS_GET, &registers(target.cpu()));
+            }
+
+            REGISTERS_SET =>
+            {
+                memspace(body.u8()?)?;
+                let count = body.u16()?;
+
+                let mut values = Vec::new();
+                for _ in 0..count
+                {
+                    let size = body.u8()? as usize;
+                    let item = body.bytes(size)?;
+                    if size < 3
+                    {
+                        return Err(INVALID_LENGTH);
+                    }
+
+                    let (_, _, _, reg) = REGISTERS.iter().find(|(id, ..)| *id == item[0]).ok_or(INVALID_PARAMETER)?;
+                    values.push((*reg, u16::from_le_bytes([item[1], item[2]])));
+                }
+
+                let mut state = target.cpu().state();
+                for (reg, value) in values
+                {
+                    let value = match reg
+                    {
+                        Registers::SP => 0x0100 | (value & 0xFF),
+                        _ => value,
+                    };
+
+                    state.set_reg(reg, value);
+                }
+
+                target.cpu_mut().set_state(&state);
+
+                respond(out, REGISTERS_GET, &registers(target.cpu()));
+            }
+
+            ADVANCE_INSTRUCTIONS =>
+            {
+                let (over, count) = (body.u8()? != 0, body.u16()?.max(1));
+                respond(out, ADVANCE_INSTRUCTIONS, &[]);
+
+                return Ok(Action::Run(if over { RunMode::StepOver(count) } else { RunMode::Step(count) }));
+            }
+
+            EXECUTE_UNTIL_RETURN =>
+            {
+                respond(out, EXECUTE_UNTIL_RETURN, &[]);
+                return Ok(Action::Run(RunMode::UntilReturn));
+            }
+
+            PING => respond(out, PING, &[]),
+
+            BANKS_AVAILABLE =>
+            {
+                let mut data = 1u16.to_le_bytes().to_vec();
+                item(&mut data, &[&0u16.to_le_bytes(), &name("cpu")]);
+                respond(out, BANKS_AVAILABLE, &data);
+            }
+
+            REGISTERS_AVAILABLE =>
+            {
+                memspace(body.u8()?)?;
+
+                let mut data = (REGISTERS.len() as u16).to_le_bytes().to_vec();
+                for (id, reg_name, bits, _) in REGISTERS.iter()
+                {
+                    item(&mut data, &[&[*id, *bits], &name(reg_name)]);
+                }
+
+                respond(out, REGISTERS_AVAILABLE, &data);
+            }
+
+            VICE_INFO =>
+            {
+                // Says it's VICE 3.7, which has all of the commands above
+                respond(out, VICE_INFO, &[4, 3, 7, 0, 0, 4, 0, 0, 0, 0]);
+            }
+
+            EXIT =>
+            {
+                respond(out, EXIT, &[]);
+                return Ok(Action::Run(RunMode::Continue));
+            }
+
+            QUIT =>
+            {
+                respond(out, QUIT, &[]);
+                return Ok(Action::Quit);
+            }
+
+            RESET =>
+            {
+                target.reset();
+                respond(out, RESET, &[]);
+            }
+
+            _ => return Err(INVALID_COMMAND),
+        }
+
+        Ok(Action::Stay)
+    }
+
+    /////////////////////////////////////////////////////////////////////
+    //				RUNNING
+    /////////////////////////////////////////////////////////////////////
+
+    fn run<T: Target>(&mut self, target: &mut T, stream: &mut TcpStream, mode: RunMode) -> io::Result<()>
+    {
+        stream.write_all(&message(RESUMED_EVENT, OK, EVENT_ID, &target.cpu().pc.to_le_bytes()))?;
+
+        let mut mode = mode;
+        let mut resumed = false;
+        loop
+        {
+            // Steps go one instruction at a time so a checkpoint that doesn't stop
+            // the machine doesn't lose count of them
+            let returning = !resumed && is_return(target);
+            if !resumed
+            {
+                let mut debugger = self.debugger.borrow_mut();
+                match mode
+                {
+                    RunMode::Continue => debugger.step(target.cpu(), SLICE),
+                    RunMode::Step(_) => debugger.step(target.cpu(), 1),
+                    RunMode::StepOver(_) => debugger.next(target.cpu(), target.bus()),
+                    RunMode::UntilReturn if returning => debugger.step(target.cpu(), 1),
+                    RunMode::UntilReturn => debugger.next(target.cpu(), target.bus()),
+                }
+            }
+
+            target.run();
+            resumed = false;
+
+            let reason = self.debugger.borrow().stop_reason();
+            let done = match reason
+            {
+                Some(StopReason::Step) => self.stepped(&mut mode, returning, stream)?,
+                Some(reason @ (StopReason::Breakpoint(_) | StopReason::Watchpoint { .. })) =>
+                {
+                    if self.hit(target, stream, reason)?
+                    {
+                        break;
+                    }
+
+                    // Carry on with a step over a JSR, or count the instruction a watchpoint
+                    // stopped after. Breakpoints stop before it, so it runs again.
+                    resumed = self.debugger.borrow_mut().carry_on(target.cpu());
+                    match reason
+                    {
+                        StopReason::Watchpoint { .. } if !resumed => self.stepped(&mut mode, returning, stream)?,
+                        _ => false,
+                    }
+                }
+
+                _ => true,
+            };
+
+            if done
+            {
+                break;
+            }
+        }
+
+        stream.write_all(&message(STOPPED_EVENT, OK, EVENT_ID, &target.cpu().pc.to_le_bytes()))
+    }
+
+    // One instruction, or a slice of them, has run. Returns true when the run is over.
+    fn stepped(&self, mode: &mut RunMode, returning: bool, stream: &TcpStream) -> io::Result<bool>
+    {
+        Ok(match *mode
+        {
+            RunMode::Continue => client_waiting(stream)?,
+            RunMode::Step(n) if n > 1 =>
+            {
+                *mode = RunMode::Step(n - 1);
+                false
+            }
+
+            RunMode::StepOver(n) if n > 1 =>
+            {
+                *mode = RunMode::StepOver(n - 1);
+                false
+            }
+
+            RunMode::UntilReturn => returning,
+            _ => true,
+        })
+    }
+
+    // Send the info of the checkpoints the debugger stopped for, returns true if one of
+    // them stops the machine
+    fn hit<T: Target>(&mut self, target: &T, stream: &mut TcpStream, reason: StopReason) -> io::Result<bool>
+    {
+        let numbers: Vec<u32> = match reason
+        {
+            StopReason::Breakpoint(addr) => self.checkpoints.values()
+                .filter(|c| c.enabled && c.op & OP_EXEC != 0 && addr >= c.start && addr <= c.end)
+                .map(|c| c.number).collect(),
+
+            StopReason::Watchpoint { id, .. } => self.watchpoints.iter().filter(|(w, _)| *w == id).map(|(_, c)| *c).collect(),
+            _ => Vec::new(),
+        };
+
+        let mut stop = false;
+        let mut deleted = false;
+        for number in numbers
+        {
+            let checkpoint = self.checkpoints.get_mut(&number).unwrap();
+            if checkpoint.condition.as_ref().is_some_and(|c| !c.is_true(target.cpu(), target.bus()))
+            {
+                continue;
+            }
+
+            checkpoint.hits += 1;
+            if checkpoint.ignore > 0
+            {
+                checkpoint.ignore -= 1;
+                continue;
+            }
+
+            stream.write_all(&message(CHECKPOINT_GET, OK, EVENT_ID, &checkpoint_info(checkpoint, true)))?;
+            stop |= checkpoint.stop;
+
+            if checkpoint.temporary
+            {
+                self.checkpoints.remove(&number);
+                deleted = true;
+            }
+        }
+
+        if deleted
+        {
+            self.sync();
+        }
+
+        Ok(stop)
+    }
+
+    // Give the debugger the breakpoints and watchpoints of the enabled checkpoints
+    fn sync(&mut self)
+    {
+        let mut debugger = self.debugger.borrow_mut();
+        debugger.clear_breakpoints();
+        for (id, _) in self.watchpoints.drain(..)
+        {
+            debugger.remove_watchpoint(id);
+        }
+
+        for checkpoint in self.checkpoints.values().filter(|c| c.enabled)
+        {
+            if checkpoint.op & OP_EXEC != 0
+            {
+                for addr in checkpoint.start..=checkpoint.end
+                {
+                    debugger.add_breakpoint(addr);
+                }
+            }
+
+            let kind = match (checkpoint.op & OP_LOAD != 0, checkpoint.op & OP_STORE != 0)
+            {
+                (true, true) => WatchKind::Access,
+                (true, false) => WatchKind::Read,
+                (false, true) => WatchKind::Write,
+                (false, false) => continue,
+            };
+
+            let id = debugger.add_watchpoint(Watchpoint::new(checkpoint.start, checkpoint.end, kind));
+            self.watchpoints.push((id, checkpoint.number));
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////
+//				MESSAGES
+/////////////////////////////////////////////////////////////////////
+
+// None when the client closed the connection
+fn read_request(stream: &mut impl Read) -> io::Result<Option<Request>>
+{
+    let mut header = [0u8; 11];
+    match stream.read_exact(&mut header)
+    {
+        Ok(()) => (),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    if header[0] != STX
+    {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "request doesn't start with STX"));
+    }
+
+    let len = u32::from_le_bytes([header[2], header[3], header[4], header[5]]) as usize;
+    if len > MAX_BODY
+    {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("request body of {} bytes is longer than any command", len)));
+    }
+
+    let mut body = vec![0; len];
+    stream.read_exact(&mut body)?;
+
+    Ok(Some(Request { version: header[1], id: u32::from_le_bytes([header[6], header[7], header[8], header[9]]), command: header[10], body }))
+}
+
+fn message(kind: u8, error: u8, id: u32, body: &[u8]) -> Vec<u8>
+{
+    let mut out = vec![STX, API_VERSION];
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.push(kind);
+    out.push(error);
+    out.extend_from_slice(&id.to_le_bytes());
+    out.extend_from_slice(body);
+    out
+}
+
+fn checkpoint_info(checkpoint: &Checkpoint, hit: bool) -> Vec<u8>
+{
+    let mut data = checkpoint.number.to_le_bytes().to_vec();
+    data.push(hit as u8);
+    data.extend_from_slice(&checkpoint.start.to_le_bytes());
+    data.extend_from_slice(&checkpoint.end.to_le_bytes());
+    data.extend_from_slice(&[checkpoint.stop as u8, checkpoint.enabled as u8, checkpoint.op, checkpoint.temporary as u8]);
+    data.extend_from_slice(&checkpoint.hits.to_le_bytes());
+    data.extend_from_slice(&checkpoint.ignore.to_le_bytes());
+    data.extend_from_slice(&[checkpoint.condition.is_some() as u8, 0]);
+    data
+}
+
+// Count, then the size of each item, its id and its value
+fn registers(cpu: &R6502) -> Vec<u8>
+{
+    let state = cpu.state();
+    let mut data = (REGISTERS.len() as u16).to_le_bytes().to_vec();
+    for (id, _, _, reg) in REGISTERS.iter()
+    {
+        // VICE shows the stack pointer as a byte
+        let value = match reg
+        {
+            Registers::SP => state.reg(*reg) & 0xFF,
+            _ => state.reg(*reg),
+        };
+
+        item(&mut data, &[&[*id], &value.to_le_bytes()]);
+    }
+
+    data
+}
+
+// An item in a list, which starts with its size
+fn item(data: &mut Vec<u8>, parts: &[&[u8]])
+{
+    data.push(parts.iter().map(|p| p.len()).sum::<usize>() as u8);
+    parts.iter().for_each(|p| data.extend_from_slice(p));
+}
+
+fn name(text: &str) -> Vec<u8>
+{
+    let mut data = vec![text.len() as u8];
+    data.extend_from_slice(text.as_bytes());
+    data
+}
+
+fn memspace(memspace: u8) -> Result<(), u8>
+{
+    match memspace
+    {
+        0 => Ok(()),
+        _ => Err(INVALID_MEMSPACE),
+    }
+}
+
+// Reads the fields of a request body, running out is an invalid length
+struct Body<'a>
+{
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Body<'a>
+{
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], u8>
+    {
+        let bytes = self.data.get(self.pos..self.pos + len).ok_or(INVALID_LENGTH)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, u8>
+    {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, u8>
+    {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, u8>
+    {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn remaining(&self) -> usize
+    {
+        self.data.len() - self.pos
+    }
+}
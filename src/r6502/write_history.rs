@@ -0,0 +1,110 @@
+
+#![allow(dead_code)]
+
+// Memory write history
+//
+// An observer that remembers which instruction last wrote each address: its
+// address, the cycle it started on, the instruction itself and the value written.
+// The most recent writes are kept for every address, up to the depth given to
+// WriteHistory::new(). Handy for finding out which routine trashed a pointer.
+//
+// Register it with R6502::add_observer() through an Rc<RefCell<>> to be able to
+// query it while the cpu runs. Only writes made by instructions are seen, see
+// observer.rs.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use super::observer::{Observer, Instruction, Access, Control};
+use super::R6502;
+
+#[derive(Clone, Copy)]
+pub struct WriteRecord
+{
+    pub pc: u16,
+    pub cycle: u64,
+    pub instr: Instruction,
+    pub value: u8,
+}
+
+// cycle 1234: $0204 STA wrote $42
+impl fmt::Display for WriteRecord
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "cycle {}: ${:04X} {} wrote ${:02X}", self.cycle, self.pc, self.instr.decoded.name, self.value)
+    }
+}
+
+pub struct WriteHistory
+{
+    depth: usize,
+    range: (u16, u16),
+    writes: HashMap<u16, VecDeque<WriteRecord>>,
+
+    // The instruction being run
+    current: Option<(u64, Instruction)>,
+}
+
+impl WriteHistory
+{
+    // Keeps the last depth writes to each address
+    pub fn new(depth: usize) -> WriteHistory
+    {
+        WriteHistory { depth: depth.max(1), range: (0x0000, 0xFFFF), writes: HashMap::new(), current: None }
+    }
+
+    // Only track writes to start..=end, the whole address space by default
+    pub fn set_range(&mut self, start: u16, end: u16)
+    {
+        self.range = (start, end);
+        self.writes.retain(|addr, _| *addr >= start && *addr <= end);
+    }
+
+    pub fn clear(&mut self)
+    {
+        self.writes.clear();
+    }
+
+    pub fn last_write(&self, addr: u16) -> Option<&WriteRecord>
+    {
+        self.writes.get(&addr).and_then(|w| w.back())
+    }
+
+    // Newest first
+    pub fn history(&self, addr: u16) -> impl Iterator<Item = &WriteRecord>
+    {
+        self.writes.get(&addr).into_iter().flat_map(|w| w.iter().rev())
+    }
+}
+
+impl Observer for WriteHistory
+{
+    fn before(&mut self, cpu: &R6502, instr: &Instruction) -> Control
+    {
+        self.current = Some((cpu.cycles(), *instr));
+        Control::Continue
+    }
+
+    fn access(&mut self, addr: u16, value: u8, access: Access)
+    {
+        if access != Access::Write || addr < self.range.0 || addr > self.range.1
+        {
+            return;
+        }
+
+        let (cycle, instr) = match self.current
+        {
+            Some(current) => current,
+            None => return,
+        };
+
+        let writes = self.writes.entry(addr).or_default();
+        if writes.len() == self.depth
+        {
+            writes.pop_front();
+        }
+
+        writes.push_back(WriteRecord { pc: instr.pc, cycle, instr, value });
+    }
+}
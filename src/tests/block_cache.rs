@@ -0,0 +1,113 @@
+#![allow(dead_code, non_snake_case)]
+
+use crate::tests::test_bus::{boot, registers};
+use crate::r6502::Bus;
+use crate::r6502::block_cache::BlockCache;
+
+#[test]
+fn matches_clock()
+{
+    // Count X down from 5, adding 3 to A each time around the loop
+    let program = 
+    [
+        0xA9, 0x00,         // LDA #0
+        0xA2, 0x05,         // LDX #5
+        0x18,               // loop: CLC
+        0x69, 0x03,         // ADC #3
+        0x20, 0x10, 0x02,   // JSR store
+        0xCA,               // DEX
+        0xD0, 0xF7,         // BNE loop
+        0x60,               // RTS
+        0xEA, 0xEA,         // NOP NOP
+        0x85, 0x40,         // store: STA $40
+        0x60,               // RTS
+    ];
+
+    let (mut cpu, mut bus) = boot(0x0200, &program);
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(&mut bus);
+    }
+
+    let (mut cached_cpu, mut cached_bus) = boot(0x0200, &program);
+    let mut cache = BlockCache::new();
+    while !cached_cpu.is_program_stopped()
+    {
+        cached_cpu.clock_block(&mut cached_bus, &mut cache);
+    }
+
+    assert_eq!(15, cpu.state().a);
+    assert_eq!(15, bus.read(0x0040));
+    assert_eq!(registers(&cpu), registers(&cached_cpu), "Cached run should leave the cpu in the same state");
+    assert_eq!(15, cached_bus.read(0x0040));
+    assert!(!cache.is_empty());
+}
+
+#[test]
+fn self_modifying_code()
+{
+    let program = 
+    [
+        0x20, 0x10, 0x02,   // JSR sub
+        0xEE, 0x11, 0x02,   // INC $0211 - patch the LDA operand in sub
+        0x20, 0x10, 0x02,   // JSR sub
+        0x60,               // RTS
+        0xEA, 0xEA, 0xEA, 0xEA, 0xEA, 0xEA,
+        0xA9, 0x01,         // sub: LDA #1
+        0x60,               // RTS
+    ];
+
+    let (mut cpu, mut bus) = boot(0x0200, &program);
+    let mut cache = BlockCache::new();
+
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock_block(&mut bus, &mut cache);
+    }
+
+    // The second call has to see the patched instruction
+    assert_eq!(0x02, cpu.state().a);
+}
+
+#[test]
+fn external_write()
+{
+    let (mut cpu, mut bus) = boot(0x0200, &[0xA2, 0x01, 0x4C, 0x00, 0x02]); // LDX #1, JMP $0200
+    let mut cache = BlockCache::new();
+    cpu.clock_block(&mut bus, &mut cache);
+    assert_eq!(0x01, cpu.state().x);
+
+    // Memory changed behind the cpu's back has to be reported
+    bus.write(0x0201, 0x02);
+    cache.invalidate_range(0x0200, 0x02FF);
+    assert!(cache.is_empty());
+
+    cpu.clock_block(&mut bus, &mut cache);
+    assert_eq!(0x02, cpu.state().x);
+}
+
+#[test]
+fn held_irq()
+{
+    let program =
+    [
+        0x78,               // SEI
+        0xEA,               // NOP
+        0x58,               // CLI
+        0xEA,               // NOP
+    ];
+
+    let (mut cpu, mut bus) = boot(0x0200, &program);
+    bus.write(0xFFFE, 0x00);
+    bus.write(0xFFFF, 0x03);
+    let mut cache = BlockCache::new();
+
+    cpu.clock(&mut bus);
+    cpu.irq(&mut bus);
+
+    // The block ends at the CLI, the handler is entered before the NOP after it
+    assert_eq!(2, cpu.clock_block(&mut bus, &mut cache));
+    assert_eq!(0, cpu.clock_block(&mut bus, &mut cache));
+    assert_eq!(0x0300, cpu.state().pc);
+    assert_eq!((0x02, 0x03), (bus.read(0x01FF), bus.read(0x01FE)));
+}
@@ -0,0 +1,167 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::tests::test_bus::{RAMBus, boot};
+use crate::r6502::{R6502, Bus};
+use crate::r6502::call_stack::{CallStack, FrameKind, AnomalyKind};
+use crate::r6502::symbols::SymbolTable;
+
+fn setup(program: &[u8]) -> (R6502, RAMBus, Rc<RefCell<CallStack>>)
+{
+    let (mut cpu, bus) = boot(0x0200, program);
+
+    let stack = Rc::new(RefCell::new(CallStack::new()));
+    cpu.add_observer(stack.clone());
+    (cpu, bus, stack)
+}
+
+fn run_until(cpu: &mut R6502, bus: &mut RAMBus, done: impl Fn(&R6502) -> bool)
+{
+    for _ in 0..1000
+    {
+        if done(cpu)
+        {
+            return;
+        }
+
+        cpu.clock(bus);
+    }
+
+    panic!("the program didn't get there");
+}
+
+#[test]
+fn nested_calls()
+{
+    let program =
+    [
+        0x20, 0x04, 0x02,   // main: JSR a
+        0x60,               // RTS
+        0x20, 0x08, 0x02,   // a: JSR b
+        0x60,               // RTS
+        0xEA,               // b: NOP
+        0x60,               // RTS
+    ];
+
+    let (mut cpu, mut bus, stack) = setup(&program);
+    let mut symbols = SymbolTable::new();
+    symbols.insert("main", 0x0200);
+    symbols.insert("a", 0x0204);
+    symbols.insert("b", 0x0208);
+
+    run_until(&mut cpu, &mut bus, |cpu| cpu.state().pc == 0x0208);
+    let inside = cpu.cycles();
+    {
+        let stack = stack.borrow();
+        let entries: Vec<(FrameKind, u16, u16, u16)> = stack.frames().iter().map(|f| (f.kind, f.from, f.entry, f.return_addr)).collect();
+        assert_eq!(entries, vec![(FrameKind::Call, 0x0200, 0x0204, 0x0203), (FrameKind::Call, 0x0204, 0x0208, 0x0207)]);
+        assert_eq!(stack.backtrace(0x0208, &symbols), vec!["#0  $0208  b", "#1  $0204  a  JSR", "#2  $0200  main  JSR"]);
+    }
+
+    run_until(&mut cpu, &mut bus, |cpu| cpu.is_program_stopped());
+    assert_eq!(stack.borrow().depth(), 0);
+    assert_eq!(stack.borrow().anomalies().count(), 0);
+
+    // Going back puts the frames returned from since then back
+    stack.borrow_mut().rewind(inside);
+    let entries: Vec<u16> = stack.borrow().frames().iter().map(|f| f.entry).collect();
+    assert_eq!(entries, vec![0x0204, 0x0208]);
+}
+
+#[test]
+fn interrupt_frames()
+{
+    let program =
+    [
+        0x58,               // CLI
+        0xEA,               // NOP
+        0xEA,               // NOP
+        0x60,               // RTS
+    ];
+
+    let (mut cpu, mut bus, stack) = setup(&program);
+    bus.write(0x0210, 0x40);   // handler: RTI
+    bus.write(0xFFFE, 0x10);
+    bus.write(0xFFFF, 0x02);
+
+    cpu.clock(&mut bus);
+    cpu.clock(&mut bus);
+    cpu.irq(&mut bus);
+
+    let frame = stack.borrow().frames()[0];
+    assert_eq!((frame.kind, frame.from, frame.entry, frame.return_addr), (FrameKind::Irq, 0x0202, 0x0210, 0x0202));
+    assert_eq!(stack.borrow().backtrace(0x0210, &SymbolTable::new())[1], "#1  $0202  $0202  IRQ");
+
+    cpu.clock(&mut bus);
+    assert_eq!(cpu.state().pc, 0x0202);
+    assert_eq!(stack.borrow().depth(), 0);
+    assert_eq!(stack.borrow().anomalies().count(), 0);
+}
+
+#[test]
+fn pushed_return_addresses()
+{
+    let program =
+    [
+        0x20, 0x05, 0x02,   // JSR sub
+        0xEA,               // back: NOP
+        0x60,               // RTS
+        0xA9, 0x02,         // sub: LDA #>target-1
+        0x48,               // PHA
+        0xA9, 0x0D,         // LDA #<target-1
+        0x48,               // PHA
+        0x60,               // RTS
+        0xEA, 0xEA,         // NOP NOP
+        0x68,               // target: PLA
+        0x68,               // PLA
+        0x4C, 0x03, 0x02,   // JMP back
+    ];
+
+    let (mut cpu, mut bus, stack) = setup(&program);
+
+    // The RTS jumps to target, sub is still on the call stack
+    run_until(&mut cpu, &mut bus, |cpu| cpu.state().pc == 0x020E);
+    assert_eq!(stack.borrow().depth(), 1);
+    let anomalies: Vec<(u16, AnomalyKind)> = stack.borrow().anomalies().map(|a| (a.pc, a.kind)).collect();
+    assert_eq!(anomalies, vec![(0x020B, AnomalyKind::UnmatchedReturn { to: 0x020E })]);
+
+    // Pulling its return address off the stack drops it
+    run_until(&mut cpu, &mut bus, |cpu| cpu.is_program_stopped());
+    let stack = stack.borrow();
+    assert_eq!(stack.depth(), 0);
+
+    let last = stack.anomalies().last().unwrap();
+    match last.kind
+    {
+        AnomalyKind::Discarded(frame) => assert_eq!((last.pc, frame.from), (0x020F, 0x0200)),
+        kind => panic!("expected a discarded frame, got {:?}", kind),
+    }
+
+    assert_eq!(last.to_string(), format!("cycle {}: $020F dropped the return address of the JSR at $0200", last.cycle));
+}
+
+#[test]
+fn modified_return_address()
+{
+    let program =
+    [
+        0x20, 0x07, 0x02,   // JSR skip
+        0xFF,               // An inline argument skip steps over
+        0x60,               // RTS
+        0xEA, 0xEA,         // NOP NOP
+        0xBA,               // skip: TSX
+        0xFE, 0x01, 0x01,   // INC $0101,X
+        0x60,               // RTS
+    ];
+
+    let (mut cpu, mut bus, stack) = setup(&program);
+    run_until(&mut cpu, &mut bus, |cpu| cpu.is_program_stopped());
+
+    let stack = stack.borrow();
+    assert_eq!(stack.depth(), 0);
+    let anomalies: Vec<AnomalyKind> = stack.anomalies().map(|a| a.kind).collect();
+    assert_eq!(anomalies, vec![AnomalyKind::ReturnModified { expected: 0x0203, actual: 0x0204 }]);
+}
@@ -0,0 +1,81 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::tests::test_bus::{RAMBus, boot};
+use crate::r6502::coverage::{Coverage, BranchCounts};
+use crate::r6502::source_map::SourceMap;
+
+const LISTING: &str = "\
+    1                  ; count down\n\
+    2  0200  A2 02     main    ldx #2\n\
+    3  0202  CA        loop    dex\n\
+    4  0203  D0 FD             bne loop\n\
+    5  0205  F0 01             beq done\n\
+    6  0207  EA                nop\n\
+    7  0208  60        done    rts\n\
+    8  0209  01 02 03  data    .byte 1,2,3\n";
+
+fn run() -> (RAMBus, Rc<RefCell<Coverage>>, SourceMap)
+{
+    let program = [0xA2, 0x02, 0xCA, 0xD0, 0xFD, 0xF0, 0x01, 0xEA, 0x60, 0x01, 0x02, 0x03];
+
+    let (mut cpu, mut bus) = boot(0x0200, &program);
+
+    let coverage = Rc::new(RefCell::new(Coverage::new()));
+    cpu.add_observer(coverage.clone());
+
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(&mut bus);
+    }
+
+    let mut map = SourceMap::new();
+    map.load_listing(LISTING, "count.asm", None).unwrap();
+    (bus, coverage, map)
+}
+
+#[test]
+fn hits_and_branches()
+{
+    let (bus, coverage, map) = run();
+    let coverage = coverage.borrow();
+
+    assert_eq!((coverage.hits(0x0200), coverage.hits(0x0202), coverage.hits(0x0207)), (1, 2, 0));
+    assert_eq!(coverage.executed(), 5);
+    assert_eq!(coverage.branch(0x0203), Some(BranchCounts { taken: 1, not_taken: 1 }));
+    assert_eq!(coverage.branch(0x0205), Some(BranchCounts { taken: 1, not_taken: 0 }));
+    assert_eq!(coverage.branch(0x0207), None);
+
+    // The .byte line is data
+    let lines = coverage.lines(&map, &bus);
+    let numbers: Vec<u32> = lines.keys().map(|(_, n)| *n).collect();
+    assert_eq!(numbers, vec![2, 3, 4, 5, 6, 7]);
+}
+
+#[test]
+fn lcov()
+{
+    let (bus, coverage, map) = run();
+    let lcov = coverage.borrow().lcov(&map, &bus, "count");
+
+    assert_eq!(lcov, "TN:count\nSF:count.asm\n\
+        BRDA:4,0,0,1\nBRDA:4,0,1,1\nBRDA:5,0,0,1\nBRDA:5,0,1,0\nBRF:4\nBRH:3\n\
+        DA:2,1\nDA:3,2\nDA:4,2\nDA:5,1\nDA:6,0\nDA:7,1\nLF:6\nLH:5\nend_of_record\n");
+}
+
+#[test]
+fn cobertura()
+{
+    let (bus, coverage, map) = run();
+    let xml = coverage.borrow().cobertura(&map, &bus);
+
+    assert!(xml.contains("<coverage line-rate=\"0.8333\" branch-rate=\"0.75\" lines-covered=\"5\" lines-valid=\"6\" branches-covered=\"3\" branches-valid=\"4\""), "{}", xml);
+    assert!(xml.contains("<class name=\"count.asm\" filename=\"count.asm\" line-rate=\"0.8333\" branch-rate=\"0.75\" complexity=\"0\">"), "{}", xml);
+    assert!(xml.contains("<line number=\"5\" hits=\"1\" branch=\"true\" condition-coverage=\"50% (1/2)\"/>"), "{}", xml);
+    assert!(xml.contains("<line number=\"6\" hits=\"0\" branch=\"false\"/>"), "{}", xml);
+    assert!(!xml.contains("number=\"8\""), "{}", xml);
+    assert!(xml.trim_end().ends_with("</coverage>"));
+}
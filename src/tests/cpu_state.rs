@@ -0,0 +1,80 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use crate::tests::test_bus::{RAMBus, boot, load};
+use crate::r6502::{R6502, Flags};
+use crate::r6502::state::{CpuState, StatusFlags};
+use crate::r6502::profiler::Profiler;
+
+#[test]
+fn state_after_program()
+{
+    let program = 
+    [
+        0xA9, 0x80,     // LDA #$80
+        0xA2, 0x12,     // LDX #$12
+        0xA0, 0x34,     // LDY #$34
+        0x38,           // SEC
+        0x78,           // SEI
+        0x60,           // RTS
+    ];
+
+    let (mut cpu, mut bus) = boot(0x0200, &program);
+
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(&mut bus);
+    }
+
+    let flags = StatusFlags { n: true, u: true, i: true, c: true, ..StatusFlags::default() };
+    let expected = CpuState { a: 0x80, x: 0x12, y: 0x34, pc: 0x0209, sp: 0x01FF, flags, cycles: 7 + 10 + 6, halted: true, irq_pending: false };
+    assert_eq!(cpu.state(), expected);
+    assert_eq!(cpu.state().status(), 0xA5);
+    assert_eq!(cpu.state().to_string(), "A=80 X=12 Y=34 SP=FF P=Nv-bdIzC");
+}
+
+#[test]
+fn set_state()
+{
+    let mut cpu = R6502::new();
+    let mut bus = RAMBus::new();
+    load(&mut bus, 0x0300, &[0x69, 0x01]); // ADC #1
+
+    let mut state = cpu.state();
+    state.a = 0xFF;
+    state.pc = 0x0300;
+    state.sp = 0x01F0;
+    state.flags.c = true;
+    state.flags.d = true;
+    state.halted = false;
+    cpu.set_state(&state);
+
+    assert_eq!(cpu.state(), state);
+    assert_eq!(cpu.check_flag(Flags::D), 1);
+
+    cpu.clock(&mut bus);
+
+    let after = cpu.state();
+    assert_eq!(after.a, 0x01);
+    assert_eq!(after.pc, 0x0302);
+    assert_eq!(after.sp, 0x01F0);
+    assert_eq!(after.cycles, state.cycles + 2);
+    assert!(after.flags.c);
+    assert_eq!(StatusFlags::from_byte(after.status()), after.flags);
+}
+
+#[test]
+fn compare_cpus()
+{
+    let (mut cpu, mut bus) = boot(0x0200, &[0xEA, 0xEA]);   // NOP NOP
+    let mut other = cpu.clone();
+    other.add_observer(Profiler::new());
+    assert!(cpu == other);
+
+    // Any difference in the state counts, the observers don't
+    cpu.clock(&mut bus);
+    assert!(cpu != other);
+
+    other.clock(&mut bus);
+    assert!(cpu == other);
+}
@@ -0,0 +1,52 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use crate::tests::test_bus::boot;
+
+fn run(addr: u16, program: &[u8]) -> u64
+{
+    let (mut cpu, mut bus) = boot(addr, program);
+
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(&mut bus);
+    }
+
+    cpu.cycles()
+}
+
+#[test]
+fn instruction_cycles()
+{
+    let program = 
+    [
+        0xA9, 0x01,         // LDA #1           2
+        0x85, 0x40,         // STA $40          3
+        0xA2, 0xFF,         // LDX #$FF         2
+        0xBD, 0xF0, 0x02,   // LDA $02F0,X      4 + 1 for the page crossing
+        0xA0, 0x01,         // LDY #1           2
+        0xB9, 0x00, 0x02,   // LDA $0200,Y      4
+        0x9D, 0x00, 0x03,   // STA $0300,X      5 - stores always take the extra cycle
+        0xE6, 0x40,         // INC $40          5
+        0x18,               // CLC              2
+        0x90, 0x00,         // BCC +0           2 + 1 for the taken branch
+        0x60,               // RTS              6
+    ];
+
+    // Plus 7 for the reset
+    assert_eq!(46, run(0x0200, &program));
+}
+
+#[test]
+fn branch_page_crossing()
+{
+    let program = 
+    [
+        0x18,               // CLC              2
+        0x90, 0x02,         // BCC $0301        2 + 2 for the taken branch onto the next page
+        0xEA, 0xEA,         // NOP NOP
+        0x60,               // RTS              6
+    ];
+
+    assert_eq!(19, run(0x02FC, &program));
+}
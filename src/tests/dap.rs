@@ -0,0 +1,316 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::panic;
+use std::thread;
+use std::time::Duration;
+
+use crate::tests::test_bus::{RAMBus, boot};
+use crate::r6502::{R6502, Bus};
+use crate::r6502::dap::DapServer;
+use crate::r6502::json::Json;
+use crate::r6502::source_map::SourceMap;
+use crate::r6502::symbols::SymbolTable;
+use crate::r6502::debug_server::Target;
+
+const PROGRAM: [u8; 13] =
+[
+    0xA2, 0x03,         // LDX #3
+    0x20, 0x0B, 0x02,   // loop: JSR sub
+    0xCA,               // DEX
+    0xD0, 0xFA,         // BNE loop
+    0x00,               // BRK
+    0xEA, 0xEA,         // NOP NOP
+    0xC8,               // sub: INY
+    0x60,               // RTS
+];
+
+// test.asm line, address and size. DEX and BNE are on one line, and lines 7 to 9 are
+// comments.
+const LINES: [(u32, u16, u16); 7] =
+[
+    (1, 0x0200, 2),
+    (2, 0x0202, 3),
+    (3, 0x0205, 3),
+    (5, 0x0208, 1),
+    (6, 0x0209, 2),
+    (10, 0x020B, 1),
+    (11, 0x020C, 1),
+];
+
+struct Machine
+{
+    cpu: R6502,
+    bus: RAMBus,
+}
+
+impl Target for Machine
+{
+    fn cpu(&self) -> &R6502 { &self.cpu }
+    fn cpu_mut(&mut self) -> &mut R6502 { &mut self.cpu }
+    fn bus(&self) -> &dyn Bus { &self.bus }
+    fn bus_mut(&mut self) -> &mut dyn Bus { &mut self.bus }
+
+    fn run(&mut self)
+    {
+        while !self.cpu.is_program_stopped()
+        {
+            self.cpu.clock(&mut self.bus);
+            if self.cpu.stop_requested()
+            {
+                return;
+            }
+        }
+    }
+
+    fn reset(&mut self)
+    {
+        self.cpu.reset(&mut self.bus);
+    }
+}
+
+struct Client
+{
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    seq: i64,
+}
+
+impl Client
+{
+    fn send(&mut self, command: &str, args: Json) -> i64
+    {
+        self.seq += 1;
+
+        let text = Json::object(vec![("seq", self.seq.into()), ("type", "request".into()), ("command", command.into()), ("arguments", args)]).to_string();
+        write!(self.writer, "Content-Length: {}\r\n\r\n{}", text.len(), text).unwrap();
+        self.seq
+    }
+
+    fn read(&mut self) -> Json
+    {
+        let mut header = String::new();
+        self.reader.read_line(&mut header).unwrap();
+        let len: usize = header.trim().strip_prefix("Content-Length: ").unwrap().parse().unwrap();
+
+        self.reader.read_line(&mut header).unwrap();
+        let mut body = vec![0; len];
+        self.reader.read_exact(&mut body).unwrap();
+        Json::parse(&String::from_utf8(body).unwrap()).unwrap()
+    }
+
+    // Send a request and return the body of its response, which must have succeeded
+    fn call(&mut self, command: &str, args: Json) -> Json
+    {
+        let seq = self.send(command, args);
+        let response = self.read();
+
+        assert_eq!(response.get("type").as_str(), Some("response"), "{}", response);
+        assert_eq!(response.get("request_seq").as_i64(), Some(seq));
+        assert_eq!(response.get("success").as_bool(), Some(true), "{}", response);
+        response.get("body").clone()
+    }
+
+    // The next event, which must be this one, and its body
+    fn event(&mut self, event: &str) -> Json
+    {
+        let message = self.read();
+        assert_eq!(message.get("event").as_str(), Some(event), "{}", message);
+        message.get("body").clone()
+    }
+
+    // Where the program stopped, and why
+    fn stopped(&mut self) -> (String, u16, i64)
+    {
+        let reason = self.event("stopped").get("reason").as_str().unwrap().to_string();
+        let stack = self.call("stackTrace", Json::object(vec![("threadId", 1i64.into())]));
+        let top = &stack.get("stackFrames").as_array()[0];
+
+        let pc = u16::from_str_radix(top.get("instructionPointerReference").as_str().unwrap().trim_start_matches("0x"), 16).unwrap();
+        (reason, pc, top.get("line").as_i64().unwrap())
+    }
+
+    // initialize, breakpoints on lines of test.asm, launch and configurationDone
+    fn start(&mut self, lines: &[i64], stop_on_entry: bool) -> Json
+    {
+        self.call("initialize", Json::object(vec![("adapterID", "re6502".into())]));
+        self.event("initialized");
+
+        let breakpoints = self.call("setBreakpoints", source_breakpoints(lines.iter().map(|l| (*l, None)).collect()));
+        self.call("launch", Json::object(vec![("stopOnEntry", stop_on_entry.into())]));
+        self.call("configurationDone", Json::object(Vec::new()));
+        breakpoints
+    }
+}
+
+fn source_breakpoints(lines: Vec<(i64, Option<&str>)>) -> Json
+{
+    let breakpoints = lines.into_iter().map(|(line, condition)|
+    {
+        let mut breakpoint = Json::object(vec![("line", line.into())]);
+        if let Some(condition) = condition
+        {
+            breakpoint.set("condition", condition.into());
+        }
+
+        breakpoint
+    }).collect::<Vec<Json>>();
+
+    Json::object(vec![("source", Json::object(vec![("path", "test.asm".into())])), ("breakpoints", breakpoints.into())])
+}
+
+fn step(client: &mut Client, command: &str, granularity: &str) -> (String, u16, i64)
+{
+    client.call(command, Json::object(vec![("threadId", 1i64.into()), ("granularity", granularity.into())]));
+    client.stopped()
+}
+
+// A server that stops answering fails the test instead of hanging it
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn connect(addr: SocketAddr) -> TcpStream
+{
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    stream
+}
+
+// Serve PROGRAM to the script, which runs on another thread, and return the machine
+fn serve(script: fn(&mut Client)) -> Machine
+{
+    let (cpu, bus) = boot(0x0200, &PROGRAM);
+    let mut machine = Machine { cpu, bus };
+
+    let mut map = SourceMap::new();
+    let file = map.add_file("test.asm");
+    for (line, addr, size) in LINES
+    {
+        map.add_line(file, line, addr, size);
+    }
+
+    let mut symbols = SymbolTable::new();
+    symbols.insert("loop", 0x0202);
+    symbols.insert("sub", 0x020B);
+
+    let listener = DapServer::listen(0).unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+
+    // The server stops when the client hangs up, panicking or not
+    let client = thread::spawn(move ||
+    {
+        let stream = connect(addr);
+        let mut client = Client { reader: BufReader::new(stream.try_clone().unwrap()), writer: stream, seq: 0 };
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| script(&mut client)));
+        drop(client);
+
+        if let Err(e) = result
+        {
+            panic::resume_unwind(e);
+        }
+    });
+
+    let mut server = DapServer::new(&mut machine, map, symbols);
+    server.serve(&mut machine, &listener).unwrap();
+    client.join().unwrap();
+
+    machine
+}
+
+#[test]
+fn source_breakpoints_and_call_stack()
+{
+    serve(|client|
+    {
+        // Line 8 is a comment, so that one moves down to sub
+        let breakpoints = client.start(&[8, 20], false);
+        let breakpoints = breakpoints.get("breakpoints").as_array();
+        assert_eq!(breakpoints[0].get("verified").as_bool(), Some(true));
+        assert_eq!(breakpoints[0].get("line").as_i64(), Some(10));
+        assert_eq!(breakpoints[1].get("verified").as_bool(), Some(false));
+
+        assert_eq!(client.stopped(), ("breakpoint".to_string(), 0x020B, 10));
+
+        let stack = client.call("stackTrace", Json::object(vec![("threadId", 1i64.into())]));
+        let frames: Vec<(&str, i64)> = stack.get("stackFrames").as_array().iter().map(|f| (f.get("name").as_str().unwrap(), f.get("line").as_i64().unwrap())).collect();
+        assert_eq!(frames, vec![("sub", 10), ("loop", 2)]);
+
+        // Only stop on DEX the second time round
+        let breakpoints = client.call("setBreakpoints", source_breakpoints(vec![(3, Some("X == 2"))]));
+        let id = breakpoints.get("breakpoints").as_array()[0].get("id").clone();
+        client.call("continue", Json::object(Vec::new()));
+        assert_eq!(client.event("stopped").get("hitBreakpointIds").as_array(), &[id]);
+
+        let registers = client.call("variables", Json::object(vec![("variablesReference", 1i64.into())]));
+        assert_eq!(registers.get("variables").as_array()[2].get("value").as_str(), Some("$02"));
+
+        // Then on to the BRK
+        client.call("setBreakpoints", source_breakpoints(Vec::new()));
+        client.call("continue", Json::object(Vec::new()));
+        assert_eq!(client.stopped(), ("exception".to_string(), 0x0208, 5));
+    });
+}
+
+#[test]
+fn stepping_by_line_and_instruction()
+{
+    let machine = serve(|client|
+    {
+        client.start(&[], true);
+        assert_eq!(client.stopped(), ("entry".to_string(), 0x0200, 1));
+
+        // Over the JSR, then into it and back out
+        assert_eq!(step(client, "next", "line"), ("step".to_string(), 0x0202, 2));
+        assert_eq!(step(client, "next", "line"), ("step".to_string(), 0x0205, 3));
+
+        // DEX and BNE are one line, the BNE goes back to loop
+        assert_eq!(step(client, "next", "line"), ("step".to_string(), 0x0202, 2));
+        assert_eq!(step(client, "stepIn", "line"), ("step".to_string(), 0x020B, 10));
+        assert_eq!(step(client, "stepOut", "line"), ("step".to_string(), 0x0205, 3));
+        assert_eq!(step(client, "next", "instruction"), ("step".to_string(), 0x0206, 3));
+    });
+
+    assert_eq!((machine.cpu.state().x, machine.cpu.state().y), (1, 2));
+}
+
+#[test]
+fn variables_memory_and_disassembly()
+{
+    let machine = serve(|client|
+    {
+        client.start(&[], true);
+        client.stopped();
+
+        let set = |name: &str, value: &str| Json::object(vec![("variablesReference", 1i64.into()), ("name", name.into()), ("value", value.into())]);
+        assert_eq!(client.call("setVariable", set("A", "$40 + 2")).get("value").as_str(), Some("$42"));
+        assert_eq!(client.call("setVariable", set("PC", "loop")).get("value").as_str(), Some("$0202"));
+
+        let carry = Json::object(vec![("variablesReference", 2i64.into()), ("name", "C".into()), ("value", "true".into())]);
+        client.call("setVariable", carry);
+
+        let result = client.call("evaluate", Json::object(vec![("expression", "sub + 1".into())]));
+        assert_eq!((result.get("result").as_str(), result.get("memoryReference").as_str()), (Some("$020C (524)"), Some("0x020C")));
+
+        // Three bytes written and four read back, past the top of memory is unreadable
+        client.call("writeMemory", Json::object(vec![("memoryReference", "0x3000".into()), ("data", "qrvM".into())]));
+        let memory = client.call("readMemory", Json::object(vec![("memoryReference", "0x3000".into()), ("count", 4i64.into())]));
+        assert_eq!(memory.get("data").as_str(), Some("qrvMAA=="));
+        let memory = client.call("readMemory", Json::object(vec![("memoryReference", "0xFFFE".into()), ("count", 4i64.into())]));
+        assert_eq!(memory.get("unreadableBytes").as_i64(), Some(2));
+
+        // Two instructions back from DEX
+        let args = Json::object(vec![("memoryReference", "0x0205".into()), ("instructionOffset", (-2i64).into()), ("instructionCount", 3i64.into())]);
+        let disassembly = client.call("disassemble", args);
+        let instructions: Vec<(&str, &str)> = disassembly.get("instructions").as_array().iter().map(|i| (i.get("address").as_str().unwrap(), i.get("instruction").as_str().unwrap())).collect();
+        assert_eq!(instructions, vec![("0x0200", "LDX #$03"), ("0x0202", "JSR sub"), ("0x0205", "DEX")]);
+
+        let seq = client.send("restartFrame", Json::object(Vec::new()));
+        let response = client.read();
+        assert_eq!((response.get("request_seq").as_i64(), response.get("success").as_bool()), (Some(seq), Some(false)));
+    });
+
+    let state = machine.cpu.state();
+    assert_eq!((state.a, state.pc, state.flags.c), (0x42, 0x0202, true));
+    assert_eq!(machine.bus.read(0x3002), 0xCC);
+}
@@ -0,0 +1,152 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::tests::test_bus::{RAMBus, boot};
+use crate::r6502::{R6502, Bus};
+use crate::r6502::debugger::{Debugger, StopReason, Watchpoint, WatchKind};
+use crate::r6502::expr::Expr;
+use crate::r6502::observer::{Interrupt, Access};
+
+const PROGRAM: [u8; 13] = 
+[
+    0xA2, 0x03,         // LDX #3
+    0x20, 0x0B, 0x02,   // loop: JSR sub
+    0xCA,               // DEX
+    0xD0, 0xFA,         // BNE loop
+    0x00,               // BRK
+    0xEA, 0xEA,         // NOP NOP
+    0xC8,               // sub: INY
+    0x60,               // RTS
+];
+
+fn setup() -> (R6502, RAMBus, Rc<RefCell<Debugger>>)
+{
+    let (mut cpu, bus) = boot(0x0200, &PROGRAM);
+
+    let debugger = Rc::new(RefCell::new(Debugger::new()));
+    cpu.add_observer(debugger.clone());
+    (cpu, bus, debugger)
+}
+
+// Run until the debugger stops the cpu
+fn run(cpu: &mut R6502, bus: &mut RAMBus, debugger: &Rc<RefCell<Debugger>>) -> StopReason
+{
+    for _ in 0..1000
+    {
+        cpu.clock(bus);
+        if let Some(reason) = debugger.borrow().stop_reason()
+        {
+            return reason;
+        }
+    }
+
+    panic!("the debugger never stopped the cpu");
+}
+
+#[test]
+fn breakpoints_and_continue()
+{
+    let (mut cpu, mut bus, debugger) = setup();
+    debugger.borrow_mut().add_breakpoint(0x020B);
+
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::Breakpoint(0x020B));
+    assert_eq!(cpu.state().x, 3);
+
+    // Continuing runs the instruction at the breakpoint and stops there again next time round
+    debugger.borrow_mut().cont(&cpu);
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::Breakpoint(0x020B));
+    assert_eq!(cpu.state().x, 2);
+
+    debugger.borrow_mut().remove_breakpoint(0x020B);
+    debugger.borrow_mut().cont(&cpu);
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::Brk(0x0208));
+    assert_eq!(cpu.state().y, 3);
+}
+
+#[test]
+fn step_next_and_run_to()
+{
+    let (mut cpu, mut bus, debugger) = setup();
+
+    debugger.borrow_mut().step(&cpu, 2);
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::Step);
+    assert_eq!(cpu.state().pc, 0x020B);
+
+    debugger.borrow_mut().run_to(&cpu, 0x0202);
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::Step);
+    assert_eq!(cpu.state().x, 2);
+
+    // Stepping over the JSR runs the whole subroutine
+    debugger.borrow_mut().next(&cpu, &bus);
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::Step);
+    assert_eq!(cpu.state().pc, 0x0205);
+    assert_eq!(cpu.state().y, 2);
+}
+
+#[test]
+fn interrupts_and_illegal_opcodes()
+{
+    let (mut cpu, mut bus, debugger) = setup();
+    bus.write(0xFFFE, 0x09);
+    bus.write(0xFFFF, 0x02);
+    bus.write(0x020A, 0x02);    // Undocumented
+    bus.write(0x020B, 0x03);    // Not an instruction at all
+
+    cpu.irq(&mut bus);
+    assert_eq!(debugger.borrow().stop_reason(), Some(StopReason::Interrupt(Interrupt::Irq)));
+    assert!(cpu.stop_requested());
+    assert_eq!(cpu.state().pc, 0x0209);
+
+    debugger.borrow_mut().cont(&cpu);
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::IllegalOpcode(0x020A, 0x02));
+
+    // An undocumented opcode can be run, $03 can't however many times the cpu is clocked
+    bus.write(0x020A, 0xEA);
+    debugger.borrow_mut().cont(&cpu);
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::IllegalOpcode(0x020B, 0x03));
+
+    debugger.borrow_mut().cont(&cpu);
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::IllegalOpcode(0x020B, 0x03));
+    assert_eq!(cpu.state().pc, 0x020B);
+}
+
+#[test]
+fn conditions()
+{
+    let (mut cpu, mut bus, debugger) = setup();
+
+    // Only stops at sub on the last time round
+    debugger.borrow_mut().add_conditional_breakpoint(0x020B, Expr::parse("X == 1").unwrap());
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::Breakpoint(0x020B));
+    assert_eq!(cpu.state().x, 1);
+    debugger.borrow_mut().remove_breakpoint(0x020B);
+
+    // Stops when Y becomes 3, not again while it stays 3
+    let (mut cpu, mut bus, debugger) = setup();
+    let id = debugger.borrow_mut().add_condition(Expr::parse("Y == 3").unwrap());
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::Condition(id));
+    assert_eq!(cpu.state().pc, 0x020C);
+
+    debugger.borrow_mut().cont(&cpu);
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::Brk(0x0208));
+}
+
+#[test]
+fn watchpoints()
+{
+    let (mut cpu, mut bus, debugger) = setup();
+
+    // JSR pushes the return address, $02 then $04
+    let id = debugger.borrow_mut().add_watchpoint(Watchpoint::new(0x0100, 0x01FF, WatchKind::Write).with_value(0x04));
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::Watchpoint { id, pc: 0x0202, addr: 0x01FE, value: 0x04, access: Access::Write });
+    assert_eq!(cpu.state().pc, 0x020B);
+    debugger.borrow_mut().remove_watchpoint(id);
+
+    // RTS reads them back
+    let id = debugger.borrow_mut().add_watchpoint(Watchpoint::new(0x01FF, 0x01FF, WatchKind::Access));
+    debugger.borrow_mut().cont(&cpu);
+    assert_eq!(run(&mut cpu, &mut bus, &debugger), StopReason::Watchpoint { id, pc: 0x020C, addr: 0x01FF, value: 0x02, access: Access::Read });
+}
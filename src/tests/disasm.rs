@@ -0,0 +1,109 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use crate::tests::test_bus::RAMBus;
+use crate::r6502::Bus;
+use crate::r6502::disasm;
+
+#[test]
+fn every_mode()
+{
+    let code = 
+    [
+        0xEA,               // NOP
+        0x0A,               // ASL A
+        0xA9, 0x10,         // LDA #$10
+        0xA5, 0x20,         // LDA $20
+        0xB5, 0x21,         // LDA $21,X
+        0xB6, 0x22,         // LDX $22,Y
+        0xD0, 0xF4,         // BNE $0200
+        0xAD, 0x34, 0x12,   // LDA $1234
+        0xBD, 0x35, 0x12,   // LDA $1235,X
+        0xB9, 0x36, 0x12,   // LDA $1236,Y
+        0x6C, 0xFC, 0xFF,   // JMP ($FFFC)
+        0xA1, 0x40,         // LDA ($40,X)
+        0xB1, 0x41,         // LDA ($41),Y
+        0x10, 0x10,         // BPL $022E
+    ];
+
+    let lines: Vec<String> = disasm::disassemble_bytes(&code, 0x0200).iter().map(|l| l.to_string()).collect();
+    assert_eq!(lines, vec![
+        "$0200  EA        NOP",
+        "$0201  0A        ASL A",
+        "$0202  A9 10     LDA #$10",
+        "$0204  A5 20     LDA $20",
+        "$0206  B5 21     LDA $21,X",
+        "$0208  B6 22     LDX $22,Y",
+        "$020A  D0 F4     BNE $0200",
+        "$020C  AD 34 12  LDA $1234",
+        "$020F  BD 35 12  LDA $1235,X",
+        "$0212  B9 36 12  LDA $1236,Y",
+        "$0215  6C FC FF  JMP ($FFFC)",
+        "$0218  A1 40     LDA ($40,X)",
+        "$021A  B1 41     LDA ($41),Y",
+        "$021C  10 10     BPL $022E",
+    ]);
+}
+
+#[test]
+fn unknown_bytes()
+{
+    // Opcodes the cpu can't run or that are undocumented, and an instruction
+    // cut off by the end of the data
+    let lines = disasm::disassemble_bytes(&[0x03, 0x02, 0x89, 0x80, 0x04, 0xEA, 0x4C, 0x00], 0xC000);
+    let text: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    assert_eq!(text, vec![
+        "$C000  03        .byte $03",
+        "$C001  02        .byte $02",
+        "$C002  89        .byte $89",
+        "$C003  80        .byte $80",
+        "$C004  04        .byte $04",
+        "$C005  EA        NOP",
+        "$C006  4C        .byte $4C",
+        "$C007  00        BRK",
+    ]);
+    assert!(lines.iter().take(5).all(|l| l.decoded.is_none()));
+}
+
+#[test]
+fn from_bus()
+{
+    let mut bus = RAMBus::new();
+    for (i, byte) in [0x20, 0x00, 0x80, 0xF0, 0x80, 0x60].iter().enumerate()
+    {
+        bus.write(0xFFFA + i as u16, *byte);
+    }
+
+    // Wraps around the end of the address space
+    let lines = disasm::disassemble(&bus, 0xFFFA, 3);
+    assert_eq!(lines[0].text(), "JSR $8000");
+    assert_eq!(lines[1].text(), "BEQ $FF7F");
+    assert_eq!(lines[2].addr, 0xFFFF);
+    assert_eq!(lines[2].next(), 0x0000);
+    assert_eq!(lines[2].text(), "RTS");
+}
+
+#[test]
+fn range()
+{
+    let mut bus = RAMBus::new();
+    for (i, byte) in [0xA9, 0x01, 0x8D, 0x00, 0x03, 0x60].iter().enumerate()
+    {
+        bus.write(0x0200 + i as u16, *byte);
+    }
+
+    let text: Vec<String> = disasm::disassemble_range(&bus, 0x0200, 0x0205).iter().map(|l| l.to_string()).collect();
+    assert_eq!(text, vec![
+        "$0200  A9 01     LDA #$01",
+        "$0202  8D 00 03  STA $0300",
+        "$0205  60        RTS",
+    ]);
+
+    // The range ends in the middle of the STA, the rest of it isn't read
+    let text: Vec<String> = disasm::disassemble_range(&bus, 0x0200, 0x0203).iter().map(|l| l.to_string()).collect();
+    assert_eq!(text, vec![
+        "$0200  A9 01     LDA #$01",
+        "$0202  8D        .byte $8D",
+        "$0203  00        BRK",
+    ]);
+}
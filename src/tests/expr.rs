@@ -0,0 +1,92 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use crate::tests::test_bus::RAMBus;
+use crate::r6502::{R6502, Bus, Registers};
+use crate::r6502::expr::Expr;
+use crate::r6502::symbols::SymbolTable;
+
+fn setup() -> (R6502, RAMBus)
+{
+    let mut cpu = R6502::new();
+    let mut bus = RAMBus::new();
+    bus.write(0xFFFC, 0x00);
+    bus.write(0xFFFD, 0x02);
+    cpu.reset(&mut bus);
+
+    let mut state = cpu.state();
+    state.a = 0x10;
+    state.x = 3;
+    state.set_reg(Registers::STATUS, 0x21);     // U and C
+    cpu.set_state(&state);
+    bus.write(0x0200, 7);
+    bus.write(0x0010, 0x34);
+    bus.write(0x0011, 0x12);
+
+    (cpu, bus)
+}
+
+fn eval(text: &str) -> i64
+{
+    let (cpu, bus) = setup();
+    Expr::parse(text).unwrap_or_else(|e| panic!("{}: {}", text, e)).eval(&cpu, &bus)
+}
+
+#[test]
+fn values_and_operators()
+{
+    assert_eq!(eval("A == $10 && [$0200] > 5 && C"), 1);
+    assert_eq!(eval("a == 0x10 && !z"), 1);
+    assert_eq!(eval("[$10] + [$11] * 256"), 0x1234);
+    assert_eq!(eval("[$0200 - 2 * $F8]"), 0x34);
+    assert_eq!(eval("P & %00000001"), 1);
+    assert_eq!(eval("SP == $FF && PC == $0200 && CYC == 7"), 1);
+
+    // Precedence, bitwise operators bind tighter than comparisons
+    assert_eq!(eval("1 + 2 * 3"), 7);
+    assert_eq!(eval("(1 + 2) * 3"), 9);
+    assert_eq!(eval("X & 1 == 1"), 1);
+    assert_eq!(eval("1 << 4 | 1"), 17);
+    assert_eq!(eval("-X + ~0"), -4);
+    assert_eq!(eval("A / 0 || X % 2"), 1);
+}
+
+#[test]
+fn remainder_or_binary()
+{
+    // % after an operand is the remainder, even without a space
+    assert_eq!(eval("CYC%1000 == 0"), 0);
+    assert_eq!(eval("CYC%1000 == 7"), 1);
+    assert_eq!(eval("X % 2"), 1);
+    assert_eq!(eval("X %10"), 3);
+    assert_eq!(eval("(X + 1)%10"), 4);
+
+    // and a binary number where an operand goes
+    assert_eq!(eval("%11 + (%10) * [%10000]"), 3 + 2 * 0x34);
+}
+
+#[test]
+fn symbols()
+{
+    let mut symbols = SymbolTable::new();
+    symbols.insert("counter", 0x0200);
+    symbols.insert("c", 0x0300);
+
+    let (cpu, bus) = setup();
+    assert_eq!(Expr::parse_with("[counter] == 7", &symbols).unwrap().eval(&cpu, &bus), 1);
+
+    // Flags come first
+    assert_eq!(Expr::parse_with("C", &symbols).unwrap().eval(&cpu, &bus), 1);
+    assert!(Expr::parse("[counter] == 7").is_err());
+}
+
+#[test]
+fn errors()
+{
+    for text in ["", "A ==", "(A", "[$10", "A = 1", "$G0", "A B", "foo"]
+    {
+        assert!(Expr::parse(text).is_err(), "{} should not parse", text);
+    }
+
+    assert_eq!(Expr::parse("  X >= 3  ").unwrap().to_string(), "X >= 3");
+}
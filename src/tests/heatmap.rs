@@ -0,0 +1,132 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::tests::test_bus::boot;
+use crate::r6502::heatmap::{Heatmap, AccessCounts};
+use crate::r6502::png;
+use crate::r6502::symbols::SymbolTable;
+
+fn run() -> Rc<RefCell<Heatmap>>
+{
+    let program =
+    [
+        0xA5, 0x10,         // LDA $10
+        0x85, 0x11,         // STA $11
+        0xE6, 0x10,         // INC $10
+        0xAD, 0x00, 0x02,   // LDA $0200
+        0x60,               // RTS
+    ];
+
+    let (mut cpu, mut bus) = boot(0x0200, &program);
+
+    let heatmap = Rc::new(RefCell::new(Heatmap::new()));
+    cpu.add_observer(heatmap.clone());
+
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(&mut bus);
+    }
+
+    heatmap
+}
+
+#[test]
+fn counts_and_table()
+{
+    let heatmap = run();
+    let heatmap = heatmap.borrow();
+
+    // The addressing modes read the operand before the instruction uses it, so INC reads
+    // $10 twice and STA reads $11 before writing it
+    assert_eq!(heatmap.counts(0x10), AccessCounts { reads: 3, writes: 1, executes: 0 });
+    assert_eq!(heatmap.counts(0x11), AccessCounts { reads: 1, writes: 1, executes: 0 });
+    assert_eq!(heatmap.counts(0x0207), AccessCounts { reads: 0, writes: 0, executes: 1 });
+
+    // The code byte that was also read as data
+    let opcode = heatmap.counts(0x0200);
+    assert_eq!(opcode, AccessCounts { reads: 1, writes: 0, executes: 1 });
+    assert!(opcode.is_overlap());
+    assert_eq!(heatmap.page(0x02), AccessCounts { reads: 1, writes: 0, executes: 10 });
+
+    let mut symbols = SymbolTable::new();
+    symbols.insert("counter", 0x10);
+
+    let table = heatmap.table(&symbols, 2);
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines[1], "$00               4            2            0      2        0");
+    assert_eq!(lines[2], "$02               1            0           10     10        1");
+    assert_eq!(lines[5], "$0010  counter                    3            1            0");
+    assert_eq!(lines.len(), 7);
+}
+
+#[test]
+fn png_image()
+{
+    // Known values for the checksums
+    assert_eq!(png::crc32(b"IEND"), 0xAE42_6082);
+    assert_eq!(png::adler32(b"Wikipedia"), 0x11E6_0398);
+
+    let heatmap = run();
+    let heatmap = heatmap.borrow();
+
+    assert_eq!(heatmap.pixel(0x0207), [0, 0, 255]);
+    assert_eq!(heatmap.pixel(0x0011), [64, 255, 0]);
+    assert_eq!(heatmap.pixel(0x0010), [255, 255, 0]);
+    assert_eq!(heatmap.pixel(0x0300), [0, 0, 0]);
+
+    let image = heatmap.png();
+    assert_eq!(&image[..8], &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']);
+
+    // Walk the chunks, checking their CRCs and collecting the image data
+    let mut pos = 8;
+    let mut kinds = Vec::new();
+    let mut idat = Vec::new();
+    while pos < image.len()
+    {
+        let len = u32::from_be_bytes(image[pos..pos + 4].try_into().unwrap()) as usize;
+        let body = &image[pos + 4..pos + 8 + len];
+        let crc = u32::from_be_bytes(image[pos + 8 + len..pos + 12 + len].try_into().unwrap());
+        assert_eq!(png::crc32(body), crc);
+
+        let kind = std::str::from_utf8(&body[..4]).unwrap().to_string();
+        match kind.as_str()
+        {
+            "IHDR" => assert_eq!(&body[4..], &[0, 0, 1, 0, 0, 0, 1, 0, 8, 2, 0, 0, 0]),
+            "IDAT" => idat.extend_from_slice(&body[4..]),
+            _ => (),
+        }
+
+        kinds.push(kind);
+        pos += 12 + len;
+    }
+
+    assert_eq!(kinds, vec!["IHDR", "IDAT", "IEND"]);
+
+    // Unpack the stored deflate blocks
+    let mut raw = Vec::new();
+    let mut block = 2;
+    loop
+    {
+        let last = idat[block] & 1 == 1;
+        let len = u16::from_le_bytes([idat[block + 1], idat[block + 2]]) as usize;
+        let nlen = u16::from_le_bytes([idat[block + 3], idat[block + 4]]) as usize;
+        assert_eq!(len, !nlen & 0xFFFF);
+
+        raw.extend_from_slice(&idat[block + 5..block + 5 + len]);
+        block += 5 + len;
+        if last
+        {
+            break;
+        }
+    }
+
+    assert_eq!(&idat[block..], &png::adler32(&raw).to_be_bytes());
+    assert_eq!(raw.len(), 256 * (1 + 256 * 3));
+
+    // Row 2 is page 2, and each row starts with its filter byte
+    let pixel = 2 * (1 + 256 * 3) + 1 + 7 * 3;
+    assert_eq!(&raw[pixel..pixel + 3], &[0, 0, 255]);
+}
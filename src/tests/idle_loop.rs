@@ -0,0 +1,72 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use crate::tests::test_bus::{RAMBus, boot, load, registers};
+use crate::r6502::{R6502, Bus};
+use crate::r6502::idle::RunResult;
+
+// Wait for $10 to become 1
+const WAIT_LOOP: [u8; 8] = 
+[
+    0xA2, 0x03,         // LDX #3
+    0xA5, 0x10,         // wait: LDA $10
+    0xC9, 0x01,         // CMP #1
+    0xD0, 0xFA,         // BNE wait
+];
+
+fn wait_loop(idle_skip: bool) -> (R6502, RAMBus)
+{
+    let (mut cpu, bus) = boot(0x0200, &WAIT_LOOP);
+    cpu.set_idle_skip(idle_skip);
+
+    (cpu, bus)
+}
+
+#[test]
+fn skip_matches_running()
+{
+    let (mut cpu, mut bus) = wait_loop(false);
+    assert_eq!(RunResult::CycleLimit, cpu.run_until(&mut bus, 100_003));
+
+    let (mut skip_cpu, mut skip_bus) = wait_loop(true);
+    assert_eq!(RunResult::CycleLimit, skip_cpu.run_until(&mut skip_bus, 100_003));
+
+    assert_eq!(cpu.cycles(), skip_cpu.cycles());
+    assert_eq!(registers(&cpu), registers(&skip_cpu));
+
+    // The loop ends once something changes the flag
+    skip_bus.write(0x0010, 0x01);
+    let mut count = 0;
+    while skip_cpu.state().pc != 0x0208 && count < 6
+    {
+        skip_cpu.clock(&mut skip_bus);
+        count += 1;
+    }
+    assert_eq!(0x0208, skip_cpu.state().pc);
+}
+
+#[test]
+fn skip_far_ahead()
+{
+    let (mut cpu, mut bus) = wait_loop(true);
+
+    let target = 1_000_000_000_000;
+    assert_eq!(RunResult::CycleLimit, cpu.run_until(&mut bus, target));
+
+    // Lands within one pass of the loop (LDA, CMP and a taken BNE)
+    assert!(cpu.cycles() >= target && cpu.cycles() < target + 8);
+}
+
+#[test]
+fn infinite_loop()
+{
+    let (mut cpu, mut bus) = boot(0x0200, &[0x78, 0x4C, 0x01, 0x02]); // SEI, JMP *
+
+    assert_eq!(RunResult::InfiniteLoop(0x0201), cpu.run_until(&mut bus, u64::MAX));
+
+    // A loop that writes to memory isn't idle
+    load(&mut bus, 0x0200, &[0x78, 0xE6, 0x40, 0x4C, 0x01, 0x02]); // SEI, INC $40, JMP $0201
+    cpu.reset(&mut bus);
+
+    assert_eq!(RunResult::CycleLimit, cpu.run_until(&mut bus, 1000));
+}
@@ -1,8 +1,9 @@
 
-#![allow(dead_code, non_snake_case)]
+#![allow(dead_code, non_snake_case, deprecated)]
 
-use crate::tests::test_bus::RAMBus;
+use crate::tests::test_bus::{RAMBus, boot};
 use crate::r6502::{R6502, Bus, Registers, Flags};
+use crate::r6502::decoder;
 
 /////////////////////////////////////////////////////////////////////
 //				GROUP ONE
@@ -709,4 +710,154 @@ fn BNE()
 fn BEQ()
 {
 
-}
\ No newline at end of file
+}
+
+/////////////////////////////////////////////////////////////////////
+//				SINGLE BYTE
+/////////////////////////////////////////////////////////////////////
+
+// Run one single byte instruction with A, X and the stack pointer set up
+fn run_single(opcode: u8, a: u8, x: u8, sp: u16) -> R6502
+{
+    let (mut cpu, mut bus) = boot(0x0020, &[opcode]);
+
+    let mut state = cpu.state();
+    state.a = a;
+    state.x = x;
+    state.sp = sp;
+    cpu.set_state(&state);
+
+    cpu.clock(&mut bus);
+    assert_eq!(0x0021, cpu.state().pc, "${:02X} should be one byte long", opcode);
+    cpu
+}
+
+#[test]
+fn TXA()
+{
+    let cpu = run_single(0x8A, 0x00, 0x80, 0x01FF);
+    assert_eq!(0x80, cpu.state().a);
+    assert_eq!(1, cpu.check_flag(Flags::N));
+}
+
+#[test]
+fn TXS()
+{
+    let cpu = run_single(0x9A, 0x00, 0x40, 0x01FF);
+    assert_eq!(0x0140, cpu.state().sp);
+}
+
+#[test]
+fn TAX()
+{
+    let cpu = run_single(0xAA, 0x00, 0x33, 0x01FF);
+    assert_eq!(0x00, cpu.state().x);
+    assert_eq!(1, cpu.check_flag(Flags::Z));
+}
+
+#[test]
+fn TSX()
+{
+    let cpu = run_single(0xBA, 0x00, 0x00, 0x01E0);
+    assert_eq!(0xE0, cpu.state().x);
+}
+
+#[test]
+fn DEX()
+{
+    let cpu = run_single(0xCA, 0x00, 0x01, 0x01FF);
+    assert_eq!(0x00, cpu.state().x);
+    assert_eq!(1, cpu.check_flag(Flags::Z));
+}
+
+#[test]
+fn group_a_decoding()
+{
+    let names: Vec<&str> = [0x8A, 0x9A, 0xAA, 0xBA, 0xCA, 0xEA].iter().map(|op| decoder::decode(*op).unwrap().name).collect();
+    assert_eq!(names, vec!["TXA", "TXS", "TAX", "TSX", "DEX", "NOP"]);
+
+    // The neighbours with the same low nibble are not in the group
+    assert_ne!("TXA", decoder::decode(0x7A).map_or("", |d| d.name));
+    assert_ne!("DEX", decoder::decode(0xDA).map_or("", |d| d.name));
+}
+
+/////////////////////////////////////////////////////////////////////
+//				INTERRUPTS
+/////////////////////////////////////////////////////////////////////
+
+// Handlers at different addresses with the vectors stored low byte first
+fn interrupt_setup(program: &[u8]) -> (R6502, RAMBus)
+{
+    let (cpu, mut bus) = boot(0x0020, program);
+    bus.write(0xFFFA, 0x78);    // NMI: $5678
+    bus.write(0xFFFB, 0x56);
+    bus.write(0xFFFE, 0x34);    // IRQ and BRK: $1234
+    bus.write(0xFFFF, 0x12);
+
+    (cpu, bus)
+}
+
+#[test]
+fn BRK()
+{
+    let (mut cpu, mut bus) = interrupt_setup(&[0x00]);
+    cpu.clock(&mut bus);
+
+    assert_eq!(0x1234, cpu.state().pc);
+    assert_eq!(1, cpu.check_flag(Flags::B));
+    assert_eq!(0x01FC, cpu.state().sp);
+}
+
+#[test]
+fn irq_vector()
+{
+    let (mut cpu, mut bus) = interrupt_setup(&[0xEA]);
+    cpu.irq(&mut bus);
+
+    assert_eq!(0x1234, cpu.state().pc);
+    assert_eq!(1, cpu.check_flag(Flags::I));
+
+    // Masked now, so a second one is ignored
+    let mut state = cpu.state();
+    state.pc = 0x0020;
+    cpu.set_state(&state);
+    cpu.irq(&mut bus);
+    assert_eq!(0x0020, cpu.state().pc);
+}
+
+#[test]
+fn nmi_vector()
+{
+    let (mut cpu, mut bus) = interrupt_setup(&[0x78]);     // SEI
+    cpu.clock(&mut bus);
+    cpu.nmi(&mut bus);
+
+    assert_eq!(0x5678, cpu.state().pc);
+}
+
+#[test]
+fn irq_held_while_disabled()
+{
+    let (mut cpu, mut bus) = interrupt_setup(&[0x78, 0xEA, 0x58, 0xEA]);     // SEI, NOP, CLI, NOP
+    cpu.clock(&mut bus);
+    cpu.irq(&mut bus);
+    cpu.irq(&mut bus);
+
+    assert_eq!(0x0021, cpu.state().pc);
+    assert!(cpu.state().irq_pending);
+
+    cpu.clock(&mut bus);
+    cpu.clock(&mut bus);
+    assert_eq!(0x0023, cpu.state().pc);
+
+    // Taken once interrupts are enabled, returning to the NOP after the CLI
+    cpu.clock(&mut bus);
+    assert_eq!(0x1234, cpu.state().pc);
+    assert!(!cpu.state().irq_pending);
+    assert_eq!((0x00, 0x23), (bus.read(0x01FF), bus.read(0x01FE)));
+
+    // Only one was held
+    bus.write(0x1234, 0xEA);
+    cpu.clock(&mut bus);
+    assert_eq!(0x1235, cpu.state().pc);
+}
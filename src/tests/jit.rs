@@ -0,0 +1,267 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use std::cell::RefCell;
+
+use crate::tests::test_bus::{RAMBus, boot, load};
+use crate::r6502::{R6502, Bus};
+use crate::r6502::decoder;
+use crate::r6502::jit::Jit;
+
+fn new_jit() -> Jit
+{
+    let mut jit = Jit::new().expect("Failed to create the JIT");
+    jit.set_threshold(1);
+    jit.set_differential(true);
+    jit
+}
+
+#[test]
+fn compute_loop()
+{
+    // Sum 1 through 100 into a 16 bit value at $40
+    let program =
+    [
+        0xA9, 0x00,         // LDA #0
+        0x85, 0x40,         // STA $40
+        0x85, 0x41,         // STA $41
+        0xA2, 0x64,         // LDX #100
+        0x8A,               // loop: TXA
+        0x18,               // CLC
+        0x65, 0x40,         // ADC $40
+        0x85, 0x40,         // STA $40
+        0x90, 0x02,         // BCC skip
+        0xE6, 0x41,         // INC $41
+        0xCA,               // skip: DEX
+        0xD0, 0xF3,         // BNE loop
+        0x60,               // RTS
+    ];
+
+    let (mut cpu, mut bus) = boot(0x0200, &program);
+    let mut jit = new_jit();
+
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock_jit(&mut bus, &mut jit);
+    }
+
+    assert_eq!(5050, (bus.read(0x0041) as u16) << 8 | bus.read(0x0040) as u16);
+    assert!(jit.blocks_compiled() > 0);
+    assert!(jit.blocks_run() > 0);
+}
+
+#[test]
+fn self_modifying_code()
+{
+    let program =
+    [
+        0xA2, 0x03,         // LDX #3
+        0xEE, 0x06, 0x02,   // loop: INC $0206 - patch the LDA operand below
+        0xA9, 0x00,         // LDA #0
+        0xCA,               // DEX
+        0xD0, 0xF8,         // BNE loop
+        0x60,               // RTS
+    ];
+
+    let (mut cpu, mut bus) = boot(0x0200, &program);
+    let mut jit = new_jit();
+
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock_jit(&mut bus, &mut jit);
+    }
+
+    // Every pass has to see the value written by the pass before it
+    assert_eq!(0x03, cpu.state().a);
+}
+
+#[test]
+fn random_programs()
+{
+    // Straight line code, no branches or stack changes
+    let opcodes: Vec<u8> = (0..=255u8)
+        .filter(|op| match decoder::decode(*op)
+        {
+            Some(decoded) => decoded.documented && !decoder::is_flow_control(*op),
+            None => false,
+        })
+        .filter(|op| ![0x08, 0x28, 0x48, 0x68, 0x9A].contains(op))
+        .collect();
+
+    let mut seed: u32 = 0x6502;
+    let mut random = move ||
+    {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) as u8
+    };
+
+    let mut jit = new_jit();
+    for _ in 0..50
+    {
+        let mut bus = RAMBus::new();
+
+        // Keep pointers and absolute addresses away from the code at $8000
+        for addr in 0..0x0100
+        {
+            let value = random();
+            bus.write(addr, if addr & 1 == 1 { value & 0x07 } else { value });
+        }
+
+        let mut program = Vec::new();
+        for _ in 0..24
+        {
+            let op = opcodes[random() as usize % opcodes.len()];
+            let decoded = decoder::decode(op).unwrap();
+            program.push(op);
+
+            match decoded.size()
+            {
+                2 => program.push(random()),
+                3 =>
+                {
+                    program.push(random());
+                    program.push(random() & 0x07);
+                }
+                _ => (),
+            }
+        }
+
+        // JMP $8000
+        program.extend_from_slice(&[0x4C, 0x00, 0x80]);
+
+        let mut cpu = R6502::new();
+        load(&mut bus, 0x8000, &program);
+        jit.invalidate_range(0x8000, 0x80FF);
+        cpu.reset(&mut bus);
+
+        // Differential mode panics at the first difference
+        for _ in 0..20
+        {
+            cpu.clock_jit(&mut bus, &mut jit);
+        }
+
+        assert!(jit.blocks_run() > 0);
+    }
+}
+
+// RAM with a device at $D000-$D0FF that isn't exposed through page(). Its reads
+// return how many accesses came before, so an extra, missing or reordered access
+// changes the values the program sees as well as the log.
+struct MmioBus
+{
+    ram: RAMBus,
+    log: RefCell<Vec<(char, u16, u8)>>,
+}
+
+impl MmioBus
+{
+    fn is_device(addr: u16) -> bool
+    {
+        addr >> 8 == 0xD0
+    }
+}
+
+impl Bus for MmioBus
+{
+    fn read(&self, addr: u16) -> u8
+    {
+        if !MmioBus::is_device(addr)
+        {
+            return self.ram.read(addr);
+        }
+
+        let mut log = self.log.borrow_mut();
+        let value = (log.len() as u8).wrapping_mul(7) ^ addr as u8;
+        log.push(('r', addr, value));
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8)
+    {
+        match MmioBus::is_device(addr)
+        {
+            true => self.log.borrow_mut().push(('w', addr, value)),
+            false => self.ram.write(addr, value),
+        }
+    }
+
+    fn page(&self, page: u8) -> Option<&[u8; 256]>
+    {
+        match page
+        {
+            0xD0 => None,
+            _ => self.ram.page(page),
+        }
+    }
+
+    fn page_mut(&mut self, page: u8) -> Option<&mut [u8; 256]>
+    {
+        match page
+        {
+            0xD0 => None,
+            _ => self.ram.page_mut(page),
+        }
+    }
+}
+
+#[test]
+fn memory_mapped_io()
+{
+    // Indexed and indirect accesses into the device, which the JIT translates
+    // (only zero page and absolute operands in MMIO are left to the interpreter)
+    let program =
+    [
+        0xA2, 0x00,         // LDX #0
+        0xA0, 0x00,         // LDY #0
+        0xBD, 0x00, 0xD0,   // loop: LDA $D000,X
+        0x99, 0x80, 0xD0,   // STA $D080,Y
+        0xB1, 0x10,         // LDA ($10),Y
+        0x81, 0x20,         // STA ($20,X)
+        0xFE, 0x40, 0xD0,   // INC $D040,X
+        0x5E, 0x50, 0xD0,   // LSR $D050,X
+        0x79, 0xF8, 0xCF,   // ADC $CFF8,Y - crosses into the device from Y = 8
+        0x91, 0x10,         // STA ($10),Y
+        0xE8,               // INX
+        0xC8,               // INY
+        0xE0, 0x10,         // CPX #16
+        0xD0, 0xE5,         // BNE loop
+        0x60,               // RTS
+    ];
+
+    let machine = ||
+    {
+        let mut bus = MmioBus { ram: RAMBus::new(), log: RefCell::new(Vec::new()) };
+
+        // ($10) points at $D010, ($20,X) at $D0D0 for every X
+        bus.write(0x0010, 0x10);
+        bus.write(0x0011, 0xD0);
+        for addr in 0x0020..0x0040
+        {
+            bus.write(addr, 0xD0);
+        }
+
+        load(&mut bus, 0x0200, &program);
+        let mut cpu = R6502::new();
+        cpu.reset(&mut bus);
+        (cpu, bus)
+    };
+
+    let (mut cpu, mut bus) = machine();
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(&mut bus);
+    }
+
+    let (mut jit_cpu, mut jit_bus) = machine();
+    let mut jit = Jit::new().expect("Failed to create the JIT");
+    jit.set_threshold(1);
+    while !jit_cpu.is_program_stopped()
+    {
+        jit_cpu.clock_jit(&mut jit_bus, &mut jit);
+    }
+
+    assert!(jit.blocks_run() > 0);
+    assert!(bus.log.borrow().len() > 16 * 8);
+    assert_eq!(*jit_bus.log.borrow(), *bus.log.borrow());
+    assert_eq!(jit_cpu.state(), cpu.state());
+}
@@ -0,0 +1,38 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use crate::r6502::json::Json;
+
+#[test]
+fn parse_and_write()
+{
+    let text = r#" { "seq": 3, "arguments": { "path": "C:\\asm\\echo.asm", "lines": [1, -2.5, true, null] }, "text": "\u00e9\ud83d\ude00\n" } "#;
+    let json = Json::parse(text).unwrap();
+
+    assert_eq!(json.get("seq").as_i64(), Some(3));
+    assert_eq!(json.get("arguments").get("path").as_str(), Some("C:\\asm\\echo.asm"));
+    assert_eq!(json.get("arguments").get("lines").as_array(), &[Json::Number(1.0), Json::Number(-2.5), Json::Bool(true), Json::Null]);
+    assert_eq!(json.get("text").as_str(), Some("é😀\n"));
+
+    // Missing keys read as null
+    assert!(json.get("nope").get("deeper").is_null());
+    assert_eq!(json.get("arguments").get("lines").as_i64(), None);
+
+    // Written back on one line, keys in order
+    assert_eq!(json.to_string(), r#"{"seq":3,"arguments":{"path":"C:\\asm\\echo.asm","lines":[1,-2.5,true,null]},"text":"é😀\n"}"#);
+}
+
+#[test]
+fn building_and_errors()
+{
+    let mut json = Json::object(vec![("a", 1i64.into()), ("b", "x\"y".into())]);
+    json.set("a", vec![Json::from(true)].into());
+    json.set("c", Json::object(Vec::new()));
+    assert_eq!(json.to_string(), r#"{"a":[true],"b":"x\"y","c":{}}"#);
+
+    assert!(Json::parse("{\"a\" 1}").is_err());
+    assert!(Json::parse("[1, 2").is_err());
+    assert!(Json::parse("\"open").is_err());
+    assert!(Json::parse("1 2").is_err());
+    assert!(Json::parse("tru").is_err());
+}
@@ -7,3 +7,87 @@ mod test_bus;
 
 #[cfg(test)]
 mod instructions;
+
+#[cfg(test)]
+mod page_access;
+
+#[cfg(test)]
+mod block_cache;
+
+#[cfg(all(test, feature = "jit"))]
+mod jit;
+
+#[cfg(test)]
+mod cycles;
+
+#[cfg(test)]
+mod wrapping;
+
+#[cfg(test)]
+mod idle_loop;
+
+#[cfg(test)]
+mod scheduler;
+
+#[cfg(test)]
+mod throttle;
+
+#[cfg(test)]
+mod observer;
+
+#[cfg(test)]
+mod cpu_state;
+
+#[cfg(test)]
+mod snapshot;
+
+#[cfg(test)]
+mod rewind;
+
+#[cfg(test)]
+mod write_history;
+
+#[cfg(test)]
+mod replay;
+
+#[cfg(test)]
+mod disasm;
+
+#[cfg(test)]
+mod symbols;
+
+#[cfg(test)]
+mod trace;
+
+#[cfg(test)]
+mod trace_diff;
+
+#[cfg(test)]
+mod debugger;
+
+#[cfg(test)]
+mod expr;
+
+#[cfg(test)]
+mod call_stack;
+
+#[cfg(test)]
+mod profiler;
+
+#[cfg(test)]
+mod source_map;
+
+#[cfg(test)]
+mod coverage;
+
+#[cfg(test)]
+mod heatmap;
+
+#[cfg(test)]
+mod vice_monitor;
+
+#[cfg(test)]
+mod json;
+
+#[cfg(test)]
+mod dap;
@@ -0,0 +1,147 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::tests::test_bus::{RAMBus, boot};
+use crate::r6502::{R6502, Bus};
+use crate::r6502::block_cache::BlockCache;
+use crate::r6502::idle::RunResult;
+use crate::r6502::observer::{Observer, Instruction, Access, Control};
+
+const PROGRAM: [u8; 8] = 
+[
+    0xA2, 0x02,         // LDX #2
+    0xA5, 0x10,         // LDA $10
+    0x9D, 0x00, 0x03,   // STA $0300,X
+    0x60,               // RTS
+];
+
+#[derive(Default)]
+struct Recorder
+{
+    before: Vec<(u16, &'static str, Option<u16>)>,
+    after: Vec<u16>,
+    accesses: Vec<(u16, u8, Access)>,
+    inspected: Vec<(u16, u8)>,
+    stop_at: Option<u16>,
+}
+
+impl Observer for Recorder
+{
+    // What is at the operand address before the instruction runs
+    fn inspect(&mut self, _cpu: &R6502, bus: &dyn Bus, instr: &Instruction)
+    {
+        if let Some(addr) = instr.addr
+        {
+            self.inspected.push((addr, bus.read(addr)));
+        }
+    }
+
+    fn before(&mut self, _cpu: &R6502, instr: &Instruction) -> Control
+    {
+        if self.stop_at == Some(instr.pc)
+        {
+            return Control::Stop;
+        }
+
+        self.before.push((instr.pc, instr.decoded.name, instr.addr));
+        Control::Continue
+    }
+
+    fn after(&mut self, cpu: &R6502, _instr: &Instruction) -> Control
+    {
+        self.after.push(cpu.state().pc);
+        Control::Continue
+    }
+
+    fn access(&mut self, addr: u16, value: u8, access: Access)
+    {
+        self.accesses.push((addr, value, access));
+    }
+}
+
+fn setup() -> (R6502, RAMBus)
+{
+    let (cpu, mut bus) = boot(0x0200, &PROGRAM);
+    bus.write(0x0010, 0x42);
+
+    (cpu, bus)
+}
+
+#[test]
+fn instructions_and_accesses()
+{
+    let (mut cpu, mut bus) = setup();
+    let recorder = Rc::new(RefCell::new(Recorder::default()));
+    cpu.add_observer(recorder.clone());
+
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(&mut bus);
+    }
+
+    let recorder = recorder.borrow();
+    assert_eq!(recorder.before, vec![
+        (0x0200, "LDX", None),
+        (0x0202, "LDA", Some(0x0010)),
+        (0x0204, "STA", Some(0x0302)),
+        (0x0207, "RTS", None),
+    ]);
+    assert_eq!(recorder.after, vec![0x0202, 0x0204, 0x0207, 0x0208]);
+    assert_eq!(recorder.inspected, vec![(0x0010, 0x42), (0x0302, 0x00)]);
+
+    // The reads made by inspect() aren't among them
+    use Access::*;
+    assert_eq!(recorder.accesses, vec![
+        (0x0200, 0xA2, Fetch), (0x0201, 0x02, Fetch),
+        (0x0202, 0xA5, Fetch), (0x0203, 0x10, Fetch), (0x0010, 0x42, Read),
+        (0x0204, 0x9D, Fetch), (0x0205, 0x00, Fetch), (0x0206, 0x03, Fetch), (0x0302, 0x00, Read), (0x0302, 0x42, Write),
+        (0x0207, 0x60, Fetch),
+    ]);
+}
+
+#[test]
+fn stop_before_instruction()
+{
+    let (mut cpu, mut bus) = setup();
+    let recorder = Rc::new(RefCell::new(Recorder { stop_at: Some(0x0204), ..Recorder::default() }));
+    let id = cpu.add_observer(recorder.clone());
+
+    assert_eq!(cpu.run_until(&mut bus, 1000), RunResult::Break);
+    assert!(cpu.stop_requested());
+    assert_eq!(cpu.state().pc, 0x0204);
+    assert_eq!(bus.read(0x0302), 0x00);
+
+    // Carry on once the observer is gone
+    assert!(cpu.remove_observer(id));
+    assert_eq!(cpu.run_until(&mut bus, 1000), RunResult::ProgramStopped);
+    assert!(!cpu.stop_requested());
+    assert_eq!(bus.read(0x0302), 0x42);
+    assert_eq!(recorder.borrow().before.len(), 2);
+}
+
+#[test]
+fn block_cache_falls_back()
+{
+    let (mut plain, mut plain_bus) = setup();
+    while !plain.is_program_stopped()
+    {
+        plain.clock(&mut plain_bus);
+    }
+
+    let (mut cpu, mut bus) = setup();
+    let recorder = Rc::new(RefCell::new(Recorder::default()));
+    cpu.add_observer(recorder.clone());
+
+    let mut cache = BlockCache::new();
+    while !cpu.is_program_stopped()
+    {
+        assert_eq!(cpu.clock_block(&mut bus, &mut cache), 1);
+    }
+
+    assert!(cpu == plain);
+    assert_eq!(cpu.cycles(), plain.cycles());
+    assert_eq!(recorder.borrow().before.len(), 4);
+}
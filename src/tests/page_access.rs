@@ -0,0 +1,183 @@
+#![allow(dead_code, non_snake_case)]
+
+use std::cell::Cell;
+
+use crate::tests::test_bus::load;
+use crate::r6502::{R6502, Bus};
+
+// Bus that exposes every page directly except for page 0x10, which
+// behaves like a memory mapped device and counts its accesses.
+// Calls to read() and write() for the other pages are counted too.
+struct MMIOBus
+{
+    ram: [u8; 64 * 1024],
+    io_reads: Cell<u32>,
+    io_writes: u32,
+    ram_reads: Cell<u32>,
+    ram_writes: u32,
+}
+
+impl MMIOBus
+{
+    fn new() -> MMIOBus
+    {
+        MMIOBus { ram: [0; 64 * 1024], io_reads: Cell::new(0), io_writes: 0, ram_reads: Cell::new(0), ram_writes: 0 }
+    }
+}
+
+impl Bus for MMIOBus
+{
+    fn read(&self, addr: u16) -> u8 
+    {
+        if addr >> 8 == 0x10
+        {
+            self.io_reads.set(self.io_reads.get() + 1);
+        }
+        else
+        {
+            self.ram_reads.set(self.ram_reads.get() + 1);
+        }
+
+        self.ram[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) 
+    {
+        if addr >> 8 == 0x10
+        {
+            self.io_writes += 1;
+        }
+        else
+        {
+            self.ram_writes += 1;
+        }
+
+        self.ram[addr as usize] = value;
+    }
+
+    fn page(&self, page: u8) -> Option<&[u8; 256]>
+    {
+        if page == 0x10
+        {
+            return None;
+        }
+
+        let start = (page as usize) << 8;
+        self.ram[start..start + 256].try_into().ok()
+    }
+
+    fn page_mut(&mut self, page: u8) -> Option<&mut [u8; 256]>
+    {
+        if page == 0x10
+        {
+            return None;
+        }
+
+        let start = (page as usize) << 8;
+        (&mut self.ram[start..start + 256]).try_into().ok()
+    }
+}
+
+#[test]
+fn page_crossing()
+{
+    let mut cpu = R6502::new();
+    let mut bus = MMIOBus::new();
+
+    // program address - the LDA operand and the STA are on the next page
+    let addr: u16 = 0x02FF;
+
+    // Set the program counter address
+    bus.write(0xFFFC, (addr & 0x00FF) as u8);  // low byte
+    bus.write(0xFFFD, ((addr & 0xFF00) >> 8) as u8);  // high byte
+
+    // LDA #$42
+    bus.write(addr, 0xA9);
+    bus.write(addr + 1, 0x42);
+
+    // STA $1000
+    bus.write(addr + 2, 0x8D);
+    bus.write(addr + 3, 0x00);
+    bus.write(addr + 4, 0x10);
+
+    cpu.reset(&mut bus);
+    cpu.clock(&mut bus);
+    cpu.clock(&mut bus);
+
+    assert_eq!(0x42, cpu.state().a);
+    assert_eq!(0x0304, cpu.state().pc);
+    assert_eq!(0x42, bus.read(0x1000));
+    assert_eq!(1, bus.io_writes);
+}
+
+#[test]
+fn code_in_mmio_page()
+{
+    let mut cpu = R6502::new();
+    let mut bus = MMIOBus::new();
+
+    // program address - inside the page that isn't exposed to the cpu
+    let addr: u16 = 0x1080;
+
+    // Set the program counter address
+    bus.write(0xFFFC, (addr & 0x00FF) as u8);  // low byte
+    bus.write(0xFFFD, ((addr & 0xFF00) >> 8) as u8);  // high byte
+
+    // LDX #$07
+    bus.write(addr, 0xA2);
+    bus.write(addr + 1, 0x07);
+
+    cpu.reset(&mut bus);
+    cpu.clock(&mut bus);
+
+    // Both the opcode and the operand had to go through Bus::read()
+    assert_eq!(0x07, cpu.state().x);
+    assert_eq!(2, bus.io_reads.get());
+}
+
+#[test]
+fn data_and_stack_access()
+{
+    let program =
+    [
+        0xAD, 0x00, 0x03,   // LDA $0300
+        0x8D, 0x01, 0x03,   // STA $0301
+        0xE6, 0x40,         // INC $40
+        0xA0, 0x01,         // LDY #1
+        0xB1, 0x41,         // LDA ($41),Y
+        0x48,               // PHA
+        0x68,               // PLA
+        0x20, 0x18, 0x02,   // JSR sub
+        0xAD, 0x00, 0x10,   // LDA $1000
+        0x8D, 0x01, 0x10,   // STA $1001
+        0x60,               // RTS
+        0x60,               // sub: RTS
+    ];
+
+    let mut cpu = R6502::new();
+    let mut bus = MMIOBus::new();
+    load(&mut bus, 0x0200, &program);
+    bus.write(0x0300, 0x42);
+    bus.write(0x0041, 0xFF);    // ($41),Y is $1000
+    bus.write(0x0042, 0x0F);
+
+    cpu.reset(&mut bus);
+    bus.ram_reads.set(0);
+    bus.ram_writes = 0;
+
+    for _ in 0..11
+    {
+        cpu.clock(&mut bus);
+    }
+
+    // Memory, the zero page and the stack are all accessed through the pages
+    assert_eq!((0, 0), (bus.ram_reads.get(), bus.ram_writes));
+    assert_eq!(0x42, bus.ram[0x0301]);
+    assert_eq!(0x01, bus.ram[0x0040]);
+    assert_eq!(0x0217, cpu.state().pc);
+
+    // The device still sees each of its accesses, the addressing
+    // mode reads the STA target before it is written too
+    assert_eq!(3, bus.io_reads.get());
+    assert_eq!(1, bus.io_writes);
+}
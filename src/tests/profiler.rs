@@ -0,0 +1,124 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::tests::test_bus::boot;
+use crate::r6502::profiler::Profiler;
+use crate::r6502::symbols::SymbolTable;
+
+fn profile(program: &[u8]) -> Rc<RefCell<Profiler>>
+{
+    let (mut cpu, mut bus) = boot(0x0200, program);
+
+    let profiler = Rc::new(RefCell::new(Profiler::new()));
+    cpu.add_observer(profiler.clone());
+
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(&mut bus);
+    }
+
+    profiler
+}
+
+const LOOP: [u8; 11] =
+[
+    0xA2, 0x03,         // main: LDX #3
+    0x20, 0x09, 0x02,   // loop: JSR sub
+    0xCA,               // DEX
+    0xD0, 0xFA,         // BNE loop
+    0x60,               // RTS
+    0xC8,               // sub: INY
+    0x60,               // RTS
+];
+
+fn symbols() -> SymbolTable
+{
+    let mut symbols = SymbolTable::new();
+    symbols.insert("main", 0x0200);
+    symbols.insert("loop", 0x0202);
+    symbols.insert("sub", 0x0209);
+    symbols
+}
+
+#[test]
+fn routines_and_addresses()
+{
+    let profiler = profile(&LOOP);
+    let profiler = profiler.borrow();
+
+    let total = profiler.total();
+    assert_eq!(total.instructions, 1 + 3 * 5 + 1);
+
+    // INY and RTS, 3 times
+    let sub = profiler.routine(0x0209).unwrap();
+    assert_eq!((sub.calls, sub.instructions, sub.self_cycles, sub.total_cycles), (3, 6, 3 * (2 + 6), 3 * (2 + 6)));
+
+    let main = profiler.routine(0x0200).unwrap();
+    assert_eq!((main.calls, main.total_cycles), (1, total.cycles));
+    assert_eq!(main.self_cycles + sub.self_cycles, total.cycles);
+
+    assert_eq!(profiler.address(0x0209).instructions, 3);
+    assert_eq!(profiler.address(0x0202).cycles, 3 * 6);
+    assert_eq!(profiler.opcode(0x20).instructions, 3);
+
+    // The JSRs are the hottest spot, then the RTSs in sub
+    let spots: Vec<u16> = profiler.hot_spots(2).iter().map(|(addr, _)| *addr).collect();
+    assert_eq!(spots, vec![0x0202, 0x020A]);
+
+    let report = profiler.report(&symbols(), 10);
+    assert!(report.starts_with(&format!("Total: {} cycles, 17 instructions\n", total.cycles)));
+    assert!(report.contains("        24  "), "{}", report);
+    assert!(report.contains("$0202  loop         JSR"), "{}", report);
+}
+
+#[test]
+fn folded_stacks_and_recursion()
+{
+    let program =
+    [
+        0xA2, 0x03,         // main: LDX #3
+        0x20, 0x06, 0x02,   // JSR rec
+        0x60,               // RTS
+        0xCA,               // rec: DEX
+        0xF0, 0x03,         // BEQ done
+        0x20, 0x06, 0x02,   // JSR rec
+        0x60,               // done: RTS
+    ];
+
+    let profiler = profile(&program);
+    let profiler = profiler.borrow();
+
+    // Recursive calls don't count the same cycles twice
+    let rec = profiler.routine(0x0206).unwrap();
+    assert_eq!(rec.calls, 3);
+    assert_eq!(rec.total_cycles, rec.self_cycles);
+
+    let mut symbols = SymbolTable::new();
+    symbols.insert("main", 0x0200);
+    symbols.insert("rec", 0x0206);
+
+    let folded = profiler.folded_stacks(&symbols);
+    let paths: Vec<&str> = folded.lines().map(|line| line.rsplit_once(' ').unwrap().0).collect();
+    assert_eq!(paths, vec!["main", "main;rec", "main;rec;rec", "main;rec;rec;rec"]);
+
+    let sum: u64 = folded.lines().map(|line| line.rsplit_once(' ').unwrap().1.parse::<u64>().unwrap()).sum();
+    assert_eq!(sum, profiler.total().cycles);
+}
+
+#[test]
+fn opcode_histogram()
+{
+    let profiler = profile(&LOOP);
+    let profiler = profiler.borrow();
+
+    let names: Vec<(&str, u64)> = profiler.mnemonics().iter().map(|(name, c)| (*name, c.instructions)).collect();
+    assert_eq!(names, vec![("RTS", 4), ("BNE", 3), ("DEX", 3), ("INY", 3), ("JSR", 3), ("LDX", 1)]);
+
+    let histogram = profiler.opcode_histogram();
+    let first = histogram.lines().next().unwrap();
+    assert_eq!(first, format!("RTS           4  {:>4.1}%  {}", 4.0 * 100.0 / 17.0, "#".repeat(40)));
+    assert!(histogram.lines().last().unwrap().ends_with("  ##########"));
+}
@@ -0,0 +1,143 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use crate::tests::test_bus::{RAMBus, load};
+use crate::r6502::{R6502, Bus};
+use crate::r6502::snapshot::{self, SnapshotError};
+use crate::r6502::replay::{Recording, Replay, InputEvent};
+
+#[test]
+fn file_round_trip()
+{
+    let mut recording = Recording::new(vec![1, 2, 3]);
+    recording.record(10, InputEvent::Irq);
+    recording.record(500, InputEvent::Input(b"hello\n".to_vec()));
+    recording.record(501, InputEvent::Irq);
+    recording.record(9000, InputEvent::Nmi);
+
+    let bytes = recording.to_bytes();
+    assert_eq!(Recording::from_bytes(&bytes), Ok(recording));
+
+    assert_eq!(Recording::from_bytes(b"RE65"), Err(SnapshotError::NotASnapshot));
+    assert_eq!(Recording::from_bytes(&bytes[..bytes.len() - 1]), Err(SnapshotError::Truncated));
+}
+
+#[test]
+fn replay_order()
+{
+    let mut recording = Recording::new(Vec::new());
+    recording.record(100, InputEvent::Irq);
+    recording.record(200, InputEvent::Input(vec![42]));
+
+    let mut replay = Replay::new(&recording);
+    assert_eq!(replay.due(99), None);
+    assert_eq!(replay.due(105), Some(InputEvent::Irq));
+    assert_eq!(replay.due(300), None);
+
+    // Asking for input at the wrong time means the run has gone its own way
+    assert!(replay.take(199).is_err());
+    assert_eq!(replay.take(200), Ok(InputEvent::Input(vec![42])));
+    assert!(replay.is_finished());
+    assert!(replay.take(300).is_err());
+}
+
+// A tiny machine: an IRQ handler that counts, and a port at $4000 that
+// the outside world writes bytes into, which the program adds up
+struct Machine
+{
+    cpu: R6502,
+    bus: RAMBus,
+}
+
+const PROGRAM: [u8; 13] = 
+[
+    0x58,               // CLI
+    0xAD, 0x00, 0x40,   // loop: LDA $4000
+    0x18,               // CLC
+    0x65, 0x20,         // ADC $20
+    0x85, 0x20,         // STA $20
+    0x4C, 0x01, 0x02,   // JMP loop
+    0x00,
+];
+
+const HANDLER: [u8; 3] = 
+[
+    0xE6, 0x21,         // INC $21
+    0x40,               // RTI
+];
+
+impl Machine
+{
+    fn new() -> Machine
+    {
+        let mut bus = RAMBus::new();
+        load(&mut bus, 0x0200, &PROGRAM);
+        for (i, byte) in HANDLER.iter().enumerate()
+        {
+            bus.write(0x0300 + i as u16, *byte);
+        }
+
+        bus.write(0xFFFE, 0x00);
+        bus.write(0xFFFF, 0x03);
+
+        let mut cpu = R6502::new();
+        cpu.reset(&mut bus);
+        Machine { cpu, bus }
+    }
+
+    fn apply(&mut self, event: &InputEvent)
+    {
+        match event
+        {
+            InputEvent::Input(data) => self.bus.write(0x4000, data[0]),
+            InputEvent::Irq => self.cpu.irq(&mut self.bus),
+            _ => (),
+        }
+    }
+}
+
+#[test]
+fn replay_reproduces_run()
+{
+    let mut machine = Machine::new();
+    let mut recording = Recording::new(snapshot::save_state(&machine.cpu, &machine.bus));
+
+    // Inputs arrive whenever the outside world feels like it
+    let mut seed: u32 = 12345;
+    for _ in 0..2000
+    {
+        machine.cpu.clock(&mut machine.bus);
+
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let event = match seed >> 28
+        {
+            0 => InputEvent::Input(vec![(seed >> 8) as u8]),
+            1 => InputEvent::Irq,
+            _ => continue,
+        };
+
+        recording.record(machine.cpu.cycles(), event.clone());
+        machine.apply(&event);
+    }
+
+    assert!(machine.bus.read(0x21) > 0);
+
+    let recording = Recording::from_bytes(&recording.to_bytes()).unwrap();
+    let mut replayed = Machine::new();
+    snapshot::load_state(&mut replayed.cpu, &mut replayed.bus, &recording.start).unwrap();
+
+    let mut replay = Replay::new(&recording);
+    for _ in 0..2000
+    {
+        replayed.cpu.clock(&mut replayed.bus);
+
+        while replay.next_cycle() == Some(replayed.cpu.cycles())
+        {
+            let event = replay.take(replayed.cpu.cycles()).unwrap();
+            replayed.apply(&event);
+        }
+    }
+
+    assert!(replay.is_finished());
+    assert_eq!(snapshot::save_state(&replayed.cpu, &replayed.bus), snapshot::save_state(&machine.cpu, &machine.bus));
+}
@@ -0,0 +1,124 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use crate::tests::test_bus::{RAMBus, boot};
+use crate::r6502::Bus;
+use crate::r6502::rewind::Rewind;
+use crate::r6502::state::CpuState;
+
+// Fill $0300-$03FF with a pattern that depends on everything before it,
+// calling a subroutine so the stack gets written too
+const PROGRAM: [u8; 18] = 
+[
+    0xA2, 0x00,         // LDX #0
+    0xA9, 0x01,         // LDA #1
+    0x20, 0x0E, 0x02,   // loop: JSR add
+    0x9D, 0x00, 0x03,   // STA $0300,X
+    0xE8,               // INX
+    0xD0, 0xF7,         // BNE loop
+    0x60,               // RTS
+    0x7D, 0xFF, 0x02,   // add: ADC $02FF,X
+    0x60,               // RTS
+];
+
+fn memory(bus: &RAMBus) -> Vec<u8>
+{
+    (0x0100..0x0400).map(|addr| bus.read(addr)).collect()
+}
+
+#[test]
+fn step_back()
+{
+    let (mut cpu, mut bus) = boot(0x0200, &PROGRAM);
+    let mut rewind = Rewind::new();
+
+    let mut history: Vec<(CpuState, Vec<u8>)> = Vec::new();
+    for _ in 0..200
+    {
+        history.push((cpu.state(), memory(&bus)));
+        rewind.step(&mut cpu, &mut bus);
+    }
+
+    assert_eq!(rewind.steps(), 200);
+
+    while let Some((state, mem)) = history.pop()
+    {
+        assert!(rewind.step_back(&mut cpu, &mut bus));
+        assert_eq!(cpu.state(), state);
+        assert!(memory(&bus) == mem, "memory differs at cycle {}", state.cycles);
+    }
+
+    assert!(!rewind.step_back(&mut cpu, &mut bus));
+}
+
+#[test]
+fn rewind_past_log()
+{
+    // Reference run to compare against
+    let (mut reference, mut reference_bus) = boot(0x0200, &PROGRAM);
+    let mut states = Vec::new();
+    for _ in 0..1000
+    {
+        states.push((reference.state(), memory(&reference_bus)));
+        reference.clock(&mut reference_bus);
+    }
+
+    let (mut cpu, mut bus) = boot(0x0200, &PROGRAM);
+    let mut rewind = Rewind::new();
+    rewind.set_snapshot_interval(500);
+    rewind.set_max_steps(50);
+    for _ in 0..1000
+    {
+        rewind.step(&mut cpu, &mut bus);
+    }
+
+    // Within the log the exact instruction is reached
+    let target = states[980].0.cycles + 1;
+    assert_eq!(rewind.rewind_to(&mut cpu, &mut bus, target), Some(states[980].0.cycles));
+    assert_eq!(cpu.state(), states[980].0);
+
+    // Further back it runs forward from the snapshot before it, which is
+    // somewhere between 1200 - 500 and 1200
+    let index = states.iter().rposition(|(s, _)| s.cycles <= 1200).unwrap();
+    assert_eq!(rewind.rewind_to(&mut cpu, &mut bus, 1200), Some(states[index].0.cycles));
+    assert_eq!(cpu.state(), states[index].0);
+    assert!(memory(&bus) == states[index].1);
+
+    // Logging the instructions on the way so they can be stepped back through
+    assert!(rewind.steps() > 0);
+    assert!(rewind.step_back(&mut cpu, &mut bus));
+    assert_eq!(cpu.state(), states[index - 1].0);
+    assert!(memory(&bus) == states[index - 1].1);
+    rewind.step(&mut cpu, &mut bus);
+
+    // And it carries on from there like it never left
+    for (state, mem) in states[index..].iter().take(100)
+    {
+        assert_eq!(cpu.state(), *state);
+        assert!(memory(&bus) == *mem);
+        rewind.step(&mut cpu, &mut bus);
+    }
+
+    assert_eq!(rewind.rewind_to(&mut cpu, &mut bus, 0), None);
+}
+
+#[test]
+fn reverse_continue()
+{
+    let (mut cpu, mut bus) = boot(0x0200, &PROGRAM);
+    let mut rewind = Rewind::new();
+    for _ in 0..100
+    {
+        rewind.step(&mut cpu, &mut bus);
+    }
+
+    // Back to the last time the subroutine was called
+    let x = cpu.state().x;
+    assert!(rewind.reverse_continue(&mut cpu, &mut bus, &[0x020E]));
+    assert_eq!(cpu.state().pc, 0x020E);
+    assert!(cpu.state().x == x || cpu.state().x == x.wrapping_sub(1));
+
+    // And to the very start
+    assert!(!rewind.reverse_continue(&mut cpu, &mut bus, &[0x1234]));
+    assert_eq!(cpu.state().pc, 0x0200);
+}
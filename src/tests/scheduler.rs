@@ -0,0 +1,106 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::tests::test_bus::{RAMBus, boot, load};
+use crate::r6502::{R6502, Bus};
+use crate::r6502::idle::RunResult;
+use crate::r6502::scheduler::Scheduler;
+
+#[test]
+fn event_order()
+{
+    let (mut cpu, mut bus) = boot(0x0200, &[0x4C, 0x00, 0x02]); // JMP *
+
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut scheduler = Scheduler::new();
+
+    for (name, cycle) in [("a", 50), ("b", 20), ("c", 50), ("d", 30)]
+    {
+        let log = log.clone();
+        scheduler.schedule(cycle, move |due, cpu: &mut R6502, _bus: &mut RAMBus, _| 
+        {
+            assert!(cpu.cycles() >= due);
+            log.borrow_mut().push(name);
+            None
+        });
+    }
+
+    let cancelled = scheduler.schedule(40, |_, _: &mut R6502, _: &mut RAMBus, _| panic!("Cancelled event fired"));
+    assert!(scheduler.cancel(cancelled));
+    assert!(!scheduler.cancel(cancelled));
+
+    assert_eq!(RunResult::CycleLimit, cpu.run_scheduled(&mut bus, &mut scheduler, 100));
+    assert_eq!(vec!["b", "d", "a", "c"], *log.borrow());
+    assert!(scheduler.is_empty());
+}
+
+#[test]
+fn timer_interrupt()
+{
+    let program = 
+    [
+        0x58,               // CLI
+        0xA5, 0x10,         // wait: LDA $10
+        0xC9, 0x03,         // CMP #3
+        0xD0, 0xFA,         // BNE wait
+        0x60,               // RTS
+        0xE6, 0x10,         // irq: INC $10
+        0x40,               // RTI
+    ];
+
+    let run = |idle_skip: bool| -> (u64, u16)
+    {
+        let mut cpu = R6502::new();
+        let mut bus = RAMBus::new();
+        load(&mut bus, 0x0200, &program);
+        bus.write(0xFFFE, 0x08);
+        bus.write(0xFFFF, 0x02);
+        cpu.reset(&mut bus);
+        cpu.set_idle_skip(idle_skip);
+
+        // Timer interrupt every 1000 cycles
+        let mut scheduler = Scheduler::new();
+        scheduler.schedule(1000, |due, cpu: &mut R6502, bus: &mut RAMBus, _| 
+        {
+            cpu.irq(bus);
+            Some(due + 1000)
+        });
+
+        assert_eq!(RunResult::ProgramStopped, cpu.run_scheduled(&mut bus, &mut scheduler, u64::MAX));
+        (cpu.cycles(), cpu.state().a as u16)
+    };
+
+    let (cycles, a) = run(false);
+    assert_eq!(3, a);
+    assert!(cycles > 3000 && cycles < 3100);
+
+    // Skipping the idle loop has to give the same timing
+    assert_eq!((cycles, a), run(true));
+}
+
+#[test]
+fn overdue_reschedule()
+{
+    let (mut cpu, mut bus) = boot(0x0200, &[0x4C, 0x00, 0x02]); // JMP *
+
+    // Asking to fire again at a cycle that has gone by waits for the next instruction
+    let fired = Rc::new(RefCell::new(0));
+    let count = fired.clone();
+    let mut scheduler = Scheduler::new();
+    let id = scheduler.schedule(0, move |due, _: &mut R6502, _: &mut RAMBus, _|
+    {
+        *count.borrow_mut() += 1;
+        Some(due)
+    });
+
+    scheduler.fire_due(&mut cpu, &mut bus);
+    assert_eq!(1, *fired.borrow());
+    assert_eq!(Some(cpu.cycles() + 1), scheduler.due(id));
+
+    cpu.clock(&mut bus);
+    scheduler.fire_due(&mut cpu, &mut bus);
+    assert_eq!(2, *fired.borrow());
+}
@@ -0,0 +1,96 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use crate::tests::test_bus::{RAMBus, boot};
+use crate::r6502::{R6502, Bus};
+use crate::r6502::snapshot::{self, SnapshotError, SNAPSHOT_VERSION};
+
+// Fill $0300-$03FF with a pattern that depends on everything before it
+const PROGRAM: [u8; 14] = 
+[
+    0xA2, 0x00,         // LDX #0
+    0xA9, 0x01,         // LDA #1
+    0x7D, 0xFF, 0x02,   // loop: ADC $02FF,X
+    0x9D, 0x00, 0x03,   // STA $0300,X
+    0xE8,               // INX
+    0xD0, 0xF7,         // BNE loop
+    0x60,               // RTS
+];
+
+fn run(cpu: &mut R6502, bus: &mut RAMBus)
+{
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(bus);
+    }
+}
+
+#[test]
+fn restore_and_resume()
+{
+    let (mut cpu, mut bus) = boot(0x0200, &PROGRAM);
+
+    for _ in 0..300
+    {
+        cpu.clock(&mut bus);
+    }
+
+    let state = snapshot::save_state(&cpu, &bus);
+    run(&mut cpu, &mut bus);
+
+    let mut restored = R6502::new();
+    let mut restored_bus = RAMBus::new();
+    snapshot::load_state(&mut restored, &mut restored_bus, &state).unwrap();
+    run(&mut restored, &mut restored_bus);
+
+    assert!(restored == cpu);
+    assert_eq!(restored.state(), cpu.state());
+    for addr in 0x0300..0x0400
+    {
+        assert_eq!(restored_bus.read(addr), bus.read(addr), "${:04X}", addr);
+    }
+
+    // The same state saves the same way
+    assert_eq!(snapshot::save_state(&restored, &restored_bus), snapshot::save_state(&cpu, &bus));
+}
+
+#[test]
+fn bad_snapshots()
+{
+    let (mut cpu, mut bus) = boot(0x0200, &PROGRAM);
+    cpu.clock(&mut bus);
+
+    let state = snapshot::save_state(&cpu, &bus);
+    let before = cpu.state();
+
+    let mut other = RAMBus::new();
+    let mut target = cpu.clone();
+    assert_eq!(snapshot::load_state(&mut target, &mut other, b"not a snapshot"), Err(SnapshotError::NotASnapshot));
+    assert_eq!(snapshot::load_state(&mut target, &mut other, &state[..20]), Err(SnapshotError::Truncated));
+
+    let mut newer = state.clone();
+    newer[4..6].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+    assert_eq!(snapshot::load_state(&mut target, &mut other, &newer), Err(SnapshotError::WrongVersion(SNAPSHOT_VERSION + 1)));
+
+    let mut longer = state.clone();
+    longer.push(0);
+    assert!(matches!(snapshot::load_state(&mut target, &mut other, &longer), Err(SnapshotError::Invalid(_))));
+
+    // The cpu isn't touched when loading fails
+    assert_eq!(target.state(), before);
+}
+
+#[test]
+fn trailing_data_leaves_machine_alone()
+{
+    let (mut cpu, mut bus) = boot(0x0200, &PROGRAM);
+    let mut longer = snapshot::save_state(&cpu, &bus);
+    longer.push(0);
+
+    run(&mut cpu, &mut bus);
+    let before = snapshot::save_state(&cpu, &bus);
+
+    // Neither the cpu nor the bus take anything from a snapshot that fails to load
+    assert!(matches!(snapshot::load_state(&mut cpu, &mut bus, &longer), Err(SnapshotError::Invalid(_))));
+    assert_eq!(snapshot::save_state(&cpu, &bus), before);
+}
@@ -0,0 +1,94 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use crate::r6502::source_map::SourceMap;
+
+#[test]
+fn ca65_debug_info()
+{
+    let text = "version\tmajor=2,minor=0\n\
+        file\tid=0,name=\"prog.s\",size=100,mtime=0x5F6AF84B,mod=0\n\
+        file\tid=1,name=\"macros.inc\",size=40,mtime=0x5F6AF84B,mod=0\n\
+        line\tid=0,file=0,line=3,span=0\n\
+        line\tid=1,file=1,line=10,type=2,span=1\n\
+        line\tid=2,file=0,line=4,span=1+2\n\
+        line\tid=3,file=0,line=1\n\
+        seg\tid=0,name=\"CODE\",start=0x000200,size=0x0010,addrsize=absolute,type=ro,oname=\"prog.bin\",ooffs=0\n\
+        span\tid=0,seg=0,start=0,size=2\n\
+        span\tid=1,seg=0,start=2,size=3\n\
+        span\tid=2,seg=0,start=5,size=1\n";
+
+    let mut map = SourceMap::new();
+    assert_eq!(map.load_ca65_dbg(text), Ok(4));
+
+    assert_eq!(map.files(), ["prog.s", "macros.inc"]);
+    assert_eq!(map.lookup(0x0201).map(|l| (l.file, l.line)), Some((0, 3)));
+
+    // The source line wins over the macro it used
+    assert_eq!(map.describe(0x0203).as_deref(), Some("prog.s:4"));
+    assert_eq!(map.describe(0x0205).as_deref(), Some("prog.s:4"));
+    assert_eq!(map.lookup(0x0206), None);
+    assert_eq!(map.address(0, 4), Some(0x0202));
+
+    assert!(SourceMap::new().load_ca65_dbg("line\tid=0,file=0,line=3,span=0\n").is_err());
+}
+
+#[test]
+fn numbered_listing()
+{
+    let listing = "\
+    1                  ; test\n\
+    2  0200            main    .org $0200\n\
+    3  0200  A2 00             ldx #0\n\
+    4  0202  BD 09 02  loop    lda text,x\n";
+
+    let mut map = SourceMap::new();
+    assert_eq!(map.load_listing(listing, "test.asm", None), Ok(2));
+
+    let lines: Vec<(u32, u16, u16)> = map.lines().iter().map(|l| (l.line, l.addr, l.size)).collect();
+    assert_eq!(lines, vec![(3, 0x0200, 2), (4, 0x0202, 3)]);
+    assert_eq!(map.describe(0x0204).as_deref(), Some("test.asm:4"));
+}
+
+#[test]
+fn listing_matched_to_source()
+{
+    let source = "; test\n        .org $0200\nmain:   ldx #0\nloop:   lda text,x\n        bne loop\ntext:   .byte \"hi\", 0\n";
+    let listing = "\
+ca65 V2.19 - Git 1234\n\
+Main file   : test.s\n\
+Current file: test.s\n\
+\n\
+000000r 1               ; test\n\
+000000r 1                       .org $0200\n\
+000200  1  A2 00        main:   ldx #0\n\
+000202  1  BD 09 02     loop:   lda text,x\n\
+000205  1  D0 FB                bne loop\n\
+000207  1  68 69 00     text:   .byte \"hi\", 0\n";
+
+    let mut map = SourceMap::new();
+    assert_eq!(map.load_listing(listing, "test.s", Some(source)), Ok(4));
+
+    let lines: Vec<(u32, u16)> = map.lines().iter().map(|l| (l.line, l.addr)).collect();
+    assert_eq!(lines, vec![(3, 0x0200), (4, 0x0202), (5, 0x0205), (6, 0x0207)]);
+
+    // Without line numbers the source is needed
+    assert!(SourceMap::new().load_listing(listing, "test.s", None).is_err());
+}
+
+#[test]
+fn relocatable_listing()
+{
+    // Without an .org ca65 doesn't know where the code goes
+    let source = "main:   ldx #0\n        rts\n";
+    let listing = "\
+ca65 V2.19 - Git 1234\n\
+Main file   : test.s\n\
+Current file: test.s\n\
+\n\
+000000r 1  A2 00        main:   ldx #0\n\
+000002r 1  60                   rts\n";
+
+    let result = SourceMap::new().load_listing(listing, "test.s", Some(source));
+    assert!(result.unwrap_err().contains("line 5: the address is relocatable"));
+}
@@ -0,0 +1,148 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use crate::r6502::symbols::SymbolTable;
+use crate::r6502::disasm;
+
+#[test]
+fn vice_labels()
+{
+    let mut symbols = SymbolTable::new();
+    let text = "al C:0200 .main\nal C:0202 .loop\n\nal 1100 .con_out\n";
+    assert_eq!(symbols.load_vice(text), Ok(3));
+
+    assert_eq!(symbols.lookup("loop"), Some(0x0202));
+    assert_eq!(symbols.lookup("con_out"), Some(0x1100));
+    assert_eq!(symbols.name(0x0200), Some("main"));
+    assert_eq!(symbols.to_vice(), "al C:0200 .main\nal C:0202 .loop\nal C:1100 .con_out\n");
+
+    assert!(symbols.load_vice("al C:02G0 .bad").is_err());
+    assert!(symbols.load_vice("break 0200").is_err());
+}
+
+#[test]
+fn ca65_debug_info()
+{
+    let text = "version\tmajor=2,minor=0\n\
+        file\tid=0,name=\"echo.s\",size=1054,mtime=0x5F6AF84B,mod=0\n\
+        sym\tid=0,name=\"con_out\",addrsize=absolute,scope=0,def=1,ref=5,val=0x1100,type=equ\n\
+        sym\tid=1,name=\"loop2\",addrsize=absolute,size=1,scope=0,def=3,ref=7,val=0x215,seg=0,type=lab\n\
+        sym\tid=2,name=\"ext\",addrsize=absolute,scope=0,def=9,type=imp,exp=3\n\
+        symbol\tthis isn't a sym line\n";
+
+    let mut symbols = SymbolTable::new();
+    assert_eq!(symbols.load_ca65_dbg(text), Ok(2));
+    assert_eq!(symbols.lookup("loop2"), Some(0x0215));
+    assert_eq!(symbols.lookup("con_out"), Some(0x1100));
+    assert_eq!(symbols.lookup("ext"), None);
+}
+
+#[test]
+fn ca65_wide_constants()
+{
+    let text = "sym\tid=0,name=\"big\",addrsize=zeropage,scope=0,def=1,val=0x12345,type=equ\n\
+        sym\tid=1,name=\"main\",addrsize=absolute,scope=0,def=2,val=0x200,seg=0,type=lab\n";
+
+    // Only the symbol that fits in 16 bits is loaded, the rest of the file still is
+    let mut symbols = SymbolTable::new();
+    assert_eq!(symbols.load_ca65_dbg(text), Ok(1));
+    assert_eq!(symbols.lookup("main"), Some(0x0200));
+    assert_eq!(symbols.lookup("big"), None);
+
+    assert!(symbols.load_ca65_dbg("sym\tid=0,name=\"bad\",val=0xZZ\n").is_err());
+}
+
+#[test]
+fn ca65_scopes()
+{
+    // Two procs with a local called loop, and a cheap local
+    let text = "scope\tid=0,name=\"\",mod=0,size=32\n\
+        scope\tid=1,name=\"clear\",mod=0,type=scope,size=8,parent=0,sym=0\n\
+        scope\tid=2,name=\"copy\",mod=0,type=scope,size=8,parent=0,sym=2\n\
+        scope\tid=3,name=\"inner\",mod=0,type=scope,size=4,parent=2\n\
+        sym\tid=0,name=\"clear\",addrsize=absolute,scope=0,def=1,val=0x200,seg=0,type=lab\n\
+        sym\tid=1,name=\"loop\",addrsize=absolute,scope=1,def=2,val=0x202,seg=0,type=lab\n\
+        sym\tid=2,name=\"copy\",addrsize=absolute,scope=0,def=3,val=0x210,seg=0,type=lab\n\
+        sym\tid=3,name=\"loop\",addrsize=absolute,scope=2,def=4,val=0x212,seg=0,type=lab\n\
+        sym\tid=4,name=\"loop\",addrsize=absolute,scope=3,def=5,val=0x218,seg=0,type=lab\n\
+        sym\tid=5,name=\"@skip\",addrsize=absolute,scope=0,def=6,val=0x21C,parent=2,seg=0,type=lab\n";
+
+    let mut symbols = SymbolTable::new();
+    assert_eq!(symbols.load_ca65_dbg(text), Ok(5));
+    assert_eq!(symbols.lookup("clear"), Some(0x0200));
+    assert_eq!(symbols.lookup("clear::loop"), Some(0x0202));
+    assert_eq!(symbols.lookup("copy::loop"), Some(0x0212));
+    assert_eq!(symbols.lookup("copy::inner::loop"), Some(0x0218));
+    assert_eq!(symbols.lookup("loop"), None);
+    assert_eq!(symbols.lookup("@skip"), None);
+    assert_eq!(symbols.name(0x0202), Some("clear::loop"));
+}
+
+#[test]
+fn listing()
+{
+    let text = "; Symbols for echo.asm\ncon_flags = $009A\ncon_in = 0x1200\nmain = 512  ; decimal\n";
+
+    let mut symbols = SymbolTable::new();
+    assert_eq!(symbols.load_listing(text), Ok(3));
+    assert_eq!(symbols.to_listing(), "con_flags = $009A\nmain = $0200\ncon_in = $1200\n");
+
+    // Export and load back in
+    let mut copy = SymbolTable::new();
+    copy.load_listing(&symbols.to_listing()).unwrap();
+    assert_eq!(copy.to_vice(), symbols.to_vice());
+
+    assert_eq!(symbols.load_listing("not a symbol"), Err("line 1: expected \"name = $addr\"".to_string()));
+}
+
+#[test]
+fn addresses()
+{
+    let mut symbols = SymbolTable::new();
+    symbols.insert("loop2", 0x0215);
+    symbols.insert("text", 0x0230);
+
+    assert_eq!(symbols.parse_addr("loop2"), Some(0x0215));
+    assert_eq!(symbols.parse_addr("text+3"), Some(0x0233));
+    assert_eq!(symbols.parse_addr("text-$10"), Some(0x0220));
+    assert_eq!(symbols.parse_addr("$1234"), Some(0x1234));
+    assert_eq!(symbols.parse_addr("0x1234"), Some(0x1234));
+    assert_eq!(symbols.parse_addr("4660"), Some(0x1234));
+    assert_eq!(symbols.parse_addr("nope"), None);
+
+    assert_eq!(symbols.describe(0x0215), "loop2");
+    assert_eq!(symbols.describe(0x0218), "loop2+3");
+    assert_eq!(symbols.describe(0x0100), "$0100");
+
+    // Moving a name
+    symbols.insert("loop2", 0x0216);
+    assert_eq!(symbols.name(0x0215), None);
+    assert_eq!(symbols.len(), 2);
+}
+
+#[test]
+fn disassembly()
+{
+    let mut symbols = SymbolTable::new();
+    symbols.load_listing("main = $0200\nloop = $0202\ncon_out = $1100\ncon_flags = $009A\ntext = $0213\n").unwrap();
+
+    let code = 
+    [
+        0xA2, 0x00,         // LDX #0
+        0xBD, 0x13, 0x02,   // LDA text,X
+        0x9D, 0x00, 0x11,   // STA con_out,X
+        0xE8,               // INX
+        0xD0, 0xF7,         // BNE loop
+        0xA5, 0x9A,         // LDA con_flags
+    ];
+
+    let lines: Vec<String> = disasm::disassemble_bytes(&code, 0x0200).iter().map(|l| l.line_with(&symbols)).collect();
+    assert_eq!(lines, vec![
+        "$0200  A2 00     main    LDX #$00",
+        "$0202  BD 13 02  loop    LDA text,X",
+        "$0205  9D 00 11          STA con_out,X",
+        "$0208  E8                INX",
+        "$0209  D0 F7             BNE loop",
+        "$020B  A5 9A             LDA con_flags",
+    ]);
+}
@@ -1,28 +1,86 @@
-use crate::r6502::Bus;
-
-// All-RAM bus for testing
-pub struct RAMBus
-{
-    ram: [u8; 64 * 1024]
-}
-
-impl RAMBus
-{
-    pub fn new() -> RAMBus
-    {
-        RAMBus { ram: [0; 64 * 1024] }
-    }
-}
-
-impl Bus for RAMBus
-{
-    fn read(&self, addr: u16) -> u8 
-    {
-        self.ram[addr as usize]    
-    }
-
-    fn write(&mut self, addr: u16, value: u8) 
-    {
-        self.ram[addr as usize] = value;    
-    }
-}
\ No newline at end of file
+use crate::r6502::{R6502, Bus};
+use crate::r6502::snapshot::{Snapshot, SnapshotWriter, SnapshotReader, SnapshotError};
+
+// All-RAM bus for testing
+#[derive(Clone)]
+pub struct RAMBus
+{
+    ram: [u8; 64 * 1024]
+}
+
+impl RAMBus
+{
+    pub fn new() -> RAMBus
+    {
+        RAMBus { ram: [0; 64 * 1024] }
+    }
+}
+
+impl Bus for RAMBus
+{
+    fn read(&self, addr: u16) -> u8 
+    {
+        self.ram[addr as usize]    
+    }
+
+    fn write(&mut self, addr: u16, value: u8) 
+    {
+        self.ram[addr as usize] = value;    
+    }
+
+    fn page(&self, page: u8) -> Option<&[u8; 256]>
+    {
+        let start = (page as usize) << 8;
+        self.ram[start..start + 256].try_into().ok()
+    }
+
+    fn page_mut(&mut self, page: u8) -> Option<&mut [u8; 256]>
+    {
+        let start = (page as usize) << 8;
+        (&mut self.ram[start..start + 256]).try_into().ok()
+    }
+}
+
+impl Snapshot for RAMBus
+{
+    fn save(&self, out: &mut SnapshotWriter)
+    {
+        out.bytes(&self.ram);
+    }
+
+    fn load(&mut self, data: &mut SnapshotReader) -> Result<(), SnapshotError>
+    {
+        data.bytes_into(&mut self.ram)
+    }
+}
+
+// Write the program to memory at addr and point the reset vector at it
+pub fn load(bus: &mut dyn Bus, addr: u16, program: &[u8])
+{
+    // Set the program counter address
+    bus.write(0xFFFC, (addr & 0x00FF) as u8);  // low byte
+    bus.write(0xFFFD, ((addr & 0xFF00) >> 8) as u8);  // high byte
+
+    for (i, byte) in program.iter().enumerate()
+    {
+        bus.write(addr + i as u16, *byte);
+    }
+}
+
+// A cpu reset to the program, loaded into an all-RAM bus
+pub fn boot(addr: u16, program: &[u8]) -> (R6502, RAMBus)
+{
+    let mut cpu = R6502::new();
+    let mut bus = RAMBus::new();
+    load(&mut bus, addr, program);
+    cpu.reset(&mut bus);
+
+    (cpu, bus)
+}
+
+// A, X, Y, PC, SP and the status flags, for comparing runs
+pub fn registers(cpu: &R6502) -> [u16; 6]
+{
+    let state = cpu.state();
+    [state.a as u16, state.x as u16, state.y as u16, state.pc, state.sp, state.status() as u16]
+}
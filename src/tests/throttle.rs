@@ -0,0 +1,102 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::tests::test_bus::boot;
+use crate::r6502::idle::RunResult;
+use crate::r6502::throttle::{Throttle, Clock};
+
+// Time only moves when the test or a sleep moves it
+#[derive(Clone, Default)]
+struct FakeClock
+{
+    now: Rc<Cell<Duration>>,
+    slept: Rc<Cell<Duration>>,
+}
+
+impl FakeClock
+{
+    fn advance(&self, time: Duration)
+    {
+        self.now.set(self.now.get() + time);
+    }
+}
+
+impl Clock for FakeClock
+{
+    fn now(&self) -> Duration
+    {
+        self.now.get()
+    }
+
+    fn sleep(&mut self, time: Duration)
+    {
+        self.advance(time);
+        self.slept.set(self.slept.get() + time);
+    }
+}
+
+#[test]
+fn paced_to_clock_rate()
+{
+    let (mut cpu, mut bus) = boot(0x0200, &[0xE8, 0x4C, 0x00, 0x02]); // INX, JMP $0200
+
+    // 20000 cycles at 200KHz take 100ms, and running them takes no time at all
+    let clock = FakeClock::default();
+    let mut throttle = Throttle::with_clock(200_000.0, clock.clone());
+    let result = cpu.run_throttled(&mut bus, &mut throttle, cpu.cycles() + 20_000);
+
+    assert_eq!(result, RunResult::CycleLimit);
+    let emulated = Duration::from_secs_f64(cpu.cycles().saturating_sub(7) as f64 / 200_000.0);
+    assert!(clock.now() >= Duration::from_millis(100) && clock.now() <= emulated, "ran for {:?}", clock.now());
+
+    let stats = throttle.stats();
+    assert_eq!(stats.bursts, 10);
+    assert_eq!(stats.late_bursts, 0);
+    assert_eq!(stats.slept, clock.slept.get());
+}
+
+#[test]
+fn resync_after_falling_behind()
+{
+    let clock = FakeClock::default();
+    let mut throttle = Throttle::with_clock(1_000_000.0, clock.clone());
+    throttle.start(0);
+
+    // The host stalls for longer than the cpu could make up
+    clock.advance(Duration::from_millis(300));
+    throttle.pace(10_000);
+
+    let stats = throttle.stats();
+    assert_eq!(stats.late_bursts, 1);
+    assert_eq!(stats.resyncs, 1);
+    assert_eq!(stats.max_behind, Duration::from_millis(290));
+    assert!(stats.drift < 0.0);
+    assert_eq!(clock.slept.get(), Duration::ZERO);
+
+    // Starting over means the next burst isn't behind, it's 20ms ahead
+    throttle.pace(30_000);
+    assert_eq!(throttle.stats().late_bursts, 1);
+    assert_eq!(clock.slept.get(), Duration::from_millis(20));
+}
+
+#[test]
+fn late_but_not_resynced()
+{
+    let clock = FakeClock::default();
+    let mut throttle = Throttle::with_clock(1_000_000.0, clock.clone());
+    throttle.start(0);
+
+    // 10ms behind is made up by the bursts after it
+    clock.advance(Duration::from_millis(20));
+    throttle.pace(10_000);
+    assert_eq!(throttle.stats().late_bursts, 1);
+    assert_eq!(throttle.stats().resyncs, 0);
+
+    throttle.pace(30_000);
+    assert_eq!(clock.slept.get(), Duration::from_millis(10));
+    assert_eq!(throttle.stats().late_bursts, 1);
+}
@@ -0,0 +1,149 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::tests::test_bus::boot;
+use crate::r6502::Bus;
+use crate::r6502::symbols::SymbolTable;
+use crate::r6502::trace::{Tracer, TraceFormat};
+
+// Collects the trace so the test can look at it
+#[derive(Clone, Default)]
+struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuf
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize>
+    {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()>
+    {
+        Ok(())
+    }
+}
+
+impl SharedBuf
+{
+    fn lines(&self) -> Vec<String>
+    {
+        String::from_utf8(self.0.borrow().clone()).unwrap().lines().map(|l| l.to_string()).collect()
+    }
+}
+
+const PROGRAM: [u8; 9] = 
+[
+    0xA2, 0x02,         // LDX #2
+    0xCA,               // loop: DEX
+    0xD0, 0xFD,         // BNE loop
+    0x8E, 0x00, 0x03,   // STX $0300
+    0x60,               // RTS
+];
+
+fn run(tracer: Tracer)
+{
+    let (mut cpu, mut bus) = boot(0x0200, &PROGRAM);
+    cpu.add_observer(tracer);
+
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(&mut bus);
+    }
+}
+
+#[test]
+fn nestest_format()
+{
+    let buf = SharedBuf::default();
+    run(Tracer::new(buf.clone(), TraceFormat::Nestest));
+
+    assert_eq!(buf.lines(), vec![
+        "0200  A2 02     LDX #$02                        A:00 X:00 Y:00 P:20 SP:FF CYC:7",
+        "0202  CA        DEX                             A:00 X:02 Y:00 P:20 SP:FF CYC:9",
+        "0203  D0 FD     BNE $0202                       A:00 X:01 Y:00 P:20 SP:FF CYC:11",
+        "0202  CA        DEX                             A:00 X:01 Y:00 P:20 SP:FF CYC:14",
+        "0203  D0 FD     BNE $0202                       A:00 X:00 Y:00 P:22 SP:FF CYC:16",
+        "0205  8E 00 03  STX $0300 = 00                  A:00 X:00 Y:00 P:22 SP:FF CYC:18",
+        "0208  60        RTS                             A:00 X:00 Y:00 P:22 SP:FF CYC:22",
+    ]);
+}
+
+#[test]
+fn json_format()
+{
+    let buf = SharedBuf::default();
+    let mut tracer = Tracer::new(buf.clone(), TraceFormat::JsonLines);
+
+    let mut symbols = SymbolTable::new();
+    symbols.insert("loop", 0x0202);
+    tracer.set_symbols(symbols);
+    run(tracer);
+
+    let lines = buf.lines();
+    assert_eq!(lines.len(), 7);
+    assert_eq!(lines[2], r#"{"pc":515,"bytes":[208,253],"disasm":"BNE loop","a":0,"x":1,"y":0,"p":32,"sp":255,"cycles":11}"#);
+}
+
+#[test]
+fn filters()
+{
+    let buf = SharedBuf::default();
+    let mut tracer = Tracer::new(buf.clone(), TraceFormat::Nestest);
+    tracer.set_range(0x0202, 0x0204);
+    tracer.set_cycles(10, 15);
+    run(tracer);
+
+    let pcs: Vec<String> = buf.lines().iter().map(|l| l[..4].to_string()).collect();
+    assert_eq!(pcs, vec!["0203", "0202"]);
+}
+
+
+#[test]
+fn nestest_annotations()
+{
+    let program =
+    [
+        0xA2, 0x01,         // LDX #1
+        0xA0, 0x02,         // LDY #2
+        0xB1, 0x40,         // LDA ($40),Y
+        0xA1, 0x3F,         // LDA ($3F,X)
+        0xBD, 0x00, 0x03,   // LDA $0300,X
+        0xB5, 0x40,         // LDA $40,X
+        0x34, 0x40,         // BIT $40,X - undocumented
+        0x6C, 0x10, 0x03,   // JMP ($0310)
+        0x60,               // RTS
+    ];
+
+    let (mut cpu, mut bus) = boot(0x0200, &program);
+    for (addr, value) in [(0x0040, 0x00), (0x0041, 0x03), (0x0300, 0x11), (0x0301, 0x22), (0x0302, 0x33), (0x0310, 0x12), (0x0311, 0x02)]
+    {
+        bus.write(addr, value);
+    }
+
+    let buf = SharedBuf::default();
+    cpu.add_observer(Tracer::new(buf.clone(), TraceFormat::Nestest));
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(&mut bus);
+    }
+
+    let lines = buf.lines();
+    let text: Vec<&str> = lines.iter().map(|l| l[15..48].trim_end()).collect();
+    assert_eq!(text, vec![
+        " LDX #$01",
+        " LDY #$02",
+        " LDA ($40),Y = 0300 @ 0302 = 33",
+        " LDA ($3F,X) @ 40 = 0300 = 11",
+        " LDA $0300,X @ 0301 = 22",
+        " LDA $40,X @ 41 = 03",
+        "*BIT $40,X @ 41 = 03",
+        " JMP ($0310) = 0212",
+        " RTS",
+    ]);
+    assert_eq!(lines[6], "020D  34 40    *BIT $40,X @ 41 = 03             A:03 X:01 Y:02 P:20 SP:FF CYC:30");
+}
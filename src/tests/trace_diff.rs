@@ -0,0 +1,89 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use crate::tests::test_bus::boot;
+use crate::r6502::trace_diff::{self, TraceDiff};
+
+const PROGRAM: [u8; 9] = 
+[
+    0xA2, 0x02,         // LDX #2
+    0xCA,               // loop: DEX
+    0xD0, 0xFD,         // BNE loop
+    0x8E, 0x00, 0x03,   // STX $0300
+    0x60,               // RTS
+];
+
+const REFERENCE: &str = "\
+0200  A2 02     LDX #$02                        A:00 X:00 Y:00 P:20 SP:FF CYC:7
+0202  CA        DEX                             A:00 X:02 Y:00 P:20 SP:FF CYC:9
+0203  D0 FD     BNE $0202                       A:00 X:01 Y:00 P:20 SP:FF CYC:11
+0202  CA        DEX                             A:00 X:01 Y:00 P:20 SP:FF CYC:14
+0203  D0 FD     BNE $0202                       A:00 X:00 Y:00 P:22 SP:FF CYC:16
+0205  8E 00 03  STX $0300                       A:00 X:00 Y:00 P:22 SP:FF CYC:18
+0208  60        RTS                             A:00 X:00 Y:00 P:22 SP:FF CYC:22
+";
+
+fn diff(reference: &str) -> Result<usize, trace_diff::Mismatch>
+{
+    let (mut cpu, mut bus) = boot(0x0200, &PROGRAM);
+
+    let mut diff = TraceDiff::parse(reference).unwrap();
+    diff.set_context(2);
+    trace_diff::diff_trace(&mut cpu, &mut bus, &mut diff)
+}
+
+#[test]
+fn matching_trace()
+{
+    assert_eq!(diff(REFERENCE).unwrap(), 7);
+}
+
+#[test]
+fn first_mismatch_with_context()
+{
+    // The second DEX is expected to leave X at 5 and the cycle count is off too
+    let reference = REFERENCE.replace("P:22 SP:FF CYC:16", "P:22 SP:FF CYC:17").replace("A:00 X:00 Y:00 P:22 SP:FF CYC:17", "A:00 X:05 Y:00 P:22 SP:FF CYC:17");
+    let mismatch = diff(&reference).unwrap_err();
+
+    assert_eq!(mismatch.step, 4);
+    assert_eq!(mismatch.line, 5);
+    assert_eq!(mismatch.context.len(), 2);
+    assert!(mismatch.context[1].starts_with("0202  CA"));
+    assert_eq!(mismatch.actual, "0203  D0 FD     BNE $0202                       A:00 X:00 Y:00 P:22 SP:FF CYC:16");
+    assert_eq!(mismatch.differences, vec!["X: expected $05, got $00", "CYC: expected 17, got 16"]);
+}
+
+#[test]
+fn flags_and_stopped_program()
+{
+    let reference = REFERENCE.replace("P:22 SP:FF CYC:18", "P:A2 SP:FF CYC:18");
+    let mismatch = diff(&reference).unwrap_err();
+    assert_eq!(mismatch.differences, vec!["P: expected Nv-bdiZc ($A2), got nv-bdiZc ($22)"]);
+
+    // The reference goes on after the program has finished
+    let reference = format!("{}0209  EA        NOP                             A:00 X:00 Y:00 P:22 SP:FF CYC:28\n", REFERENCE);
+    let mismatch = diff(&reference).unwrap_err();
+    assert_eq!(mismatch.step, 7);
+    assert!(mismatch.differences.contains(&"the program has stopped".to_string()));
+}
+
+#[test]
+fn other_formats()
+{
+    // nestest.log style with a PPU column and no cycles, and JSON lines
+    let nestest = "0200  A2 02     LDX #$02                        A:00 X:00 Y:00 P:20 SP:FF PPU:  0, 21\n\
+                   0202  CA        DEX                             A:00 X:02 Y:00 P:20 SP:FF PPU:  0, 27\n";
+    assert_eq!(diff(nestest).unwrap(), 2);
+
+    let json = r#"{"pc":512,"bytes":[162,2],"disasm":"LDX #$02","a":0,"x":0,"y":0,"p":32,"sp":255,"cycles":7}
+{"pc":514,"bytes":[202],"disasm":"DEX","a":0,"x":3,"y":0,"p":32,"sp":255,"cycles":9}"#;
+    let mismatch = diff(json).unwrap_err();
+    assert_eq!(mismatch.actual, r#"{"pc":514,"bytes":[202],"disasm":"DEX","a":0,"x":2,"y":0,"p":32,"sp":255,"cycles":9}"#);
+
+    assert!(TraceDiff::parse("not a trace\n").is_err());
+
+    // Out of range values aren't truncated into something that might match
+    assert!(trace_diff::parse_line(r#"{"pc":66048,"a":0,"x":0,"y":0,"p":32,"sp":255}"#).is_none());
+    assert!(trace_diff::parse_line(r#"{"pc":512,"a":256,"x":0,"y":0,"p":32,"sp":255}"#).is_none());
+    assert!(trace_diff::parse_line(r#"{"pc":512,"a":0,"x":0,"y":0,"p":32,"sp":511}"#).is_none());
+}
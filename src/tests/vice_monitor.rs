@@ -0,0 +1,308 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::panic;
+use std::thread;
+use std::time::Duration;
+
+use crate::tests::test_bus::{RAMBus, boot};
+use crate::r6502::{R6502, Bus};
+use crate::r6502::debug_server::Target;
+use crate::r6502::vice_monitor::ViceServer;
+
+const PROGRAM: [u8; 13] =
+[
+    0xA2, 0x03,         // LDX #3
+    0x20, 0x0B, 0x02,   // loop: JSR sub
+    0xCA,               // DEX
+    0xD0, 0xFA,         // BNE loop
+    0x00,               // BRK
+    0xEA, 0xEA,         // NOP NOP
+    0xC8,               // sub: INY
+    0x60,               // RTS
+];
+
+struct Machine
+{
+    cpu: R6502,
+    bus: RAMBus,
+}
+
+impl Target for Machine
+{
+    fn cpu(&self) -> &R6502 { &self.cpu }
+    fn cpu_mut(&mut self) -> &mut R6502 { &mut self.cpu }
+    fn bus(&self) -> &dyn Bus { &self.bus }
+    fn bus_mut(&mut self) -> &mut dyn Bus { &mut self.bus }
+
+    fn run(&mut self)
+    {
+        while !self.cpu.is_program_stopped()
+        {
+            self.cpu.clock(&mut self.bus);
+            if self.cpu.stop_requested()
+            {
+                return;
+            }
+        }
+    }
+
+    fn reset(&mut self)
+    {
+        self.cpu.reset(&mut self.bus);
+    }
+}
+
+struct Client
+{
+    stream: TcpStream,
+    next_id: u32,
+}
+
+impl Client
+{
+    fn send(&mut self, command: u8, body: &[u8]) -> u32
+    {
+        self.next_id += 1;
+
+        let mut message = vec![0x02, 0x02];
+        message.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        message.extend_from_slice(&self.next_id.to_le_bytes());
+        message.push(command);
+        message.extend_from_slice(body);
+        self.stream.write_all(&message).unwrap();
+
+        self.next_id
+    }
+
+    // Response type, error code, request id and body
+    fn read(&mut self) -> (u8, u8, u32, Vec<u8>)
+    {
+        let mut header = [0u8; 12];
+        self.stream.read_exact(&mut header).unwrap();
+        assert_eq!(&header[..2], &[0x02, 0x02]);
+
+        let mut body = vec![0; u32::from_le_bytes(header[2..6].try_into().unwrap()) as usize];
+        self.stream.read_exact(&mut body).unwrap();
+        (header[6], header[7], u32::from_le_bytes(header[8..12].try_into().unwrap()), body)
+    }
+
+    // Send a command and check its response
+    fn call(&mut self, command: u8, body: &[u8]) -> Vec<u8>
+    {
+        let id = self.send(command, body);
+        let (kind, error, response_id, body) = self.read();
+
+        // Setting registers and checkpoints answers with them
+        let expected = match command
+        {
+            0x32 => 0x31,
+            0x12 => 0x11,
+            _ => command,
+        };
+
+        assert_eq!((kind, error, response_id), (expected, 0, id), "response to command ${:02X}", command);
+        body
+    }
+
+    // The next event, which must be of this kind, and its body
+    fn event(&mut self, kind: u8) -> Vec<u8>
+    {
+        let (event, error, id, body) = self.read();
+        assert_eq!((event, error, id), (kind, 0, 0xFFFF_FFFF));
+        body
+    }
+
+    fn pc(&mut self, event: u8) -> u16
+    {
+        let body = self.event(event);
+        u16::from_le_bytes([body[0], body[1]])
+    }
+
+    // Register id and value pairs
+    fn registers(body: &[u8]) -> Vec<(u8, u16)>
+    {
+        body[2..].chunks(4).map(|item| (item[1], u16::from_le_bytes([item[2], item[3]]))).collect()
+    }
+}
+
+// A server that stops answering fails the test instead of hanging it
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn connect(addr: SocketAddr) -> TcpStream
+{
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    stream
+}
+
+// Serve PROGRAM to the script, which runs on another thread, and return the machine
+fn serve(script: fn(&mut Client)) -> Machine
+{
+    let (cpu, bus) = boot(0x0200, &PROGRAM);
+    let mut machine = Machine { cpu, bus };
+
+    let listener = ViceServer::listen(0).unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move ||
+    {
+        let mut client = Client { stream: connect(addr), next_id: 0 };
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| script(&mut client)));
+
+        // Always quit so the server doesn't wait for another client
+        let mut quit = Client { stream: connect(addr), next_id: 0 };
+        drop(client);
+        quit.send(0xBB, &[]);
+
+        if let Err(e) = result
+        {
+            panic::resume_unwind(e);
+        }
+    });
+
+    let mut server = ViceServer::new(&mut machine);
+    server.serve(&mut machine, &listener).unwrap();
+    client.join().unwrap();
+
+    machine
+}
+
+#[test]
+fn memory_and_registers()
+{
+    let machine = serve(|client|
+    {
+        client.call(0x81, &[]);
+
+        // Memory set and get, start and end inclusive, memspace and bank
+        client.call(0x02, &[0, 0x00, 0x30, 0x02, 0x30, 0, 0, 0, 0xAA, 0xBB, 0xCC]);
+        assert_eq!(client.call(0x01, &[0, 0x00, 0x30, 0x02, 0x30, 0, 0, 0]), vec![3, 0, 0xAA, 0xBB, 0xCC]);
+        assert_eq!(client.call(0x01, &[0, 0x00, 0x02, 0x01, 0x02, 0, 0, 0]), vec![2, 0, 0xA2, 0x03]);
+
+        let regs = Client::registers(&client.call(0x31, &[0]));
+        assert_eq!(regs, vec![(0, 0), (1, 0), (2, 0), (3, 0x0200), (4, 0xFF), (5, 0x20)]);
+
+        // Set A and PC, the response has all of them
+        let regs = Client::registers(&client.call(0x32, &[0, 2, 0, 3, 0x00, 0x42, 0x00, 3, 0x03, 0x05, 0x02]));
+        assert_eq!(regs[0], (0, 0x42));
+        assert_eq!(regs[3], (3, 0x0205));
+
+        // Unknown commands, other memory spaces and short bodies are errors
+        let id = client.send(0x41, &[]);
+        assert_eq!(client.read(), (0x41, 0x83, id, Vec::new()));
+        let id = client.send(0x31, &[1]);
+        assert_eq!(client.read(), (0x31, 0x02, id, Vec::new()));
+        let id = client.send(0x01, &[0, 0x00]);
+        assert_eq!(client.read(), (0x01, 0x80, id, Vec::new()));
+    });
+
+    assert_eq!(machine.bus.read(0x3001), 0xBB);
+    assert_eq!(machine.cpu.state().a, 0x42);
+    assert_eq!(machine.cpu.state().pc, 0x0205);
+}
+
+#[test]
+fn oversized_request()
+{
+    serve(|client|
+    {
+        // A length longer than any command ends the session instead of being allocated
+        let mut message = vec![0x02, 0x02];
+        message.extend_from_slice(&u32::MAX.to_le_bytes());
+        message.extend_from_slice(&1u32.to_le_bytes());
+        message.push(0x02);
+        client.stream.write_all(&message).unwrap();
+
+        let mut response = Vec::new();
+        client.stream.read_to_end(&mut response).unwrap();
+        assert!(response.is_empty());
+    });
+}
+
+#[test]
+fn checkpoints_stop_the_machine()
+{
+    serve(|client|
+    {
+        // Exec checkpoint on sub: start, end, stop, enabled, op, temporary
+        let info = client.call(0x12, &[0x0B, 0x02, 0x0B, 0x02, 1, 1, 4, 0]);
+        assert_eq!(&info[..4], &1u32.to_le_bytes());
+
+        client.call(0xAA, &[]);
+        assert_eq!(client.pc(0x63), 0x0200);
+
+        let info = client.event(0x11);
+        assert_eq!((info[4], &info[13..17]), (1, &1u32.to_le_bytes()[..]));     // Currently hit, hit count
+        assert_eq!(client.pc(0x62), 0x020B);
+        assert_eq!(Client::registers(&client.call(0x31, &[0]))[1], (1, 3));
+
+        // Only stop on the last time round
+        client.call(0x22, &[1, 0, 0, 0, 6, b'X', b' ', b'=', b'=', b' ', b'1']);
+        client.call(0xAA, &[]);
+        client.pc(0x63);
+        client.event(0x11);
+        assert_eq!(client.pc(0x62), 0x020B);
+        assert_eq!(Client::registers(&client.call(0x31, &[0]))[1], (1, 1));
+
+        // Stepping
+        client.call(0x71, &[0, 2, 0]);
+        client.pc(0x63);
+        assert_eq!(client.pc(0x62), 0x0205);
+
+        // Deleted, so the program runs to its BRK
+        client.call(0x13, &1u32.to_le_bytes());
+        assert_eq!(client.call(0x14, &[]), 0u32.to_le_bytes());
+        client.call(0xAA, &[]);
+        client.pc(0x63);
+        assert_eq!(client.pc(0x62), 0x0208);
+    });
+}
+
+#[test]
+fn stepping_over_and_tracepoints()
+{
+    let machine = serve(|client|
+    {
+        // A checkpoint that doesn't stop, and one that's deleted when it's hit
+        client.call(0x12, &[0x0B, 0x02, 0x0B, 0x02, 0, 1, 4, 0]);
+        client.call(0x12, &[0x05, 0x02, 0x05, 0x02, 1, 1, 4, 1]);
+
+        // Over LDX and the JSR, the tracepoint still reports the call
+        client.call(0x71, &[1, 2, 0]);
+        client.pc(0x63);
+        assert_eq!(client.event(0x11)[..4], 1u32.to_le_bytes());
+        assert_eq!(client.event(0x11)[..4], 2u32.to_le_bytes());
+        assert_eq!(client.pc(0x62), 0x0205);
+
+        // Listing sends the info of each checkpoint before the count
+        let id = client.send(0x14, &[]);
+        assert_eq!(client.read().3[..4], 1u32.to_le_bytes());
+        assert_eq!(client.read(), (0x14, 0, id, 1u32.to_le_bytes().to_vec()));
+        let id = client.send(0x11, &2u32.to_le_bytes());
+        assert_eq!(client.read(), (0x11, 0x01, id, Vec::new()));
+
+        // Into the subroutine and back out of it
+        client.call(0x71, &[0, 3, 0]);
+        client.pc(0x63);
+        assert_eq!(client.pc(0x62), 0x020B);
+
+        client.call(0x73, &[]);
+        client.pc(0x63);
+        assert_eq!(client.pc(0x62), 0x0205);
+
+        // The last time round only the tracepoint is left
+        client.call(0xAA, &[]);
+        client.pc(0x63);
+        client.event(0x11);
+        assert_eq!(client.pc(0x62), 0x0208);
+
+        // Not hit when the step into the subroutine stopped on it
+        let info = client.call(0x11, &1u32.to_le_bytes());
+        assert_eq!(info[13..17], 2u32.to_le_bytes());
+    });
+
+    assert_eq!(machine.cpu.state().y, 3);
+}
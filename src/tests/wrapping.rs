@@ -0,0 +1,143 @@
+
+#![allow(dead_code, non_snake_case)]
+
+// Arithmetic that wraps on the 6502. Each of these used to overflow
+// (and panic in debug builds) instead of wrapping round.
+
+use crate::tests::test_bus::{RAMBus, boot};
+use crate::r6502::{R6502, Bus, Flags};
+
+fn run(addr: u16, program: &[u8], count: usize) -> (R6502, RAMBus)
+{
+    let (mut cpu, mut bus) = boot(addr, program);
+    for _ in 0..count
+    {
+        cpu.clock(&mut bus);
+    }
+
+    (cpu, bus)
+}
+
+#[test]
+fn indexed_past_the_top_of_memory()
+{
+    let program =
+    [
+        0xA2, 0x02,         // LDX #2
+        0xBD, 0xFF, 0xFF,   // LDA $FFFF,X
+        0x85, 0x40,         // STA $40
+        0xA0, 0x03,         // LDY #3
+        0xB9, 0xFF, 0xFF,   // LDA $FFFF,Y
+    ];
+
+    let (mut cpu, mut bus) = boot(0x0200, &program);
+    bus.write(0x0001, 0x5A);
+    bus.write(0x0002, 0xA5);
+    for _ in 0..5
+    {
+        cpu.clock(&mut bus);
+    }
+
+    assert_eq!(0x5A, bus.read(0x0040));
+    assert_eq!(0xA5, cpu.state().a);
+}
+
+#[test]
+fn indirect_jump_through_FFFF()
+{
+    // The pointer's high byte comes from the start of the same page
+    let (mut cpu, mut bus) = boot(0x0200, &[0x6C, 0xFF, 0xFF]);     // JMP ($FFFF)
+    bus.write(0xFFFF, 0x34);
+    bus.write(0xFF00, 0x12);
+    cpu.clock(&mut bus);
+
+    assert_eq!(0x1234, cpu.state().pc);
+}
+
+#[test]
+fn memory_increment_and_decrement()
+{
+    let program =
+    [
+        0xE6, 0x40,         // INC $40
+        0xC6, 0x41,         // DEC $41
+    ];
+
+    let (mut cpu, mut bus) = boot(0x0200, &program);
+    bus.write(0x0040, 0xFF);
+
+    cpu.clock(&mut bus);
+    assert_eq!(0x00, bus.read(0x0040));
+    assert_eq!(1, cpu.check_flag(Flags::Z));
+
+    cpu.clock(&mut bus);
+    assert_eq!(0xFF, bus.read(0x0041));
+    assert_eq!(1, cpu.check_flag(Flags::N));
+}
+
+#[test]
+fn register_increment_and_decrement()
+{
+    let program =
+    [
+        0xA2, 0xFF,         // LDX #$FF
+        0xE8,               // INX
+        0xA0, 0xFF,         // LDY #$FF
+        0xC8,               // INY
+        0xCA,               // DEX
+        0x88,               // DEY
+    ];
+
+    let (cpu, _) = run(0x0200, &program, 4);
+    assert_eq!((0x00, 0x00), (cpu.state().x, cpu.state().y));
+
+    let (cpu, _) = run(0x0200, &program, 6);
+    assert_eq!((0xFF, 0xFF), (cpu.state().x, cpu.state().y));
+    assert_eq!(1, cpu.check_flag(Flags::N));
+}
+
+#[test]
+fn compare_below()
+{
+    // 0 - 1 leaves bit 7 set
+    let (cpu, _) = run(0x0200, &[0xA0, 0x00, 0xC0, 0x01], 2);    // LDY #0, CPY #1
+    assert_eq!(1, cpu.check_flag(Flags::N));
+    assert_eq!(0, cpu.check_flag(Flags::C));
+
+    // -128 - 1 as signed bytes, $7F leaves it clear
+    let (cpu, _) = run(0x0200, &[0xA2, 0x80, 0xE0, 0x01], 2);    // LDX #$80, CPX #1
+    assert_eq!(0, cpu.check_flag(Flags::N));
+    assert_eq!(1, cpu.check_flag(Flags::C));
+}
+
+#[test]
+fn branch_across_7FFF()
+{
+    // The program counter is added to the offset as a signed value
+    let (cpu, _) = run(0x7FFC, &[0x18, 0x90, 0x10], 2);     // CLC, BCC +16
+    assert_eq!(0x800F, cpu.state().pc);
+
+    let (cpu, _) = run(0x8000, &[0x18, 0x90, 0xF0], 2);     // CLC, BCC -16
+    assert_eq!(0x7FF3, cpu.state().pc);
+}
+
+#[test]
+fn subroutine_at_the_top_of_memory()
+{
+    // A JSR in the last three bytes returns to $0000
+    let (mut cpu, mut bus) = boot(0x0300, &[0x60]);     // sub: RTS
+    bus.write(0xFFFD, 0x20);    // JSR sub
+    bus.write(0xFFFE, 0x00);
+    bus.write(0xFFFF, 0x03);
+
+    let mut state = cpu.state();
+    state.pc = 0xFFFD;
+    cpu.set_state(&state);
+
+    cpu.clock(&mut bus);
+    assert_eq!(0x0300, cpu.state().pc);
+    assert_eq!((0xFF, 0xFF), (bus.read(0x01FF), bus.read(0x01FE)));
+
+    cpu.clock(&mut bus);
+    assert_eq!(0x0000, cpu.state().pc);
+}
@@ -0,0 +1,75 @@
+
+#![allow(dead_code, non_snake_case)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::tests::test_bus::boot;
+use crate::r6502::write_history::WriteHistory;
+
+#[test]
+fn last_writers()
+{
+    let program = 
+    [
+        0xA9, 0x11,         // LDA #$11
+        0x85, 0x10,         // STA $10
+        0xA2, 0x22,         // LDX #$22
+        0x86, 0x10,         // STX $10
+        0xE6, 0x10,         // INC $10
+        0x20, 0x10, 0x02,   // JSR sub
+        0x60,               // RTS
+        0xEA, 0xEA,         // NOP NOP
+        0x84, 0x10,         // sub: STY $10
+        0x60,               // RTS
+    ];
+
+    let (mut cpu, mut bus) = boot(0x0200, &program);
+
+    let history = Rc::new(RefCell::new(WriteHistory::new(3)));
+    cpu.add_observer(history.clone());
+
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(&mut bus);
+    }
+
+    let history = history.borrow();
+
+    // Only the last 3 of the 4 writes to $10 are kept
+    let writes: Vec<(u16, &str, u8)> = history.history(0x10).map(|w| (w.pc, w.instr.decoded.name, w.value)).collect();
+    assert_eq!(writes, vec![(0x0210, "STY", 0x00), (0x0208, "INC", 0x23), (0x0206, "STX", 0x22)]);
+
+    let last = history.last_write(0x10).unwrap();
+    assert_eq!(last.to_string(), format!("cycle {}: $0210 STY wrote $00", last.cycle));
+
+    // The return address pushed by JSR
+    assert_eq!(history.last_write(0x01FF).unwrap().instr.decoded.name, "JSR");
+    assert!(history.last_write(0x11).is_none());
+}
+
+#[test]
+fn range()
+{
+    let program = 
+    [
+        0xA9, 0x11,         // LDA #$11
+        0x85, 0x10,         // STA $10
+        0x8D, 0x00, 0x03,   // STA $0300
+        0x60,               // RTS
+    ];
+
+    let (mut cpu, mut bus) = boot(0x0200, &program);
+
+    let history = Rc::new(RefCell::new(WriteHistory::new(8)));
+    history.borrow_mut().set_range(0x0000, 0x00FF);
+    cpu.add_observer(history.clone());
+
+    while !cpu.is_program_stopped()
+    {
+        cpu.clock(&mut bus);
+    }
+
+    assert_eq!(history.borrow().last_write(0x10).unwrap().pc, 0x0202);
+    assert!(history.borrow().last_write(0x0300).is_none());
+}